@@ -0,0 +1,109 @@
+use bitcoin::{OutPoint, Transaction, Txid};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+// Target of the audit log records, so they can be routed separately from other logs
+pub const AUDIT_LOG_TARGET: &str = "bitcoin_da::audit";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TxPurpose {
+    Commit,
+    Reveal,
+    Consolidation,
+    Recovery,
+    // funding of an inscription slot
+    Slot,
+    // OP_RETURN commitment to a blob hash ahead of its inscription
+    Precommitment,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditEvent {
+    // built and signed, a tx whose broadcast fails or is held back has no other record
+    Signed,
+    Broadcast,
+}
+
+/// Audit record of a transaction the service signed, and again once it is broadcast
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxAuditRecord {
+    pub txid: Txid,
+    pub purpose: TxPurpose,
+    pub event: AuditEvent,
+    pub inputs: Vec<OutPoint>,
+    pub fee: u64,
+    pub vsize: usize,
+}
+
+impl TxAuditRecord {
+    // input_total is the sum of the values of the outputs spent by tx
+    pub fn new(tx: &Transaction, purpose: TxPurpose, event: AuditEvent, input_total: u64) -> Self {
+        let output_total: u64 = tx.output.iter().map(|output| output.value).sum();
+
+        Self {
+            txid: tx.txid(),
+            purpose,
+            event,
+            inputs: tx.input.iter().map(|input| input.previous_output).collect(),
+            fee: input_total.saturating_sub(output_total),
+            vsize: tx.vsize(),
+        }
+    }
+
+    // Emits the record as a single line of JSON
+    pub fn log(&self) {
+        match serde_json::to_string(self) {
+            Ok(record) => info!(target: AUDIT_LOG_TARGET, "{}", record),
+            Err(e) => error!(
+                target: AUDIT_LOG_TARGET,
+                "Failed to serialize audit record of tx {}: {}", self.txid, e
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+    use bitcoin::{
+        absolute, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness,
+    };
+
+    use super::{AuditEvent, TxAuditRecord, TxPurpose};
+
+    #[test]
+    fn audit_record_json() {
+        let tx = Transaction {
+            version: 2,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint {
+                    txid: Txid::all_zeros(),
+                    vout: 1,
+                },
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: 9_000,
+                script_pubkey: ScriptBuf::new(),
+            }],
+        };
+
+        let record = TxAuditRecord::new(&tx, TxPurpose::Commit, AuditEvent::Signed, 10_000);
+
+        assert_eq!(record.fee, 1_000);
+        assert_eq!(record.vsize, tx.vsize());
+
+        let json: serde_json::Value = serde_json::to_value(&record).unwrap();
+
+        assert_eq!(json["purpose"], "commit");
+        assert_eq!(json["event"], "signed");
+        assert_eq!(json["txid"], tx.txid().to_string());
+        assert_eq!(json["inputs"][0], format!("{}:1", Txid::all_zeros()));
+        assert_eq!(json["fee"], 1_000);
+    }
+}
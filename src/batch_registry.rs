@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+use bitcoin::Txid;
+use serde::{Deserialize, Serialize};
+
+/// Bitcoin transactions carrying a rollup batch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchRecord {
+    // public key the blob is signed with, batches are numbered per sender
+    // Records written before it was recorded have an empty sender
+    #[serde(default, with = "hex::serde")]
+    pub sender: Vec<u8>,
+    pub batch_number: u64,
+    pub commit_txid: Txid,
+    pub reveal_txid: Txid,
+    // height of the block including the reveal tx, None while it is unconfirmed
+    pub height: Option<u64>,
+}
+
+// BatchRegistry maps the batch numbers of the senders of the service to the transactions
+// that carry them
+// With a path, records are appended to a JSON lines file and the latest record
+// of a batch wins when the file is loaded again
+#[derive(Debug, Default)]
+pub struct BatchRegistry {
+    path: Option<PathBuf>,
+    records: HashMap<(Vec<u8>, u64), BatchRecord>,
+}
+
+impl BatchRegistry {
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, anyhow::Error> {
+        let path = path.into();
+        let mut records = HashMap::new();
+
+        if path.exists() {
+            for line in BufReader::new(File::open(&path)?).lines() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                let record: BatchRecord = serde_json::from_str(&line)?;
+                records.insert((record.sender.clone(), record.batch_number), record);
+            }
+        }
+
+        Ok(Self {
+            path: Some(path),
+            records,
+        })
+    }
+
+    pub fn get(&self, sender: &[u8], batch_number: u64) -> Option<BatchRecord> {
+        self.records.get(&(sender.to_vec(), batch_number)).cloned()
+    }
+
+    // Whether the service sent a batch signed by sender
+    pub fn has_sender(&self, sender: &[u8]) -> bool {
+        self.records.keys().any(|(recorded, _)| recorded == sender)
+    }
+
+    // Highest batch number of the service over all its senders
+    pub fn last_batch_number(&self) -> Option<u64> {
+        self.records
+            .keys()
+            .map(|(_, batch_number)| *batch_number)
+            .max()
+    }
+
+    // Records a batch sent by the service, a resubmission replaces the previous record
+    pub fn record_submission(
+        &mut self,
+        sender: &[u8],
+        batch_number: u64,
+        commit_txid: Txid,
+        reveal_txid: Txid,
+    ) -> Result<(), anyhow::Error> {
+        self.insert(BatchRecord {
+            sender: sender.to_vec(),
+            batch_number,
+            commit_txid,
+            reveal_txid,
+            height: None,
+        })
+    }
+
+    // Records a batch of the service found on chain, it replaces any unconfirmed submission
+    // of the batch. The caller checks the sender is one of the keys of the service
+    pub fn record_inclusion(
+        &mut self,
+        sender: &[u8],
+        batch_number: u64,
+        commit_txid: Txid,
+        reveal_txid: Txid,
+        height: u64,
+    ) -> Result<(), anyhow::Error> {
+        let record = BatchRecord {
+            sender: sender.to_vec(),
+            batch_number,
+            commit_txid,
+            reveal_txid,
+            height: Some(height),
+        };
+
+        if self.get(sender, batch_number).as_ref() == Some(&record) {
+            return Ok(());
+        }
+
+        self.insert(record)
+    }
+
+    fn insert(&mut self, record: BatchRecord) -> Result<(), anyhow::Error> {
+        if let Some(path) = &self.path {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(file, "{}", serde_json::to_string(&record)?)?;
+        }
+
+        self.records
+            .insert((record.sender.clone(), record.batch_number), record);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+    use bitcoin::Txid;
+
+    use super::{BatchRecord, BatchRegistry};
+
+    #[test]
+    fn batch_registry_persists() {
+        let path = std::env::temp_dir().join(format!("batches_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let commit_txid = Txid::from_byte_array([1; 32]);
+        let reveal_txid = Txid::from_byte_array([2; 32]);
+
+        let mut registry = BatchRegistry::open(&path).unwrap();
+        registry
+            .record_submission(b"alice", 7, Txid::all_zeros(), Txid::all_zeros())
+            .unwrap();
+        registry
+            .record_submission(b"alice", 7, commit_txid, reveal_txid)
+            .unwrap();
+        registry
+            .record_inclusion(b"alice", 7, commit_txid, reveal_txid, 100)
+            .unwrap();
+        // batches are numbered per sender
+        registry
+            .record_inclusion(b"bob", 7, Txid::all_zeros(), Txid::all_zeros(), 101)
+            .unwrap();
+        assert_eq!(registry.get(b"alice", 8), None);
+        assert_eq!(registry.last_batch_number(), Some(7));
+
+        let registry = BatchRegistry::open(&path).unwrap();
+        assert!(registry.has_sender(b"alice"));
+        assert!(!registry.has_sender(b"carol"));
+        assert_eq!(registry.get(b"bob", 7).unwrap().height, Some(101));
+        assert_eq!(
+            registry.get(b"alice", 7),
+            Some(BatchRecord {
+                sender: b"alice".to_vec(),
+                batch_number: 7,
+                commit_txid,
+                reveal_txid,
+                height: Some(100),
+            })
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
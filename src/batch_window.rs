@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+
+/// When queued blobs may be posted, e.g. at most once per 2 blocks or when 300KB is pending
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchWindowConfig {
+    pub min_blocks_between_posts: u64,
+    pub max_pending_bytes: usize,
+}
+
+// BatchWindow tracks the pending blobs and the height of the last post
+#[derive(Debug, Clone)]
+pub struct BatchWindow {
+    config: BatchWindowConfig,
+    last_post_height: Option<u64>,
+    pending_bytes: usize,
+}
+
+impl BatchWindow {
+    pub fn new(config: BatchWindowConfig) -> Self {
+        Self {
+            config,
+            last_post_height: None,
+            pending_bytes: 0,
+        }
+    }
+
+    pub fn add_pending(&mut self, size: usize) {
+        self.pending_bytes += size;
+    }
+
+    pub fn pending_bytes(&self) -> usize {
+        self.pending_bytes
+    }
+
+    // Pending blobs can be posted if enough blocks passed since the last post
+    // or too much data is waiting
+    pub fn is_open(&self, height: u64) -> bool {
+        if self.pending_bytes == 0 {
+            return false;
+        }
+
+        let blocks_passed = self.last_post_height.map_or(true, |last_post_height| {
+            height >= last_post_height + self.config.min_blocks_between_posts
+        });
+
+        blocks_passed || self.pending_bytes >= self.config.max_pending_bytes
+    }
+
+    pub fn record_post(&mut self, height: u64) {
+        self.last_post_height = Some(height);
+        self.pending_bytes = 0;
+    }
+
+    // Forgets the pending blobs of a post that failed as a whole. It does not count as a post,
+    // so the next blobs are not held back for it
+    pub fn record_failed_post(&mut self) {
+        self.pending_bytes = 0;
+    }
+}
+
+// Splits blobs of the given sizes, in order, into groups of at most max_bytes that are
+// posted through one reveal tx each. Returns the number of blobs of every group, a blob
+// larger than max_bytes is posted alone
+pub fn group_by_size(sizes: &[usize], max_bytes: usize) -> Vec<usize> {
+    let mut groups = vec![];
+    let mut group_len = 0;
+    let mut group_bytes = 0;
+
+    for &size in sizes {
+        if group_len > 0 && group_bytes + size > max_bytes {
+            groups.push(group_len);
+            group_len = 0;
+            group_bytes = 0;
+        }
+
+        group_len += 1;
+        group_bytes += size;
+    }
+
+    if group_len > 0 {
+        groups.push(group_len);
+    }
+
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BatchWindow, BatchWindowConfig};
+
+    #[test]
+    fn batch_window() {
+        let mut window = BatchWindow::new(BatchWindowConfig {
+            min_blocks_between_posts: 2,
+            max_pending_bytes: 300_000,
+        });
+
+        // nothing to post
+        assert!(!window.is_open(100));
+
+        window.add_pending(1_000);
+        assert!(window.is_open(100));
+
+        window.record_post(100);
+        window.add_pending(1_000);
+        assert!(!window.is_open(100));
+        assert!(!window.is_open(101));
+        assert!(window.is_open(102));
+
+        // size threshold opens the window early
+        window.add_pending(299_000);
+        assert!(window.is_open(101));
+        assert_eq!(window.pending_bytes(), 300_000);
+
+        // a failed post does not hold the next blobs back
+        window.record_failed_post();
+        assert_eq!(window.pending_bytes(), 0);
+        window.add_pending(1_000);
+        assert!(window.is_open(102));
+        assert!(!window.is_open(101));
+    }
+
+    #[test]
+    fn groups_of_blobs() {
+        assert!(super::group_by_size(&[], 100).is_empty());
+        assert_eq!(
+            super::group_by_size(&[40, 60, 1, 100, 30], 100),
+            vec![2, 1, 1, 1]
+        );
+        // too large for any group
+        assert_eq!(super::group_by_size(&[150, 10, 150], 100), vec![1, 1, 1]);
+    }
+}
@@ -0,0 +1,72 @@
+use crate::spec::blob::Checkpoint;
+
+// CheckpointBeacon schedules the checkpoints the service posts every interval_blocks blocks,
+// independently of the batches, so that light clients have a cheap heartbeat to follow
+// The latest checkpoint is posted again if the rollup did not report a new one
+#[derive(Debug, Clone)]
+pub struct CheckpointBeacon {
+    interval_blocks: u64,
+    latest: Option<Checkpoint>,
+    last_post_height: Option<u64>,
+}
+
+impl CheckpointBeacon {
+    pub fn new(interval_blocks: u64) -> Self {
+        Self {
+            interval_blocks: interval_blocks.max(1),
+            latest: None,
+            last_post_height: None,
+        }
+    }
+
+    pub fn update(&mut self, checkpoint: Checkpoint) {
+        self.latest = Some(checkpoint);
+    }
+
+    pub fn latest(&self) -> Option<Checkpoint> {
+        self.latest
+    }
+
+    // Checkpoint to post at height, None if there is none yet or the last one is too recent
+    pub fn due(&self, height: u64) -> Option<Checkpoint> {
+        let interval_passed = self.last_post_height.map_or(true, |last_post_height| {
+            height >= last_post_height + self.interval_blocks
+        });
+
+        self.latest.filter(|_| interval_passed)
+    }
+
+    pub fn record_post(&mut self, height: u64) {
+        self.last_post_height = Some(height);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CheckpointBeacon;
+    use crate::spec::blob::Checkpoint;
+
+    #[test]
+    fn checkpoint_schedule() {
+        let mut beacon = CheckpointBeacon::new(6);
+        assert_eq!(beacon.due(100), None);
+
+        let checkpoint = Checkpoint {
+            state_root: [1; 32],
+        };
+        beacon.update(checkpoint);
+        assert_eq!(beacon.due(100), Some(checkpoint));
+
+        beacon.record_post(100);
+        assert_eq!(beacon.due(105), None);
+        // the same state root is a heartbeat too
+        assert_eq!(beacon.due(106), Some(checkpoint));
+
+        let next = Checkpoint {
+            state_root: [2; 32],
+        };
+        beacon.update(next);
+        assert_eq!(beacon.due(103), None);
+        assert_eq!(beacon.due(110), Some(next));
+    }
+}
@@ -0,0 +1,330 @@
+use std::collections::HashMap;
+use std::process::ExitCode;
+use std::{fs, panic};
+
+use anyhow::{anyhow, Context};
+use bitcoin::consensus::deserialize;
+use bitcoin_da::replay::panic_message;
+use bitcoin_da::service::{BitcoinService, DaServiceConfig};
+use bitcoin_da::spec::blob::BlobWithSender;
+use bitcoin_da::spec::header::HeaderWrapper;
+use bitcoin_da::spec::proof::InclusionMultiProof;
+use bitcoin_da::spec::transaction::Transaction;
+use bitcoin_da::spec::RollupParams;
+use bitcoin_da::verifier::BitcoinVerifier;
+use borsh::BorshDeserialize;
+use sov_rollup_interface::da::DaVerifier;
+
+const USAGE: &str = "usage:
+  bitcoin-da verify --header <file> --blobs <file> --inclusion <file> --completeness <file> \
+--rollup-name <name> [--reveal-tx-prefix <hex>] [--reveal-tx-zero-bits <bits>] \
+[--max-relevant-txs <count>] [--max-decompressed-len <bytes>] \
+[--monotonic-batch-numbers <true|false>]
+  bitcoin-da replay --config <file> --from <height> --to <height> --rollup-name <name> \
+[--reveal-tx-prefix <hex>] [--reveal-tx-zero-bits <bits>] [--max-relevant-txs <count>] \
+[--max-decompressed-len <bytes>] [--monotonic-batch-numbers <true|false>]
+
+verify runs verify_relevant_tx_list natively on proof inputs dumped from the prover.
+Header, blobs and inclusion proof are borsh encoded, the completeness proof is the
+consensus encoding of the transactions.
+
+replay re-extracts and re-verifies the blocks from..=to with a watch-only service built
+from the JSON service config and prints the divergences from the stored batch registry";
+
+// Parses `--flag value` pairs
+fn parse_flags(args: &[String]) -> Result<HashMap<String, String>, anyhow::Error> {
+    let mut flags = HashMap::new();
+
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let name = flag
+            .strip_prefix("--")
+            .ok_or_else(|| anyhow!("unexpected argument {}", flag))?;
+        let value = args
+            .next()
+            .ok_or_else(|| anyhow!("missing value for {}", flag))?;
+
+        flags.insert(name.to_string(), value.clone());
+    }
+
+    Ok(flags)
+}
+
+fn take_flag(flags: &mut HashMap<String, String>, name: &str) -> Result<String, anyhow::Error> {
+    flags
+        .remove(name)
+        .ok_or_else(|| anyhow!("--{} is required", name))
+}
+
+fn rollup_params(flags: &mut HashMap<String, String>) -> Result<RollupParams, anyhow::Error> {
+    Ok(RollupParams {
+        rollup_name: take_flag(flags, "rollup-name")?,
+        reveal_tx_id_prefix: hex::decode(flags.remove("reveal-tx-prefix").unwrap_or_default())
+            .context("invalid --reveal-tx-prefix")?,
+        reveal_tx_id_zero_bits: flags
+            .remove("reveal-tx-zero-bits")
+            .map_or(Ok(0), |bits| bits.parse())
+            .context("invalid --reveal-tx-zero-bits")?,
+        max_relevant_txs: flags
+            .remove("max-relevant-txs")
+            .map(|cap| cap.parse())
+            .transpose()
+            .context("invalid --max-relevant-txs")?,
+        max_decompressed_len: flags
+            .remove("max-decompressed-len")
+            .map(|max_len| max_len.parse())
+            .transpose()
+            .context("invalid --max-decompressed-len")?,
+        monotonic_batch_numbers: flags
+            .remove("monotonic-batch-numbers")
+            .map_or(Ok(false), |enabled| enabled.parse())
+            .context("invalid --monotonic-batch-numbers")?,
+    })
+}
+
+fn no_unknown_flags(flags: HashMap<String, String>) -> Result<(), anyhow::Error> {
+    match flags.keys().next() {
+        Some(flag) => Err(anyhow!("unknown argument --{}", flag)),
+        None => Ok(()),
+    }
+}
+
+// Arguments of the verify subcommand
+#[derive(Debug, PartialEq)]
+struct VerifyArgs {
+    header: String,
+    blobs: String,
+    inclusion: String,
+    completeness: String,
+    rollup_params: RollupParams,
+}
+
+fn parse_verify_args(args: &[String]) -> Result<VerifyArgs, anyhow::Error> {
+    let mut flags = parse_flags(args)?;
+
+    let verify_args = VerifyArgs {
+        header: take_flag(&mut flags, "header")?,
+        blobs: take_flag(&mut flags, "blobs")?,
+        inclusion: take_flag(&mut flags, "inclusion")?,
+        completeness: take_flag(&mut flags, "completeness")?,
+        rollup_params: rollup_params(&mut flags)?,
+    };
+    no_unknown_flags(flags)?;
+
+    Ok(verify_args)
+}
+
+fn read_borsh<T: BorshDeserialize>(path: &str) -> Result<T, anyhow::Error> {
+    let bytes = fs::read(path).with_context(|| format!("cannot read {}", path))?;
+    T::try_from_slice(&bytes).with_context(|| format!("cannot decode {}", path))
+}
+
+fn verify(args: VerifyArgs) -> Result<(), anyhow::Error> {
+    let header: HeaderWrapper = read_borsh(&args.header)?;
+    let blobs: Vec<BlobWithSender> = read_borsh(&args.blobs)?;
+    let inclusion_proof: InclusionMultiProof = read_borsh(&args.inclusion)?;
+    let completeness_proof: Vec<Transaction> = deserialize(
+        &fs::read(&args.completeness)
+            .with_context(|| format!("cannot read {}", args.completeness))?,
+    )
+    .with_context(|| format!("cannot decode {}", args.completeness))?;
+
+    let verifier = BitcoinVerifier::new(args.rollup_params);
+
+    // the verifier asserts on some malformed inputs, report those like any other error
+    panic::set_hook(Box::new(|_| {}));
+    let result = panic::catch_unwind(|| {
+        verifier.verify_relevant_tx_list(&header, &blobs, inclusion_proof, completeness_proof)
+    });
+    let _ = panic::take_hook();
+
+    match result {
+        Ok(Ok(validity_condition)) => {
+            println!("valid");
+            println!("prev_hash: {}", hex::encode(validity_condition.prev_hash));
+            println!("block_hash: {}", hex::encode(validity_condition.block_hash));
+            Ok(())
+        }
+        Ok(Err(e)) => Err(anyhow!("verification failed: {:?}", e)),
+        Err(payload) => Err(anyhow!(
+            "verification failed: {}",
+            panic_message(payload.as_ref())
+        )),
+    }
+}
+
+// Arguments of the replay subcommand
+#[derive(Debug, PartialEq)]
+struct ReplayArgs {
+    config: String,
+    from: u64,
+    to: u64,
+    rollup_params: RollupParams,
+}
+
+fn parse_replay_args(args: &[String]) -> Result<ReplayArgs, anyhow::Error> {
+    let mut flags = parse_flags(args)?;
+
+    let replay_args = ReplayArgs {
+        config: take_flag(&mut flags, "config")?,
+        from: take_flag(&mut flags, "from")?
+            .parse()
+            .context("invalid --from")?,
+        to: take_flag(&mut flags, "to")?
+            .parse()
+            .context("invalid --to")?,
+        rollup_params: rollup_params(&mut flags)?,
+    };
+    no_unknown_flags(flags)?;
+
+    if replay_args.from > replay_args.to {
+        return Err(anyhow!("--from must not be greater than --to"));
+    }
+
+    Ok(replay_args)
+}
+
+fn replay(args: ReplayArgs) -> Result<(), anyhow::Error> {
+    let config: DaServiceConfig = serde_json::from_slice(
+        &fs::read(&args.config).with_context(|| format!("cannot read {}", args.config))?,
+    )
+    .with_context(|| format!("cannot decode {}", args.config))?;
+
+    // replaying never submits
+    let config = DaServiceConfig {
+        watch_only: Some(true),
+        batch_min_blocks: None,
+        batch_max_pending_bytes: None,
+        ..config
+    };
+
+    let report = tokio::runtime::Runtime::new()?.block_on(async {
+        BitcoinService::new(config, args.rollup_params)
+            .await
+            .replay(args.from, args.to)
+            .await
+    })?;
+
+    println!("{}", serde_json::to_string_pretty(&report)?);
+
+    if report.divergences.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!("{} divergences found", report.divergences.len()))
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let result = match args.first().map(String::as_str) {
+        Some("verify") => parse_verify_args(&args[1..]).and_then(verify),
+        Some("replay") => parse_replay_args(&args[1..]).and_then(replay),
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin_da::spec::RollupParams;
+
+    use super::{parse_replay_args, parse_verify_args, ReplayArgs, VerifyArgs};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn verify_args() {
+        assert_eq!(
+            parse_verify_args(&args(&[
+                "--header",
+                "h.bin",
+                "--blobs",
+                "blobs.bin",
+                "--inclusion",
+                "inc.bin",
+                "--completeness",
+                "comp.bin",
+                "--rollup-name",
+                "sov-btc",
+                "--reveal-tx-prefix",
+                "0000",
+                "--reveal-tx-zero-bits",
+                "4",
+            ]))
+            .unwrap(),
+            VerifyArgs {
+                header: "h.bin".to_string(),
+                blobs: "blobs.bin".to_string(),
+                inclusion: "inc.bin".to_string(),
+                completeness: "comp.bin".to_string(),
+                rollup_params: RollupParams {
+                    rollup_name: "sov-btc".to_string(),
+                    reveal_tx_id_prefix: vec![0, 0],
+                    reveal_tx_id_zero_bits: 4,
+                    max_relevant_txs: None,
+                    max_decompressed_len: None,
+                    monotonic_batch_numbers: false,
+                },
+            }
+        );
+
+        assert!(parse_verify_args(&args(&["--header", "h.bin"])).is_err());
+        assert!(parse_verify_args(&args(&["--header"])).is_err());
+        assert!(parse_verify_args(&args(&["--unknown", "x"])).is_err());
+    }
+
+    #[test]
+    fn replay_args() {
+        assert_eq!(
+            parse_replay_args(&args(&[
+                "--config",
+                "da.json",
+                "--from",
+                "100",
+                "--to",
+                "200",
+                "--rollup-name",
+                "sov-btc",
+            ]))
+            .unwrap(),
+            ReplayArgs {
+                config: "da.json".to_string(),
+                from: 100,
+                to: 200,
+                rollup_params: RollupParams {
+                    rollup_name: "sov-btc".to_string(),
+                    reveal_tx_id_prefix: vec![],
+                    reveal_tx_id_zero_bits: 0,
+                    max_relevant_txs: None,
+                    max_decompressed_len: None,
+                    monotonic_batch_numbers: false,
+                },
+            }
+        );
+
+        assert!(parse_replay_args(&args(&[
+            "--config",
+            "da.json",
+            "--from",
+            "200",
+            "--to",
+            "100",
+            "--rollup-name",
+            "sov-btc",
+        ]))
+        .is_err());
+    }
+}
@@ -0,0 +1,257 @@
+// BIP-322 simple signatures, to authenticate blobs by the address of the sequencer instead of
+// a raw public key. Operators can sign with their existing wallet infrastructure and
+// verifiers can keep an allowlist of addresses
+// Only single key P2WPKH and P2TR key path spends are verified
+
+use bitcoin::blockdata::opcodes::all::OP_RETURN;
+use bitcoin::blockdata::opcodes::OP_0;
+use bitcoin::blockdata::script;
+use bitcoin::consensus::encode;
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::key::TapTweak;
+use bitcoin::psbt::Prevouts;
+use bitcoin::secp256k1::{self, Message, Secp256k1, SecretKey, XOnlyPublicKey};
+use bitcoin::sighash::{SighashCache, TapSighashType};
+use bitcoin::{
+    absolute, Address, OutPoint, PublicKey, Script, ScriptBuf, Sequence, Transaction, TxIn, TxOut,
+    Txid, Witness,
+};
+
+// Encoded witness of a P2WPKH spend with the largest DER signature, the longest supported
+pub const MAX_SIMPLE_SIGNATURE_LEN: usize = 1 + 1 + 73 + 1 + 33;
+// Script pubkey of a P2TR address, the longest supported
+pub const MAX_SCRIPT_PUBKEY_LEN: usize = 34;
+
+// Tagged hash of the message committed to by the to_spend tx
+pub fn message_hash(message: &[u8]) -> [u8; 32] {
+    let tag_hash = sha256::Hash::hash(b"BIP0322-signed-message");
+
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag_hash.as_byte_array());
+    engine.input(tag_hash.as_byte_array());
+    engine.input(message);
+
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+// Virtual tx whose only output is spent by the signature
+fn to_spend(script_pubkey: &Script, message: &[u8]) -> Transaction {
+    Transaction {
+        version: 0,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(Txid::all_zeros(), 0xFFFFFFFF),
+            script_sig: script::Builder::new()
+                .push_opcode(OP_0)
+                .push_slice(message_hash(message))
+                .into_script(),
+            sequence: Sequence::ZERO,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: 0,
+            script_pubkey: script_pubkey.to_owned(),
+        }],
+    }
+}
+
+// Virtual tx carrying the signature in its witness
+fn to_sign(to_spend: &Transaction, witness: Witness) -> Transaction {
+    Transaction {
+        version: 0,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(to_spend.txid(), 0),
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ZERO,
+            witness,
+        }],
+        output: vec![TxOut {
+            value: 0,
+            script_pubkey: script::Builder::new().push_opcode(OP_RETURN).into_script(),
+        }],
+    }
+}
+
+// Whether signature is a valid simple signature of message by the owner of script_pubkey
+pub fn verify_simple(script_pubkey: &Script, message: &[u8], signature: &[u8]) -> bool {
+    let Ok(witness) = encode::deserialize::<Witness>(signature) else {
+        return false;
+    };
+    let to_spend = to_spend(script_pubkey, message);
+    let to_sign = to_sign(&to_spend, witness.clone());
+    let secp = Secp256k1::verification_only();
+
+    if script_pubkey.is_v0_p2wpkh() {
+        let (Some(signature), Some(public_key), None) =
+            (witness.nth(0), witness.nth(1), witness.nth(2))
+        else {
+            return false;
+        };
+        let (Ok(signature), Ok(public_key)) = (
+            bitcoin::ecdsa::Signature::from_slice(signature),
+            PublicKey::from_slice(public_key),
+        ) else {
+            return false;
+        };
+        if public_key
+            .wpubkey_hash()
+            .map(|hash| ScriptBuf::new_v0_p2wpkh(&hash))
+            != Some(script_pubkey.to_owned())
+        {
+            return false;
+        }
+
+        let script_code = script_pubkey
+            .to_owned()
+            .p2wpkh_script_code()
+            .expect("P2WPKH script pubkey");
+        let Ok(sighash) = SighashCache::new(&to_sign).segwit_signature_hash(
+            0,
+            &script_code,
+            0,
+            signature.hash_ty,
+        ) else {
+            return false;
+        };
+
+        return secp
+            .verify_ecdsa(
+                &Message::from_slice(sighash.as_byte_array()).expect("32 byte hash"),
+                &signature.sig,
+                &public_key.inner,
+            )
+            .is_ok();
+    }
+
+    if script_pubkey.is_v1_p2tr() {
+        let (Some(signature), None) = (witness.nth(0), witness.nth(1)) else {
+            return false;
+        };
+        let (Ok(signature), Ok(output_key)) = (
+            bitcoin::taproot::Signature::from_slice(signature),
+            XOnlyPublicKey::from_slice(&script_pubkey.as_bytes()[2..]),
+        ) else {
+            return false;
+        };
+        let Ok(sighash) = SighashCache::new(&to_sign).taproot_key_spend_signature_hash(
+            0,
+            &Prevouts::All(&to_spend.output),
+            signature.hash_ty,
+        ) else {
+            return false;
+        };
+
+        return secp
+            .verify_schnorr(
+                &signature.sig,
+                &Message::from_slice(sighash.as_byte_array()).expect("32 byte hash"),
+                &output_key,
+            )
+            .is_ok();
+    }
+
+    false
+}
+
+// Whether the sender of a BIP-322 signed blob, a script pubkey, is one of the addresses
+pub fn is_allowed_sender(sender: &[u8], allowlist: &[Address]) -> bool {
+    allowlist
+        .iter()
+        .any(|address| address.script_pubkey().as_bytes() == sender)
+}
+
+// Simple signature of message by the P2TR key path address of private_key, without a script
+// tree (BIP86). Returns the signature and the script pubkey of the address
+pub fn sign_simple_p2tr(message: &[u8], private_key: &SecretKey) -> (Vec<u8>, Vec<u8>) {
+    let secp = Secp256k1::new();
+    let key_pair = secp256k1::KeyPair::from_secret_key(&secp, private_key);
+    let (internal_key, _parity) = key_pair.x_only_public_key();
+    let script_pubkey = ScriptBuf::new_v1_p2tr(&secp, internal_key, None);
+
+    let to_spend = to_spend(&script_pubkey, message);
+    let to_sign = to_sign(&to_spend, Witness::new());
+    let sighash = SighashCache::new(&to_sign)
+        .taproot_key_spend_signature_hash(
+            0,
+            &Prevouts::All(&to_spend.output),
+            TapSighashType::Default,
+        )
+        .expect("Cannot create hash for signature");
+
+    let tweaked = key_pair.tap_tweak(&secp, None);
+    let signature = secp.sign_schnorr_no_aux_rand(
+        &Message::from_slice(sighash.as_byte_array()).expect("32 byte hash"),
+        &tweaked.to_inner(),
+    );
+    let mut witness = Witness::new();
+    witness.push(signature.as_ref());
+
+    (encode::serialize(&witness), script_pubkey.into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+
+    use bitcoin::secp256k1::SecretKey;
+    use bitcoin::{Address, Network, PrivateKey};
+
+    use super::{is_allowed_sender, message_hash, sign_simple_p2tr, verify_simple};
+
+    #[test]
+    fn bip322_vectors() {
+        assert_eq!(
+            hex::encode(message_hash(b"")),
+            "c90c269c4f8fcbe6880f72a721ddfbf1914268a794cbb21cfafee13770ae19f1"
+        );
+        assert_eq!(
+            hex::encode(message_hash(b"Hello World")),
+            "f0eb03b1a75ac6d9847f55c624a99169b5dccba2a31f5b23bea77ba270de0a7a"
+        );
+
+        let p2wpkh = Address::from_str("bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l")
+            .unwrap()
+            .assume_checked()
+            .script_pubkey();
+        let signature = base64::decode(
+            "AkcwRAIgM2gBAQqvZX15ZiysmKmQpDrG83avLIT492QBzLnQIxYCIBaTpOaD20qRlEylyxFSeEA2ba9YOixpX8z46TSDtS40ASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=",
+        )
+        .unwrap();
+        assert!(verify_simple(&p2wpkh, b"", &signature));
+        assert!(!verify_simple(&p2wpkh, b"Hello World", &signature));
+        let signature = base64::decode(
+            "AkcwRAIgZRfIY3p7/DoVTty6YZbWS71bc5Vct9p9Fia83eRmw2QCICK/ENGfwLtptFluMGs2KsqoNSk89pO7F29zJLUx9a/sASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=",
+        )
+        .unwrap();
+        assert!(verify_simple(&p2wpkh, b"Hello World", &signature));
+
+        let p2tr =
+            Address::from_str("bc1ppv609nr0vr25u07u95waq5lucwfm6tde4nydujnu8npg4q75mr5sxq8lt3")
+                .unwrap()
+                .assume_checked()
+                .script_pubkey();
+        let signature = base64::decode(
+            "AUHd69PrJQEv+oKTfZ8l+WROBHuy9HKrbFCJu7U1iK2iiEy1vMU5EfMtjc+VSHM7aU0SDbak5IUZRVno2P5mjSafAQ==",
+        )
+        .unwrap();
+        assert!(verify_simple(&p2tr, b"Hello World", &signature));
+        assert!(!verify_simple(&p2wpkh, b"Hello World", &signature));
+
+        // the vectors are signed by the same key
+        let private_key =
+            PrivateKey::from_wif("L3VFeEujGtevx9w18HD1fhRbCH67Az2dpCymeRE1SoPK6XQtaN2k").unwrap();
+        let (signature, script_pubkey) = sign_simple_p2tr(b"blob hash", &private_key.inner);
+        assert_eq!(script_pubkey, p2tr.as_bytes());
+        assert!(verify_simple(&p2tr, b"blob hash", &signature));
+        assert!(!verify_simple(&p2tr, b"another blob hash", &signature));
+
+        let other_key = SecretKey::from_slice(&[1; 32]).unwrap();
+        let (signature, other_script_pubkey) = sign_simple_p2tr(b"blob hash", &other_key);
+        assert!(!verify_simple(&p2tr, b"blob hash", &signature));
+
+        let allowlist = [Address::from_script(&p2tr, Network::Bitcoin).unwrap()];
+        assert!(is_allowed_sender(&script_pubkey, &allowlist));
+        assert!(!is_allowed_sender(&other_script_pubkey, &allowlist));
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+use bitcoin::Txid;
+use serde::{Deserialize, Serialize};
+
+/// Where a blob was inscribed on chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlobLocation {
+    pub height: u64,
+    pub txid: Txid,
+}
+
+// BlobIndex maps blob hashes (sha256d of the inscribed body) to their location
+// It is filled as the service extracts relevant blobs from blocks
+#[derive(Debug, Clone, Default)]
+pub struct BlobIndex {
+    locations: HashMap<[u8; 32], BlobLocation>,
+}
+
+impl BlobIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The same blob can be inscribed more than once, the first location is kept
+    pub fn insert(&mut self, blob_hash: [u8; 32], location: BlobLocation) {
+        self.locations.entry(blob_hash).or_insert(location);
+    }
+
+    pub fn get(&self, blob_hash: &[u8; 32]) -> Option<BlobLocation> {
+        self.locations.get(blob_hash).copied()
+    }
+
+    // Location of the blob inscribed by the reveal tx with txid
+    pub fn find_txid(&self, txid: &Txid) -> Option<BlobLocation> {
+        self.locations
+            .values()
+            .find(|location| location.txid == *txid)
+            .copied()
+    }
+
+    // Drops the blobs inscribed below height, returns how many were dropped
+    pub fn prune_below(&mut self, height: u64) -> usize {
+        let len = self.locations.len();
+        self.locations
+            .retain(|_, location| location.height >= height);
+        len - self.locations.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+    use bitcoin::Txid;
+
+    use super::{BlobIndex, BlobLocation};
+
+    #[test]
+    fn blob_index_keeps_first_location() {
+        let mut index = BlobIndex::new();
+
+        let first = BlobLocation {
+            height: 10,
+            txid: Txid::all_zeros(),
+        };
+        let second = BlobLocation {
+            height: 12,
+            txid: Txid::from_byte_array([1; 32]),
+        };
+
+        index.insert([7; 32], first);
+        index.insert([7; 32], second);
+
+        assert_eq!(index.get(&[7; 32]), Some(first));
+        assert_eq!(index.get(&[8; 32]), None);
+        assert_eq!(index.len(), 1);
+    }
+
+    #[test]
+    fn blob_index_prune() {
+        let mut index = BlobIndex::new();
+
+        for height in 10..20 {
+            index.insert(
+                [height as u8; 32],
+                BlobLocation {
+                    height,
+                    txid: Txid::from_byte_array([height as u8; 32]),
+                },
+            );
+        }
+
+        assert_eq!(index.prune_below(15), 5);
+        assert_eq!(index.len(), 5);
+        assert_eq!(index.get(&[14; 32]), None);
+        assert_eq!(
+            index
+                .find_txid(&Txid::from_byte_array([15; 32]))
+                .unwrap()
+                .height,
+            15
+        );
+    }
+}
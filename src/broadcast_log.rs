@@ -0,0 +1,189 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use bitcoin::Txid;
+use serde::{Deserialize, Serialize};
+
+use crate::audit::TxPurpose;
+
+/// Transaction the service is about to broadcast
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BroadcastIntent {
+    pub txid: Txid,
+    // hex encoded signed transaction
+    pub raw_tx: String,
+    pub purpose: TxPurpose,
+}
+
+// What the node said when a pending intent was broadcast again
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BroadcastStatus {
+    // the tx is in the mempool, it may have been there already
+    Accepted,
+    AlreadyConfirmed,
+    // its inputs were spent by a confirmed tx, the tx itself or one replacing it
+    InputsSpent,
+    // e.g. its inputs were spent by another tx, the intent stays pending
+    Rejected(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+enum BroadcastEvent {
+    Intent(BroadcastIntent),
+    Done { txid: Txid },
+}
+
+// BroadcastLog is a write-ahead log of broadcasts. An intent is synced to disk before
+// sendrawtransaction is called and marked done once the node accepted the tx, so after
+// a crash the pending intents are exactly the txs that may or may not have reached the network
+// Pending intents keep their broadcast order, e.g. a commit comes before its reveal
+#[derive(Debug, Default)]
+pub struct BroadcastLog {
+    path: Option<PathBuf>,
+    pending: Vec<BroadcastIntent>,
+}
+
+impl BroadcastLog {
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    // Loads the pending intents and compacts the file down to them
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, anyhow::Error> {
+        let path = path.into();
+        let mut log = Self {
+            path: Some(path.clone()),
+            pending: Vec::new(),
+        };
+
+        if !path.exists() {
+            return Ok(log);
+        }
+
+        let content = fs::read_to_string(&path)?;
+        // the last line is torn if the service crashed while appending it
+        let torn_tail = content.lines().last().filter(|_| !content.ends_with('\n'));
+
+        for line in content.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match serde_json::from_str(line) {
+                Ok(BroadcastEvent::Intent(intent)) => log.pending.push(intent),
+                Ok(BroadcastEvent::Done { txid }) => log.pending.retain(|i| i.txid != txid),
+                Err(_) if torn_tail == Some(line) => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let compacted = path.with_extension("compact");
+        {
+            let mut file = File::create(&compacted)?;
+            for intent in &log.pending {
+                writeln!(
+                    file,
+                    "{}",
+                    serde_json::to_string(&BroadcastEvent::Intent(intent.clone()))?
+                )?;
+            }
+            file.sync_all()?;
+        }
+        fs::rename(&compacted, &path)?;
+
+        Ok(log)
+    }
+
+    pub fn pending(&self) -> &[BroadcastIntent] {
+        &self.pending
+    }
+
+    // Must succeed before the tx is broadcast
+    pub fn record_intent(&mut self, intent: BroadcastIntent) -> Result<(), anyhow::Error> {
+        self.append(&BroadcastEvent::Intent(intent.clone()))?;
+        self.pending.retain(|i| i.txid != intent.txid);
+        self.pending.push(intent);
+
+        Ok(())
+    }
+
+    pub fn record_done(&mut self, txid: Txid) -> Result<(), anyhow::Error> {
+        if !self.pending.iter().any(|intent| intent.txid == txid) {
+            return Ok(());
+        }
+
+        self.append(&BroadcastEvent::Done { txid })?;
+        self.pending.retain(|intent| intent.txid != txid);
+
+        Ok(())
+    }
+
+    fn append(&self, event: &BroadcastEvent) -> Result<(), anyhow::Error> {
+        if let Some(path) = &self.path {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(file, "{}", serde_json::to_string(event)?)?;
+            file.sync_data()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use bitcoin::hashes::Hash;
+    use bitcoin::Txid;
+
+    use super::{BroadcastIntent, BroadcastLog};
+    use crate::audit::TxPurpose;
+
+    #[test]
+    fn broadcast_log_recovers_pending() {
+        let path = std::env::temp_dir().join(format!("broadcasts_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let commit = BroadcastIntent {
+            txid: Txid::from_byte_array([1; 32]),
+            raw_tx: "01".to_string(),
+            purpose: TxPurpose::Commit,
+        };
+        let reveal = BroadcastIntent {
+            txid: Txid::from_byte_array([2; 32]),
+            raw_tx: "02".to_string(),
+            purpose: TxPurpose::Reveal,
+        };
+
+        let mut log = BroadcastLog::open(&path).unwrap();
+        log.record_intent(commit.clone()).unwrap();
+        log.record_done(commit.txid).unwrap();
+        log.record_intent(reveal.clone()).unwrap();
+        log.record_intent(commit.clone()).unwrap();
+
+        // crash while appending
+        write!(
+            std::fs::OpenOptions::new()
+                .append(true)
+                .open(&path)
+                .unwrap(),
+            "{{\"event\":\"done\",\"tx"
+        )
+        .unwrap();
+
+        let mut log = BroadcastLog::open(&path).unwrap();
+        assert_eq!(log.pending(), &[reveal.clone(), commit.clone()]);
+
+        log.record_done(reveal.txid).unwrap();
+        log.record_done(commit.txid).unwrap();
+
+        // compacted on open
+        let log = BroadcastLog::open(&path).unwrap();
+        assert!(log.pending().is_empty());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
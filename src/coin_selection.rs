@@ -0,0 +1,314 @@
+use core::fmt::Debug;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+use crate::spec::utxo::UTXO;
+
+// Steps of the branch and bound search before it gives up, as in bitcoind
+const BNB_MAX_TRIES: usize = 100_000;
+
+/// Picks the utxos funding a commit tx. Strategies trade off the fees paid now, the number of
+/// utxos left in the wallet and how much the change output tells about the wallet
+pub trait CoinSelection: Debug + Send + Sync {
+    // Utxos worth at least amount and their total. Commit txs pass the utxos at their effective
+    // value, net of the fee of their own input, and the fee of the rest of the tx in amount
+    fn select(&self, utxos: &[UTXO], amount: u64) -> Result<(Vec<UTXO>, u64), anyhow::Error>;
+}
+
+// Smallest utxo that covers the amount on its own, else the largest utxos until they do
+// Keeps commit txs at one input whenever the wallet allows it
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SingleUtxoFirst;
+
+impl CoinSelection for SingleUtxoFirst {
+    fn select(&self, utxos: &[UTXO], amount: u64) -> Result<(Vec<UTXO>, u64), anyhow::Error> {
+        match utxos
+            .iter()
+            .filter(|utxo| utxo.amount >= amount)
+            .min_by_key(|utxo| utxo.amount)
+        {
+            Some(utxo) => Ok((vec![utxo.clone()], utxo.amount)),
+            None => LargestFirst.select(utxos, amount),
+        }
+    }
+}
+
+// Largest utxos first, the fewest inputs and so the lowest fee now
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LargestFirst;
+
+impl CoinSelection for LargestFirst {
+    fn select(&self, utxos: &[UTXO], amount: u64) -> Result<(Vec<UTXO>, u64), anyhow::Error> {
+        let mut sorted: Vec<&UTXO> = utxos.iter().collect();
+        sorted.sort_by(|a, b| b.amount.cmp(&a.amount));
+
+        accumulate(sorted, amount)
+    }
+}
+
+// Most confirmed utxos first, consolidates old coins before they are buried under new ones
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OldestFirst;
+
+impl CoinSelection for OldestFirst {
+    fn select(&self, utxos: &[UTXO], amount: u64) -> Result<(Vec<UTXO>, u64), anyhow::Error> {
+        let mut sorted: Vec<&UTXO> = utxos.iter().collect();
+        sorted.sort_by(|a, b| b.confirmations.cmp(&a.confirmations));
+
+        accumulate(sorted, amount)
+    }
+}
+
+// Searches the utxos whose total exceeds the amount by at most cost_of_change, so that the
+// commit tx needs no change output and does not link the wallet to the inscription
+// Falls back to SingleUtxoFirst if there is no such set
+#[derive(Debug, Clone, Copy)]
+pub struct BranchAndBound {
+    // excess worth dropping to the fee rather than paying for a change output, the dust
+    // limit of the commit tx leaves no change below it anyway
+    pub cost_of_change: u64,
+}
+
+impl Default for BranchAndBound {
+    fn default() -> Self {
+        Self {
+            cost_of_change: crate::helpers::builders::DEFAULT_DUST_LIMIT,
+        }
+    }
+}
+
+impl CoinSelection for BranchAndBound {
+    fn select(&self, utxos: &[UTXO], amount: u64) -> Result<(Vec<UTXO>, u64), anyhow::Error> {
+        match self.select_changeless(utxos, amount) {
+            Some(selected) => Ok(selected),
+            None => SingleUtxoFirst.select(utxos, amount),
+        }
+    }
+}
+
+impl BranchAndBound {
+    // Utxos whose total exceeds the amount by at most cost_of_change, None if there are none
+    pub fn select_changeless(&self, utxos: &[UTXO], amount: u64) -> Option<(Vec<UTXO>, u64)> {
+        let mut sorted: Vec<&UTXO> = utxos.iter().collect();
+        sorted.sort_by(|a, b| b.amount.cmp(&a.amount));
+
+        // value of the utxos after each index, to prune branches that cannot reach amount
+        let mut remaining = vec![0; sorted.len() + 1];
+        for index in (0..sorted.len()).rev() {
+            remaining[index] = remaining[index + 1] + sorted[index].amount;
+        }
+
+        let mut selected = Vec::with_capacity(sorted.len());
+        let mut best: Option<(Vec<usize>, u64)> = None;
+        let mut tries = 0;
+        self.search(
+            &sorted,
+            &remaining,
+            amount,
+            0,
+            0,
+            &mut selected,
+            &mut best,
+            &mut tries,
+        );
+
+        best.map(|(indices, sum)| {
+            (
+                indices
+                    .into_iter()
+                    .map(|index| sorted[index].clone())
+                    .collect(),
+                sum,
+            )
+        })
+    }
+
+    // Depth first over including or excluding each utxo, keeps the set of least excess
+    #[allow(clippy::too_many_arguments)]
+    fn search(
+        &self,
+        sorted: &[&UTXO],
+        remaining: &[u64],
+        amount: u64,
+        index: usize,
+        sum: u64,
+        selected: &mut Vec<usize>,
+        best: &mut Option<(Vec<usize>, u64)>,
+        tries: &mut usize,
+    ) {
+        *tries += 1;
+        if *tries > BNB_MAX_TRIES
+            || sum > amount.saturating_add(self.cost_of_change)
+            || sum + remaining[index] < amount
+            || best
+                .as_ref()
+                .map_or(false, |(_, best_sum)| *best_sum == amount)
+        {
+            return;
+        }
+        if sum >= amount {
+            if best.as_ref().map_or(true, |(_, best_sum)| sum < *best_sum) {
+                *best = Some((selected.clone(), sum));
+            }
+            return;
+        }
+        if index == sorted.len() {
+            return;
+        }
+
+        selected.push(index);
+        self.search(
+            sorted,
+            remaining,
+            amount,
+            index + 1,
+            sum + sorted[index].amount,
+            selected,
+            best,
+            tries,
+        );
+        selected.pop();
+        self.search(
+            sorted,
+            remaining,
+            amount,
+            index + 1,
+            sum,
+            selected,
+            best,
+            tries,
+        );
+    }
+}
+
+// Takes utxos in order until they cover the amount
+fn accumulate(sorted: Vec<&UTXO>, amount: u64) -> Result<(Vec<UTXO>, u64), anyhow::Error> {
+    let mut chosen_utxos = vec![];
+    let mut sum: u64 = 0;
+
+    for utxo in sorted {
+        if sum >= amount {
+            break;
+        }
+        sum += utxo.amount;
+        chosen_utxos.push(utxo.clone());
+    }
+
+    if sum < amount {
+        return Err(anyhow!("not enough UTXOs"));
+    }
+
+    Ok((chosen_utxos, sum))
+}
+
+/// Built-in coin selection strategies, by name for the service config
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoinSelectionStrategy {
+    #[default]
+    SingleUtxoFirst,
+    LargestFirst,
+    OldestFirst,
+    BranchAndBound,
+}
+
+impl CoinSelectionStrategy {
+    pub fn coin_selection(self) -> Arc<dyn CoinSelection> {
+        match self {
+            Self::SingleUtxoFirst => Arc::new(SingleUtxoFirst),
+            Self::LargestFirst => Arc::new(LargestFirst),
+            Self::OldestFirst => Arc::new(OldestFirst),
+            Self::BranchAndBound => Arc::new(BranchAndBound::default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+
+    use bitcoin::Txid;
+
+    use super::{BranchAndBound, CoinSelection, LargestFirst, OldestFirst, SingleUtxoFirst};
+    use crate::spec::utxo::UTXO;
+
+    fn utxo(index: u8, amount: u64, confirmations: u64) -> UTXO {
+        UTXO {
+            tx_id: Txid::from_str(&format!("{:064x}", index)).unwrap(),
+            vout: 0,
+            address: "bc1pp8qru0ve43rw9xffmdd8pvveths3cx6a5t6mcr0xfn9cpxx2k24qf70xq9".to_string(),
+            script_pubkey: "512009c03e3d99ac46e29929db5a70b1995de11c1b5da2f5bc0de64ccb8098cab2aa"
+                .to_string(),
+            amount,
+            confirmations,
+            spendable: true,
+            solvable: true,
+            ancestor_count: None,
+        }
+    }
+
+    #[test]
+    fn coin_selection_strategies() {
+        let utxos = vec![
+            utxo(1, 50_000, 10),
+            utxo(2, 30_000, 500),
+            utxo(3, 20_000, 1),
+            utxo(4, 100_000, 3),
+        ];
+        let amounts = |(chosen, sum): (Vec<UTXO>, u64)| {
+            (
+                chosen.iter().map(|utxo| utxo.amount).collect::<Vec<_>>(),
+                sum,
+            )
+        };
+
+        assert_eq!(
+            amounts(SingleUtxoFirst.select(&utxos, 40_000).unwrap()),
+            (vec![50_000], 50_000)
+        );
+        assert_eq!(
+            amounts(SingleUtxoFirst.select(&utxos, 120_000).unwrap()),
+            (vec![100_000, 50_000], 150_000)
+        );
+        assert_eq!(
+            amounts(LargestFirst.select(&utxos, 40_000).unwrap()),
+            (vec![100_000], 100_000)
+        );
+        assert_eq!(
+            amounts(OldestFirst.select(&utxos, 40_000).unwrap()),
+            (vec![30_000, 50_000], 80_000)
+        );
+
+        // 50k + 30k leaves too little for a change output, which would be worth its cost
+        let bnb = BranchAndBound {
+            cost_of_change: 500,
+        };
+        assert_eq!(
+            amounts(bnb.select(&utxos, 79_700).unwrap()),
+            (vec![50_000, 30_000], 80_000)
+        );
+        assert_eq!(
+            amounts(bnb.select(&utxos, 149_700).unwrap()),
+            (vec![100_000, 50_000], 150_000)
+        );
+        // without a changeless set it falls back to a single utxo
+        assert_eq!(
+            amounts(bnb.select(&utxos, 40_000).unwrap()),
+            (vec![50_000], 50_000)
+        );
+
+        for strategy in [
+            &SingleUtxoFirst as &dyn CoinSelection,
+            &LargestFirst,
+            &OldestFirst,
+            &bnb,
+        ] {
+            assert_eq!(
+                strategy.select(&utxos, 200_001).unwrap_err().to_string(),
+                "not enough UTXOs"
+            );
+        }
+    }
+}
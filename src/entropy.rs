@@ -0,0 +1,48 @@
+use bitcoin::bip32::{self, ChildNumber, ExtendedPrivKey};
+use bitcoin::secp256k1::{KeyPair, Secp256k1, SecretKey, Signing};
+use rand::{CryptoRng, RngCore};
+
+/// Source of the randomness used for commit keys and signature nonces
+/// Only the transaction builders draw from it, parsing and verification are deterministic
+pub trait EntropySource {
+    fn fill_bytes(&mut self, dest: &mut [u8]);
+}
+
+// Any cryptographically secure rng can be used, e.g. OsRng or a seeded StdRng in tests
+impl<R: RngCore + CryptoRng + ?Sized> EntropySource for R {
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        RngCore::fill_bytes(self, dest)
+    }
+}
+
+// Draws 32 random bytes from the source
+pub(crate) fn random_bytes(entropy: &mut dyn EntropySource) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    entropy.fill_bytes(&mut bytes);
+    bytes
+}
+
+// Generates a key pair, retrying in the negligible case the bytes are not a valid key
+pub(crate) fn random_key_pair<C: Signing>(
+    secp: &Secp256k1<C>,
+    entropy: &mut dyn EntropySource,
+) -> KeyPair {
+    loop {
+        if let Ok(secret_key) = SecretKey::from_slice(&random_bytes(entropy)) {
+            return KeyPair::from_secret_key(secp, &secret_key);
+        }
+    }
+}
+
+// Commit key at the hardened child m/<index>' of the xpriv. Unlike a random key it can be
+// derived again after a crash between the commit and the reveal broadcast, to rebuild the
+// reveal or sweep the commit output
+pub fn derive_commit_key<C: Signing>(
+    secp: &Secp256k1<C>,
+    xpriv: &ExtendedPrivKey,
+    index: u32,
+) -> Result<KeyPair, bip32::Error> {
+    let child = xpriv.derive_priv(secp, &[ChildNumber::from_hardened_idx(index)?])?;
+
+    Ok(child.to_keypair(secp))
+}
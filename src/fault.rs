@@ -0,0 +1,154 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bitcoin::hashes::Hash;
+use bitcoin::Txid;
+use rand::Rng;
+
+use crate::spec::utxo::UTXO;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Faults {
+    drop_broadcast_ratio: f64,
+    rpc_delay: Duration,
+    reorg_depth: u64,
+    corrupt_utxo_refresh: bool,
+}
+
+/// Injects failures into the bitcoind client of a service, so operators can rehearse
+/// dropped broadcasts, a slow node, reorgs and a bad wallet view against their rollup stack.
+/// Clones share the same faults, they can be changed while the service is running
+#[derive(Debug, Clone, Default)]
+pub struct FaultInjector {
+    faults: Arc<Mutex<Faults>>,
+}
+
+impl FaultInjector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Share of broadcasts (0.0 to 1.0) that are reported as sent but never reach the node
+    pub fn drop_broadcasts(&self, ratio: f64) {
+        self.update(|faults| faults.drop_broadcast_ratio = ratio.clamp(0.0, 1.0));
+    }
+
+    // Delay before every RPC request
+    pub fn delay_rpc(&self, delay: Duration) {
+        self.update(|faults| faults.rpc_delay = delay);
+    }
+
+    // The node reports a chain `depth` blocks shorter, as while it switches to a competing
+    // branch. Blocks above the reported tip are unknown until the reorg is set back to 0
+    pub fn reorg(&self, depth: u64) {
+        self.update(|faults| faults.reorg_depth = depth);
+    }
+
+    // The next wallet UTXO refresh returns outpoints that do not exist
+    pub fn corrupt_next_utxo_refresh(&self) {
+        self.update(|faults| faults.corrupt_utxo_refresh = true);
+    }
+
+    pub fn clear(&self) {
+        self.update(|faults| *faults = Faults::default());
+    }
+
+    fn update(&self, f: impl FnOnce(&mut Faults)) {
+        if let Ok(mut faults) = self.faults.lock() {
+            f(&mut faults);
+        }
+    }
+
+    fn faults(&self) -> Faults {
+        self.faults.lock().map(|faults| *faults).unwrap_or_default()
+    }
+
+    pub(crate) async fn delay(&self) {
+        let delay = self.faults().rpc_delay;
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    pub(crate) fn should_drop_broadcast(&self) -> bool {
+        let ratio = self.faults().drop_broadcast_ratio;
+
+        ratio > 0.0 && rand::thread_rng().gen_bool(ratio)
+    }
+
+    pub(crate) fn reorg_depth(&self) -> u64 {
+        self.faults().reorg_depth
+    }
+
+    // Tip height the node reports during a simulated reorg
+    pub(crate) fn tip_height(&self, height: u64) -> u64 {
+        height.saturating_sub(self.reorg_depth())
+    }
+
+    pub(crate) fn corrupt_utxos(&self, utxos: &mut [UTXO]) {
+        let corrupt = self
+            .faults
+            .lock()
+            .map(|mut faults| core::mem::take(&mut faults.corrupt_utxo_refresh))
+            .unwrap_or_default();
+        if !corrupt {
+            return;
+        }
+
+        for utxo in utxos {
+            let mut txid = utxo.tx_id.to_byte_array();
+            txid.iter_mut().for_each(|byte| *byte = !*byte);
+            utxo.tx_id = Txid::from_byte_array(txid);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+    use bitcoin::Txid;
+
+    use super::FaultInjector;
+    use crate::spec::utxo::UTXO;
+
+    #[test]
+    fn fault_injector() {
+        let faults = FaultInjector::new();
+        let shared = faults.clone();
+
+        assert!(!faults.should_drop_broadcast());
+        shared.drop_broadcasts(1.0);
+        assert!(faults.should_drop_broadcast());
+
+        shared.reorg(3);
+        assert_eq!(faults.tip_height(100), 97);
+        assert_eq!(faults.tip_height(2), 0);
+
+        let utxo = UTXO {
+            tx_id: Txid::all_zeros(),
+            vout: 0,
+            address: String::new(),
+            script_pubkey: String::new(),
+            amount: 10_000,
+            confirmations: 1,
+            spendable: true,
+            solvable: true,
+            ancestor_count: None,
+        };
+        let mut utxos = vec![utxo.clone()];
+        faults.corrupt_utxos(&mut utxos);
+        assert_eq!(utxos, vec![utxo.clone()]);
+
+        // only the next refresh is corrupted
+        shared.corrupt_next_utxo_refresh();
+        faults.corrupt_utxos(&mut utxos);
+        assert_ne!(utxos[0].tx_id, utxo.tx_id);
+        let mut utxos = vec![utxo.clone()];
+        faults.corrupt_utxos(&mut utxos);
+        assert_eq!(utxos, vec![utxo]);
+
+        shared.clear();
+        assert!(!faults.should_drop_broadcast());
+        assert_eq!(faults.tip_height(100), 100);
+    }
+}
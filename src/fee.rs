@@ -0,0 +1,416 @@
+use core::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use bitcoin::Script;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::helpers::builders::{dust_threshold, FeeCap, DEFAULT_DUST_RELAY_FEE_RATE};
+use crate::rpc::BitcoinNode;
+
+/// Fee rates (sat/vB) returned by the /api/v1/fees/recommended endpoint of mempool.space
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecommendedFees {
+    pub fastest_fee: f64,
+    pub half_hour_fee: f64,
+    pub hour_fee: f64,
+    pub economy_fee: f64,
+    pub minimum_fee: f64,
+}
+
+impl RecommendedFees {
+    // Rate of the tier whose confirmation time fits the target, in blocks of 10 minutes
+    pub fn for_conf_target(&self, conf_target: u16) -> f64 {
+        match conf_target {
+            0..=1 => self.fastest_fee,
+            2..=3 => self.half_hour_fee,
+            4..=6 => self.hour_fee,
+            _ => self.economy_fee,
+        }
+    }
+}
+
+/// Source of the fee rates (sat/vB) of the txs of a submission, consulted for every
+/// submission in place of rates tuned by hand
+#[async_trait]
+pub trait FeeEstimator: Debug + Send + Sync {
+    // Fee rate for a tx to confirm within conf_target blocks
+    async fn estimate_fee_rate(&self, conf_target: u16) -> Result<f64, anyhow::Error>;
+}
+
+// estimatesmartfee of the node
+#[async_trait]
+impl FeeEstimator for BitcoinNode {
+    async fn estimate_fee_rate(&self, conf_target: u16) -> Result<f64, anyhow::Error> {
+        self.estimate_smart_fee(conf_target).await
+    }
+}
+
+// Fee rates (sat/vB) below min_fee_rate are considered bogus and ignored, and so are the
+// ones above the fee cap of the service, submissions could not pay them anyway
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FeeBounds {
+    pub min_fee_rate: f64,
+    pub fee_cap: FeeCap,
+}
+
+impl Default for FeeBounds {
+    fn default() -> Self {
+        Self {
+            min_fee_rate: 1.0,
+            fee_cap: FeeCap::default(),
+        }
+    }
+}
+
+impl FeeBounds {
+    pub fn contains(&self, fee_rate: f64) -> bool {
+        fee_rate >= self.min_fee_rate && self.fee_cap.check_fee_rate(fee_rate).is_ok()
+    }
+}
+
+/// Relay policy of the node, in sat/vB. The relay fees are fetched from getnetworkinfo at
+/// startup, the dust relay fee is not reported by the node and only comes from the config
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RelayPolicy {
+    // lowest fee rate the node relays
+    pub min_relay_fee_rate: f64,
+    // fee rate a replacement has to pay on top of the fees of the txs it replaces
+    pub incremental_relay_fee_rate: f64,
+    pub dust_relay_fee_rate: f64,
+}
+
+// bitcoind defaults
+impl Default for RelayPolicy {
+    fn default() -> Self {
+        Self {
+            min_relay_fee_rate: 1.0,
+            incremental_relay_fee_rate: 1.0,
+            dust_relay_fee_rate: DEFAULT_DUST_RELAY_FEE_RATE,
+        }
+    }
+}
+
+impl RelayPolicy {
+    pub fn floor_fee_rate(&self, fee_rate: f64) -> f64 {
+        fee_rate.max(self.min_relay_fee_rate)
+    }
+
+    // Smallest amount an output to script_pubkey can carry without being dust at the dust
+    // relay fee of the node
+    pub fn dust_threshold(&self, script_pubkey: &Script) -> u64 {
+        dust_threshold(script_pubkey, self.dust_relay_fee_rate)
+    }
+
+    // Smallest fee a replacement of replacement_vsize vbytes has to pay to replace txs paying
+    // replaced_fee (BIP 125 rules 3 and 4)
+    pub fn min_replacement_fee(&self, replaced_fee: u64, replacement_vsize: u64) -> u64 {
+        replaced_fee + (replacement_vsize as f64 * self.incremental_relay_fee_rate).ceil() as u64
+    }
+}
+
+// MempoolSpaceFeeSource fetches fee rates from a mempool.space compatible HTTP API
+// Responses are cached for cache_ttl to avoid hitting rate limits
+#[derive(Debug, Clone)]
+pub struct MempoolSpaceFeeSource {
+    client: reqwest::Client,
+    url: String,
+    cache_ttl: Duration,
+    cache: Arc<Mutex<Option<(Instant, RecommendedFees)>>>,
+}
+
+impl MempoolSpaceFeeSource {
+    pub fn new(url: String, cache_ttl: Duration) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+            cache_ttl,
+            cache: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    fn cached_fees(&self, now: Instant) -> Option<RecommendedFees> {
+        let cache = self.cache.lock().ok()?;
+
+        cache
+            .filter(|(fetched_at, _)| now.duration_since(*fetched_at) < self.cache_ttl)
+            .map(|(_, fees)| fees)
+    }
+
+    fn cache_fees(&self, fees: RecommendedFees, now: Instant) {
+        if let Ok(mut cache) = self.cache.lock() {
+            *cache = Some((now, fees));
+        }
+    }
+
+    pub async fn recommended_fees(&self) -> Result<RecommendedFees, anyhow::Error> {
+        if let Some(fees) = self.cached_fees(Instant::now()) {
+            return Ok(fees);
+        }
+
+        let fees = self
+            .client
+            .get(format!("{}/api/v1/fees/recommended", self.url))
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<RecommendedFees>()
+            .await?;
+
+        self.cache_fees(fees, Instant::now());
+
+        Ok(fees)
+    }
+}
+
+#[async_trait]
+impl FeeEstimator for MempoolSpaceFeeSource {
+    async fn estimate_fee_rate(&self, conf_target: u16) -> Result<f64, anyhow::Error> {
+        Ok(self.recommended_fees().await?.for_conf_target(conf_target))
+    }
+}
+
+// FallbackFeeEstimator takes the rate of a primary source, e.g. an HTTP fee API, and the one
+// of the fallback, e.g. the node, if the primary fails or returns a rate out of bounds
+#[derive(Debug, Clone)]
+pub struct FallbackFeeEstimator {
+    primary: Arc<dyn FeeEstimator>,
+    fallback: Arc<dyn FeeEstimator>,
+    bounds: FeeBounds,
+}
+
+impl FallbackFeeEstimator {
+    pub fn new(
+        primary: Arc<dyn FeeEstimator>,
+        fallback: Arc<dyn FeeEstimator>,
+        bounds: FeeBounds,
+    ) -> Self {
+        Self {
+            primary,
+            fallback,
+            bounds,
+        }
+    }
+}
+
+#[async_trait]
+impl FeeEstimator for FallbackFeeEstimator {
+    async fn estimate_fee_rate(&self, conf_target: u16) -> Result<f64, anyhow::Error> {
+        match self.primary.estimate_fee_rate(conf_target).await {
+            Ok(fee_rate) if self.bounds.contains(fee_rate) => return Ok(fee_rate),
+            Ok(fee_rate) => warn!(
+                "Fee source {:?} returned {} sat/vB, outside of {:?}",
+                self.primary, fee_rate, self.bounds
+            ),
+            Err(e) => warn!("Fee source {:?} failed: {}", self.primary, e),
+        }
+
+        self.fallback.estimate_fee_rate(conf_target).await
+    }
+}
+
+// Virtual size available to transactions in a block
+const BLOCK_VSIZE: u64 = 1_000_000;
+
+// MempoolHistogram is a snapshot of the fee rates paid in the mempool
+#[derive(Debug, Clone, Default)]
+pub struct MempoolHistogram {
+    // (fee rate in sat/vB, vsize), highest fee rate first
+    entries: Vec<(f64, u64)>,
+}
+
+impl MempoolHistogram {
+    pub fn new(mut entries: Vec<(f64, u64)>) -> Self {
+        entries.sort_by(|a, b| b.0.total_cmp(&a.0));
+        Self { entries }
+    }
+
+    // Virtual size of the transactions paying at least fee_rate, miners pick them first
+    pub fn vsize_ahead(&self, fee_rate: f64) -> u64 {
+        self.entries
+            .iter()
+            .take_while(|(entry_fee_rate, _)| *entry_fee_rate >= fee_rate)
+            .map(|(_, vsize)| vsize)
+            .sum()
+    }
+
+    // Heuristic probability that a transaction paying fee_rate is included in the next
+    // `blocks` blocks: the share of their block space not already claimed by transactions
+    // paying more. Higher paying transactions arriving meanwhile compete for that share
+    pub fn inclusion_probability(&self, fee_rate: f64, blocks: u64) -> f64 {
+        let capacity = blocks.saturating_mul(BLOCK_VSIZE);
+        if capacity == 0 {
+            return 0.0;
+        }
+
+        let ahead = self.vsize_ahead(fee_rate);
+
+        (capacity.saturating_sub(ahead) as f64 / capacity as f64).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use async_trait::async_trait;
+    use bitcoin::{Address, ScriptBuf};
+
+    use super::{
+        FallbackFeeEstimator, FeeBounds, FeeEstimator, MempoolHistogram, MempoolSpaceFeeSource,
+        RecommendedFees, RelayPolicy,
+    };
+    use crate::helpers::builders::FeeCap;
+
+    // bounds of a service capping fee rates at 1000 sat/vB
+    fn bounds() -> FeeBounds {
+        FeeBounds {
+            fee_cap: FeeCap {
+                max_fee_rate: Some(1_000.0),
+                max_fee: None,
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn recommended_fees() {
+        let fees: RecommendedFees = serde_json::from_str(
+            r#"{"fastestFee":21,"halfHourFee":18,"hourFee":15,"economyFee":8,"minimumFee":4}"#,
+        )
+        .unwrap();
+
+        assert_eq!(fees.fastest_fee, 21.0);
+        assert_eq!(fees.minimum_fee, 4.0);
+        assert_eq!(fees.for_conf_target(1), 21.0);
+        assert_eq!(fees.for_conf_target(3), 18.0);
+        assert_eq!(fees.for_conf_target(6), 15.0);
+        assert_eq!(fees.for_conf_target(144), 8.0);
+
+        assert!(FeeBounds::default().contains(fees.fastest_fee));
+        assert!(!FeeBounds::default().contains(0.5));
+        assert!(!bounds().contains(5_000.0));
+    }
+
+    #[test]
+    fn fee_rate_cache() {
+        let source = MempoolSpaceFeeSource::new(
+            "https://mempool.space/".to_string(),
+            Duration::from_secs(60),
+        );
+        let now = Instant::now();
+
+        let fees = RecommendedFees {
+            fastest_fee: 21.0,
+            half_hour_fee: 18.0,
+            hour_fee: 15.0,
+            economy_fee: 8.0,
+            minimum_fee: 4.0,
+        };
+
+        assert_eq!(source.cached_fees(now), None);
+
+        source.cache_fees(fees, now);
+
+        assert_eq!(
+            source.cached_fees(now + Duration::from_secs(59)),
+            Some(fees)
+        );
+        assert_eq!(source.cached_fees(now + Duration::from_secs(60)), None);
+    }
+
+    // Fixed rate per target, an error if there is none
+    #[derive(Debug)]
+    struct FixedFeeEstimator(Option<f64>);
+
+    #[async_trait]
+    impl FeeEstimator for FixedFeeEstimator {
+        async fn estimate_fee_rate(&self, conf_target: u16) -> Result<f64, anyhow::Error> {
+            self.0
+                .map(|fee_rate| fee_rate / conf_target as f64)
+                .ok_or_else(|| anyhow::anyhow!("no estimate"))
+        }
+    }
+
+    #[tokio::test]
+    async fn fallback_fee_estimator() {
+        let estimator = |primary: Option<f64>| {
+            FallbackFeeEstimator::new(
+                Arc::new(FixedFeeEstimator(primary)),
+                Arc::new(FixedFeeEstimator(Some(12.0))),
+                bounds(),
+            )
+        };
+
+        assert_eq!(
+            estimator(Some(30.0)).estimate_fee_rate(2).await.unwrap(),
+            15.0
+        );
+        // the fallback is asked for the same target
+        assert_eq!(estimator(None).estimate_fee_rate(2).await.unwrap(), 6.0);
+        assert_eq!(
+            estimator(Some(5_000.0)).estimate_fee_rate(1).await.unwrap(),
+            12.0
+        );
+    }
+
+    #[test]
+    fn relay_policy() {
+        let dust_threshold = |address: &str, policy: &RelayPolicy| {
+            let address = Address::from_str(address).unwrap().assume_checked();
+            policy.dust_threshold(&address.script_pubkey())
+        };
+        let policy = RelayPolicy::default();
+
+        // the well known thresholds at the default dust relay fee
+        assert_eq!(
+            dust_threshold(
+                "bc1pp8qru0ve43rw9xffmdd8pvveths3cx6a5t6mcr0xfn9cpxx2k24qf70xq9",
+                &policy
+            ),
+            330
+        );
+        assert_eq!(
+            dust_threshold("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq", &policy),
+            294
+        );
+        assert_eq!(
+            dust_threshold("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2", &policy),
+            546
+        );
+        assert_eq!(policy.dust_threshold(&ScriptBuf::new_op_return(&[1])), 0);
+
+        let policy = RelayPolicy {
+            min_relay_fee_rate: 0.1,
+            incremental_relay_fee_rate: 2.0,
+            dust_relay_fee_rate: 1.0,
+        };
+        assert_eq!(
+            dust_threshold("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2", &policy),
+            182
+        );
+        assert_eq!(policy.floor_fee_rate(0.5), 0.5);
+        assert_eq!(policy.floor_fee_rate(0.01), 0.1);
+        assert_eq!(policy.min_replacement_fee(1_000, 150), 1_300);
+    }
+
+    #[test]
+    fn inclusion_probability() {
+        let histogram =
+            MempoolHistogram::new(vec![(5.0, 400_000), (50.0, 500_000), (20.0, 1_000_000)]);
+
+        assert_eq!(histogram.vsize_ahead(100.0), 0);
+        assert_eq!(histogram.vsize_ahead(20.0), 1_500_000);
+
+        assert_eq!(histogram.inclusion_probability(100.0, 1), 1.0);
+        assert_eq!(histogram.inclusion_probability(30.0, 1), 0.5);
+        assert_eq!(histogram.inclusion_probability(10.0, 1), 0.0);
+        assert_eq!(histogram.inclusion_probability(10.0, 2), 0.25);
+        assert_eq!(histogram.inclusion_probability(10.0, 0), 0.0);
+    }
+}
@@ -0,0 +1,185 @@
+// Handoff of a prepared commit and reveal pair to third party broadcast or acceleration
+// services, for when the node path fails, or of a pre-signed reveal held back to be
+// broadcast later. The JSON follows the output of ord's `wallet inscribe`, with the signed
+// transactions added as consensus encoded hex
+
+use bitcoin::consensus::encode;
+use bitcoin::{Transaction, Txid};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::helpers::parsers::parse_inscriptions;
+use crate::helpers::simulation::{simulate_reveal_spend, RevealSimulationError};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HandoffInscription {
+    // <reveal txid>i<envelope index>
+    pub id: String,
+    // <txid>:<vout>:<offset> of the sat the inscription is on
+    pub location: String,
+}
+
+/// Signed commit and reveal txs in an ord compatible layout
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InscriptionHandoff {
+    pub commit: Txid,
+    pub reveal: Txid,
+    pub inscriptions: Vec<HandoffInscription>,
+    pub total_fees: u64,
+    #[serde(default)]
+    pub reveal_broadcast: bool,
+    pub commit_tx: String,
+    pub reveal_tx: String,
+}
+
+#[derive(Debug, Error)]
+pub enum HandoffError {
+    #[error("invalid handoff JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("invalid transaction hex: {0}")]
+    Hex(#[from] hex::FromHexError),
+    #[error("invalid transaction: {0}")]
+    Decode(#[from] encode::Error),
+    #[error("{0} tx does not match its txid")]
+    TxidMismatch(&'static str),
+    #[error("reveal does not spend the commit: {0}")]
+    Reveal(#[from] RevealSimulationError),
+}
+
+impl InscriptionHandoff {
+    // commit_input_total is the sum of the values of the outputs spent by the commit tx
+    // The rollup name is used to list the envelopes of the reveal as inscriptions
+    pub fn new(
+        commit_tx: &Transaction,
+        reveal_tx: &Transaction,
+        commit_input_total: u64,
+        rollup_name: &str,
+    ) -> Self {
+        let reveal = reveal_tx.txid();
+        let reveal_output_total: u64 = reveal_tx.output.iter().map(|output| output.value).sum();
+        let envelopes = parse_inscriptions(reveal_tx, rollup_name).map_or(0, |i| i.len());
+
+        Self {
+            commit: commit_tx.txid(),
+            reveal,
+            inscriptions: (0..envelopes)
+                .map(|index| HandoffInscription {
+                    id: format!("{}i{}", reveal, index),
+                    location: format!("{}:0:0", reveal),
+                })
+                .collect(),
+            // what goes in and does not come out of the pair, change included
+            total_fees: commit_input_total
+                .saturating_sub(commit_tx.output[1..].iter().map(|o| o.value).sum())
+                .saturating_sub(reveal_output_total),
+            reveal_broadcast: false,
+            commit_tx: hex::encode(encode::serialize(commit_tx)),
+            reveal_tx: hex::encode(encode::serialize(reveal_tx)),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, HandoffError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    // Parses a handoff and checks its txs, see transactions
+    pub fn from_json(json: &str) -> Result<Self, HandoffError> {
+        let handoff: Self = serde_json::from_str(json)?;
+        handoff.transactions()?;
+
+        Ok(handoff)
+    }
+
+    // Decodes the commit and reveal txs, checking they match the txids and that the reveal
+    // is a valid spend of the commit output
+    pub fn transactions(&self) -> Result<(Transaction, Transaction), HandoffError> {
+        let commit_tx: Transaction = encode::deserialize(&hex::decode(&self.commit_tx)?)?;
+        let reveal_tx: Transaction = encode::deserialize(&hex::decode(&self.reveal_tx)?)?;
+
+        if commit_tx.txid() != self.commit {
+            return Err(HandoffError::TxidMismatch("commit"));
+        }
+        if reveal_tx.txid() != self.reveal {
+            return Err(HandoffError::TxidMismatch("reveal"));
+        }
+
+        simulate_reveal_spend(&reveal_tx, &commit_tx)?;
+
+        Ok((commit_tx, reveal_tx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+
+    use bitcoin::hashes::Hash;
+    use bitcoin::secp256k1::SecretKey;
+    use bitcoin::{Address, Txid};
+
+    use super::{HandoffError, InscriptionHandoff};
+    use crate::helpers::builders::{compress_blob, sign_blob_with_private_key, InscriptionBuilder};
+    use crate::spec::utxo::UTXO;
+
+    #[test]
+    fn handoff_round_trip() {
+        let address =
+            Address::from_str("bc1pp8qru0ve43rw9xffmdd8pvveths3cx6a5t6mcr0xfn9cpxx2k24qf70xq9")
+                .unwrap()
+                .assume_checked();
+        let body = compress_blob(b"handoff");
+        let (signature, public_key) =
+            sign_blob_with_private_key(&body, &SecretKey::from_slice(&[1; 32]).unwrap());
+
+        let (commit, reveal) = InscriptionBuilder::new("sov-btc")
+            .with_body(body)
+            .with_signature(signature, public_key)
+            .with_utxos(vec![UTXO {
+                tx_id: Txid::all_zeros(),
+                vout: 0,
+                address: address.to_string(),
+                script_pubkey: address.script_pubkey().to_hex_string(),
+                amount: 1_000_000,
+                confirmations: 1,
+                spendable: true,
+                solvable: true,
+                ancestor_count: None,
+            }])
+            .with_recipient(address)
+            .with_fee_rates(2.0, 2.0)
+            .build()
+            .unwrap();
+
+        let handoff = InscriptionHandoff::new(&commit, &reveal, 1_000_000, "sov-btc");
+        assert_eq!(handoff.inscriptions.len(), 1);
+        assert_eq!(handoff.inscriptions[0].id, format!("{}i0", reveal.txid()));
+        let commit_fee = 1_000_000 - commit.output.iter().map(|o| o.value).sum::<u64>();
+        assert_eq!(
+            handoff.total_fees,
+            commit_fee + commit.output[0].value - reveal.output[0].value
+        );
+
+        let json = handoff.to_json().unwrap();
+        let imported = InscriptionHandoff::from_json(&json).unwrap();
+        assert_eq!(imported, handoff);
+        assert_eq!(imported.transactions().unwrap(), (commit, reveal.clone()));
+
+        // a reveal swapped for another tx
+        let mut tampered = handoff.clone();
+        tampered.reveal_tx = tampered.commit_tx.clone();
+        assert!(matches!(
+            tampered.transactions(),
+            Err(HandoffError::TxidMismatch("reveal"))
+        ));
+
+        // ord output without the txs is not enough to broadcast
+        let json = format!(
+            r#"{{"commit":"{}","reveal":"{}","inscriptions":[],"total_fees":0}}"#,
+            handoff.commit, handoff.reveal
+        );
+        assert!(matches!(
+            InscriptionHandoff::from_json(&json),
+            Err(HandoffError::Json(_))
+        ));
+    }
+}
@@ -1,56 +1,475 @@
 use core::result::Result::Ok;
-use core::str::FromStr;
-use std::fs::File;
-use std::io::{BufWriter, Write};
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::anyhow;
 use bitcoin::absolute::LockTime;
 use bitcoin::blockdata::opcodes::all::{OP_CHECKSIG, OP_ENDIF, OP_IF};
 use bitcoin::blockdata::opcodes::OP_FALSE;
 use bitcoin::blockdata::script;
-use bitcoin::hashes::{sha256d, Hash};
-use bitcoin::key::{TapTweak, TweakedPublicKey, UntweakedKeyPair};
-use bitcoin::psbt::Prevouts;
-use bitcoin::script::PushBytesBuf;
+use bitcoin::hashes::{sha256, sha256d, Hash};
+use bitcoin::key::TapTweak;
+use bitcoin::psbt::{Prevouts, Psbt};
+use bitcoin::script::{Instruction, PushBytes, PushBytesBuf};
 use bitcoin::secp256k1::constants::SCHNORR_SIGNATURE_SIZE;
 use bitcoin::secp256k1::schnorr::Signature;
 use bitcoin::secp256k1::{self, Secp256k1, SecretKey, XOnlyPublicKey};
-use bitcoin::sighash::SighashCache;
-use bitcoin::taproot::{ControlBlock, LeafVersion, TapLeafHash, TaprootBuilder};
+use bitcoin::sighash::{EcdsaSighashType, SighashCache, TapSighashType};
+use bitcoin::taproot::{
+    self, ControlBlock, LeafVersion, TapLeafHash, TapNodeHash, TaprootBuilder, TAPROOT_ANNEX_PREFIX,
+};
 use bitcoin::{
-    Address, Network, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness,
+    Address, Network, OutPoint, Script, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid,
+    Witness,
 };
 use brotli::{CompressorWriter, DecompressorWriter};
+use flate2::bufread::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rand::rngs::OsRng;
+use ruzstd::decoding::StreamingDecoder;
+use ruzstd::encoding::CompressionLevel;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::coin_selection::{BranchAndBound, CoinSelection, SingleUtxoFirst};
+use crate::entropy::{random_bytes, random_key_pair, EntropySource};
+use crate::helpers::policy::{check_tapscript_spend, check_tx_standardness, PolicyError};
+use crate::helpers::{
+    BATCH_NUMBER_TAG, BODY_ROOT_TAG, BODY_TAG, CHUNK_TAG, COMPRESSION_TAG, KIND_TAG, PARAMS_TAG,
+    PRECOMMITMENT_TAG, PUBLICKEY_TAG, RANDOM_TAG, ROLLUP_NAME_TAG, SCHEME_TAG, SIGNATURE_TAG,
+    TIMESTAMP_TAG, VERSION_TAG,
+};
+use crate::spec::blob::{ChunkManifest, CompressionAlgorithm, InscriptionMetadata};
+use crate::spec::body_root::body_root;
+use crate::spec::precommitment::precommitment_script;
+use crate::spec::utxo::{InputScriptType, UTXO};
+use crate::spec::{
+    meets_reveal_pow, validate_rollup_name, RollupNameError, DEFAULT_MAX_DECOMPRESSED_LEN,
+    ENVELOPE_VERSION, MAX_ROLLUP_NAME_LEN,
+};
+use crate::{bip322, REVEAL_OUTPUT_AMOUNT};
+
+// Largest uncompressed chunk of a split blob. Its reveal tx stays under the 400k weight units
+// standardness limit even if the chunk does not compress at all
+pub const MAX_CHUNK_LEN: usize = 380_000;
 
-use crate::helpers::{BODY_TAG, PUBLICKEY_TAG, RANDOM_TAG, ROLLUP_NAME_TAG, SIGNATURE_TAG};
-use crate::spec::utxo::UTXO;
+// Splits a blob into chunks of up to max_chunk_len bytes, each to be compressed, signed and
+// inscribed in its own reveal tx with its manifest in the metadata
+// See parsers::reassemble_chunks for the way back
+pub fn split_blob(blob: &[u8], max_chunk_len: usize) -> Vec<(Vec<u8>, ChunkManifest)> {
+    let blob_hash = sha256d::Hash::hash(blob).to_byte_array();
+
+    let chunks: Vec<&[u8]> = if blob.is_empty() {
+        vec![blob]
+    } else {
+        blob.chunks(max_chunk_len.max(1)).collect()
+    };
+    let total = chunks.len() as u32;
+
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            (
+                chunk.to_vec(),
+                ChunkManifest {
+                    index: index as u32,
+                    total,
+                    blob_hash,
+                },
+            )
+        })
+        .collect()
+}
 
 pub fn compress_blob(blob: &[u8]) -> Vec<u8> {
-    let mut writer = CompressorWriter::new(Vec::new(), 4096, 11, 22);
-    writer.write_all(blob).unwrap();
-    writer.into_inner()
+    compress_blob_with(blob, CompressionAlgorithm::Brotli)
+}
+
+// Panics if the blob cannot be compressed, see try_compress_blob
+pub fn compress_blob_with(blob: &[u8], algorithm: CompressionAlgorithm) -> Vec<u8> {
+    try_compress_blob(blob, algorithm).expect("Cannot compress in memory")
+}
+
+// Compresses the blob with algorithm, the errors of the encoder are returned instead of
+// panicking the caller
+pub fn try_compress_blob(blob: &[u8], algorithm: CompressionAlgorithm) -> io::Result<Vec<u8>> {
+    compress_stream(blob, Vec::new(), algorithm)
+}
+
+// Compresses everything reader yields into writer and returns the writer, so that a large
+// batch can be compressed from a file or a socket without buffering it
+pub fn compress_stream<R: Read, W: Write>(
+    mut reader: R,
+    writer: W,
+    algorithm: CompressionAlgorithm,
+) -> io::Result<W> {
+    match algorithm {
+        CompressionAlgorithm::Brotli => {
+            // the brotli encoder drops the errors of the last write, they are caught instead
+            let mut encoder = CompressorWriter::new(CatchIo::new(writer), 4096, 11, 22);
+            io::copy(&mut reader, &mut encoder)?;
+            encoder.into_inner().into_result()
+        }
+        CompressionAlgorithm::Zstd => {
+            // the zstd encoder panics on io errors, they are caught instead
+            let mut source = CatchIo::new(reader);
+            let mut drain = CatchIo::new(writer);
+            ruzstd::encoding::compress(&mut source, &mut drain, CompressionLevel::Fastest);
+            source.into_result()?;
+            drain.into_result()
+        }
+        CompressionAlgorithm::Gzip => {
+            let mut encoder = GzEncoder::new(writer, Compression::default());
+            io::copy(&mut reader, &mut encoder)?;
+            encoder.finish()
+        }
+        CompressionAlgorithm::None => {
+            let mut writer = writer;
+            io::copy(&mut reader, &mut writer)?;
+            Ok(writer)
+        }
+    }
+}
+
+// Keeps the first io error of inner and hides it from the encoders that cannot report it:
+// reads return the end of the stream and writes are dropped
+struct CatchIo<T> {
+    inner: T,
+    error: Option<io::Error>,
+}
+
+impl<T> CatchIo<T> {
+    fn new(inner: T) -> Self {
+        Self { inner, error: None }
+    }
+
+    fn into_result(self) -> io::Result<T> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(self.inner),
+        }
+    }
+}
+
+impl<T: Read> Read for CatchIo<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.error.is_none() {
+            match self.inner.read(buf) {
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => self.error = Some(error),
+                read => return read,
+            }
+        }
+        Ok(0)
+    }
+}
+
+impl<T: Write> Write for CatchIo<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.error.is_none() {
+            if let Err(error) = self.inner.write_all(buf) {
+                self.error = Some(error);
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.error.is_none() {
+            if let Err(error) = self.inner.flush() {
+                self.error = Some(error);
+            }
+        }
+        Ok(())
+    }
+}
+
+// Compresses the blob with algorithm, unless that does not make it smaller, e.g. because it
+// is already compressed or encrypted: the blob is then stored as is
+// Returns the body and the algorithm to tag it with
+pub fn compress_blob_or_store(
+    blob: &[u8],
+    algorithm: CompressionAlgorithm,
+) -> (Vec<u8>, CompressionAlgorithm) {
+    try_compress_blob_or_store(blob, algorithm).expect("Cannot compress in memory")
+}
+
+// Like compress_blob_or_store, but returns the errors of the encoder
+pub fn try_compress_blob_or_store(
+    blob: &[u8],
+    algorithm: CompressionAlgorithm,
+) -> io::Result<(Vec<u8>, CompressionAlgorithm)> {
+    let compressed = try_compress_blob(blob, algorithm)?;
+    if compressed.len() < blob.len() {
+        Ok((compressed, algorithm))
+    } else {
+        Ok((blob.to_vec(), CompressionAlgorithm::None))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum DecompressError {
+    #[error("blob is not a valid compressed stream")]
+    Corrupted,
+    #[error("compressed stream of the blob is truncated")]
+    Truncated,
+    #[error("blob decompresses to more than {max_len} bytes")]
+    TooLarge { max_len: usize },
+}
+
+// zstd frames and gzip members start with a magic number, brotli streams have none
+// The first byte of a stream of compress_blob encodes its 22 bit window, as 0x?b
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+// Algorithm of a body whose envelope has no compression tag, i.e. brotli unless the body
+// starts with the magic number of another algorithm. A stored body cannot be detected
+pub fn detect_compression(blob: &[u8]) -> CompressionAlgorithm {
+    if blob.starts_with(ZSTD_MAGIC) {
+        CompressionAlgorithm::Zstd
+    } else if blob.starts_with(GZIP_MAGIC) {
+        CompressionAlgorithm::Gzip
+    } else {
+        CompressionAlgorithm::Brotli
+    }
+}
+
+// Blobs are read from the chain, anyone can inscribe data that does not decompress
+// The algorithm is detected from the body, see detect_compression
+pub fn decompress_blob(blob: &[u8]) -> Result<Vec<u8>, DecompressError> {
+    try_decompress_blob(blob, None)
+}
+
+// Decompresses a body with the algorithm of its compression tag, or the one detected from
+// the body if it has none. Corrupt data is an error, never a panic, and so is a body that
+// decompresses to more than DEFAULT_MAX_DECOMPRESSED_LEN bytes
+pub fn try_decompress_blob(
+    blob: &[u8],
+    algorithm: Option<CompressionAlgorithm>,
+) -> Result<Vec<u8>, DecompressError> {
+    decompress_blob_with_limit(
+        blob,
+        algorithm.unwrap_or_else(|| detect_compression(blob)),
+        DEFAULT_MAX_DECOMPRESSED_LEN,
+    )
+}
+
+// Decompresses a body compressed with algorithm, as given by its compression tag
+pub fn decompress_blob_with(
+    blob: &[u8],
+    algorithm: CompressionAlgorithm,
+) -> Result<Vec<u8>, DecompressError> {
+    try_decompress_blob(blob, Some(algorithm))
 }
 
-pub fn decompress_blob(blob: &[u8]) -> Vec<u8> {
-    let mut writer = DecompressorWriter::new(Vec::new(), 4096);
-    writer.write_all(blob).unwrap();
-    writer.into_inner().expect("decompression failed")
+// Like decompress_blob_with, but gives up once the blob decompresses to more than max_len
+// bytes. A few bytes of brotli can expand to gigabytes, verifiers must not buffer them
+pub fn decompress_blob_with_limit(
+    blob: &[u8],
+    algorithm: CompressionAlgorithm,
+    max_len: usize,
+) -> Result<Vec<u8>, DecompressError> {
+    let mut writer = LimitedWriter {
+        inner: Vec::new(),
+        max_len,
+    };
+
+    let result = decompress_stream(blob, &mut writer, algorithm).map(|_| ());
+    if writer.inner.len() > max_len {
+        return Err(DecompressError::TooLarge { max_len });
+    }
+
+    result.map(|_| writer.inner)
+}
+
+// Vec that fails writes once it holds more than max_len bytes
+struct LimitedWriter {
+    inner: Vec<u8>,
+    max_len: usize,
+}
+
+impl Write for LimitedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.inner.len() > self.max_len {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "decompressed blob is too large",
+            ));
+        }
+        // one byte past the limit tells a blob at the limit from a larger one
+        let len = buf
+            .len()
+            .min((self.max_len - self.inner.len()).saturating_add(1));
+        self.inner.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Decompresses the stream reader yields into writer and returns the writer
+// Like brotli, a zstd or gzip stream is a single frame with nothing after it
+// Errors of reader and writer are reported as a corrupted stream
+// Nothing bounds the output but writer, decompress untrusted blobs with
+// decompress_blob_with_limit
+pub fn decompress_stream<R: Read, W: Write>(
+    reader: R,
+    mut writer: W,
+    algorithm: CompressionAlgorithm,
+) -> Result<W, DecompressError> {
+    let mut source = BufReader::new(reader);
+    let copied = match algorithm {
+        CompressionAlgorithm::Brotli => {
+            let mut decoder = DecompressorWriter::new(writer, 4096);
+            io::copy(&mut source, &mut decoder).map_err(|_| DecompressError::Corrupted)?;
+            return decoder.into_inner().map_err(|_| DecompressError::Truncated);
+        }
+        CompressionAlgorithm::None => {
+            io::copy(&mut source, &mut writer).map_err(|_| DecompressError::Corrupted)?;
+            return Ok(writer);
+        }
+        CompressionAlgorithm::Zstd => StreamingDecoder::new(&mut source)
+            .map_err(|_| ())
+            .and_then(|mut decoder| io::copy(&mut decoder, &mut writer).map_err(|_| ())),
+        CompressionAlgorithm::Gzip => {
+            io::copy(&mut GzDecoder::new(&mut source), &mut writer).map_err(|_| ())
+        }
+    };
+
+    let at_end = source.fill_buf().map_or(false, |rest| rest.is_empty());
+    match copied {
+        Ok(_) if at_end => Ok(writer),
+        Ok(_) => Err(DecompressError::Corrupted),
+        // the stream needs more bytes than the reader has
+        Err(_) if at_end => Err(DecompressError::Truncated),
+        Err(_) => Err(DecompressError::Corrupted),
+    }
 }
 
 // Signs a message with a private key
-pub fn sign_blob_with_private_key(
+pub(crate) fn sign_blob_with_private_key(
     blob: &[u8],
     private_key: &SecretKey,
-) -> Result<(Vec<u8>, Vec<u8>), ()> {
+) -> (Vec<u8>, Vec<u8>) {
     let message = sha256d::Hash::hash(blob).to_byte_array();
     let secp = Secp256k1::new();
     let public_key = secp256k1::PublicKey::from_secret_key(&secp, private_key);
-    let msg = secp256k1::Message::from_slice(&message).unwrap();
+    let msg = secp256k1::Message::from_slice(&message).expect("Hash is 32 bytes");
     let sig = secp.sign_ecdsa(&msg, private_key);
-    Ok((
+    (
         sig.serialize_compact().to_vec(),
         public_key.serialize().to_vec(),
-    ))
+    )
+}
+
+// Signs a message with a private key, returns the BIP340 signature and the x-only public key
+pub(crate) fn sign_blob_schnorr_with_private_key(
+    blob: &[u8],
+    private_key: &SecretKey,
+) -> (Vec<u8>, Vec<u8>) {
+    let message = sha256d::Hash::hash(blob).to_byte_array();
+    let secp = Secp256k1::new();
+    let key_pair = secp256k1::KeyPair::from_secret_key(&secp, private_key);
+    let msg = secp256k1::Message::from_slice(&message).unwrap();
+    let sig = secp.sign_schnorr_no_aux_rand(&msg, &key_pair);
+    (
+        sig.as_ref().to_vec(),
+        key_pair.x_only_public_key().0.serialize().to_vec(),
+    )
+}
+
+// Tag of the signing message of envelopes with tags, see blob_signing_message
+const BLOB_SIGNING_TAG: &[u8] = b"bitcoin-da/blob";
+
+/// Message the blob signature is over, its sha256d is the signed hash.
+/// It commits to the metadata and extra tags of the envelope as well as to the body, so that
+/// none of them can be added, removed or changed without the key. The message starts like a
+/// BIP340 tagged hash, followed by the number of tags, the length prefixed (tag, value) pairs
+/// of the metadata in envelope order and of the extra tags sorted by tag, and the body.
+/// An envelope without any tag signs its bare body, as envelopes predating the message do
+pub fn blob_signing_message<'a>(
+    body: &[u8],
+    metadata: &InscriptionMetadata,
+    extra_tags: impl IntoIterator<Item = (&'a [u8], &'a [u8])>,
+) -> Vec<u8> {
+    let metadata_tags = metadata_tags(metadata);
+    let extra_tags: BTreeMap<_, _> = extra_tags.into_iter().collect();
+    if metadata_tags.is_empty() && extra_tags.is_empty() {
+        return body.to_vec();
+    }
+
+    let tag_hash = sha256::Hash::hash(BLOB_SIGNING_TAG);
+    let mut message = Vec::with_capacity(2 * 32 + 4 + body.len());
+    message.extend_from_slice(tag_hash.as_ref());
+    message.extend_from_slice(tag_hash.as_ref());
+    message.extend_from_slice(&((metadata_tags.len() + extra_tags.len()) as u32).to_le_bytes());
+    let tags = metadata_tags
+        .iter()
+        .map(|(tag, value)| (*tag, value.as_slice()))
+        .chain(extra_tags);
+    for (tag, value) in tags {
+        for bytes in [tag, value] {
+            message.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+            message.extend_from_slice(bytes);
+        }
+    }
+    message.extend_from_slice(body);
+
+    message
+}
+
+// (tag, value) pushes of the metadata in envelope order, integers are 8 bytes little endian
+// and enums a single byte
+fn metadata_tags(metadata: &InscriptionMetadata) -> Vec<(&'static [u8], Vec<u8>)> {
+    let mut tags = Vec::new();
+    if let Some(batch_number) = metadata.batch_number {
+        tags.push((BATCH_NUMBER_TAG, batch_number.to_le_bytes().to_vec()));
+    }
+    if let Some(timestamp) = metadata.timestamp {
+        tags.push((TIMESTAMP_TAG, timestamp.to_le_bytes().to_vec()));
+    }
+    if let Some(kind) = metadata.kind {
+        tags.push((KIND_TAG, vec![kind.to_u8()]));
+    }
+    if let Some(params_fingerprint) = metadata.params_fingerprint {
+        tags.push((PARAMS_TAG, params_fingerprint.to_vec()));
+    }
+    if let Some(chunk) = metadata.chunk {
+        tags.push((CHUNK_TAG, chunk.to_bytes().to_vec()));
+    }
+    if let Some(scheme) = metadata.signature_scheme {
+        tags.push((SCHEME_TAG, vec![scheme.to_u8()]));
+    }
+    if let Some(compression) = metadata.compression {
+        tags.push((COMPRESSION_TAG, vec![compression.to_u8()]));
+    }
+    if let Some(precommitment) = metadata.precommitment {
+        tags.push((PRECOMMITMENT_TAG, precommitment.to_vec()));
+    }
+
+    tags
+}
+
+// Reveal signature of the commit key, the sighash type byte follows the signature unless
+// it is the default one
+fn reveal_signature(signature: Signature, sighash_type: TapSighashType) -> Vec<u8> {
+    taproot::Signature {
+        sig: signature,
+        hash_ty: sighash_type,
+    }
+    .to_vec()
+}
+
+fn placeholder_reveal_witness(sighash_type: TapSighashType) -> Witness {
+    let signature = Signature::from_slice(&[0; SCHNORR_SIGNATURE_SIZE]).unwrap();
+    Witness::from_slice(&[reveal_signature(signature, sighash_type)])
 }
 
 #[allow(clippy::ptr_arg)]
@@ -67,12 +486,15 @@ fn get_size(
         version: 2,
     };
 
+    // inputs without placeholders are sized as taproot key path spends
     for i in 0..tx.input.len() {
-        tx.input[i].witness.push(
-            Signature::from_slice(&[0; SCHNORR_SIGNATURE_SIZE])
-                .unwrap()
-                .as_ref(),
-        );
+        if tx.input[i].witness.is_empty() && tx.input[i].script_sig.is_empty() {
+            tx.input[i].witness.push(
+                Signature::from_slice(&[0; SCHNORR_SIGNATURE_SIZE])
+                    .unwrap()
+                    .as_ref(),
+            );
+        }
     }
 
     #[allow(clippy::unnecessary_unwrap)]
@@ -84,79 +506,121 @@ fn get_size(
     tx.vsize()
 }
 
-fn choose_utxos(utxos: &[UTXO], amount: u64) -> Result<(Vec<UTXO>, u64), anyhow::Error> {
-    let mut bigger_utxos: Vec<&UTXO> = utxos.iter().filter(|utxo| utxo.amount >= amount).collect();
-    let mut sum: u64 = 0;
+// No output type is dust at this amount under the default dust relay fee of bitcoind, it is
+// the threshold of P2PKH outputs
+pub(crate) const DEFAULT_DUST_LIMIT: u64 = 546;
 
-    if !bigger_utxos.is_empty() {
-        // sort vec by amount (small first)
-        bigger_utxos.sort_by(|a, b| a.amount.cmp(&b.amount));
+// Default -dustrelayfee of bitcoind, in sat/vB
+pub const DEFAULT_DUST_RELAY_FEE_RATE: f64 = 3.0;
 
-        // single utxo will be enough
-        // so return the transaction
-        let utxo = bigger_utxos[0];
-        sum += utxo.amount;
+// Smallest amount an output to script_pubkey can carry without being dust, i.e. without
+// costing more to spend than it is worth at the dust relay fee, as computed by bitcoind
+// 330 sats for P2TR, 294 for P2WPKH and 546 for P2PKH at the default dust relay fee
+pub fn dust_threshold(script_pubkey: &Script, dust_relay_fee_rate: f64) -> u64 {
+    if script_pubkey.is_op_return() {
+        return 0;
+    }
 
-        Ok((vec![utxo.clone()], sum))
+    let output_size = bitcoin::consensus::encode::serialize(&TxOut {
+        value: 0,
+        script_pubkey: script_pubkey.to_owned(),
+    })
+    .len();
+    // outpoint, script length, sequence and the script sig or discounted witness
+    let spend_size = if script_pubkey.is_witness_program() {
+        32 + 4 + 1 + 107 / 4 + 4
     } else {
-        let mut smaller_utxos: Vec<&UTXO> =
-            utxos.iter().filter(|utxo| utxo.amount < amount).collect();
+        32 + 4 + 1 + 107 + 4
+    };
 
-        // sort vec by amount (large first)
-        smaller_utxos.sort_by(|a, b| b.amount.cmp(&a.amount));
+    ((output_size + spend_size) as f64 * dust_relay_fee_rate) as u64
+}
 
-        let mut chosen_utxos: Vec<UTXO> = vec![];
+// Whether the utxo is worth more than it costs to spend at the default dust relay fee
+fn is_above_dust(utxo: &UTXO) -> bool {
+    let dust_limit = ScriptBuf::from_hex(&utxo.script_pubkey)
+        .map_or(DEFAULT_DUST_LIMIT, |script| {
+            dust_threshold(&script, DEFAULT_DUST_RELAY_FEE_RATE)
+        });
 
-        for utxo in smaller_utxos {
-            sum += utxo.amount;
-            chosen_utxos.push(utxo.clone());
+    utxo.amount > dust_limit
+}
 
-            if sum >= amount {
-                break;
-            }
-        }
+// sat/vB a replacement tx has to pay on top of the fees of the txs it evicts, the default
+// incremental relay fee of bitcoind
+const INCREMENTAL_RELAY_FEE_RATE: f64 = 1.0;
 
-        if sum < amount {
-            return Err(anyhow!("not enough UTXOs"));
-        }
+// Excess of the inputs below dust_limit, or below min_change if it is set, is left as fee
+// rather than paid to a change output. With min_change, a changeless set of utxos is tried
+// first, then a set leaving at least min_change of change, before near-dust change is folded
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn build_commit_transaction(
+    utxos: Vec<UTXO>,
+    recipient: Address,
+    change_address: Address,
+    output_value: u64,
+    fee_rate: f64,
+    dust_limit: u64,
+    min_change: Option<u64>,
+    coin_selection: &dyn CoinSelection,
+) -> Result<Transaction, anyhow::Error> {
+    let output = TxOut {
+        value: output_value,
+        script_pubkey: recipient.script_pubkey(),
+    };
 
-        Ok((chosen_utxos, sum))
-    }
+    build_funded_transaction(
+        utxos,
+        output,
+        change_address,
+        fee_rate,
+        dust_limit,
+        min_change,
+        coin_selection,
+    )
 }
 
-fn build_commit_transaction(
+// Tx pre-committing to the blob hash with an OP_RETURN output, see spec::precommitment
+// It is funded like a commit tx, the OP_RETURN output carries no value so the inputs only
+// pay the fee and the change
+pub fn build_precommitment_transaction(
     utxos: Vec<UTXO>,
-    recipient: Address,
+    blob_hash: [u8; 32],
     change_address: Address,
-    output_value: u64,
     fee_rate: f64,
+    dust_limit: u64,
+    coin_selection: &dyn CoinSelection,
 ) -> Result<Transaction, anyhow::Error> {
-    // get single input single output transaction size
-    let mut size = get_size(
-        &vec![TxIn {
-            previous_output: OutPoint {
-                txid: Txid::from_str(
-                    "0000000000000000000000000000000000000000000000000000000000000000",
-                )
-                .unwrap(),
-                vout: 0,
-            },
-            script_sig: script::Builder::new().into_script(),
-            witness: Witness::new(),
-            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
-        }],
-        &vec![TxOut {
-            script_pubkey: recipient.clone().script_pubkey(),
-            value: output_value,
-        }],
-        None,
+    let output = TxOut {
+        value: 0,
+        script_pubkey: precommitment_script(blob_hash),
+    };
+
+    build_funded_transaction(
+        utxos,
+        output,
+        change_address,
+        fee_rate,
+        dust_limit,
         None,
-    );
-    let mut last_size = size;
+        coin_selection,
+    )
+}
 
+// Funds output from utxos at fee_rate, with a change output if the excess allows it
+fn build_funded_transaction(
+    utxos: Vec<UTXO>,
+    output: TxOut,
+    change_address: Address,
+    fee_rate: f64,
+    dust_limit: u64,
+    min_change: Option<u64>,
+    coin_selection: &dyn CoinSelection,
+) -> Result<Transaction, anyhow::Error> {
+    let output_value = output.value;
     let utxos: Vec<UTXO> = utxos
         .iter()
-        .filter(|utxo| utxo.spendable && utxo.solvable && utxo.amount > 546)
+        .filter(|utxo| utxo.spendable && utxo.solvable && is_above_dust(utxo))
         .cloned()
         .collect();
 
@@ -164,63 +628,227 @@ fn build_commit_transaction(
         return Err(anyhow::anyhow!("no spendable UTXOs"));
     }
 
-    let tx = loop {
-        let fee = ((last_size as f64) * fee_rate).ceil() as u64;
+    let fee = |vsize: usize| (vsize as f64 * fee_rate).ceil() as u64;
+    let change_output = TxOut {
+        value: 0,
+        script_pubkey: change_address.script_pubkey(),
+    };
+    let change_fee = fee(bitcoin::consensus::encode::serialize(&change_output).len());
+    let min_change = min_change.map(|min_change| min_change.max(dust_limit));
+
+    // utxos are selected by effective value, each pays for its own input, so the target is
+    // the output value and the fee of the rest of the tx. Rounding the vsize of the whole tx
+    // may cost a few sats more, the target is raised by them until the inputs pay the fee
+    let effective_utxos = effective_values(&utxos, fee_rate);
+    let mut target = output_value
+        + (weight_without_inputs(std::slice::from_ref(&output)) as f64 / 4.0 * fee_rate).ceil()
+            as u64;
+    let (tx, sum) = loop {
+        let (selected, _) = match min_change {
+            Some(min_change) => select_avoiding_change(
+                coin_selection,
+                &effective_utxos,
+                target,
+                dust_limit,
+                change_fee + min_change,
+            )?,
+            None => coin_selection.select(&effective_utxos, target)?,
+        };
+        let sum: u64 = selected
+            .iter()
+            .filter_map(|selected| {
+                utxos
+                    .iter()
+                    .find(|utxo| utxo.tx_id == selected.tx_id && utxo.vout == selected.vout)
+            })
+            .map(|utxo| utxo.amount)
+            .sum();
+
+        let tx = Transaction {
+            lock_time: LockTime::ZERO,
+            version: 2,
+            input: selected
+                .iter()
+                .map(|u| TxIn {
+                    previous_output: OutPoint {
+                        txid: u.tx_id,
+                        vout: u.vout,
+                    },
+                    script_sig: script::Builder::new().into_script(),
+                    witness: Witness::new(),
+                    sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                })
+                .collect(),
+            output: vec![output.clone()],
+        };
+
+        let required = output_value + fee(with_placeholder_signatures(&tx, &utxos).vsize());
+        if sum >= required {
+            break (tx, sum);
+        }
+        target += required - sum;
+    };
+
+    // the change pays for its own output, if dust is left, leave it for fee
+    let mut with_change = tx.clone();
+    with_change.output.push(change_output);
+    let fee_with_change = fee(with_placeholder_signatures(&with_change, &utxos).vsize());
+    match sum.checked_sub(output_value + fee_with_change) {
+        Some(change) if change >= min_change.unwrap_or(dust_limit) => {
+            with_change.output[1].value = change;
+            Ok(with_change)
+        }
+        _ => Ok(tx),
+    }
+}
+
+// Weight of a signed input spending utxo, inputs of unknown script types are sized as
+// taproot key path spends
+fn input_weight(utxo: &UTXO) -> usize {
+    let script_type = utxo.script_type().unwrap_or(InputScriptType::P2tr);
+    let input = TxIn {
+        script_sig: script_type.script_sig(),
+        witness: script_type.witness(),
+        ..Default::default()
+    };
 
-        let input_total = output_value + fee;
+    input.segwit_weight()
+}
 
-        let res = choose_utxos(&utxos, input_total)?;
+// Weight of a segwit tx paying outputs, before any input is added
+fn weight_without_inputs(outputs: &[TxOut]) -> usize {
+    let tx = Transaction {
+        lock_time: LockTime::ZERO,
+        version: 2,
+        input: vec![],
+        output: outputs.to_vec(),
+    };
 
-        let (chosen_utxos, sum) = res;
+    // the segwit marker and flag weigh 2 WU
+    tx.weight().to_wu() as usize + 2
+}
 
-        let mut outputs: Vec<TxOut> = vec![];
+// Utxos valued at their effective value, their amount less the fee of their own input at
+// fee_rate. Utxos which cannot pay for their input are left out
+fn effective_values(utxos: &[UTXO], fee_rate: f64) -> Vec<UTXO> {
+    utxos
+        .iter()
+        .filter_map(|utxo| {
+            let input_fee = (input_weight(utxo) as f64 / 4.0 * fee_rate) as u64;
+            Some(UTXO {
+                amount: utxo
+                    .amount
+                    .checked_sub(input_fee)
+                    .filter(|amount| *amount > 0)?,
+                ..utxo.clone()
+            })
+        })
+        .collect()
+}
 
-        outputs.push(TxOut {
-            value: output_value,
-            script_pubkey: recipient.script_pubkey(),
-        });
+// Utxos worth amount without a change output, else utxos worth amount plus change_cost, the
+// fee of the change output and its smallest value, else utxos whose excess goes to the fee
+fn select_avoiding_change(
+    coin_selection: &dyn CoinSelection,
+    utxos: &[UTXO],
+    amount: u64,
+    dust_limit: u64,
+    change_cost: u64,
+) -> Result<(Vec<UTXO>, u64), anyhow::Error> {
+    let changeless = BranchAndBound {
+        cost_of_change: dust_limit,
+    };
+    if let Some(selected) = changeless.select_changeless(utxos, amount) {
+        return Ok(selected);
+    }
 
-        let mut direct_return = false;
-        if let Some(excess) = sum.checked_sub(input_total) {
-            if excess >= 546 {
-                outputs.push(TxOut {
-                    value: excess,
-                    script_pubkey: change_address.script_pubkey(),
-                });
-            } else {
-                // if dust is left, leave it for fee
-                direct_return = true;
-            }
-        }
+    coin_selection
+        .select(utxos, amount + change_cost)
+        .or_else(|_| coin_selection.select(utxos, amount))
+}
+
+// The commit tx with the placeholder signatures it was sized with by build_commit_transaction
+// Inputs of unknown script types are sized as taproot key path spends
+pub(crate) fn with_placeholder_signatures(tx: &Transaction, utxos: &[UTXO]) -> Transaction {
+    let mut tx = tx.clone();
 
-        let inputs = chosen_utxos
+    for input in tx.input.iter_mut() {
+        let script_type = utxos
             .iter()
-            .map(|u| TxIn {
-                previous_output: OutPoint {
-                    txid: u.tx_id,
-                    vout: u.vout,
-                },
-                script_sig: script::Builder::new().into_script(),
-                witness: Witness::new(),
-                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            .find(|utxo| {
+                utxo.tx_id == input.previous_output.txid && utxo.vout == input.previous_output.vout
             })
-            .collect();
+            .and_then(UTXO::script_type)
+            .unwrap_or(InputScriptType::P2tr);
 
-        size = get_size(&inputs, &outputs, None, None);
+        input.script_sig = script_type.script_sig();
+        input.witness = script_type.witness();
+    }
 
-        if size == last_size || direct_return {
-            break Transaction {
-                lock_time: LockTime::ZERO,
-                version: 2,
-                input: inputs,
-                output: outputs,
-            };
+    tx
+}
+
+#[derive(Debug, Error)]
+pub enum PsbtError {
+    #[error("output {0} spent by the commit tx is not one of the utxos")]
+    MissingUtxo(OutPoint),
+    #[error("invalid script pubkey of utxo {0}")]
+    InvalidScriptPubkey(OutPoint),
+    #[error(transparent)]
+    Psbt(#[from] bitcoin::psbt::Error),
+}
+
+// Unsigned commit tx as a PSBT, for any PSBT capable wallet or signing device to sign
+// instead of bitcoind. The spent outputs are filled in as witness UTXOs from the utxos the tx
+// was built from. P2PKH inputs are left without, their signer needs the whole previous tx
+pub fn commit_psbt(commit_tx: &Transaction, utxos: &[UTXO]) -> Result<Psbt, PsbtError> {
+    let mut psbt = Psbt::from_unsigned_tx(commit_tx.clone())?;
+
+    for (input, psbt_input) in commit_tx.input.iter().zip(psbt.inputs.iter_mut()) {
+        let outpoint = input.previous_output;
+        let utxo = utxos
+            .iter()
+            .find(|utxo| utxo.tx_id == outpoint.txid && utxo.vout == outpoint.vout)
+            .ok_or(PsbtError::MissingUtxo(outpoint))?;
+        let script_pubkey = ScriptBuf::from_hex(&utxo.script_pubkey)
+            .map_err(|_| PsbtError::InvalidScriptPubkey(outpoint))?;
+
+        if !script_pubkey.is_p2pkh() {
+            psbt_input.witness_utxo = Some(TxOut {
+                value: utxo.amount,
+                script_pubkey,
+            });
         }
+    }
+
+    Ok(psbt)
+}
 
-        last_size = size;
+// Value of the commit output spent by a reveal tx with a single output, the output value
+// and the fee of the reveal tx at the fee rate (ord-style postage)
+// The reveal input is sized with a schnorr signature of the sighash type, the reveal script
+// and the control block
+fn reveal_postage(
+    reveal_output: &TxOut,
+    reveal_script: &ScriptBuf,
+    control_block: &ControlBlock,
+    sighash_type: TapSighashType,
+    fee_rate: f64,
+) -> u64 {
+    let input = TxIn {
+        previous_output: OutPoint::null(),
+        script_sig: ScriptBuf::new(),
+        witness: placeholder_reveal_witness(sighash_type),
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
     };
+    let size = get_size(
+        &vec![input],
+        &vec![reveal_output.clone()],
+        Some(reveal_script),
+        Some(control_block),
+    );
 
-    Ok(tx)
+    reveal_output.value + ((size as f64) * fee_rate).ceil() as u64
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -233,6 +861,7 @@ fn build_reveal_transaction(
     fee_rate: f64,
     reveal_script: &ScriptBuf,
     control_block: &ControlBlock,
+    sighash_type: TapSighashType,
 ) -> Result<Transaction, anyhow::Error> {
     let outputs: Vec<TxOut> = vec![TxOut {
         value: output_value,
@@ -249,13 +878,17 @@ fn build_reveal_transaction(
         sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
     }];
 
-    let size = get_size(&inputs, &outputs, Some(reveal_script), Some(control_block));
-
-    let fee = ((size as f64) * fee_rate).ceil() as u64;
-
-    let input_total = output_value + fee;
+    let input_total = reveal_postage(
+        &outputs[0],
+        reveal_script,
+        control_block,
+        sighash_type,
+        fee_rate,
+    );
 
-    if input_utxo.value < 546 || input_utxo.value < input_total {
+    if input_utxo.value < dust_threshold(&input_utxo.script_pubkey, DEFAULT_DUST_RELAY_FEE_RATE)
+        || input_utxo.value < input_total
+    {
         return Err(anyhow::anyhow!("input UTXO not big enough"));
     }
 
@@ -269,34 +902,66 @@ fn build_reveal_transaction(
     Ok(tx)
 }
 
-// TODO: parametrize hardness
-// so tests are easier
-// Creates the inscription transactions (commit and reveal)
+// Builds the tapscript carrying the inscription envelope, followed by one envelope
+// per batched blob
 #[allow(clippy::too_many_arguments)]
-pub fn create_inscription_transactions(
+fn build_reveal_script(
+    public_key: &XOnlyPublicKey,
     rollup_name: &str,
-    body: Vec<u8>,
-    signature: Vec<u8>,
-    sequencer_public_key: Vec<u8>,
-    utxos: Vec<UTXO>,
-    recipient: Address,
-    reveal_value: u64,
-    commit_fee_rate: f64,
-    reveal_fee_rate: f64,
-    network: Network,
-    reveal_tx_prefix: &[u8],
-) -> Result<(Transaction, Transaction), anyhow::Error> {
-    // Create commit key
-    let secp256k1 = Secp256k1::new();
-    let key_pair = UntweakedKeyPair::new(&secp256k1, &mut rand::thread_rng());
-    let (public_key, _parity) = XOnlyPublicKey::from_keypair(&key_pair);
+    signature: &[u8],
+    sequencer_public_key: &[u8],
+    nonce: i64,
+    metadata: InscriptionMetadata,
+    extra_tags: &[(Vec<u8>, Vec<u8>)],
+    body: &[u8],
+    batched_blobs: &[SignedBlob],
+) -> ScriptBuf {
+    let mut reveal_script_builder = script::Builder::new()
+        .push_x_only_key(public_key)
+        .push_opcode(OP_CHECKSIG);
+
+    reveal_script_builder = push_envelope(
+        reveal_script_builder,
+        rollup_name,
+        signature,
+        sequencer_public_key,
+        nonce,
+        metadata,
+        extra_tags,
+        body,
+    );
+    for blob in batched_blobs {
+        reveal_script_builder = push_envelope(
+            reveal_script_builder,
+            rollup_name,
+            &blob.signature,
+            &blob.public_key,
+            nonce,
+            blob.metadata,
+            &[],
+            &blob.body,
+        );
+    }
 
-    // start creating inscription content
-    let reveal_script_builder = script::Builder::new()
-        .push_x_only_key(&public_key)
-        .push_opcode(OP_CHECKSIG)
+    reveal_script_builder.into_script()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn push_envelope(
+    reveal_script_builder: script::Builder,
+    rollup_name: &str,
+    signature: &[u8],
+    sequencer_public_key: &[u8],
+    nonce: i64,
+    metadata: InscriptionMetadata,
+    extra_tags: &[(Vec<u8>, Vec<u8>)],
+    body: &[u8],
+) -> script::Builder {
+    let mut reveal_script_builder = reveal_script_builder
         .push_opcode(OP_FALSE)
         .push_opcode(OP_IF)
+        .push_slice(PushBytesBuf::try_from(VERSION_TAG.to_vec()).expect("Cannot push version tag"))
+        .push_slice([ENVELOPE_VERSION])
         .push_slice(PushBytesBuf::try_from(ROLLUP_NAME_TAG.to_vec()).expect("Cannot push tag"))
         .push_slice(
             PushBytesBuf::try_from(rollup_name.as_bytes().to_vec())
@@ -305,93 +970,1743 @@ pub fn create_inscription_transactions(
         .push_slice(
             PushBytesBuf::try_from(SIGNATURE_TAG.to_vec()).expect("Cannot push signature tag"),
         )
-        .push_slice(PushBytesBuf::try_from(signature).expect("Cannot push signature"))
+        .push_slice(PushBytesBuf::try_from(signature.to_vec()).expect("Cannot push signature"))
         .push_slice(
             PushBytesBuf::try_from(PUBLICKEY_TAG.to_vec()).expect("Cannot push public key tag"),
         )
         .push_slice(
-            PushBytesBuf::try_from(sequencer_public_key).expect("Cannot push sequencer public key"),
+            PushBytesBuf::try_from(sequencer_public_key.to_vec())
+                .expect("Cannot push sequencer public key"),
         )
-        .push_slice(PushBytesBuf::try_from(RANDOM_TAG.to_vec()).expect("Cannot push random tag"));
-    // This envelope is not finished yet. The random number will be added later and followed by the body
+        .push_slice(PushBytesBuf::try_from(RANDOM_TAG.to_vec()).expect("Cannot push random tag"))
+        // push random number, as data so that small numbers are not encoded as opcodes
+        .push_slice(nonce.to_le_bytes());
 
-    // Start loop to find a 'nonce' i.e. random number that makes the reveal tx hash starting with zeros given length
-    let mut nonce: i64 = 0;
-    loop {
-        let utxos = utxos.clone();
-        let recipient = recipient.clone();
-        // ownerships are moved to the loop
-        let mut reveal_script_builder = reveal_script_builder.clone();
+    // push optional metadata tags
+    for (tag, value) in metadata_tags(&metadata) {
+        reveal_script_builder = reveal_script_builder
+            .push_slice(PushBytesBuf::try_from(tag.to_vec()).expect("Cannot push metadata tag"))
+            .push_slice(PushBytesBuf::try_from(value).expect("Cannot push metadata value"));
+    }
+    reveal_script_builder = reveal_script_builder
+        .push_slice(PushBytesBuf::try_from(BODY_ROOT_TAG.to_vec()).expect("Cannot push root tag"))
+        .push_slice(body_root(body));
 
-        // push first random number and body tag
+    // push the tags of the caller after the known ones
+    for (tag, value) in extra_tags {
         reveal_script_builder = reveal_script_builder
-            .push_int(nonce)
-            .push_slice(PushBytesBuf::try_from(BODY_TAG.to_vec()).expect("Cannot push body tag"));
+            .push_slice(PushBytesBuf::try_from(tag.clone()).expect("Cannot push extra tag"))
+            .push_slice(PushBytesBuf::try_from(value.clone()).expect("Cannot push extra value"));
+    }
 
-        // push body in chunks of 520 bytes
-        for chunk in body.chunks(520) {
-            reveal_script_builder = reveal_script_builder.push_slice(
-                PushBytesBuf::try_from(chunk.to_vec()).expect("Cannot push body chunk"),
-            );
-        }
-        // push end if
-        reveal_script_builder = reveal_script_builder.push_opcode(OP_ENDIF);
+    // push body tag
+    reveal_script_builder = reveal_script_builder
+        .push_slice(PushBytesBuf::try_from(BODY_TAG.to_vec()).expect("Cannot push body tag"));
 
-        // finalize reveal script
-        let reveal_script = reveal_script_builder.into_script();
+    // push body in chunks of 520 bytes, without copying them first
+    for chunk in body.chunks(520) {
+        reveal_script_builder = reveal_script_builder
+            .push_slice(<&PushBytes>::try_from(chunk).expect("Cannot push body chunk"));
+    }
 
-        // create spend info for tapscript
-        let taproot_spend_info = TaprootBuilder::new()
-            .add_leaf(0, reveal_script.clone())
-            .expect("Cannot add reveal script to taptree")
-            .finalize(&secp256k1, public_key)
-            .expect("Cannot finalize taptree");
+    // push end if
+    reveal_script_builder.push_opcode(OP_ENDIF)
+}
 
-        // create control block for tapscript
-        let control_block = taproot_spend_info
+// Size of a script push of len bytes, pushes above 75 bytes need OP_PUSHDATA1/2/4
+fn push_size(len: usize) -> usize {
+    let length_prefix = match len {
+        0..=75 => 0,
+        76..=255 => 1,
+        256..=65535 => 2,
+        _ => 4,
+    };
+
+    1 + length_prefix + len
+}
+
+// Size of the compact size prefix of a witness item of len bytes
+fn compact_size_len(len: usize) -> usize {
+    match len {
+        0..=252 => 1,
+        253..=65535 => 3,
+        _ => 5,
+    }
+}
+
+/// Virtual size of a reveal tx inscribing a body of body_len bytes.
+/// It is exact for the worst case envelope, a rollup name of MAX_ROLLUP_NAME_LEN bytes
+/// with all metadata tags, the longest BIP-322 signature (P2WPKH) and the longest BIP-322
+/// script pubkey (P2TR). It is an upper bound for any other envelope without extra tags.
+/// The recipient is assumed to be P2TR, the largest standard single key output
+pub fn reveal_vsize_for_body_len(body_len: usize) -> usize {
+    // the body is pushed in chunks of up to 520 bytes, each with its own push opcode
+    let body_size: usize = (0..body_len)
+        .step_by(520)
+        .map(|offset| push_size((body_len - offset).min(520)))
+        .sum();
+
+    let tag_size = push_size(1);
+    let script_size = push_size(32) // commit key
+        + 3 // OP_CHECKSIG OP_FALSE OP_IF
+        + tag_size + push_size(1) // envelope version
+        + tag_size + push_size(MAX_ROLLUP_NAME_LEN)
+        + tag_size + push_size(bip322::MAX_SIMPLE_SIGNATURE_LEN) // a BIP-322 signature
+        + tag_size + push_size(bip322::MAX_SCRIPT_PUBKEY_LEN) // and its P2TR script pubkey
+        + tag_size + push_size(8) // nonce
+        + 2 * (tag_size + push_size(8)) // batch number and timestamp
+        + tag_size + push_size(1) // kind
+        + tag_size + push_size(8) // chain params fingerprint
+        + tag_size + push_size(ChunkManifest::ENCODED_LEN) // chunk manifest
+        + tag_size + push_size(1) // signature scheme
+        + tag_size + push_size(1) // compression algorithm
+        + tag_size + push_size(32) // pre-commitment txid
+        + tag_size + push_size(32) // body root
+        + push_size(0) // body tag
+        + body_size
+        + 1; // OP_ENDIF
+
+    // version, input count, outpoint, empty script sig, sequence, output count,
+    // value, P2TR script pubkey and lock time
+    let base_size = 4 + 1 + 36 + 1 + 4 + 1 + 8 + 1 + 34 + 4;
+    // segwit marker and flag, item count, schnorr signature, script and the control block
+    // of a single leaf taptree
+    let witness_size = 2
+        + 1
+        + compact_size_len(SCHNORR_SIGNATURE_SIZE)
+        + SCHNORR_SIGNATURE_SIZE
+        + compact_size_len(script_size)
+        + script_size
+        + compact_size_len(33)
+        + 33;
+
+    // witness bytes weigh 1 weight unit, the others 4, a vbyte is 4 weight units
+    (base_size * 4 + witness_size + 3) / 4
+}
+
+/// Sizes and fees of an inscription, for balance checks and quotes of DA costs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InscriptionCost {
+    pub commit_vsize: usize,
+    pub reveal_vsize: usize,
+    pub commit_fee: u64,
+    pub reveal_fee: u64,
+    // value of the reveal output, it stays with the recipient
+    pub reveal_value: u64,
+}
+
+impl InscriptionCost {
+    pub fn fee(&self) -> u64 {
+        self.commit_fee + self.reveal_fee
+    }
+
+    // sats the commit inputs have to hold beside the change
+    pub fn total(&self) -> u64 {
+        self.fee() + self.reveal_value
+    }
+}
+
+/// Cost of inscribing a body of blob_len bytes, without utxos, keys or nonce grinding
+/// The reveal tx is sized as in reveal_vsize_for_body_len, the commit tx as spending a single
+/// P2TR utxo with a P2TR change output. More or larger inputs cost more
+pub fn estimate_inscription_cost(
+    blob_len: usize,
+    commit_fee_rate: f64,
+    reveal_fee_rate: f64,
+) -> Result<InscriptionCost, InscriptionError> {
+    if !(commit_fee_rate > 0.0 && reveal_fee_rate > 0.0) {
+        return Err(InscriptionError::InvalidFeeRates {
+            commit: commit_fee_rate,
+            reveal: reveal_fee_rate,
+        });
+    }
+
+    // OP_1 and a 32 byte push
+    let p2tr_output = TxOut {
+        value: 0,
+        script_pubkey: ScriptBuf::from_bytes([&[0x51, 0x20][..], &[0; 32]].concat()),
+    };
+    let commit_vsize = get_size(
+        &vec![TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: ScriptBuf::new(),
+            witness: Witness::new(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        }],
+        &vec![p2tr_output.clone(), p2tr_output],
+        None,
+        None,
+    );
+    let reveal_vsize = reveal_vsize_for_body_len(blob_len);
+
+    Ok(InscriptionCost {
+        commit_vsize,
+        reveal_vsize,
+        commit_fee: (commit_vsize as f64 * commit_fee_rate).ceil() as u64,
+        reveal_fee: (reveal_vsize as f64 * reveal_fee_rate).ceil() as u64,
+        reveal_value: REVEAL_OUTPUT_AMOUNT,
+    })
+}
+
+// Value of a pre-funded slot output that can pay, through a key path spend, for the commit
+// and reveal of a body of up to max_body_len bytes at the given fee rates.
+// Worst case envelope is assumed (see reveal_vsize_for_body_len) and the commit may have
+// a change output
+pub(crate) fn inscription_slot_value(
+    max_body_len: usize,
+    recipient: &Address,
+    reveal_value: u64,
+    commit_fee_rate: f64,
+    reveal_fee_rate: f64,
+) -> u64 {
+    let secp256k1 = Secp256k1::new();
+    let key_pair = secp256k1::KeyPair::from_seckey_slice(&secp256k1, &[1; 32])
+        .expect("Cannot create dummy key pair");
+    let (public_key, _parity) = XOnlyPublicKey::from_keypair(&key_pair);
+
+    let input = TxIn {
+        previous_output: OutPoint::null(),
+        script_sig: script::Builder::new().into_script(),
+        witness: Witness::new(),
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+    };
+    // the taptree does not change the size of the commit output
+    let commit_output = TxOut {
+        script_pubkey: Address::p2tr(&secp256k1, public_key, None, recipient.network)
+            .script_pubkey(),
+        value: 0,
+    };
+    let recipient_output = TxOut {
+        script_pubkey: recipient.script_pubkey(),
+        value: reveal_value,
+    };
+
+    let reveal_size = reveal_vsize_for_body_len(max_body_len);
+    let commit_size = get_size(
+        &vec![input],
+        &vec![commit_output, recipient_output],
+        None,
+        None,
+    );
+
+    let commit_value = (reveal_size as f64 * reveal_fee_rate + reveal_value as f64).ceil() as u64;
+
+    commit_value + (commit_size as f64 * commit_fee_rate).ceil() as u64
+}
+
+// Value left for the reveal output when a key path P2TR output of input_value funds, without
+// change, the commit and reveal of a body of body_len bytes at the given fee rates
+pub(crate) fn chained_reveal_value(
+    input_value: u64,
+    body_len: usize,
+    network: Network,
+    commit_fee_rate: f64,
+    reveal_fee_rate: f64,
+) -> Option<u64> {
+    let secp256k1 = Secp256k1::new();
+    let key_pair = secp256k1::KeyPair::from_seckey_slice(&secp256k1, &[1; 32])
+        .expect("Cannot create dummy key pair");
+    let (public_key, _parity) = XOnlyPublicKey::from_keypair(&key_pair);
+
+    let commit_size = get_size(
+        &vec![TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: script::Builder::new().into_script(),
+            witness: InputScriptType::P2tr.witness(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        }],
+        &vec![TxOut {
+            script_pubkey: Address::p2tr(&secp256k1, public_key, None, network).script_pubkey(),
+            value: 0,
+        }],
+        None,
+        None,
+    );
+    let reveal_size = reveal_vsize_for_body_len(body_len);
+
+    let fee = (commit_size as f64 * commit_fee_rate).ceil() as u64
+        + (reveal_size as f64 * reveal_fee_rate).ceil() as u64;
+
+    input_value.checked_sub(fee)
+}
+
+/// What the commit output of an inscription commits to: the taptree of the reveal script
+/// under the internal key. Stored next to a reveal tx, it lets anyone recompute the commit
+/// address without the commit key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitArtifacts {
+    pub internal_key: XOnlyPublicKey,
+    pub merkle_root: Option<TapNodeHash>,
+    pub network: Network,
+}
+
+impl CommitArtifacts {
+    // Recovers the artifacts from the script path spend of the commit output
+    pub fn from_reveal_tx(reveal_tx: &Transaction, network: Network) -> Option<Self> {
+        let witness = &reveal_tx.input.first()?.witness;
+        let script = witness.tapscript()?;
+
+        // the annex, if any, comes after the control block
+        let has_annex =
+            witness.len() >= 2 && witness.last()?.first() == Some(&TAPROOT_ANNEX_PREFIX);
+        let control_block =
+            ControlBlock::decode(witness.nth(witness.len() - 1 - usize::from(has_annex))?).ok()?;
+
+        let merkle_root = control_block.merkle_branch.as_inner().iter().fold(
+            TapNodeHash::from_script(script, control_block.leaf_version),
+            |node, sibling| TapNodeHash::from_node_hashes(node, *sibling),
+        );
+
+        Some(Self {
+            internal_key: control_block.internal_key,
+            merkle_root: Some(merkle_root),
+            network,
+        })
+    }
+}
+
+// Whether output 0 of the commit tx is the taproot output the artifacts commit to
+pub fn verify_commit_binding(artifacts: &CommitArtifacts, commit_tx: &Transaction) -> bool {
+    let address = Address::p2tr(
+        &Secp256k1::verification_only(),
+        artifacts.internal_key,
+        artifacts.merkle_root,
+        artifacts.network,
+    );
+
+    commit_tx.output.first().map_or(false, |output| {
+        output.script_pubkey == address.script_pubkey()
+    })
+}
+
+// Signs the input at index, which spends the key path of a P2TR output of key_pair with
+// a taptree of merkle_root, if any
+pub(crate) fn sign_key_path_input(
+    tx: &mut Transaction,
+    index: usize,
+    prevouts: &[TxOut],
+    key_pair: &secp256k1::KeyPair,
+    merkle_root: Option<TapNodeHash>,
+    entropy: &mut dyn EntropySource,
+) -> Result<(), anyhow::Error> {
+    let secp256k1 = Secp256k1::new();
+    let tweaked_key_pair = key_pair.tap_tweak(&secp256k1, merkle_root).to_inner();
+
+    let mut sighash_cache = SighashCache::new(tx);
+    let signature_hash = sighash_cache.taproot_key_spend_signature_hash(
+        index,
+        &Prevouts::All(prevouts),
+        bitcoin::sighash::TapSighashType::Default,
+    )?;
+
+    let signature = secp256k1.sign_schnorr_with_aux_rand(
+        &secp256k1::Message::from_slice(signature_hash.as_byte_array())
+            .expect("should be cryptographically secure hash"),
+        &tweaked_key_pair,
+        &random_bytes(entropy),
+    );
+
+    let witness = sighash_cache
+        .witness_mut(index)
+        .ok_or_else(|| anyhow!("input {} does not exist", index))?;
+    *witness = Witness::new();
+    witness.push(signature.as_ref());
+
+    Ok(())
+}
+
+// Signs the input at index, which spends an output of one of the single key script types
+// locked to secret_key. Taproot outputs are spent through the key path, others with SIGHASH_ALL
+pub(crate) fn sign_input(
+    tx: &mut Transaction,
+    index: usize,
+    prevouts: &[TxOut],
+    secret_key: &SecretKey,
+    entropy: &mut dyn EntropySource,
+) -> Result<(), anyhow::Error> {
+    let secp256k1 = Secp256k1::new();
+    let public_key = bitcoin::PublicKey::new(secret_key.public_key(&secp256k1));
+
+    let prevout = prevouts
+        .get(index)
+        .ok_or_else(|| anyhow!("prevout of input {} is missing", index))?;
+    let script_type = InputScriptType::from_script_pubkey(&prevout.script_pubkey)
+        .ok_or_else(|| anyhow!("input {} spends an unsupported script type", index))?;
+
+    if prevout.script_pubkey != script_type.script_pubkey(&public_key) {
+        return Err(anyhow!("input {} is not locked to the signing key", index));
+    }
+
+    let sighash_cache = SighashCache::new(&*tx);
+    let signature_hash = match script_type {
+        InputScriptType::P2tr => {
+            let key_pair = secp256k1::KeyPair::from_secret_key(&secp256k1, secret_key);
+            return sign_key_path_input(tx, index, prevouts, &key_pair, None, entropy);
+        }
+        InputScriptType::P2pkh => sighash_cache
+            .legacy_signature_hash(
+                index,
+                &prevout.script_pubkey,
+                EcdsaSighashType::All.to_u32(),
+            )?
+            .to_byte_array(),
+        // the script code of P2WPKH is the P2PKH script of the key
+        InputScriptType::P2wpkh | InputScriptType::P2shP2wpkh => {
+            let mut sighash_cache = sighash_cache;
+            sighash_cache
+                .segwit_signature_hash(
+                    index,
+                    &ScriptBuf::new_p2pkh(&public_key.pubkey_hash()),
+                    prevout.value,
+                    EcdsaSighashType::All,
+                )?
+                .to_byte_array()
+        }
+    };
+
+    let signature = bitcoin::ecdsa::Signature {
+        sig: secp256k1.sign_ecdsa_with_noncedata(
+            &secp256k1::Message::from_slice(&signature_hash)
+                .expect("should be cryptographically secure hash"),
+            secret_key,
+            &random_bytes(entropy),
+        ),
+        hash_ty: EcdsaSighashType::All,
+    };
+
+    let input = tx
+        .input
+        .get_mut(index)
+        .ok_or_else(|| anyhow!("input {} does not exist", index))?;
+
+    if script_type == InputScriptType::P2pkh {
+        input.script_sig = script::Builder::new()
+            .push_slice(PushBytesBuf::try_from(signature.to_vec())?)
+            .push_key(&public_key)
+            .into_script();
+        input.witness = Witness::new();
+    } else {
+        let mut witness = Witness::new();
+        witness.push(signature.to_vec());
+        witness.push(public_key.to_bytes());
+        input.witness = witness;
+
+        input.script_sig = if script_type == InputScriptType::P2shP2wpkh {
+            let redeem_script = ScriptBuf::new_v0_p2wpkh(
+                &public_key
+                    .wpubkey_hash()
+                    .expect("public key should be compressed"),
+            );
+            script::Builder::new()
+                .push_slice(PushBytesBuf::try_from(redeem_script.into_bytes())?)
+                .into_script()
+        } else {
+            ScriptBuf::new()
+        };
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Error)]
+pub enum RecoveryError {
+    // e.g. the internal key is a NUMS point, the output can only be spent by the reveal tx
+    #[error("commit key is not the internal key of the commit output")]
+    NotCommitKey,
+    #[error("commit output does not commit to the artifacts")]
+    CommitOutputMismatch,
+    #[error("commit output of {value} sats cannot pay the {fee} sats recovery fee")]
+    InsufficientValue { value: u64, fee: u64 },
+    #[error("cannot sign the recovery tx: {0}")]
+    Signing(anyhow::Error),
+}
+
+// Sweeps the commit output of a reveal tx that never confirmed to recipient, through the key
+// path of the commit key. The artifacts can be recovered from the reveal tx, see
+// CommitArtifacts::from_reveal_tx. Commit outputs with a NUMS internal key cannot be recovered
+pub fn build_recovery_transaction(
+    commit_outpoint: OutPoint,
+    commit_output: TxOut,
+    artifacts: &CommitArtifacts,
+    commit_key: &secp256k1::KeyPair,
+    recipient: Address,
+    fee_rate: f64,
+    entropy: &mut dyn EntropySource,
+) -> Result<Transaction, RecoveryError> {
+    if commit_key.x_only_public_key().0 != artifacts.internal_key {
+        return Err(RecoveryError::NotCommitKey);
+    }
+
+    let commit_address = Address::p2tr(
+        &Secp256k1::verification_only(),
+        artifacts.internal_key,
+        artifacts.merkle_root,
+        artifacts.network,
+    );
+    if commit_output.script_pubkey != commit_address.script_pubkey() {
+        return Err(RecoveryError::CommitOutputMismatch);
+    }
+
+    let mut tx = Transaction {
+        lock_time: LockTime::ZERO,
+        version: 2,
+        input: vec![TxIn {
+            previous_output: commit_outpoint,
+            script_sig: ScriptBuf::new(),
+            witness: Witness::new(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        }],
+        output: vec![TxOut {
+            value: 0,
+            script_pubkey: recipient.script_pubkey(),
+        }],
+    };
+
+    let fee = (get_size(&tx.input, &tx.output, None, None) as f64 * fee_rate).ceil() as u64;
+    tx.output[0].value = commit_output
+        .value
+        .checked_sub(fee)
+        .filter(|value| {
+            *value >= dust_threshold(&recipient.script_pubkey(), DEFAULT_DUST_RELAY_FEE_RATE)
+        })
+        .ok_or(RecoveryError::InsufficientValue {
+            value: commit_output.value,
+            fee,
+        })?;
+
+    sign_key_path_input(
+        &mut tx,
+        0,
+        &[commit_output],
+        commit_key,
+        artifacts.merkle_root,
+        entropy,
+    )
+    .map_err(RecoveryError::Signing)?;
+
+    Ok(tx)
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ConsolidationError {
+    #[error("no utxo is worth more than the fee of spending it")]
+    NothingToSweep,
+    #[error("utxos of {value} sats cannot pay the {fee} sats consolidation fee")]
+    InsufficientValue { value: u64, fee: u64 },
+}
+
+// Sweeps utxos, e.g. the reveal outputs accumulated at the recipient, into a single output
+// to target_address, preferably while fees are low. Utxos worth less than the fee of their
+// own input at fee_rate are left out. Like a commit tx, the tx is signed by the wallet
+pub fn build_consolidation_transaction(
+    utxos: &[UTXO],
+    target_address: Address,
+    fee_rate: f64,
+) -> Result<Transaction, ConsolidationError> {
+    let swept: Vec<&UTXO> = utxos
+        .iter()
+        .filter(|utxo| utxo.amount as f64 > input_weight(utxo) as f64 / 4.0 * fee_rate)
+        .collect();
+    if swept.is_empty() {
+        return Err(ConsolidationError::NothingToSweep);
+    }
+
+    let mut tx = Transaction {
+        lock_time: LockTime::ZERO,
+        version: 2,
+        input: swept
+            .iter()
+            .map(|utxo| TxIn {
+                previous_output: OutPoint::new(utxo.tx_id, utxo.vout),
+                script_sig: ScriptBuf::new(),
+                witness: Witness::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            })
+            .collect(),
+        output: vec![TxOut {
+            value: 0,
+            script_pubkey: target_address.script_pubkey(),
+        }],
+    };
+
+    let value: u64 = swept.iter().map(|utxo| utxo.amount).sum();
+    let fee = (with_placeholder_signatures(&tx, utxos).vsize() as f64 * fee_rate).ceil() as u64;
+    tx.output[0].value = value
+        .checked_sub(fee)
+        .filter(|value| {
+            *value >= dust_threshold(&target_address.script_pubkey(), DEFAULT_DUST_RELAY_FEE_RATE)
+        })
+        .ok_or(ConsolidationError::InsufficientValue { value, fee })?;
+
+    Ok(tx)
+}
+
+// x coordinate of H from BIP341, a point with no known discrete logarithm
+const NUMS_INTERNAL_KEY: [u8; 32] = [
+    0x50, 0x92, 0x9b, 0x74, 0xc1, 0xa0, 0x49, 0x54, 0xb7, 0x8b, 0x4b, 0x60, 0x35, 0xe9, 0x7a, 0x5e,
+    0x07, 0x8a, 0x5a, 0x0f, 0x28, 0xec, 0x96, 0xd5, 0x47, 0xbf, 0xee, 0x9a, 0xce, 0x80, 0x3a, 0xc0,
+];
+
+/// Internal key of the taptree of the commit output. Either way the commit output is a plain
+/// P2TR output, like any key path payment, until the reveal tx shows the inscription script
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CommitInternalKey {
+    // the throwaway commit key, which can also spend the commit output by key path
+    #[default]
+    CommitKey,
+    // a NUMS point, the commit output can only be spent by revealing the inscription
+    Nums,
+}
+
+impl CommitInternalKey {
+    fn public_key(&self, commit_key: XOnlyPublicKey) -> XOnlyPublicKey {
+        match self {
+            CommitInternalKey::CommitKey => commit_key,
+            CommitInternalKey::Nums => {
+                XOnlyPublicKey::from_slice(&NUMS_INTERNAL_KEY).expect("NUMS point is on the curve")
+            }
+        }
+    }
+}
+
+/// Compressed blob with its signature, inscribed in an envelope of its own
+/// when it is batched into the reveal tx of another blob
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedBlob {
+    pub body: Vec<u8>,
+    pub signature: Vec<u8>,
+    pub public_key: Vec<u8>,
+    pub metadata: InscriptionMetadata,
+}
+
+// InscriptionBuilder creates the inscription transactions (commit and reveal)
+// The commit tx is returned unsigned, the reveal tx is signed with the commit key,
+// a throwaway one unless it is set
+pub struct InscriptionBuilder<'a> {
+    rollup_name: String,
+    body: Vec<u8>,
+    signature: Vec<u8>,
+    sequencer_public_key: Vec<u8>,
+    metadata: InscriptionMetadata,
+    extra_tags: Vec<(Vec<u8>, Vec<u8>)>,
+    batched_blobs: Vec<SignedBlob>,
+    utxos: Vec<UTXO>,
+    recipient: Option<Address>,
+    change_address: Option<Address>,
+    reveal_value: u64,
+    fee_rates: Option<(f64, f64)>,
+    network: Network,
+    internal_key: CommitInternalKey,
+    pow_prefix: Vec<u8>,
+    pow_zero_bits: u8,
+    skip_pow: bool,
+    // set by dry_run, the nonce is not ground whatever the proof of work
+    dry_run: bool,
+    grind: GrindControl<'a>,
+    tip_height: Option<u32>,
+    reveal_sighash_type: TapSighashType,
+    dust_limit: Option<u64>,
+    min_change: Option<u64>,
+    fee_cap: FeeCap,
+    coin_selection: Arc<dyn CoinSelection>,
+    min_confirmations: u64,
+    max_ancestor_count: Option<u32>,
+    commit_key: Option<secp256k1::KeyPair>,
+    entropy: Option<&'a mut (dyn EntropySource + Send)>,
+}
+
+// Hard limits on the fees paid per submission, applied regardless of the fee estimator
+// Guards against a misconfigured fee rate or a huge blob burning a whole UTXO
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FeeCap {
+    // sats per vbyte
+    pub max_fee_rate: Option<f64>,
+    // sats paid by the commit and reveal transactions together
+    pub max_fee: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Error)]
+pub enum FeeCapError {
+    #[error("fee rate {fee_rate} sat/vB exceeds the cap of {max_fee_rate} sat/vB")]
+    FeeRate { fee_rate: f64, max_fee_rate: f64 },
+    #[error("fee of {fee} sats exceeds the cap of {max_fee} sats")]
+    Fee { fee: u64, max_fee: u64 },
+}
+
+impl FeeCap {
+    pub fn check_fee_rate(&self, fee_rate: f64) -> Result<(), FeeCapError> {
+        match self.max_fee_rate {
+            Some(max_fee_rate) if fee_rate > max_fee_rate => Err(FeeCapError::FeeRate {
+                fee_rate,
+                max_fee_rate,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn check_fee(&self, fee: u64) -> Result<(), FeeCapError> {
+        match self.max_fee {
+            Some(max_fee) if fee > max_fee => Err(FeeCapError::Fee { fee, max_fee }),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum InscriptionError {
+    #[error(transparent)]
+    InvalidRollupName(#[from] RollupNameError),
+    #[error("inscription body is empty")]
+    EmptyBody,
+    #[error("inscription body is not signed")]
+    MissingSignature,
+    #[error("recipient address is not set")]
+    MissingRecipient,
+    #[error("fee rates are not set")]
+    MissingFeeRates,
+    #[error("reveal value of {value} sats is below the dust threshold of {threshold} sats")]
+    RevealValueBelowDust { value: u64, threshold: u64 },
+    #[error("proof of work is skipped but the reveal txid has a prefix or zero bits to meet")]
+    SkippedPow,
+    #[error("nonce grinding was cancelled")]
+    GrindCancelled,
+    #[error("tip height {0} is not a block height lock time")]
+    InvalidTipHeight(u32),
+    #[error("reveal signature cannot use sighash type {sighash_type}: {reason}")]
+    UnsupportedSighashType {
+        sighash_type: TapSighashType,
+        reason: &'static str,
+    },
+    #[error("{tx} tx is not standard: {error}")]
+    NonStandard {
+        tx: &'static str,
+        error: PolicyError,
+    },
+    #[error("invalid extra tag {tag:?}: {reason}")]
+    InvalidExtraTag { tag: Vec<u8>, reason: &'static str },
+    #[error("invalid fee rates, commit: {commit} sat/vB, reveal: {reveal} sat/vB")]
+    InvalidFeeRates { commit: f64, reveal: f64 },
+    #[error("cannot build the commit tx: {0}")]
+    Commit(anyhow::Error),
+    #[error("cannot build the reveal tx: {0}")]
+    Reveal(anyhow::Error),
+    #[error("cannot build the commit PSBT: {0}")]
+    Psbt(#[from] PsbtError),
+    #[error("reveal signature does not verify under the commit key")]
+    InvalidRevealSignature,
+    #[error("commit and reveal txs exceed the fee cap: {0}")]
+    AboveFeeCap(#[from] FeeCapError),
+    #[error("cannot bump the fee of the inscription: {0}")]
+    Bump(anyhow::Error),
+    #[error(
+        "replacement commit tx pays {fee} sats of fees, it has to pay at least {min_fee} sats"
+    )]
+    ReplacementFeeTooLow { fee: u64, min_fee: u64 },
+}
+
+impl<'a> InscriptionBuilder<'a> {
+    pub fn new(rollup_name: &str) -> Self {
+        Self {
+            rollup_name: rollup_name.to_string(),
+            body: Vec::new(),
+            signature: Vec::new(),
+            sequencer_public_key: Vec::new(),
+            metadata: InscriptionMetadata::default(),
+            extra_tags: Vec::new(),
+            batched_blobs: Vec::new(),
+            utxos: Vec::new(),
+            recipient: None,
+            change_address: None,
+            reveal_value: REVEAL_OUTPUT_AMOUNT,
+            fee_rates: None,
+            network: Network::Bitcoin,
+            internal_key: CommitInternalKey::default(),
+            pow_prefix: Vec::new(),
+            pow_zero_bits: 0,
+            skip_pow: false,
+            dry_run: false,
+            grind: GrindControl::default(),
+            tip_height: None,
+            reveal_sighash_type: TapSighashType::Default,
+            dust_limit: None,
+            min_change: None,
+            fee_cap: FeeCap::default(),
+            coin_selection: Arc::new(SingleUtxoFirst),
+            min_confirmations: 0,
+            max_ancestor_count: None,
+            commit_key: None,
+            entropy: None,
+        }
+    }
+
+    // Compressed blob to inscribe
+    pub fn with_body(mut self, body: Vec<u8>) -> Self {
+        self.body = body;
+        self
+    }
+
+    // Signature of the body and the public key it verifies with
+    pub fn with_signature(mut self, signature: Vec<u8>, sequencer_public_key: Vec<u8>) -> Self {
+        self.signature = signature;
+        self.sequencer_public_key = sequencer_public_key;
+        self
+    }
+
+    pub fn with_metadata(mut self, metadata: InscriptionMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    // (tag, value) pushes written after the known tags of the envelope, e.g. the block range
+    // of a rollup batch. Tags are at least 2 bytes long, single byte tags are reserved for
+    // the envelope format. They are not covered by the signature of the body
+    pub fn with_extra_tags(mut self, extra_tags: Vec<(Vec<u8>, Vec<u8>)>) -> Self {
+        self.extra_tags = extra_tags;
+        self
+    }
+
+    // Further blob inscribed by the same reveal tx, in an envelope after the previous ones
+    pub fn with_batched_blob(mut self, blob: SignedBlob) -> Self {
+        self.batched_blobs.push(blob);
+        self
+    }
+
+    // Outputs the commit tx may spend
+    pub fn with_utxos(mut self, utxos: Vec<UTXO>) -> Self {
+        self.utxos = utxos;
+        self
+    }
+
+    // Receives the reveal output and, unless a change address is set, the change of the commit tx
+    pub fn with_recipient(mut self, recipient: Address) -> Self {
+        self.recipient = Some(recipient);
+        self
+    }
+
+    pub fn with_change_address(mut self, change_address: Address) -> Self {
+        self.change_address = Some(change_address);
+        self
+    }
+
+    // Value of the reveal output, 546 sats by default. A larger output can later pay to CPFP
+    // the reveal tx. It may not be dust for the recipient, see with_dust_limit
+    pub fn with_reveal_value(mut self, reveal_value: u64) -> Self {
+        self.reveal_value = reveal_value;
+        self
+    }
+
+    // sat/vB of the commit and the reveal tx
+    pub fn with_fee_rates(mut self, commit_fee_rate: f64, reveal_fee_rate: f64) -> Self {
+        self.fee_rates = Some((commit_fee_rate, reveal_fee_rate));
+        self
+    }
+
+    // Network of the commit address, mainnet by default
+    pub fn with_network(mut self, network: Network) -> Self {
+        self.network = network;
+        self
+    }
+
+    // Internal key of the commit output, the commit key by default
+    pub fn with_internal_key(mut self, internal_key: CommitInternalKey) -> Self {
+        self.internal_key = internal_key;
+        self
+    }
+
+    // Bytes the reveal txid has to start with
+    pub fn with_pow_prefix(mut self, pow_prefix: &[u8]) -> Self {
+        self.pow_prefix = pow_prefix.to_vec();
+        self
+    }
+
+    // Leading zero bits the reveal txid needs on top of the prefix
+    // Every extra bit doubles the expected number of reveal txs to grind
+    pub fn with_pow_zero_bits(mut self, pow_zero_bits: u8) -> Self {
+        self.pow_zero_bits = pow_zero_bits;
+        self
+    }
+
+    // Emits the reveal tx without grinding a nonce, for deployments whose relevance rule
+    // takes any txid (see RollupParams::requires_reveal_pow), e.g. on regtest
+    pub fn with_skip_pow(mut self, skip_pow: bool) -> Self {
+        self.skip_pow = skip_pow;
+        self
+    }
+
+    // Anti fee sniping as in the bitcoind wallet: both txs lock to the tip height, so they
+    // cannot be mined in a reorg of the tip meant to take their fees
+    pub fn with_tip_height(mut self, tip_height: u32) -> Self {
+        self.tip_height = Some(tip_height);
+        self
+    }
+
+    // Sighash type of the reveal signature, TapSighashType::Default by default
+    // SIGHASH_ALL|ANYONECANPAY lets a third party add inputs paying more fees to the reveal tx
+    pub fn with_reveal_sighash_type(mut self, sighash_type: TapSighashType) -> Self {
+        self.reveal_sighash_type = sighash_type;
+        self
+    }
+
+    // Smallest change output of the commit tx, smaller change is left as fee
+    // The dust threshold of the change address at the default dust relay fee by default
+    pub fn with_dust_limit(mut self, dust_limit: u64) -> Self {
+        self.dust_limit = Some(dust_limit);
+        self
+    }
+
+    // Smallest change output worth creating, utxos leaving no change or at least min_change
+    // are preferred. Near-dust change is otherwise left as fee, see InscriptionReceipt::overpaid
+    pub fn with_min_change(mut self, min_change: u64) -> Self {
+        self.min_change = Some(min_change);
+        self
+    }
+
+    // Fees the commit and reveal txs may pay, building fails above the cap. The fee rate
+    // cap applies to both txs, the fee cap to the fees they pay together
+    pub fn with_fee_cap(mut self, fee_cap: FeeCap) -> Self {
+        self.fee_cap = fee_cap;
+        self
+    }
+
+    // Picks the utxos of the commit tx, coin_selection::SingleUtxoFirst by default
+    pub fn with_coin_selection(mut self, coin_selection: Arc<dyn CoinSelection>) -> Self {
+        self.coin_selection = coin_selection;
+        self
+    }
+
+    // Confirmations a utxo needs to fund the commit tx, none by default
+    // Unconfirmed change is cheap to reuse but its replacement or eviction drops the inscription
+    pub fn with_min_confirmations(mut self, min_confirmations: u64) -> Self {
+        self.min_confirmations = min_confirmations;
+        self
+    }
+
+    // Most unconfirmed ancestors a utxo may have to fund the commit tx. The commit and reveal
+    // txs add two to the chain, whose length the mempool limits (25 by default)
+    pub fn with_max_ancestor_count(mut self, max_ancestor_count: u32) -> Self {
+        self.max_ancestor_count = Some(max_ancestor_count);
+        self
+    }
+
+    // Key that signs the reveal tx, e.g. one from entropy::derive_commit_key
+    // A random key drawn from the entropy by default
+    pub fn with_commit_key(mut self, commit_key: secp256k1::KeyPair) -> Self {
+        self.commit_key = Some(commit_key);
+        self
+    }
+
+    // Called from the grinding threads with the number of nonces tried, every that many
+    pub fn with_grind_progress(mut self, every: u64, callback: &'a (dyn Fn(u64) + Sync)) -> Self {
+        self.grind.progress = Some((every.max(1), callback));
+        self
+    }
+
+    // Stops the nonce grinding once cancelled, the build fails with GrindCancelled
+    pub fn with_grind_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.grind.cancellation = Some(cancellation);
+        self
+    }
+
+    // Randomness of the commit key and signature nonces, the OS rng by default
+    pub fn with_entropy(mut self, entropy: &'a mut (dyn EntropySource + Send)) -> Self {
+        self.entropy = Some(entropy);
+        self
+    }
+
+    pub fn build(self) -> Result<(Transaction, Transaction), InscriptionError> {
+        let (commit_tx, reveal_tx, _) = self.build_with_artifacts()?;
+
+        Ok((commit_tx, reveal_tx))
+    }
+
+    // Returns the commit tx as a PSBT to be signed by an external wallet, see commit_psbt
+    pub fn build_psbt(self) -> Result<(Psbt, Transaction), InscriptionError> {
+        let utxos = self.utxos.clone();
+        let (commit_tx, reveal_tx) = self.build()?;
+
+        Ok((commit_psbt(&commit_tx, &utxos)?, reveal_tx))
+    }
+
+    // Also returns what the commit output commits to, to be stored for audits
+    pub fn build_with_artifacts(
+        self,
+    ) -> Result<(Transaction, Transaction, CommitArtifacts), InscriptionError> {
+        let (commit_tx, reveal_tx, artifacts, _) = self.build_signed()?;
+
+        Ok((commit_tx, reveal_tx, artifacts))
+    }
+
+    // Preflight of a build: selects the utxos, builds the envelope and checks fees and policy,
+    // but neither grinds a nonce nor signs. The txs carry nonce 0 and, without a commit key
+    // set, a placeholder one, so only their txids differ from the ones build would return
+    // An error, e.g. utxos that are not enough, is the one build would fail with
+    pub fn dry_run(
+        mut self,
+    ) -> Result<(Transaction, Transaction, InscriptionReceipt), InscriptionError> {
+        let commit_public_key = match self.commit_key {
+            Some(commit_key) => commit_key.x_only_public_key().0,
+            None => {
+                XOnlyPublicKey::from_slice(&NUMS_INTERNAL_KEY).expect("NUMS point is on the curve")
+            }
+        };
+        self.dry_run = true;
+        let unsigned = self.build_unsigned(commit_public_key)?;
+
+        Ok((unsigned.commit_tx, unsigned.reveal_tx, unsigned.receipt))
+    }
+
+    // Also returns the utxos, sizes and fees of the txs, see InscriptionReceipt
+    pub fn build_with_receipt(
+        self,
+    ) -> Result<(Transaction, Transaction, InscriptionReceipt), InscriptionError> {
+        let (commit_tx, reveal_tx, _, receipt) = self.build_signed()?;
+
+        Ok((commit_tx, reveal_tx, receipt))
+    }
+
+    fn build_signed(
+        mut self,
+    ) -> Result<
+        (
+            Transaction,
+            Transaction,
+            CommitArtifacts,
+            InscriptionReceipt,
+        ),
+        InscriptionError,
+    > {
+        let mut os_rng = OsRng;
+        let entropy = match self.entropy.take() {
+            Some(entropy) => entropy,
+            None => &mut os_rng,
+        };
+
+        // Create commit key
+        let secp256k1 = Secp256k1::new();
+        let key_pair = match self.commit_key {
+            Some(commit_key) => commit_key,
+            None => random_key_pair(&secp256k1, entropy),
+        };
+        let (public_key, _parity) = XOnlyPublicKey::from_keypair(&key_pair);
+
+        let unsigned = self.build_unsigned(public_key)?;
+
+        // sign reveal tx data
+        let signature = secp256k1.sign_schnorr_with_aux_rand(
+            &secp256k1::Message::from_slice(&unsigned.reveal_sighash())
+                .expect("should be cryptographically secure hash"),
+            &key_pair,
+            &random_bytes(entropy),
+        );
+
+        let receipt = unsigned.receipt.clone();
+        let (commit_tx, reveal_tx, artifacts) = unsigned.finalize(&signature)?;
+
+        Ok((commit_tx, reveal_tx, artifacts, receipt))
+    }
+
+    // Builds the txs for a commit key held by a signer outside of the builder, e.g. a
+    // signer::Signer. The reveal tx is signed with UnsignedInscription::finalize
+    pub fn build_unsigned(
+        self,
+        commit_public_key: XOnlyPublicKey,
+    ) -> Result<UnsignedInscription, InscriptionError> {
+        validate_rollup_name(&self.rollup_name)?;
+
+        if self.body.is_empty() || self.batched_blobs.iter().any(|blob| blob.body.is_empty()) {
+            return Err(InscriptionError::EmptyBody);
+        }
+        if self.signature.is_empty()
+            || self.sequencer_public_key.is_empty()
+            || self
+                .batched_blobs
+                .iter()
+                .any(|blob| blob.signature.is_empty() || blob.public_key.is_empty())
+        {
+            return Err(InscriptionError::MissingSignature);
+        }
+        self.check_skip_pow()?;
+        self.check_reveal_sighash_type()?;
+
+        for (index, (tag, value)) in self.extra_tags.iter().enumerate() {
+            let reason = if tag.len() < 2 {
+                "single byte tags are reserved for the envelope"
+            } else if tag.len() > 520 || value.len() > 520 {
+                "pushes are limited to 520 bytes"
+            } else if self.extra_tags[..index]
+                .iter()
+                .any(|(other, _)| other == tag)
+            {
+                "repeated tag"
+            } else {
+                continue;
+            };
+            return Err(InscriptionError::InvalidExtraTag {
+                tag: tag.clone(),
+                reason,
+            });
+        }
+
+        let recipient = self.recipient.ok_or(InscriptionError::MissingRecipient)?;
+        let change_address = self.change_address.unwrap_or_else(|| recipient.clone());
+        let reveal_dust_limit = self.dust_limit.unwrap_or_else(|| {
+            dust_threshold(&recipient.script_pubkey(), DEFAULT_DUST_RELAY_FEE_RATE)
+        });
+        if self.reveal_value < reveal_dust_limit {
+            return Err(InscriptionError::RevealValueBelowDust {
+                value: self.reveal_value,
+                threshold: reveal_dust_limit,
+            });
+        }
+        let (commit_fee_rate, reveal_fee_rate) =
+            self.fee_rates.ok_or(InscriptionError::MissingFeeRates)?;
+        if !(commit_fee_rate > 0.0 && reveal_fee_rate > 0.0) {
+            return Err(InscriptionError::InvalidFeeRates {
+                commit: commit_fee_rate,
+                reveal: reveal_fee_rate,
+            });
+        }
+        self.fee_cap
+            .check_fee_rate(commit_fee_rate.max(reveal_fee_rate))?;
+
+        let utxos: Vec<UTXO> = self
+            .utxos
+            .iter()
+            .filter(|utxo| {
+                utxo.confirmations >= self.min_confirmations
+                    && self
+                        .max_ancestor_count
+                        .zip(utxo.ancestor_count)
+                        .map_or(true, |(max, count)| count <= max)
+            })
+            .cloned()
+            .collect();
+        let dust_limit = self.dust_limit.unwrap_or_else(|| {
+            dust_threshold(&change_address.script_pubkey(), DEFAULT_DUST_RELAY_FEE_RATE)
+        });
+        let lock_time = match self.tip_height {
+            Some(tip_height) => LockTime::from_height(tip_height)
+                .map_err(|_| InscriptionError::InvalidTipHeight(tip_height))?,
+            None => LockTime::ZERO,
+        };
+
+        let unsigned = create_inscription_transactions(
+            &self.rollup_name,
+            &self.body,
+            &self.signature,
+            &self.sequencer_public_key,
+            self.metadata,
+            &self.extra_tags,
+            &self.batched_blobs,
+            &utxos,
+            recipient,
+            change_address,
+            self.reveal_value,
+            commit_fee_rate,
+            reveal_fee_rate,
+            self.network,
+            self.internal_key,
+            &self.pow_prefix,
+            self.pow_zero_bits,
+            self.skip_pow || self.dry_run,
+            &self.grind,
+            lock_time,
+            self.reveal_sighash_type,
+            dust_limit,
+            self.min_change,
+            self.coin_selection.as_ref(),
+            commit_public_key,
+        )?;
+
+        self.fee_cap.check_fee(inscription_fee(
+            &unsigned.commit_tx,
+            &unsigned.reveal_tx,
+            &self.utxos,
+        ))?;
+        unsigned.check_standardness(self.dust_limit)?;
+
+        Ok(unsigned)
+    }
+
+    // Replacement of a pending inscription paying the new fee rates. The commit tx spends
+    // the same inputs and the reveal tx carries the same envelope, only a new nonce is ground
+    // if the reveal txid of the old one misses the proof of work. The utxos are the ones the
+    // commit tx spends, the body, signature and fee rates of the builder are not used
+    // The replacement commit tx evicts the old reveal tx too, so it pays more than both (BIP125)
+    // A reveal tx emitted without a nonce would not be relevant under a proof of work
+    fn check_skip_pow(&self) -> Result<(), InscriptionError> {
+        if self.skip_pow && (!self.pow_prefix.is_empty() || self.pow_zero_bits > 0) {
+            return Err(InscriptionError::SkippedPow);
+        }
+        Ok(())
+    }
+
+    // The reveal signature has to commit to the reveal output, otherwise anyone relaying the
+    // tx can redirect it or take the reveal fee with an output of their own. Inputs added
+    // under ANYONECANPAY change the reveal txid, so the reveal pow cannot be required
+    fn check_reveal_sighash_type(&self) -> Result<(), InscriptionError> {
+        let reason = match self.reveal_sighash_type {
+            TapSighashType::Default | TapSighashType::All => return Ok(()),
+            TapSighashType::AllPlusAnyoneCanPay => {
+                if self.skip_pow || (self.pow_prefix.is_empty() && self.pow_zero_bits == 0) {
+                    return Ok(());
+                }
+                "added inputs change the reveal txid, it would miss the proof of work"
+            }
+            TapSighashType::None | TapSighashType::NonePlusAnyoneCanPay => {
+                "the reveal output would not be signed"
+            }
+            TapSighashType::Single | TapSighashType::SinglePlusAnyoneCanPay => {
+                "outputs could be added to take the reveal fee"
+            }
+        };
+        Err(InscriptionError::UnsupportedSighashType {
+            sighash_type: self.reveal_sighash_type,
+            reason,
+        })
+    }
+
+    pub fn bump_inscription_fee(
+        self,
+        commit_tx: &Transaction,
+        reveal_tx: &Transaction,
+        commit_fee_rate: f64,
+        reveal_fee_rate: f64,
+    ) -> Result<UnsignedInscription, InscriptionError> {
+        if !(commit_fee_rate > 0.0 && reveal_fee_rate > 0.0) {
+            return Err(InscriptionError::InvalidFeeRates {
+                commit: commit_fee_rate,
+                reveal: reveal_fee_rate,
+            });
+        }
+        self.fee_cap
+            .check_fee_rate(commit_fee_rate.max(reveal_fee_rate))?;
+        self.check_skip_pow()?;
+        self.check_reveal_sighash_type()?;
+
+        // the envelope and the taptree are recovered from the signed reveal tx
+        let reveal_input = reveal_tx
+            .input
+            .first()
+            .filter(|input| input.previous_output == OutPoint::new(commit_tx.txid(), 0))
+            .ok_or_else(|| {
+                InscriptionError::Bump(anyhow!("reveal tx does not spend the commit output"))
+            })?;
+        let (Some(reveal_script), Some(artifacts), Some(reveal_output)) = (
+            reveal_input.witness.tapscript(),
+            CommitArtifacts::from_reveal_tx(reveal_tx, self.network),
+            reveal_tx.output.first(),
+        ) else {
+            return Err(InscriptionError::Bump(anyhow!(
+                "reveal tx is not a signed script path spend"
+            )));
+        };
+        let commit_public_key = match reveal_script.instructions().next() {
+            Some(Ok(Instruction::PushBytes(push))) => {
+                XOnlyPublicKey::from_slice(push.as_bytes())
+                    .map_err(|_| InscriptionError::Bump(anyhow!("invalid commit key")))?
+            }
+            _ => return Err(InscriptionError::Bump(anyhow!("invalid reveal script"))),
+        };
+        let secp256k1 = Secp256k1::new();
+        let spend_info = |reveal_script: &ScriptBuf| {
+            let taproot_spend_info = TaprootBuilder::new()
+                .add_leaf(0, reveal_script.clone())
+                .expect("Cannot add reveal script to taptree")
+                .finalize(&secp256k1, artifacts.internal_key)
+                .expect("Cannot finalize taptree");
+            let control_block = taproot_spend_info
+                .control_block(&(reveal_script.clone(), LeafVersion::TapScript))
+                .expect("Cannot create control block");
+            (taproot_spend_info.merkle_root(), control_block)
+        };
+        let mut reveal_script = reveal_script.to_owned();
+        let (_, control_block) = spend_info(&reveal_script);
+
+        let input_total = commit_tx
+            .input
+            .iter()
+            .map(|input| {
+                self.utxos
+                    .iter()
+                    .find(|utxo| {
+                        utxo.tx_id == input.previous_output.txid
+                            && utxo.vout == input.previous_output.vout
+                    })
+                    .map(|utxo| utxo.amount)
+                    .ok_or_else(|| {
+                        InscriptionError::Bump(anyhow!(
+                            "output {} spent by the commit tx is not one of the utxos",
+                            input.previous_output
+                        ))
+                    })
+            })
+            .sum::<Result<u64, _>>()?;
+        let previous_fee = inscription_fee(commit_tx, reveal_tx, &self.utxos);
+
+        let mut reveal_tx = Transaction {
+            input: vec![TxIn {
+                witness: Witness::new(),
+                ..reveal_input.clone()
+            }],
+            ..reveal_tx.clone()
+        };
+        let commit_value = reveal_postage(
+            reveal_output,
+            &reveal_script,
+            &control_block,
+            self.reveal_sighash_type,
+            reveal_fee_rate,
+        );
+
+        // the change goes back where it went, without change to the reveal recipient
+        let change_script = commit_tx.output.get(1).map_or_else(
+            || reveal_output.script_pubkey.clone(),
+            |change| change.script_pubkey.clone(),
+        );
+        let mut commit_tx = Transaction {
+            input: commit_tx
+                .input
+                .iter()
+                .map(|input| TxIn {
+                    script_sig: ScriptBuf::new(),
+                    witness: Witness::new(),
+                    sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                    ..input.clone()
+                })
+                .collect(),
+            output: vec![
+                TxOut {
+                    value: commit_value,
+                    script_pubkey: commit_tx.output[0].script_pubkey.clone(),
+                },
+                TxOut {
+                    value: 0,
+                    script_pubkey: change_script,
+                },
+            ],
+            ..commit_tx.clone()
+        };
+        let commit_fee = |commit_tx: &Transaction| {
+            (with_placeholder_signatures(commit_tx, &self.utxos).vsize() as f64 * commit_fee_rate)
+                .ceil() as u64
+        };
+        let fee = commit_fee(&commit_tx);
+        let dust_limit = self.dust_limit.unwrap_or_else(|| {
+            dust_threshold(
+                &commit_tx.output[1].script_pubkey,
+                DEFAULT_DUST_RELAY_FEE_RATE,
+            )
+        });
+        match input_total.checked_sub(commit_value + fee) {
+            Some(change) if change >= self.min_change.unwrap_or(0).max(dust_limit) => {
+                commit_tx.output[1].value = change
+            }
+            _ => {
+                // if dust is left, leave it for fee
+                commit_tx.output.truncate(1);
+                if input_total < commit_value + commit_fee(&commit_tx) {
+                    return Err(InscriptionError::Commit(anyhow!(
+                        "inputs of the commit tx cannot pay the new fees"
+                    )));
+                }
+            }
+        }
+
+        let commit_output_total: u64 = commit_tx.output.iter().map(|output| output.value).sum();
+        let min_fee = previous_fee
+            + (with_placeholder_signatures(&commit_tx, &self.utxos).vsize() as f64
+                * INCREMENTAL_RELAY_FEE_RATE)
+                .ceil() as u64;
+        if input_total - commit_output_total < min_fee {
+            return Err(InscriptionError::ReplacementFeeTooLow {
+                fee: input_total - commit_output_total,
+                min_fee,
+            });
+        }
+
+        reveal_tx.input[0].previous_output = OutPoint::new(commit_tx.txid(), 0);
+        if !self.skip_pow
+            && !meets_reveal_pow(
+                &reveal_tx.txid().to_byte_array(),
+                &self.pow_prefix,
+                self.pow_zero_bits,
+            )
+        {
+            let nonce = grind_reveal_nonce(
+                &reveal_script,
+                &commit_tx,
+                &reveal_tx,
+                artifacts.internal_key,
+                &self.pow_prefix,
+                self.pow_zero_bits,
+                &self.grind,
+            )
+            .ok_or(InscriptionError::GrindCancelled)?;
+            let mut script = reveal_script.into_bytes();
+            for offset in nonce_offsets(&ScriptBuf::from_bytes(script.clone())) {
+                script[offset..offset + 8].copy_from_slice(&nonce.to_le_bytes());
+            }
+            reveal_script = ScriptBuf::from_bytes(script);
+        }
+
+        // a new nonce changes the commit address, the control block and so the reveal txid
+        let (merkle_root, control_block) = spend_info(&reveal_script);
+        let artifacts = CommitArtifacts {
+            merkle_root,
+            ..artifacts
+        };
+        commit_tx.output[0].script_pubkey = Address::p2tr(
+            &secp256k1,
+            artifacts.internal_key,
+            merkle_root,
+            self.network,
+        )
+        .script_pubkey();
+        reveal_tx.input[0].previous_output = OutPoint::new(commit_tx.txid(), 0);
+        assert!(
+            self.skip_pow
+                || meets_reveal_pow(
+                    &reveal_tx.txid().to_byte_array(),
+                    &self.pow_prefix,
+                    self.pow_zero_bits
+                ),
+            "Reveal tx does not meet the proof of work of its nonce"
+        );
+
+        self.fee_cap
+            .check_fee(inscription_fee(&commit_tx, &reveal_tx, &self.utxos))?;
+
+        let receipt = InscriptionReceipt::new(
+            &commit_tx,
+            &reveal_tx,
+            &reveal_script,
+            &control_block,
+            self.reveal_sighash_type,
+            &artifacts,
+            &self.utxos,
+            commit_fee_rate,
+        );
+
+        let unsigned = UnsignedInscription {
+            commit_tx,
+            reveal_tx,
+            artifacts,
+            receipt,
+            commit_public_key,
+            reveal_script,
+            control_block,
+            sighash_type: self.reveal_sighash_type,
+        };
+        unsigned.check_standardness(self.dust_limit)?;
+
+        Ok(unsigned)
+    }
+}
+
+// Fees paid by the commit and reveal txs together, utxos are the ones the commit tx spends
+pub fn inscription_fee(commit_tx: &Transaction, reveal_tx: &Transaction, utxos: &[UTXO]) -> u64 {
+    let input_total: u64 = commit_tx
+        .input
+        .iter()
+        .filter_map(|input| {
+            utxos.iter().find(|utxo| {
+                utxo.tx_id == input.previous_output.txid && utxo.vout == input.previous_output.vout
+            })
+        })
+        .map(|utxo| utxo.amount)
+        .sum();
+    // the reveal output is paid by the commit output it spends, only the fee leaves the txs
+    let commit_output_total: u64 = commit_tx.output.iter().map(|output| output.value).sum();
+    let reveal_input_value: u64 = reveal_tx
+        .input
+        .iter()
+        .filter(|input| input.previous_output.txid == commit_tx.txid())
+        .filter_map(|input| commit_tx.output.get(input.previous_output.vout as usize))
+        .map(|output| output.value)
+        .sum();
+    let reveal_output_total: u64 = reveal_tx.output.iter().map(|output| output.value).sum();
+
+    (input_total + reveal_input_value).saturating_sub(commit_output_total + reveal_output_total)
+}
+
+/// What an inscription spends and pays, for services to log and account for their DA costs
+#[derive(Debug, Clone, PartialEq)]
+pub struct InscriptionReceipt {
+    // utxos spent by the commit tx
+    pub utxos: Vec<UTXO>,
+    // the commit tx is sized with the placeholder signatures of its inputs
+    pub commit_vsize: usize,
+    pub reveal_vsize: usize,
+    pub commit_fee: u64,
+    pub reveal_fee: u64,
+    // commit fee above the commit fee rate, e.g. change too small for an output
+    pub overpaid: u64,
+    // fee of both txs over their vsize, in sat/vB
+    pub effective_fee_rate: f64,
+    // taproot address of the commit output, the one the reveal tx spends
+    pub commit_address: Address,
+    pub nonce: i64,
+}
+
+impl InscriptionReceipt {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        commit_tx: &Transaction,
+        reveal_tx: &Transaction,
+        reveal_script: &ScriptBuf,
+        control_block: &ControlBlock,
+        sighash_type: TapSighashType,
+        artifacts: &CommitArtifacts,
+        utxos: &[UTXO],
+        commit_fee_rate: f64,
+    ) -> Self {
+        let utxos: Vec<UTXO> = commit_tx
+            .input
+            .iter()
+            .filter_map(|input| {
+                utxos.iter().find(|utxo| {
+                    utxo.tx_id == input.previous_output.txid
+                        && utxo.vout == input.previous_output.vout
+                })
+            })
+            .cloned()
+            .collect();
+        let commit_vsize = with_placeholder_signatures(commit_tx, &utxos).vsize();
+        let reveal_inputs: Vec<TxIn> = reveal_tx
+            .input
+            .iter()
+            .map(|input| TxIn {
+                witness: placeholder_reveal_witness(sighash_type),
+                ..input.clone()
+            })
+            .collect();
+        let reveal_vsize = get_size(
+            &reveal_inputs,
+            &reveal_tx.output,
+            Some(reveal_script),
+            Some(control_block),
+        );
+
+        let input_total: u64 = utxos.iter().map(|utxo| utxo.amount).sum();
+        let commit_output_total: u64 = commit_tx.output.iter().map(|output| output.value).sum();
+        let reveal_output_total: u64 = reveal_tx.output.iter().map(|output| output.value).sum();
+        let commit_fee = input_total.saturating_sub(commit_output_total);
+        let reveal_fee = commit_tx.output[0]
+            .value
+            .saturating_sub(reveal_output_total);
+
+        // every envelope of the reveal script carries the same nonce
+        let nonce = nonce_offsets(reveal_script).first().map_or(0, |offset| {
+            i64::from_le_bytes(
+                reveal_script.as_bytes()[*offset..*offset + 8]
+                    .try_into()
+                    .expect("8 byte nonce"),
+            )
+        });
+
+        Self {
+            utxos,
+            commit_vsize,
+            reveal_vsize,
+            commit_fee,
+            reveal_fee,
+            overpaid: commit_fee
+                .saturating_sub((commit_vsize as f64 * commit_fee_rate).ceil() as u64),
+            effective_fee_rate: (commit_fee + reveal_fee) as f64
+                / (commit_vsize + reveal_vsize) as f64,
+            commit_address: Address::p2tr(
+                &Secp256k1::verification_only(),
+                artifacts.internal_key,
+                artifacts.merkle_root,
+                artifacts.network,
+            ),
+            nonce,
+        }
+    }
+
+    pub fn fee(&self) -> u64 {
+        self.commit_fee + self.reveal_fee
+    }
+
+    pub fn vsize(&self) -> usize {
+        self.commit_vsize + self.reveal_vsize
+    }
+}
+
+/// Commit and reveal txs whose reveal tx still lacks the commit key signature
+#[derive(Debug, Clone)]
+pub struct UnsignedInscription {
+    pub commit_tx: Transaction,
+    pub reveal_tx: Transaction,
+    pub artifacts: CommitArtifacts,
+    pub receipt: InscriptionReceipt,
+    // key of the reveal script, the one that has to sign the reveal tx
+    pub commit_public_key: XOnlyPublicKey,
+    reveal_script: ScriptBuf,
+    control_block: ControlBlock,
+    sighash_type: TapSighashType,
+}
+
+impl UnsignedInscription {
+    // Policy of bitcoind on both txs, with placeholder signatures, so that a reveal tx the
+    // network would not relay fails before its commit tx is broadcast
+    fn check_standardness(&self, dust_limit: Option<u64>) -> Result<(), InscriptionError> {
+        let mut reveal_tx = self.reveal_tx.clone();
+        let mut witness = placeholder_reveal_witness(self.sighash_type);
+        let signature = witness.to_vec().remove(0);
+        witness.push(self.reveal_script.as_bytes());
+        witness.push(self.control_block.serialize());
+        reveal_tx.input[0].witness = witness;
+
+        check_tx_standardness(
+            &with_placeholder_signatures(&self.commit_tx, &self.receipt.utxos),
+            dust_limit,
+            DEFAULT_DUST_RELAY_FEE_RATE,
+        )
+        .map_err(|error| InscriptionError::NonStandard {
+            tx: "commit",
+            error,
+        })?;
+        check_tx_standardness(&reveal_tx, None, DEFAULT_DUST_RELAY_FEE_RATE)
+            .and_then(|()| check_tapscript_spend(&self.reveal_script, &[&signature]))
+            .map_err(|error| InscriptionError::NonStandard {
+                tx: "reveal",
+                error,
+            })
+    }
+
+    // BIP341 script path sighash of the reveal tx input, with the sighash type of the builder
+    pub fn reveal_sighash(&self) -> [u8; 32] {
+        SighashCache::new(&self.reveal_tx)
+            .taproot_script_spend_signature_hash(
+                0,
+                &Prevouts::All(&[self.commit_tx.output[0].clone()]),
+                TapLeafHash::from_script(&self.reveal_script, LeafVersion::TapScript),
+                self.sighash_type,
+            )
+            .expect("Cannot create hash for signature")
+            .to_byte_array()
+    }
+
+    // Adds the signature to the reveal witness, it has to verify under the commit key
+    pub fn finalize(
+        mut self,
+        signature: &Signature,
+    ) -> Result<(Transaction, Transaction, CommitArtifacts), InscriptionError> {
+        let secp256k1 = Secp256k1::verification_only();
+        secp256k1
+            .verify_schnorr(
+                signature,
+                &secp256k1::Message::from_slice(&self.reveal_sighash())
+                    .expect("should be cryptographically secure hash"),
+                &self.commit_public_key,
+            )
+            .map_err(|_| InscriptionError::InvalidRevealSignature)?;
+
+        // add signature to witness and finalize reveal tx
+        let witness = &mut self.reveal_tx.input[0].witness;
+        witness.push(reveal_signature(*signature, self.sighash_type));
+        witness.push(self.reveal_script);
+        witness.push(self.control_block.serialize());
+
+        Ok((self.commit_tx, self.reveal_tx, self.artifacts))
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_inscription_transactions(
+    rollup_name: &str,
+    body: &[u8],
+    signature: &[u8],
+    sequencer_public_key: &[u8],
+    metadata: InscriptionMetadata,
+    extra_tags: &[(Vec<u8>, Vec<u8>)],
+    batched_blobs: &[SignedBlob],
+    utxos: &[UTXO],
+    recipient: Address,
+    change_address: Address,
+    reveal_value: u64,
+    commit_fee_rate: f64,
+    reveal_fee_rate: f64,
+    network: Network,
+    internal_key: CommitInternalKey,
+    reveal_tx_prefix: &[u8],
+    reveal_tx_zero_bits: u8,
+    skip_pow: bool,
+    grind: &GrindControl,
+    lock_time: LockTime,
+    sighash_type: TapSighashType,
+    dust_limit: u64,
+    min_change: Option<u64>,
+    coin_selection: &dyn CoinSelection,
+    public_key: XOnlyPublicKey,
+) -> Result<UnsignedInscription, InscriptionError> {
+    let secp256k1 = Secp256k1::new();
+    let internal_public_key = internal_key.public_key(public_key);
+
+    // Commit and reveal txs for a nonce. The nonce push has a fixed size, so the txs only
+    // differ in the commit output and the reveal outpoint from one nonce to another
+    let build_pair = |nonce: i64| {
+        let utxos = utxos.to_vec();
+        let recipient = recipient.clone();
+
+        let reveal_script = build_reveal_script(
+            &public_key,
+            rollup_name,
+            signature,
+            sequencer_public_key,
+            nonce,
+            metadata,
+            extra_tags,
+            body,
+            batched_blobs,
+        );
+
+        // create spend info for tapscript
+        let taproot_spend_info = TaprootBuilder::new()
+            .add_leaf(0, reveal_script.clone())
+            .expect("Cannot add reveal script to taptree")
+            .finalize(&secp256k1, internal_public_key)
+            .expect("Cannot finalize taptree");
+
+        // create control block for tapscript
+        let control_block = taproot_spend_info
             .control_block(&(reveal_script.clone(), LeafVersion::TapScript))
             .expect("Cannot create control block");
 
         // create commit tx address
         let commit_tx_address = Address::p2tr(
             &secp256k1,
-            public_key,
+            internal_public_key,
             taproot_spend_info.merkle_root(),
             network,
         );
 
-        let commit_value = (get_size(
-            &vec![TxIn {
-                previous_output: OutPoint {
-                    txid: Txid::from_str(
-                        "0000000000000000000000000000000000000000000000000000000000000000",
-                    )
-                    .unwrap(),
-                    vout: 0,
-                },
-                script_sig: script::Builder::new().into_script(),
-                witness: Witness::new(),
-                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
-            }],
-            &vec![TxOut {
-                script_pubkey: recipient.clone().script_pubkey(),
+        let commit_value = reveal_postage(
+            &TxOut {
+                script_pubkey: recipient.script_pubkey(),
                 value: reveal_value,
-            }],
-            Some(&reveal_script),
-            Some(&control_block),
-        ) as f64
-            * reveal_fee_rate
-            + reveal_value as f64)
-            .ceil() as u64;
+            },
+            &reveal_script,
+            &control_block,
+            sighash_type,
+            reveal_fee_rate,
+        );
 
         // build commit tx
-        let unsigned_commit_tx = build_commit_transaction(
+        let mut unsigned_commit_tx = build_commit_transaction(
             utxos,
             commit_tx_address.clone(),
-            recipient.clone(),
+            change_address.clone(),
             commit_value,
             commit_fee_rate,
-        )?;
+            dust_limit,
+            min_change,
+            coin_selection,
+        )
+        .map_err(InscriptionError::Commit)?;
+        unsigned_commit_tx.lock_time = lock_time;
 
         let output_to_reveal = unsigned_commit_tx.output[0].clone();
 
@@ -404,77 +2719,270 @@ pub fn create_inscription_transactions(
             reveal_fee_rate,
             &reveal_script,
             &control_block,
-        )?;
+            sighash_type,
+        )
+        .map_err(InscriptionError::Reveal)?;
+        reveal_tx.lock_time = lock_time;
+
+        Ok::<_, InscriptionError>((
+            reveal_script,
+            taproot_spend_info,
+            control_block,
+            unsigned_commit_tx,
+            reveal_tx,
+        ))
+    };
 
-        let reveal_hash = reveal_tx.txid().as_raw_hash().to_byte_array();
+    // Find a 'nonce' i.e. random number that makes the reveal txid start with the prefix
+    // and enough zero bits
+    // Without proof of work the txs of nonce 0 are the inscription
+    let template = build_pair(0)?;
+    let (reveal_script, taproot_spend_info, control_block, unsigned_commit_tx, reveal_tx) =
+        if skip_pow {
+            template
+        } else {
+            let (template_script, _, _, template_commit_tx, template_reveal_tx) = template;
+            let nonce = grind_reveal_nonce(
+                &template_script,
+                &template_commit_tx,
+                &template_reveal_tx,
+                internal_public_key,
+                reveal_tx_prefix,
+                reveal_tx_zero_bits,
+                grind,
+            )
+            .ok_or(InscriptionError::GrindCancelled)?;
+
+            let pair = build_pair(nonce)?;
+            assert!(
+                meets_reveal_pow(
+                    &pair.4.txid().to_byte_array(),
+                    reveal_tx_prefix,
+                    reveal_tx_zero_bits
+                ),
+                "Reveal tx does not meet the proof of work of its nonce"
+            );
+            pair
+        };
+
+    // check if inscription locked to the correct address
+    let artifacts = CommitArtifacts {
+        internal_key: internal_public_key,
+        merkle_root: taproot_spend_info.merkle_root(),
+        network,
+    };
+    assert!(
+        verify_commit_binding(&artifacts, &unsigned_commit_tx),
+        "Commit output does not commit to the reveal script"
+    );
+
+    let receipt = InscriptionReceipt::new(
+        &unsigned_commit_tx,
+        &reveal_tx,
+        &reveal_script,
+        &control_block,
+        sighash_type,
+        &artifacts,
+        utxos,
+        commit_fee_rate,
+    );
+
+    Ok(UnsignedInscription {
+        commit_tx: unsigned_commit_tx,
+        reveal_tx,
+        artifacts,
+        receipt,
+        commit_public_key: public_key,
+        reveal_script,
+        control_block,
+        sighash_type,
+    })
+}
+
+// Offsets of the nonce values in a reveal script, the 8th push of each envelope after the
+// version tag and value, if any
+fn nonce_offsets(reveal_script: &ScriptBuf) -> Vec<usize> {
+    let mut offsets = vec![];
+    let mut push_index = 0;
+    let mut nonce_index = 7;
+    for (offset, instruction) in reveal_script.instruction_indices().flatten() {
+        match instruction {
+            Instruction::Op(OP_IF) => push_index = 0,
+            Instruction::PushBytes(push) => {
+                if push_index == 0 {
+                    nonce_index = if push.as_bytes() == VERSION_TAG { 9 } else { 7 };
+                }
+                if push_index == nonce_index && push.len() == 8 {
+                    // skip the push opcode
+                    offsets.push(offset + 1);
+                }
+                push_index += 1;
+            }
+            Instruction::Op(_) => {}
+        }
+    }
+    offsets
+}
 
-        // check if first N bytes equal to the given prefix
-        if reveal_hash.starts_with(reveal_tx_prefix) {
-            // start signing reveal tx
-            let mut sighash_cache = SighashCache::new(&mut reveal_tx);
+// Searches the lowest nonce whose reveal txid meets the proof of work, across threads
+// Each attempt patches the nonces of the template script and only rehashes the taptree,
+// the commit tx and the reveal tx
+/// Cancels the nonce grinding of the builders holding a clone of it, from any thread or
+/// once its timeout elapses
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
 
-            // create data to sign
-            let signature_hash = sighash_cache
-                .taproot_script_spend_signature_hash(
-                    0,
-                    &Prevouts::All(&[output_to_reveal]),
-                    TapLeafHash::from_script(&reveal_script, LeafVersion::TapScript),
-                    bitcoin::sighash::TapSighashType::Default,
-                )
-                .expect("Cannot create hash for signature");
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-            // sign reveal tx data
-            let signature = secp256k1.sign_schnorr_with_rng(
-                &secp256k1::Message::from_slice(signature_hash.as_byte_array())
-                    .expect("should be cryptographically secure hash"),
-                &key_pair,
-                &mut rand::thread_rng(),
-            );
+    pub fn with_timeout(timeout: Duration) -> Self {
+        Self {
+            cancelled: Arc::default(),
+            deadline: Instant::now().checked_add(timeout),
+        }
+    }
 
-            // add signature to witness and finalize reveal tx
-            let witness = sighash_cache.witness_mut(0).unwrap();
-            witness.push(signature.as_ref());
-            witness.push(reveal_script);
-            witness.push(&control_block.serialize());
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
 
-            // check if inscription locked to the correct address
-            let recovery_key_pair =
-                key_pair.tap_tweak(&secp256k1, taproot_spend_info.merkle_root());
-            let (x_only_pub_key, _parity) = recovery_key_pair.to_inner().x_only_public_key();
-            assert_eq!(
-                Address::p2tr_tweaked(
-                    TweakedPublicKey::dangerous_assume_tweaked(x_only_pub_key),
-                    network,
-                ),
-                commit_tx_address
-            );
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+            || self
+                .deadline
+                .map_or(false, |deadline| Instant::now() >= deadline)
+    }
+}
 
-            return Ok((unsigned_commit_tx, reveal_tx));
-        }
+// Progress callback and cancellation of the nonce grinding
+#[derive(Default)]
+struct GrindControl<'a> {
+    progress: Option<(u64, &'a (dyn Fn(u64) + Sync))>,
+    cancellation: Option<CancellationToken>,
+}
 
-        nonce += 1;
+impl GrindControl<'_> {
+    fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .map_or(false, CancellationToken::is_cancelled)
     }
 }
 
-pub fn write_reveal_tx(tx: &[u8], tx_id: String) {
-    let reveal_tx_file = File::create(format!("reveal_{}.tx", tx_id)).unwrap();
-    let mut reveal_tx_writer = BufWriter::new(reveal_tx_file);
-    reveal_tx_writer.write_all(tx).unwrap();
+// Lowest nonce whose reveal txid meets the proof of work, None if cancelled first
+fn grind_reveal_nonce(
+    template_script: &ScriptBuf,
+    template_commit_tx: &Transaction,
+    template_reveal_tx: &Transaction,
+    internal_key: XOnlyPublicKey,
+    reveal_tx_prefix: &[u8],
+    reveal_tx_zero_bits: u8,
+    grind: &GrindControl,
+) -> Option<i64> {
+    let offsets = nonce_offsets(template_script);
+    let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+    // workers stop once a lower nonce than theirs is found, so the result does not depend
+    // on scheduling
+    let found = AtomicI64::new(i64::MAX);
+    let attempts = AtomicU64::new(0);
+
+    std::thread::scope(|scope| {
+        for first_nonce in 0..threads as i64 {
+            let (offsets, found, attempts) = (&offsets, &found, &attempts);
+            scope.spawn(move || {
+                let secp256k1 = Secp256k1::verification_only();
+                let mut script = template_script.to_bytes();
+                let mut commit_tx = template_commit_tx.clone();
+                let mut reveal_tx = template_reveal_tx.clone();
+
+                let mut nonce = first_nonce;
+                while nonce < found.load(Ordering::Relaxed) && !grind.is_cancelled() {
+                    if let Some((every, callback)) = grind.progress {
+                        let attempts = attempts.fetch_add(1, Ordering::Relaxed) + 1;
+                        if attempts % every == 0 {
+                            callback(attempts);
+                        }
+                    }
+                    for offset in offsets {
+                        script[*offset..*offset + 8].copy_from_slice(&nonce.to_le_bytes());
+                    }
+
+                    let leaf_hash = TapLeafHash::from_script(
+                        Script::from_bytes(&script),
+                        LeafVersion::TapScript,
+                    );
+                    let (output_key, _parity) =
+                        internal_key.tap_tweak(&secp256k1, Some(TapNodeHash::from(leaf_hash)));
+                    commit_tx.output[0].script_pubkey = ScriptBuf::new_v1_p2tr_tweaked(output_key);
+                    reveal_tx.input[0].previous_output.txid = commit_tx.txid();
+
+                    if meets_reveal_pow(
+                        &reveal_tx.txid().to_byte_array(),
+                        reveal_tx_prefix,
+                        reveal_tx_zero_bits,
+                    ) {
+                        found.fetch_min(nonce, Ordering::Relaxed);
+                        return;
+                    }
+
+                    nonce += threads as i64;
+                }
+            });
+        }
+    });
+
+    // a cancelled worker may have left a lower nonce untried
+    if grind.is_cancelled() {
+        return None;
+    }
+    Some(found.into_inner())
 }
 
 #[cfg(test)]
 mod tests {
     use core::str::FromStr;
+    use core::time::Duration;
+    use std::sync::atomic::{AtomicU64, Ordering};
 
-    use bitcoin::hashes::Hash;
+    use bitcoin::absolute::LockTime;
+    use bitcoin::hashes::{sha256d, Hash};
+    use bitcoin::key::TapTweak;
     use bitcoin::secp256k1::constants::SCHNORR_SIGNATURE_SIZE;
     use bitcoin::secp256k1::schnorr::Signature;
+    use bitcoin::secp256k1::{self, Secp256k1, SecretKey};
+    use bitcoin::sighash::{EcdsaSighashType, SighashCache, TapSighashType};
     use bitcoin::taproot::ControlBlock;
-    use bitcoin::{Address, ScriptBuf, TxOut, Txid};
-
-    use crate::helpers::builders::{compress_blob, decompress_blob};
-    use crate::helpers::parsers::parse_transaction;
-    use crate::spec::utxo::UTXO;
+    use bitcoin::{Address, Network, ScriptBuf, Transaction, TxOut, Txid, Witness};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use crate::bip322;
+    use crate::coin_selection::{CoinSelection, OldestFirst, SingleUtxoFirst};
+    use crate::helpers::builders::{
+        blob_signing_message, compress_blob, compress_blob_or_store, compress_blob_with,
+        compress_stream, decompress_blob, decompress_blob_with, decompress_blob_with_limit,
+        decompress_stream, sign_blob_with_private_key, split_blob, try_compress_blob,
+        try_decompress_blob, verify_commit_binding, CancellationToken, CommitArtifacts,
+        CommitInternalKey, DecompressError, FeeCap, FeeCapError, InscriptionBuilder,
+        InscriptionError, SignedBlob, DEFAULT_DUST_LIMIT,
+    };
+    use crate::helpers::parsers::{
+        parse_inscriptions, parse_transaction, reassemble_chunks, ChunkError,
+    };
+    use crate::helpers::policy::PolicyError;
+    use crate::helpers::templates::TxTemplate;
+    use crate::spec::blob::{
+        BlobKind, ChunkManifest, CompressionAlgorithm, InscriptionMetadata, SignatureScheme,
+    };
+    use crate::spec::body_root::body_root;
+    use crate::spec::precommitment::parse_precommitments;
+    use crate::spec::utxo::{InputScriptType, UTXO};
+    use crate::spec::{DEFAULT_MAX_DECOMPRESSED_LEN, MAX_ROLLUP_NAME_LEN};
 
     #[test]
     fn compression_decompression() {
@@ -487,7 +2995,7 @@ mod tests {
 
         // decompress and measure time
         let time = std::time::Instant::now();
-        let decompressed_blob = decompress_blob(&compressed_blob);
+        let decompressed_blob = decompress_blob(&compressed_blob).unwrap();
         println!("decompression time: {:?}", time.elapsed());
 
         assert_eq!(blob, decompressed_blob);
@@ -502,17 +3010,236 @@ mod tests {
     }
 
     #[test]
-    fn write_reveal_tx() {
-        let tx = vec![100, 100, 100];
-        let tx_id = "test_tx".to_string();
+    fn decompress_corrupted_blob() {
+        let blob = std::fs::read("test_data/blob.txt").unwrap();
+        let compressed_blob = compress_blob(&blob);
+
+        assert_eq!(
+            decompress_blob(&compressed_blob[..compressed_blob.len() / 2]),
+            Err(DecompressError::Truncated)
+        );
+        assert_eq!(decompress_blob(&[]), Err(DecompressError::Truncated));
+
+        // an invalid window size in the stream header
+        let mut corrupted_blob = compressed_blob.clone();
+        corrupted_blob[0] = 0xff;
+        assert_eq!(
+            decompress_blob(&corrupted_blob),
+            Err(DecompressError::Corrupted)
+        );
+
+        // data after the end of the stream
+        let mut trailing_blob = compressed_blob;
+        trailing_blob.extend_from_slice(&[100; 16]);
+        assert_eq!(
+            decompress_blob(&trailing_blob),
+            Err(DecompressError::Corrupted)
+        );
+    }
+
+    #[test]
+    fn fallible_compression() {
+        let blob = std::fs::read("test_data/blob.txt").unwrap();
+
+        for algorithm in [CompressionAlgorithm::Brotli, CompressionAlgorithm::Zstd] {
+            let compressed_blob = try_compress_blob(&blob, algorithm).unwrap();
+            assert_eq!(compressed_blob, compress_blob_with(&blob, algorithm));
+            assert_eq!(
+                try_decompress_blob(&compressed_blob, Some(algorithm)).unwrap(),
+                blob
+            );
+            // detected from the body without a compression tag
+            assert_eq!(try_decompress_blob(&compressed_blob, None).unwrap(), blob);
+        }
+
+        // corrupt data is an error of the decompression, e.g. in the verifier
+        assert_eq!(
+            try_decompress_blob(&[0xff; 16], None),
+            Err(DecompressError::Corrupted)
+        );
+    }
+
+    #[test]
+    fn decompression_limit() {
+        // a megabyte of zeros compresses to a few bytes
+        let blob = vec![0; 1_000_000];
+        for algorithm in [
+            CompressionAlgorithm::Brotli,
+            CompressionAlgorithm::Zstd,
+            CompressionAlgorithm::Gzip,
+            CompressionAlgorithm::None,
+        ] {
+            let compressed_blob = compress_blob_with(&blob, algorithm);
+            assert_eq!(
+                decompress_blob_with_limit(&compressed_blob, algorithm, 100_000),
+                Err(DecompressError::TooLarge { max_len: 100_000 }),
+                "{:?}",
+                algorithm
+            );
+            assert_eq!(
+                decompress_blob_with_limit(&compressed_blob, algorithm, blob.len() - 1),
+                Err(DecompressError::TooLarge {
+                    max_len: blob.len() - 1
+                })
+            );
+            assert_eq!(
+                decompress_blob_with_limit(&compressed_blob, algorithm, blob.len()).unwrap(),
+                blob
+            );
+        }
+
+        assert_eq!(
+            decompress_blob_with_limit(&[], CompressionAlgorithm::Brotli, 100),
+            Err(DecompressError::Truncated)
+        );
+
+        // the helpers without a limit take the default one
+        let bomb = compress_blob_with(
+            &vec![0; DEFAULT_MAX_DECOMPRESSED_LEN + 1],
+            CompressionAlgorithm::Zstd,
+        );
+        let too_large = Err(DecompressError::TooLarge {
+            max_len: DEFAULT_MAX_DECOMPRESSED_LEN,
+        });
+        assert_eq!(decompress_blob(&bomb), too_large);
+        assert_eq!(try_decompress_blob(&bomb, None), too_large);
+        assert_eq!(
+            decompress_blob_with(&bomb, CompressionAlgorithm::Zstd),
+            too_large
+        );
+    }
+
+    #[test]
+    fn compression_algorithms() {
+        let blob = std::fs::read("test_data/blob.txt").unwrap();
+
+        for algorithm in [
+            CompressionAlgorithm::Brotli,
+            CompressionAlgorithm::Zstd,
+            CompressionAlgorithm::Gzip,
+            CompressionAlgorithm::None,
+        ] {
+            let compressed_blob = compress_blob_with(&blob, algorithm);
+            assert_eq!(
+                decompress_blob_with(&compressed_blob, algorithm).unwrap(),
+                blob
+            );
+            assert_eq!(
+                decompress_blob_with(&compress_blob_with(&[], algorithm), algorithm).unwrap(),
+                Vec::<u8>::new()
+            );
+            if algorithm == CompressionAlgorithm::None {
+                assert_eq!(compressed_blob, blob);
+                continue;
+            }
+            assert!(compressed_blob.len() < blob.len());
+
+            assert_eq!(
+                decompress_blob_with(&compressed_blob[..compressed_blob.len() / 2], algorithm),
+                Err(DecompressError::Truncated),
+                "{algorithm:?}"
+            );
+            assert_eq!(
+                decompress_blob_with(&[], algorithm),
+                Err(DecompressError::Truncated),
+                "{algorithm:?}"
+            );
+
+            let mut trailing_blob = compressed_blob;
+            trailing_blob.extend_from_slice(&[100; 16]);
+            assert_eq!(
+                decompress_blob_with(&trailing_blob, algorithm),
+                Err(DecompressError::Corrupted),
+                "{algorithm:?}"
+            );
+        }
+
+        // a body of another algorithm
+        let compressed_blob = compress_blob_with(&blob, CompressionAlgorithm::Zstd);
+        assert!(decompress_blob_with(&compressed_blob, CompressionAlgorithm::Gzip).is_err());
+        assert!(decompress_blob_with(&compressed_blob, CompressionAlgorithm::Brotli).is_err());
+    }
+
+    #[test]
+    fn streaming_compression() {
+        // accepts a few bytes, then fails like a full disk
+        struct FullWriter(usize);
+        impl std::io::Write for FullWriter {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                if self.0 < buf.len() {
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, "full"));
+                }
+                self.0 -= buf.len();
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let blob = std::fs::read("test_data/blob.txt").unwrap();
+        for algorithm in [
+            CompressionAlgorithm::Brotli,
+            CompressionAlgorithm::Zstd,
+            CompressionAlgorithm::Gzip,
+            CompressionAlgorithm::None,
+        ] {
+            let file = std::fs::File::open("test_data/blob.txt").unwrap();
+            let compressed_blob = compress_stream(file, Vec::new(), algorithm).unwrap();
+            assert_eq!(compressed_blob, compress_blob_with(&blob, algorithm));
+
+            let decompressed_blob =
+                decompress_stream(&compressed_blob[..], Vec::new(), algorithm).unwrap();
+            assert_eq!(decompressed_blob, blob);
+
+            assert!(
+                compress_stream(&blob[..], FullWriter(16), algorithm).is_err(),
+                "{algorithm:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn store_incompressible_blob() {
+        let blob = std::fs::read("test_data/blob.txt").unwrap();
+        let (body, algorithm) = compress_blob_or_store(&blob, CompressionAlgorithm::Zstd);
+        assert_eq!(algorithm, CompressionAlgorithm::Zstd);
+        assert!(body.len() < blob.len());
 
-        super::write_reveal_tx(tx.as_slice(), tx_id);
+        // compressing compressed data expands it
+        let compressed_blob = compress_blob(&blob);
+        for algorithm in [CompressionAlgorithm::Brotli, CompressionAlgorithm::Gzip] {
+            assert_eq!(
+                compress_blob_or_store(&compressed_blob, algorithm),
+                (compressed_blob.clone(), CompressionAlgorithm::None)
+            );
+        }
+        assert_eq!(
+            decompress_blob_with(&compressed_blob, CompressionAlgorithm::None).unwrap(),
+            compressed_blob
+        );
+    }
 
-        let file = std::fs::read("reveal_test_tx.tx").unwrap();
+    #[test]
+    fn detect_compression() {
+        let blob = std::fs::read("test_data/blob.txt").unwrap();
 
-        assert_eq!(tx, file);
+        for algorithm in [
+            CompressionAlgorithm::Brotli,
+            CompressionAlgorithm::Zstd,
+            CompressionAlgorithm::Gzip,
+        ] {
+            for blob in [&blob[..], &[], b"a"] {
+                let compressed_blob = compress_blob_with(blob, algorithm);
+                assert_eq!(super::detect_compression(&compressed_blob), algorithm);
+                assert_eq!(decompress_blob(&compressed_blob).unwrap(), blob);
+            }
+        }
 
-        std::fs::remove_file("reveal_test_tx.tx").unwrap();
+        // brotli streams of compress_blob do not start like the other algorithms
+        for len in 0..64 {
+            assert_eq!(compress_blob(&vec![7; len])[0] & 0x0f, 0x0b);
+        }
     }
 
     #[allow(clippy::type_complexity)]
@@ -540,6 +3267,7 @@ mod tests {
                 confirmations: 100,
                 spendable: true,
                 solvable: true,
+                ancestor_count: None,
             },
             UTXO {
                 tx_id: Txid::from_str(
@@ -554,6 +3282,7 @@ mod tests {
                 confirmations: 100,
                 spendable: true,
                 solvable: true,
+                ancestor_count: None,
             },
             UTXO {
                 tx_id: Txid::from_str(
@@ -568,6 +3297,7 @@ mod tests {
                 confirmations: 100,
                 spendable: true,
                 solvable: true,
+                ancestor_count: None,
             },
         ];
 
@@ -585,32 +3315,32 @@ mod tests {
     fn choose_utxos() {
         let (_, _, _, _, _, utxos) = get_mock_data();
 
-        let (chosen_utxos, sum) = super::choose_utxos(&utxos, 105_000).unwrap();
+        let (chosen_utxos, sum) = SingleUtxoFirst.select(&utxos, 105_000).unwrap();
 
         assert_eq!(sum, 1_000_000);
         assert_eq!(chosen_utxos.len(), 1);
         assert_eq!(chosen_utxos[0], utxos[0]);
 
-        let (chosen_utxos, sum) = super::choose_utxos(&utxos, 1_005_000).unwrap();
+        let (chosen_utxos, sum) = SingleUtxoFirst.select(&utxos, 1_005_000).unwrap();
 
         assert_eq!(sum, 1_100_000);
         assert_eq!(chosen_utxos.len(), 2);
         assert_eq!(chosen_utxos[0], utxos[0]);
         assert_eq!(chosen_utxos[1], utxos[1]);
 
-        let (chosen_utxos, sum) = super::choose_utxos(&utxos, 100_000).unwrap();
+        let (chosen_utxos, sum) = SingleUtxoFirst.select(&utxos, 100_000).unwrap();
 
         assert_eq!(sum, 100_000);
         assert_eq!(chosen_utxos.len(), 1);
         assert_eq!(chosen_utxos[0], utxos[1]);
 
-        let (chosen_utxos, sum) = super::choose_utxos(&utxos, 90_000).unwrap();
+        let (chosen_utxos, sum) = SingleUtxoFirst.select(&utxos, 90_000).unwrap();
 
         assert_eq!(sum, 100_000);
         assert_eq!(chosen_utxos.len(), 1);
         assert_eq!(chosen_utxos[0], utxos[1]);
 
-        let res = super::choose_utxos(&utxos, 100_000_000);
+        let res = SingleUtxoFirst.select(&utxos, 100_000_000);
 
         assert!(res.is_err());
         assert_eq!(format!("{}", res.unwrap_err()), "not enough UTXOs");
@@ -631,6 +3361,9 @@ mod tests {
             address.clone(),
             5_000,
             8.0,
+            DEFAULT_DUST_LIMIT,
+            None,
+            &SingleUtxoFirst,
         )
         .unwrap();
 
@@ -658,6 +3391,9 @@ mod tests {
             address.clone(),
             5_000,
             45.0,
+            DEFAULT_DUST_LIMIT,
+            None,
+            &SingleUtxoFirst,
         )
         .unwrap();
 
@@ -683,6 +3419,9 @@ mod tests {
             address.clone(),
             5_000,
             32.0,
+            DEFAULT_DUST_LIMIT,
+            None,
+            &SingleUtxoFirst,
         )
         .unwrap();
 
@@ -713,66 +3452,772 @@ mod tests {
             address.clone(),
             1_050_000,
             5.0,
+            DEFAULT_DUST_LIMIT,
+            None,
+            &SingleUtxoFirst,
+        )
+        .unwrap();
+
+        tx.input[0].witness.push(
+            Signature::from_slice(&[0; SCHNORR_SIGNATURE_SIZE])
+                .unwrap()
+                .as_ref(),
+        );
+        tx.input[1].witness.push(
+            Signature::from_slice(&[0; SCHNORR_SIGNATURE_SIZE])
+                .unwrap()
+                .as_ref(),
+        );
+
+        // 212 vB * 5 sat/vB = 1060 sats
+        // 1_050_000 + 1060 = 1_051_060
+        // inputs: 1_000_000 100_000
+        // outputs: 1_050_000 8940
+        assert_eq!(tx.vsize(), 212);
+        assert_eq!(tx.input.len(), 2);
+        assert_eq!(tx.output.len(), 2);
+        assert_eq!(tx.output[0].value, 1_050_000);
+        assert_eq!(tx.output[0].script_pubkey, recipient.script_pubkey());
+        assert_eq!(tx.output[1].value, 48940);
+        assert_eq!(tx.output[1].script_pubkey, address.script_pubkey());
+
+        let tx = super::build_commit_transaction(
+            utxos.clone(),
+            recipient.clone(),
+            address.clone(),
+            100_000_000_000,
+            32.0,
+            DEFAULT_DUST_LIMIT,
+            None,
+            &SingleUtxoFirst,
+        );
+
+        assert!(tx.is_err());
+        assert_eq!(format!("{}", tx.unwrap_err()), "not enough UTXOs");
+
+        let tx = super::build_commit_transaction(
+            vec![UTXO {
+                tx_id: Txid::from_str(
+                    "4cfbec13cf1510545f285cceceb6229bd7b6a918a8f6eba1dbee64d26226a3b7",
+                )
+                .unwrap(),
+                vout: 0,
+                address: "bc1pp8qru0ve43rw9xffmdd8pvveths3cx6a5t6mcr0xfn9cpxx2k24qf70xq9"
+                    .to_string(),
+                script_pubkey: address.script_pubkey().to_hex_string(),
+                amount: 152,
+                confirmations: 100,
+                spendable: true,
+                solvable: true,
+                ancestor_count: None,
+            }],
+            recipient.clone(),
+            address.clone(),
+            100_000_000_000,
+            32.0,
+            DEFAULT_DUST_LIMIT,
+            None,
+            &SingleUtxoFirst,
+        );
+
+        assert!(tx.is_err());
+        assert_eq!(format!("{}", tx.unwrap_err()), "no spendable UTXOs");
+    }
+
+    fn p2tr_utxo(address: &Address, vout: u32, amount: u64) -> UTXO {
+        UTXO {
+            tx_id: Txid::from_str(
+                "4cfbec13cf1510545f285cceceb6229bd7b6a918a8f6eba1dbee64d26226a3b7",
+            )
+            .unwrap(),
+            vout,
+            address: address.to_string(),
+            script_pubkey: address.script_pubkey().to_hex_string(),
+            amount,
+            confirmations: 100,
+            spendable: true,
+            solvable: true,
+            ancestor_count: None,
+        }
+    }
+
+    #[test]
+    fn change_avoidance() {
+        let (_, _, _, _, address, _) = get_mock_data();
+        let utxo = |vout: u32, amount: u64| p2tr_utxo(&address, vout, amount);
+        let build = |utxos: Vec<UTXO>, min_change: Option<u64>| {
+            super::build_commit_transaction(
+                utxos,
+                address.clone(),
+                address.clone(),
+                10_000,
+                1.0,
+                DEFAULT_DUST_LIMIT,
+                min_change,
+                &SingleUtxoFirst,
+            )
+            .unwrap()
+        };
+
+        // 10_800 sats leave 646 sats of change after the 154 sats fee
+        let utxos = vec![utxo(0, 10_800), utxo(1, 50_000)];
+        let tx = build(utxos.clone(), None);
+        assert_eq!(tx.output[1].value, 646);
+        let tx = build(utxos, Some(1_000));
+        assert_eq!(tx.input[0].previous_output.vout, 1);
+        assert_eq!(tx.output[1].value, 50_000 - 10_000 - 154);
+
+        // two utxos need no change, their excess pays for the second input
+        let utxos = vec![utxo(0, 6_000), utxo(1, 4_300), utxo(2, 50_000)];
+        assert_eq!(build(utxos.clone(), None).input.len(), 1);
+        let tx = build(utxos.clone(), Some(1_000));
+        assert_eq!(tx.input.len(), 2);
+        assert_eq!(tx.output.len(), 1);
+        let signed = super::with_placeholder_signatures(&tx, &utxos);
+        assert!(300 >= (signed.vsize() as f64 * 1.0).ceil() as u64);
+
+        // without enough for the change, it is left as fee
+        let tx = build(vec![utxo(0, 10_800)], Some(1_000));
+        assert_eq!(tx.output.len(), 1);
+        assert_eq!(tx.output[0].value, 10_000);
+    }
+
+    #[test]
+    fn effective_value_selection() {
+        let (_, _, _, _, address, _) = get_mock_data();
+        // a taproot input costs 1_725 sats at 30 sat/vB, 1_275 sats are left of each utxo
+        let mut utxos: Vec<UTXO> = (0..5)
+            .map(|vout| p2tr_utxo(&address, vout, 3_000))
+            .collect();
+        let build = |utxos: Vec<UTXO>| {
+            super::build_commit_transaction(
+                utxos,
+                address.clone(),
+                address.clone(),
+                5_000,
+                30.0,
+                DEFAULT_DUST_LIMIT,
+                None,
+                &OldestFirst,
+            )
+        };
+
+        // 15_000 sats are worth 5 * 1_275 sats after the fee of their inputs
+        assert_eq!(
+            build(utxos.clone()).unwrap_err().to_string(),
+            "not enough UTXOs"
+        );
+
+        // the oldest utxo costs more than it is worth and is never chosen
+        utxos.push(UTXO {
+            confirmations: 1_000,
+            ..p2tr_utxo(&address, 5, 1_000)
+        });
+        utxos.push(p2tr_utxo(&address, 6, 3_000));
+        let tx = build(utxos.clone()).unwrap();
+        assert_eq!(tx.input.len(), 6);
+        assert!(tx.input.iter().all(|input| input.previous_output.vout != 5));
+
+        let fee = 6 * 3_000 - tx.output.iter().map(|o| o.value).sum::<u64>();
+        let signed = super::with_placeholder_signatures(&tx, &utxos);
+        assert!(fee >= (signed.vsize() as f64 * 30.0).ceil() as u64);
+    }
+
+    #[test]
+    fn precommitment() {
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
+        let blob_hash = sha256d::Hash::hash(&body).to_byte_array();
+        let funding = vec![p2tr_utxo(&address, 0, 10_000)];
+
+        let tx = super::build_precommitment_transaction(
+            funding.clone(),
+            blob_hash,
+            address.clone(),
+            10.0,
+            DEFAULT_DUST_LIMIT,
+            &OldestFirst,
         )
         .unwrap();
+        assert_eq!(parse_precommitments(&tx), vec![blob_hash]);
+        // the OP_RETURN output carries no value, the inputs pay the fee and the change
+        assert_eq!(tx.output[0].value, 0);
+        let signed = super::with_placeholder_signatures(&tx, &funding);
+        assert_eq!(
+            10_000 - tx.output[1].value,
+            (signed.vsize() as f64 * 10.0).ceil() as u64
+        );
+
+        // the envelope of the inscription names the pre-commitment
+        let (_, reveal) = InscriptionBuilder::new(rollup_name)
+            .with_body(body.clone())
+            .with_signature(signature, sequencer_public_key)
+            .with_metadata(InscriptionMetadata {
+                precommitment: Some(tx.txid().to_byte_array()),
+                ..Default::default()
+            })
+            .with_utxos(utxos)
+            .with_recipient(address)
+            .with_fee_rates(12.0, 10.0)
+            .build()
+            .unwrap();
+        let inscription = parse_transaction(&reveal, rollup_name).unwrap();
+        assert_eq!(
+            inscription.metadata.precommitment,
+            Some(tx.txid().to_byte_array())
+        );
+        assert_eq!(
+            sha256d::Hash::hash(&inscription.body).to_byte_array(),
+            blob_hash
+        );
+    }
+
+    #[test]
+    fn fee_caps() {
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
+        let builder = || {
+            InscriptionBuilder::new(rollup_name)
+                .with_body(body.clone())
+                .with_signature(signature.clone(), sequencer_public_key.clone())
+                .with_utxos(utxos.clone())
+                .with_recipient(address.clone())
+                .with_fee_rates(12.0, 10.0)
+        };
+
+        let (commit, reveal) = builder().build().unwrap();
+        let fee = super::inscription_fee(&commit, &reveal, &utxos);
+        assert!(fee as f64 >= 12.0 * commit.vsize() as f64 + 10.0 * reveal.vsize() as f64);
+        let input_total: u64 = commit
+            .input
+            .iter()
+            .map(|input| {
+                utxos
+                    .iter()
+                    .find(|utxo| utxo.tx_id == input.previous_output.txid)
+                    .unwrap()
+                    .amount
+            })
+            .sum();
+        // the change and the reveal output are not fees
+        assert_eq!(
+            fee,
+            input_total - commit.output[1].value - reveal.output[0].value
+        );
+
+        let fee_cap = |max_fee, max_fee_rate| FeeCap {
+            max_fee,
+            max_fee_rate,
+        };
+        builder()
+            .with_fee_cap(fee_cap(Some(fee), None))
+            .build()
+            .unwrap();
+        assert!(matches!(
+            builder().with_fee_cap(fee_cap(Some(fee - 1), None)).build(),
+            Err(InscriptionError::AboveFeeCap(FeeCapError::Fee { max_fee, .. })) if max_fee == fee - 1
+        ));
+
+        builder()
+            .with_fee_cap(fee_cap(None, Some(12.0)))
+            .build()
+            .unwrap();
+        assert!(matches!(
+            builder().with_fee_cap(fee_cap(None, Some(11.0))).build(),
+            Err(InscriptionError::AboveFeeCap(FeeCapError::FeeRate { fee_rate, .. })) if fee_rate == 12.0
+        ));
+    }
+
+    #[test]
+    fn reveal_postage() {
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
+
+        // the commit output pays the reveal output and exactly the reveal fee
+        for reveal_fee_rate in [1.0, 12.5, 60.0] {
+            let (commit, reveal) = InscriptionBuilder::new(rollup_name)
+                .with_body(body.clone())
+                .with_signature(signature.clone(), sequencer_public_key.clone())
+                .with_utxos(utxos.clone())
+                .with_recipient(address.clone())
+                .with_reveal_value(1_000)
+                .with_fee_rates(2.0, reveal_fee_rate)
+                .build()
+                .unwrap();
+
+            assert_eq!(reveal.output[0].value, 1_000);
+            assert_eq!(
+                commit.output[0].value,
+                1_000 + (reveal.vsize() as f64 * reveal_fee_rate).ceil() as u64
+            );
+        }
+    }
+
+    #[test]
+    fn inscription_receipt() {
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
+
+        let (commit, reveal, receipt) = InscriptionBuilder::new(rollup_name)
+            .with_body(body)
+            .with_signature(signature, sequencer_public_key)
+            .with_utxos(utxos.clone())
+            .with_recipient(address)
+            .with_fee_rates(12.0, 10.0)
+            .with_pow_prefix(&[0])
+            .build_with_receipt()
+            .unwrap();
+
+        assert_eq!(receipt.utxos, vec![utxos[2].clone()]);
+        assert_eq!(receipt.reveal_vsize, reveal.vsize());
+        assert_eq!(
+            receipt.commit_vsize,
+            super::with_placeholder_signatures(&commit, &utxos).vsize()
+        );
+        assert_eq!(
+            receipt.commit_fee,
+            utxos[2].amount - commit.output.iter().map(|o| o.value).sum::<u64>()
+        );
+        assert_eq!(
+            receipt.reveal_fee,
+            commit.output[0].value - reveal.output[0].value
+        );
+        assert_eq!(
+            receipt.fee(),
+            super::inscription_fee(&commit, &reveal, &utxos)
+        );
+        assert!(receipt.effective_fee_rate >= 10.0 && receipt.effective_fee_rate < 12.1);
+        assert_eq!(receipt.overpaid, 0);
+        assert_eq!(
+            receipt.commit_address.script_pubkey(),
+            commit.output[0].script_pubkey
+        );
+
+        // the nonce is the one in the reveal script
+        let script = reveal.input[0].witness.tapscript().unwrap().to_owned();
+        let offset = super::nonce_offsets(&script)[0];
+        assert_eq!(
+            &script.as_bytes()[offset..offset + 8],
+            &receipt.nonce.to_le_bytes()
+        );
+    }
+
+    #[test]
+    fn skip_pow() {
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
+        let builder = || {
+            InscriptionBuilder::new(rollup_name)
+                .with_body(body.clone())
+                .with_signature(signature.clone(), sequencer_public_key.clone())
+                .with_utxos(utxos.clone())
+                .with_recipient(address.clone())
+                .with_fee_rates(12.0, 10.0)
+                .with_skip_pow(true)
+        };
+
+        let (_, _, receipt) = builder().build_with_receipt().unwrap();
+        assert_eq!(receipt.nonce, 0);
+
+        assert!(matches!(
+            builder().with_pow_prefix(&[0]).build_with_receipt(),
+            Err(InscriptionError::SkippedPow)
+        ));
+        assert!(matches!(
+            builder().with_pow_zero_bits(1).build_with_receipt(),
+            Err(InscriptionError::SkippedPow)
+        ));
+    }
+
+    #[test]
+    fn grind_progress_and_cancellation() {
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
+        let builder = || {
+            InscriptionBuilder::new(rollup_name)
+                .with_body(body.clone())
+                .with_signature(signature.clone(), sequencer_public_key.clone())
+                .with_utxos(utxos.clone())
+                .with_recipient(address.clone())
+                .with_fee_rates(12.0, 10.0)
+                .with_pow_prefix(&[0])
+        };
+
+        let last_attempts = AtomicU64::new(0);
+        let progress = |attempts| {
+            last_attempts.fetch_max(attempts, Ordering::Relaxed);
+        };
+        let (_, _, receipt) = builder()
+            .with_grind_progress(1, &progress)
+            .with_grind_cancellation(CancellationToken::new())
+            .build_with_receipt()
+            .unwrap();
+        // every nonce up to the one found was tried
+        assert!(last_attempts.load(Ordering::Relaxed) > receipt.nonce as u64);
+
+        let cancellation = CancellationToken::new();
+        cancellation.cancel();
+        assert!(matches!(
+            builder().with_grind_cancellation(cancellation).build(),
+            Err(InscriptionError::GrindCancelled)
+        ));
+        assert!(matches!(
+            builder()
+                .with_grind_cancellation(CancellationToken::with_timeout(Duration::ZERO))
+                .build(),
+            Err(InscriptionError::GrindCancelled)
+        ));
+    }
+
+    #[test]
+    fn non_standard_reveal_tx() {
+        let (rollup_name, _, signature, sequencer_public_key, address, utxos) = get_mock_data();
+
+        // the body alone outweighs the 400k weight units of a standard tx
+        let result = InscriptionBuilder::new(rollup_name)
+            .with_body(vec![100; 400_000])
+            .with_signature(signature, sequencer_public_key)
+            .with_utxos(utxos)
+            .with_recipient(address)
+            .with_fee_rates(1.0, 1.0)
+            .build();
+        assert!(matches!(
+            result,
+            Err(InscriptionError::NonStandard {
+                tx: "reveal",
+                error: PolicyError::Weight { .. }
+            })
+        ));
+    }
 
-        tx.input[0].witness.push(
-            Signature::from_slice(&[0; SCHNORR_SIGNATURE_SIZE])
-                .unwrap()
-                .as_ref(),
+    #[test]
+    fn anti_fee_sniping() {
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
+        let builder = || {
+            InscriptionBuilder::new(rollup_name)
+                .with_body(body.clone())
+                .with_signature(signature.clone(), sequencer_public_key.clone())
+                .with_utxos(utxos.clone())
+                .with_recipient(address.clone())
+                .with_fee_rates(12.0, 10.0)
+                .with_pow_prefix(&[0])
+        };
+
+        let (commit, reveal) = builder().build().unwrap();
+        assert_eq!(commit.lock_time, LockTime::ZERO);
+
+        let (commit, reveal_at_tip) = builder().with_tip_height(800_000).build().unwrap();
+        assert_eq!(commit.lock_time, LockTime::from_height(800_000).unwrap());
+        assert_eq!(reveal_at_tip.lock_time, commit.lock_time);
+        // the lock time is ground into the reveal txid like the rest of the tx
+        assert!(reveal_at_tip.txid().as_byte_array().starts_with(&[0]));
+        assert_ne!(reveal_at_tip.txid(), reveal.txid());
+
+        assert!(matches!(
+            builder().with_tip_height(500_000_000).build(),
+            Err(InscriptionError::InvalidTipHeight(500_000_000))
+        ));
+    }
+
+    #[test]
+    fn dry_run() {
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
+        let builder = |utxos: Vec<UTXO>| {
+            InscriptionBuilder::new(rollup_name)
+                .with_body(body.clone())
+                .with_signature(signature.clone(), sequencer_public_key.clone())
+                .with_utxos(utxos)
+                .with_recipient(address.clone())
+                .with_fee_rates(12.0, 10.0)
+                .with_pow_prefix(&[0, 0])
+        };
+
+        let (commit, reveal, receipt) = builder(utxos.clone()).dry_run().unwrap();
+        let (built_commit, built_reveal, built_receipt) =
+            builder(utxos.clone()).build_with_receipt().unwrap();
+        // no nonce is ground
+        assert_eq!(receipt.nonce, 0);
+        assert_eq!(receipt.utxos, built_receipt.utxos);
+        assert_eq!(receipt.fee(), built_receipt.fee());
+        assert_eq!(commit.vsize(), built_commit.vsize());
+        assert_eq!(reveal.output, built_reveal.output);
+        assert!(reveal.input[0].witness.is_empty());
+
+        assert!(matches!(
+            builder(vec![UTXO {
+                amount: 1_000,
+                ..utxos[0].clone()
+            }])
+            .dry_run(),
+            Err(InscriptionError::Commit(_))
+        ));
+    }
+
+    #[test]
+    fn reveal_sighash_type() {
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
+        let builder = |sighash_type: TapSighashType| {
+            InscriptionBuilder::new(rollup_name)
+                .with_body(body.clone())
+                .with_signature(signature.clone(), sequencer_public_key.clone())
+                .with_utxos(utxos.clone())
+                .with_recipient(address.clone())
+                .with_fee_rates(12.0, 10.0)
+                .with_reveal_sighash_type(sighash_type)
+        };
+
+        let (_, default_reveal) = builder(TapSighashType::Default).build().unwrap();
+        let (commit, reveal) = builder(TapSighashType::AllPlusAnyoneCanPay)
+            .build()
+            .unwrap();
+        crate::helpers::simulation::simulate_reveal_spend(&reveal, &commit).unwrap();
+        // the sighash type byte follows the signature and is paid for
+        let signature = reveal.input[0].witness.nth(0).unwrap();
+        assert_eq!(signature.len(), SCHNORR_SIGNATURE_SIZE + 1);
+        assert_eq!(signature[SCHNORR_SIGNATURE_SIZE], 0x81);
+        assert_eq!(
+            default_reveal.input[0].witness.nth(0).unwrap().len(),
+            SCHNORR_SIGNATURE_SIZE
         );
-        tx.input[1].witness.push(
-            Signature::from_slice(&[0; SCHNORR_SIGNATURE_SIZE])
-                .unwrap()
-                .as_ref(),
+        assert_eq!(
+            commit.output[0].value - reveal.output[0].value,
+            (reveal.vsize() as f64 * 10.0).ceil() as u64
         );
 
-        // 212 vB * 5 sat/vB = 1060 sats
-        // 1_050_000 + 1060 = 1_051_060
-        // inputs: 1_000_000 100_000
-        // outputs: 1_050_000 8940
-        assert_eq!(tx.vsize(), 212);
-        assert_eq!(tx.input.len(), 2);
-        assert_eq!(tx.output.len(), 2);
-        assert_eq!(tx.output[0].value, 1_050_000);
-        assert_eq!(tx.output[0].script_pubkey, recipient.script_pubkey());
-        assert_eq!(tx.output[1].value, 48940);
-        assert_eq!(tx.output[1].script_pubkey, address.script_pubkey());
+        for sighash_type in [
+            TapSighashType::None,
+            TapSighashType::Single,
+            TapSighashType::SinglePlusAnyoneCanPay,
+        ] {
+            assert!(matches!(
+                builder(sighash_type).build(),
+                Err(InscriptionError::UnsupportedSighashType { .. })
+            ));
+        }
+        // added inputs would change the ground reveal txid
+        assert!(matches!(
+            builder(TapSighashType::AllPlusAnyoneCanPay)
+                .with_pow_prefix(&[0])
+                .build(),
+            Err(InscriptionError::UnsupportedSighashType { .. })
+        ));
+    }
 
-        let tx = super::build_commit_transaction(
-            utxos.clone(),
-            recipient.clone(),
-            address.clone(),
-            100_000_000_000,
-            32.0,
-        );
+    #[test]
+    fn reveal_value() {
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
+        let builder = |reveal_value: u64| {
+            InscriptionBuilder::new(rollup_name)
+                .with_body(body.clone())
+                .with_signature(signature.clone(), sequencer_public_key.clone())
+                .with_utxos(utxos.clone())
+                .with_recipient(address.clone())
+                .with_fee_rates(12.0, 10.0)
+                .with_reveal_value(reveal_value)
+        };
+
+        let (commit, reveal) = builder(10_000).build().unwrap();
+        assert_eq!(reveal.output[0].value, 10_000);
+        assert!(commit.output[0].value > 10_000);
+
+        // P2TR outputs are dust below 330 sats
+        assert!(builder(330).build().is_ok());
+        assert!(matches!(
+            builder(329).build(),
+            Err(InscriptionError::RevealValueBelowDust {
+                value: 329,
+                threshold: 330
+            })
+        ));
+    }
 
-        assert!(tx.is_err());
-        assert_eq!(format!("{}", tx.unwrap_err()), "not enough UTXOs");
+    #[test]
+    fn script_type_dust_limit() {
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
+        let builder = |utxo: UTXO| {
+            InscriptionBuilder::new(rollup_name)
+                .with_body(body.clone())
+                .with_signature(signature.clone(), sequencer_public_key.clone())
+                .with_utxos(vec![utxo])
+                .with_recipient(address.clone())
+                .with_fee_rates(12.0, 12.0)
+        };
+
+        // leave 400 sats of change, dust for P2PKH but not for P2TR
+        let (commit, _) = builder(utxos[1].clone())
+            .with_dust_limit(0)
+            .build()
+            .unwrap();
+        let utxo = UTXO {
+            amount: utxos[1].amount - commit.output[1].value + 400,
+            ..utxos[1].clone()
+        };
+
+        let (commit, _) = builder(utxo.clone()).build().unwrap();
+        assert_eq!(commit.output.len(), 2);
+        assert_eq!(commit.output[1].value, 400);
+        let (commit, _) = builder(utxo)
+            .with_dust_limit(DEFAULT_DUST_LIMIT)
+            .build()
+            .unwrap();
+        assert_eq!(commit.output.len(), 1);
+
+        // a P2TR utxo above its own threshold can fund a commit tx
+        let small_utxo = UTXO {
+            amount: 400,
+            ..utxos[2].clone()
+        };
+        assert!(super::is_above_dust(&small_utxo));
+        assert!(!super::is_above_dust(&UTXO {
+            amount: 330,
+            ..small_utxo
+        }));
+    }
 
-        let tx = super::build_commit_transaction(
-            vec![UTXO {
-                tx_id: Txid::from_str(
-                    "4cfbec13cf1510545f285cceceb6229bd7b6a918a8f6eba1dbee64d26226a3b7",
-                )
-                .unwrap(),
-                vout: 0,
-                address: "bc1pp8qru0ve43rw9xffmdd8pvveths3cx6a5t6mcr0xfn9cpxx2k24qf70xq9"
-                    .to_string(),
-                script_pubkey: address.script_pubkey().to_hex_string(),
-                amount: 152,
-                confirmations: 100,
-                spendable: true,
-                solvable: true,
-            }],
-            recipient.clone(),
-            address.clone(),
-            100_000_000_000,
-            32.0,
-        );
+    #[test]
+    fn funding_utxo_maturity() {
+        let (rollup_name, body, signature, sequencer_public_key, address, mut utxos) =
+            get_mock_data();
+        // the utxo picked by default is unconfirmed change at the end of a long chain
+        utxos[2].confirmations = 0;
+        utxos[2].ancestor_count = Some(24);
+        let builder = || {
+            InscriptionBuilder::new(rollup_name)
+                .with_body(body.clone())
+                .with_signature(signature.clone(), sequencer_public_key.clone())
+                .with_utxos(utxos.clone())
+                .with_recipient(address.clone())
+                .with_fee_rates(12.0, 12.0)
+        };
+        let spends_change = |commit: &Transaction| {
+            commit
+                .input
+                .iter()
+                .any(|input| input.previous_output.txid == utxos[2].tx_id)
+        };
+
+        let (commit, _) = builder().build().unwrap();
+        assert!(spends_change(&commit));
+
+        let (commit, _) = builder().with_min_confirmations(1).build().unwrap();
+        assert!(!spends_change(&commit));
+
+        let (commit, _) = builder().with_max_ancestor_count(23).build().unwrap();
+        assert!(!spends_change(&commit));
+        let (commit, _) = builder().with_max_ancestor_count(24).build().unwrap();
+        assert!(spends_change(&commit));
+    }
 
-        assert!(tx.is_err());
-        assert_eq!(format!("{}", tx.unwrap_err()), "no spendable UTXOs");
+    #[test]
+    fn bump_inscription_fee() {
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
+        let secp = Secp256k1::new();
+        let commit_key = secp256k1::KeyPair::from_seckey_slice(&secp, &[2; 32]).unwrap();
+        let builder = || {
+            InscriptionBuilder::new(rollup_name)
+                .with_utxos(utxos.clone())
+                .with_commit_key(commit_key)
+        };
+        let sign = |unsigned: super::UnsignedInscription| {
+            let signature = secp.sign_schnorr_no_aux_rand(
+                &secp256k1::Message::from_slice(&unsigned.reveal_sighash()).unwrap(),
+                &commit_key,
+            );
+            unsigned.finalize(&signature).unwrap()
+        };
+
+        for pow_prefix in [&[][..], &[0][..]] {
+            let (commit, reveal) = builder()
+                .with_body(body.clone())
+                .with_signature(signature.clone(), sequencer_public_key.clone())
+                .with_recipient(address.clone())
+                .with_fee_rates(2.0, 2.0)
+                .with_pow_prefix(pow_prefix)
+                .build()
+                .unwrap();
+
+            let unsigned = builder()
+                .with_pow_prefix(pow_prefix)
+                .bump_inscription_fee(&commit, &reveal, 10.0, 10.0)
+                .unwrap();
+            let (bumped_commit, bumped_reveal, artifacts) = sign(unsigned);
+            crate::helpers::simulation::simulate_reveal_spend(&bumped_reveal, &bumped_commit)
+                .unwrap();
+            assert!(verify_commit_binding(&artifacts, &bumped_commit));
+            assert!(bumped_reveal.txid().to_byte_array().starts_with(pow_prefix));
+
+            // the replacement spends the same inputs and signals RBF
+            assert_eq!(bumped_commit.input.len(), commit.input.len());
+            for (input, bumped_input) in commit.input.iter().zip(bumped_commit.input.iter()) {
+                assert_eq!(input.previous_output, bumped_input.previous_output);
+                assert!(bumped_input.sequence.is_rbf());
+            }
+            assert!(
+                super::inscription_fee(&bumped_commit, &bumped_reveal, &utxos)
+                    > super::inscription_fee(&commit, &reveal, &utxos)
+            );
+            assert_eq!(bumped_reveal.output, reveal.output);
+
+            let parsed = parse_transaction(&bumped_reveal, rollup_name).unwrap();
+            assert_eq!(parsed.body, body);
+            assert_eq!(parsed.signature, signature);
+            // without proof of work the envelope is the same, nonce included
+            if pow_prefix.is_empty() {
+                assert_eq!(
+                    bumped_reveal.input[0].witness.tapscript(),
+                    reveal.input[0].witness.tapscript()
+                );
+                assert_eq!(
+                    bumped_commit.output[0],
+                    TxOut {
+                        value: bumped_commit.output[0].value,
+                        ..commit.output[0].clone()
+                    }
+                );
+            }
+        }
+
+        let (commit, reveal) = builder()
+            .with_body(body.clone())
+            .with_signature(signature.clone(), sequencer_public_key.clone())
+            .with_recipient(address.clone())
+            .with_fee_rates(10.0, 10.0)
+            .build()
+            .unwrap();
+        // paying the same rates does not replace the old txs
+        assert!(matches!(
+            builder().bump_inscription_fee(&commit, &reveal, 10.0, 10.0),
+            Err(InscriptionError::ReplacementFeeTooLow { .. })
+        ));
+        assert!(matches!(
+            builder()
+                .with_fee_cap(FeeCap {
+                    max_fee_rate: Some(15.0),
+                    max_fee: None,
+                })
+                .bump_inscription_fee(&commit, &reveal, 20.0, 20.0),
+            Err(InscriptionError::AboveFeeCap(FeeCapError::FeeRate { .. }))
+        ));
+        // the fee of the replacement is capped as well
+        let (cheap_commit, cheap_reveal) = builder()
+            .with_body(body.clone())
+            .with_signature(signature.clone(), sequencer_public_key.clone())
+            .with_recipient(address.clone())
+            .with_fee_rates(2.0, 2.0)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            builder()
+                .with_fee_cap(FeeCap {
+                    max_fee_rate: None,
+                    max_fee: Some(super::inscription_fee(&cheap_commit, &cheap_reveal, &utxos)),
+                })
+                .bump_inscription_fee(&cheap_commit, &cheap_reveal, 10.0, 10.0),
+            Err(InscriptionError::AboveFeeCap(FeeCapError::Fee { .. }))
+        ));
+        // the utxos spent by the commit tx are needed to price it
+        assert!(matches!(
+            InscriptionBuilder::new(rollup_name).bump_inscription_fee(&commit, &reveal, 20.0, 20.0),
+            Err(InscriptionError::Bump(_))
+        ));
+        assert!(matches!(
+            builder().bump_inscription_fee(&commit, &commit, 20.0, 20.0),
+            Err(InscriptionError::Bump(_))
+        ));
     }
 
     #[test]
@@ -799,6 +4244,7 @@ mod tests {
             8.0,
             &script,
             &control_block,
+            TapSighashType::Default,
         )
         .unwrap();
 
@@ -828,6 +4274,7 @@ mod tests {
             75.0,
             &script,
             &control_block,
+            TapSighashType::Default,
         );
 
         assert!(tx.is_err());
@@ -847,6 +4294,7 @@ mod tests {
             1.0,
             &script,
             &control_block,
+            TapSighashType::Default,
         );
 
         assert!(tx.is_err());
@@ -857,24 +4305,48 @@ mod tests {
         let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
 
         let tx_prefix = &[0u8];
-        let (commit, reveal) = super::create_inscription_transactions(
-            rollup_name,
-            body.clone(),
-            signature.clone(),
-            sequencer_public_key.clone(),
-            utxos.clone(),
-            address.clone(),
-            546,
-            12.0,
-            10.0,
-            bitcoin::Network::Bitcoin,
-            tx_prefix,
-        )
-        .unwrap();
+        let metadata = InscriptionMetadata {
+            batch_number: Some(7),
+            timestamp: Some(1694177029),
+            kind: Some(BlobKind::Proof),
+            params_fingerprint: Some([1; 8]),
+            chunk: Some(ChunkManifest {
+                index: 1,
+                total: 3,
+                blob_hash: [2; 32],
+            }),
+            signature_scheme: Some(SignatureScheme::Ecdsa),
+            compression: Some(CompressionAlgorithm::Gzip),
+            precommitment: Some([3; 32]),
+        };
+        let (commit, reveal) = InscriptionBuilder::new(rollup_name)
+            .with_body(body.clone())
+            .with_signature(signature.clone(), sequencer_public_key.clone())
+            .with_metadata(metadata)
+            .with_utxos(utxos.clone())
+            .with_recipient(address.clone())
+            .with_fee_rates(12.0, 10.0)
+            .with_pow_prefix(tx_prefix)
+            .build()
+            .unwrap();
 
         // check pow
         assert!(reveal.txid().as_byte_array().starts_with(tx_prefix));
 
+        // a difficulty finer than whole bytes
+        let (_, reveal_with_zero_bits) = InscriptionBuilder::new(rollup_name)
+            .with_body(body.clone())
+            .with_signature(signature.clone(), sequencer_public_key.clone())
+            .with_utxos(utxos.clone())
+            .with_recipient(address.clone())
+            .with_fee_rates(12.0, 10.0)
+            .with_pow_zero_bits(10)
+            .build()
+            .unwrap();
+        let txid = reveal_with_zero_bits.txid().to_byte_array();
+        assert_eq!(txid[0], 0);
+        assert!(txid[1] < 0x40);
+
         // check outputs
         assert_eq!(commit.output.len(), 2, "commit tx should have 2 outputs");
 
@@ -917,5 +4389,786 @@ mod tests {
             inscription.public_key, sequencer_public_key,
             "sequencer public key should be correct"
         );
+        assert_eq!(inscription.metadata, metadata, "metadata should be correct");
+
+        // a name that cannot be pushed is an error, not a panic
+        let result = InscriptionBuilder::new(&"a".repeat(600))
+            .with_body(body)
+            .with_signature(signature, sequencer_public_key)
+            .with_metadata(metadata)
+            .with_utxos(utxos)
+            .with_recipient(address)
+            .with_fee_rates(12.0, 10.0)
+            .with_pow_prefix(tx_prefix)
+            .build();
+        assert!(matches!(
+            result,
+            Err(InscriptionError::InvalidRollupName(_))
+        ));
+    }
+
+    #[test]
+    fn inscription_builder_errors() {
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
+
+        let builder = || {
+            InscriptionBuilder::new(rollup_name)
+                .with_body(body.clone())
+                .with_signature(signature.clone(), sequencer_public_key.clone())
+                .with_utxos(utxos.clone())
+        };
+
+        assert!(matches!(
+            builder().with_fee_rates(5.0, 5.0).build(),
+            Err(InscriptionError::MissingRecipient)
+        ));
+        assert!(matches!(
+            builder().with_recipient(address.clone()).build(),
+            Err(InscriptionError::MissingFeeRates)
+        ));
+        assert!(matches!(
+            builder()
+                .with_recipient(address.clone())
+                .with_fee_rates(0.0, 5.0)
+                .build(),
+            Err(InscriptionError::InvalidFeeRates { .. })
+        ));
+        assert!(matches!(
+            builder()
+                .with_body(Vec::new())
+                .with_recipient(address.clone())
+                .with_fee_rates(5.0, 5.0)
+                .build(),
+            Err(InscriptionError::EmptyBody)
+        ));
+        assert!(matches!(
+            builder()
+                .with_utxos(Vec::new())
+                .with_recipient(address)
+                .with_fee_rates(5.0, 5.0)
+                .build(),
+            Err(InscriptionError::Commit(_))
+        ));
+    }
+
+    #[test]
+    fn extra_tags() {
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
+        let builder = |extra_tags: Vec<(Vec<u8>, Vec<u8>)>| {
+            InscriptionBuilder::new(rollup_name)
+                .with_body(body.clone())
+                .with_signature(signature.clone(), sequencer_public_key.clone())
+                .with_metadata(InscriptionMetadata {
+                    batch_number: Some(3),
+                    ..Default::default()
+                })
+                .with_extra_tags(extra_tags)
+                .with_utxos(utxos.clone())
+                .with_recipient(address.clone())
+                .with_fee_rates(12.0, 10.0)
+                .build()
+        };
+
+        let (_, reveal) = builder(vec![
+            (
+                b"blocks".to_vec(),
+                [100u64.to_le_bytes(), 120u64.to_le_bytes()].concat(),
+            ),
+            (b"batch-id".to_vec(), vec![7; 32]),
+        ])
+        .unwrap();
+        let inscription = parse_transaction(&reveal, rollup_name).unwrap();
+        assert_eq!(inscription.body, body);
+        assert_eq!(inscription.metadata.batch_number, Some(3));
+        assert_eq!(inscription.extra_tags.len(), 2);
+        assert_eq!(inscription.extra_tags[&b"batch-id".to_vec()], vec![7; 32]);
+        assert_eq!(inscription.body_root, Some(body_root(&body)));
+
+        for (extra_tags, reason) in [
+            (
+                vec![(vec![13], vec![])],
+                "single byte tags are reserved for the envelope",
+            ),
+            (
+                vec![(b"blocks".to_vec(), vec![0; 521])],
+                "pushes are limited to 520 bytes",
+            ),
+            (
+                vec![(b"blocks".to_vec(), vec![1]), (b"blocks".to_vec(), vec![2])],
+                "repeated tag",
+            ),
+        ] {
+            assert!(matches!(
+                builder(extra_tags),
+                Err(InscriptionError::InvalidExtraTag { reason: r, .. }) if r == reason
+            ));
+        }
+    }
+
+    #[test]
+    fn create_inscription_transactions_deterministic_entropy() {
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
+
+        let create = |seed: u64| {
+            InscriptionBuilder::new(rollup_name)
+                .with_body(body.clone())
+                .with_signature(signature.clone(), sequencer_public_key.clone())
+                .with_utxos(utxos.clone())
+                .with_recipient(address.clone())
+                .with_fee_rates(12.0, 10.0)
+                .with_pow_prefix(&[0])
+                .with_entropy(&mut StdRng::seed_from_u64(seed))
+                .build()
+                .unwrap()
+        };
+
+        // same seed gives the same commit key and signature
+        assert_eq!(create(1), create(1));
+        assert_ne!(create(1).1, create(2).1);
+    }
+
+    #[test]
+    fn sign_input() {
+        let secp256k1 = Secp256k1::new();
+        let secret_key = SecretKey::from_slice(&[7; 32]).unwrap();
+        let public_key = bitcoin::PublicKey::new(secret_key.public_key(&secp256k1));
+
+        for script_type in [
+            InputScriptType::P2tr,
+            InputScriptType::P2wpkh,
+            InputScriptType::P2shP2wpkh,
+            InputScriptType::P2pkh,
+        ] {
+            let prevout = TxOut {
+                value: 10_000,
+                script_pubkey: script_type.script_pubkey(&public_key),
+            };
+            let template = TxTemplate::new()
+                .with_input(script_type, 10_000)
+                .with_output(prevout.script_pubkey.clone(), 9_000)
+                .build();
+
+            let mut tx = template.clone();
+            tx.input[0].script_sig = ScriptBuf::new();
+            tx.input[0].witness = Witness::new();
+
+            super::sign_input(
+                &mut tx,
+                0,
+                core::slice::from_ref(&prevout),
+                &secret_key,
+                &mut rand::thread_rng(),
+            )
+            .unwrap();
+
+            // placeholders used for fee estimation are never smaller than the signed input
+            assert!(tx.vsize() <= template.vsize(), "{:?}", script_type);
+
+            let ecdsa_signature = match script_type {
+                InputScriptType::P2tr => continue,
+                InputScriptType::P2pkh => tx.input[0]
+                    .script_sig
+                    .instructions()
+                    .next()
+                    .unwrap()
+                    .unwrap()
+                    .push_bytes()
+                    .unwrap()
+                    .as_bytes()
+                    .to_vec(),
+                _ => tx.input[0].witness.nth(0).unwrap().to_vec(),
+            };
+            let ecdsa_signature = bitcoin::ecdsa::Signature::from_slice(&ecdsa_signature).unwrap();
+
+            let mut sighash_cache = SighashCache::new(&tx);
+            let signature_hash = if script_type == InputScriptType::P2pkh {
+                sighash_cache
+                    .legacy_signature_hash(0, &prevout.script_pubkey, 1)
+                    .unwrap()
+                    .to_byte_array()
+            } else {
+                let script_code = ScriptBuf::new_v0_p2wpkh(&public_key.wpubkey_hash().unwrap())
+                    .p2wpkh_script_code()
+                    .unwrap();
+                sighash_cache
+                    .segwit_signature_hash(0, &script_code, 10_000, EcdsaSighashType::All)
+                    .unwrap()
+                    .to_byte_array()
+            };
+
+            secp256k1
+                .verify_ecdsa(
+                    &secp256k1::Message::from_slice(&signature_hash).unwrap(),
+                    &ecdsa_signature.sig,
+                    &public_key.inner,
+                )
+                .unwrap();
+        }
+
+        // output of another key
+        let prevout = TxOut {
+            value: 10_000,
+            script_pubkey: InputScriptType::P2wpkh
+                .address(Network::Regtest)
+                .script_pubkey(),
+        };
+        let mut tx = TxTemplate::new()
+            .with_input(InputScriptType::P2wpkh, 10_000)
+            .build();
+        assert!(
+            super::sign_input(&mut tx, 0, &[prevout], &secret_key, &mut rand::thread_rng())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn inscription_slot() {
+        let (rollup_name, body, signature, sequencer_public_key, address, _) = get_mock_data();
+
+        let secp = bitcoin::secp256k1::Secp256k1::new();
+        let slot_key_pair = bitcoin::secp256k1::KeyPair::new(&secp, &mut rand::thread_rng());
+        let (slot_public_key, _) = slot_key_pair.x_only_public_key();
+        let slot_address = Address::p2tr(&secp, slot_public_key, None, address.network);
+
+        let slot_value = super::inscription_slot_value(body.len(), &address, 546, 5.0, 5.0);
+        let slot_output = TxOut {
+            value: slot_value,
+            script_pubkey: slot_address.script_pubkey(),
+        };
+        let slot_utxo = UTXO {
+            tx_id: Txid::all_zeros(),
+            vout: 0,
+            address: slot_address.to_string(),
+            script_pubkey: slot_address.script_pubkey().to_hex_string(),
+            amount: slot_value,
+            confirmations: 0,
+            spendable: true,
+            solvable: true,
+            ancestor_count: None,
+        };
+
+        // a body of the maximum size fits, a smaller body leaves change
+        for body_len in [body.len(), body.len() / 2] {
+            let (mut commit, reveal) = InscriptionBuilder::new(rollup_name)
+                .with_body(body[..body_len].to_vec())
+                .with_signature(signature.clone(), sequencer_public_key.clone())
+                .with_metadata(InscriptionMetadata {
+                    batch_number: Some(1),
+                    timestamp: Some(1694177029),
+                    kind: None,
+                    params_fingerprint: None,
+                    chunk: None,
+                    signature_scheme: None,
+                    compression: None,
+                    precommitment: None,
+                })
+                .with_utxos(vec![slot_utxo.clone()])
+                .with_recipient(address.clone())
+                .with_fee_rates(5.0, 5.0)
+                .build()
+                .unwrap();
+
+            assert_eq!(commit.input.len(), 1);
+            assert_eq!(reveal.input[0].previous_output.txid, commit.txid());
+
+            super::sign_key_path_input(
+                &mut commit,
+                0,
+                core::slice::from_ref(&slot_output),
+                &slot_key_pair,
+                None,
+                &mut rand::thread_rng(),
+            )
+            .unwrap();
+
+            let sighash = bitcoin::sighash::SighashCache::new(&commit)
+                .taproot_key_spend_signature_hash(
+                    0,
+                    &bitcoin::psbt::Prevouts::All(core::slice::from_ref(&slot_output)),
+                    bitcoin::sighash::TapSighashType::Default,
+                )
+                .unwrap();
+            let signature = Signature::from_slice(commit.input[0].witness.nth(0).unwrap()).unwrap();
+            let (output_key, _) = slot_public_key.tap_tweak(&secp, None);
+
+            secp.verify_schnorr(
+                &signature,
+                &bitcoin::secp256k1::Message::from_slice(sighash.as_byte_array()).unwrap(),
+                &output_key.to_inner(),
+            )
+            .unwrap();
+        }
+
+        // a bigger body does not fit
+        assert!(InscriptionBuilder::new(rollup_name)
+            .with_body(vec![100; body.len() + 1000])
+            .with_signature(signature, sequencer_public_key)
+            .with_utxos(vec![slot_utxo])
+            .with_recipient(address.clone())
+            .with_fee_rates(5.0, 5.0)
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn batched_inscription() {
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
+
+        let batched_blobs: Vec<SignedBlob> = (1..=2u8)
+            .map(|i| SignedBlob {
+                body: vec![i; 700],
+                signature: vec![i; 64],
+                public_key: vec![i; 33],
+                metadata: InscriptionMetadata {
+                    batch_number: Some(i.into()),
+                    ..Default::default()
+                },
+            })
+            .collect();
+
+        let mut builder = InscriptionBuilder::new(rollup_name)
+            .with_body(body.clone())
+            .with_signature(signature.clone(), sequencer_public_key.clone())
+            .with_utxos(utxos.clone())
+            .with_recipient(address.clone())
+            .with_fee_rates(12.0, 10.0);
+        for blob in batched_blobs.clone() {
+            builder = builder.with_batched_blob(blob);
+        }
+        let (_, reveal) = builder.build().unwrap();
+
+        let inscriptions = parse_inscriptions(&reveal, rollup_name).unwrap();
+        assert_eq!(inscriptions.len(), 3);
+        assert_eq!(inscriptions[0].body, body);
+        assert_eq!(parse_transaction(&reveal, rollup_name).unwrap().body, body);
+        for (inscription, blob) in inscriptions[1..].iter().zip(&batched_blobs) {
+            assert_eq!(inscription.body, blob.body);
+            assert_eq!(inscription.signature, blob.signature);
+            assert_eq!(inscription.public_key, blob.public_key);
+            assert_eq!(inscription.metadata, blob.metadata);
+        }
+
+        // every batched blob must be signed
+        assert!(matches!(
+            InscriptionBuilder::new(rollup_name)
+                .with_body(body)
+                .with_signature(signature, sequencer_public_key)
+                .with_batched_blob(SignedBlob {
+                    signature: vec![],
+                    ..batched_blobs[0].clone()
+                })
+                .with_utxos(utxos)
+                .with_recipient(address)
+                .with_fee_rates(12.0, 10.0)
+                .build(),
+            Err(InscriptionError::MissingSignature)
+        ));
+    }
+
+    #[test]
+    fn derived_commit_key() {
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
+        let secp = Secp256k1::new();
+        let xpriv =
+            bitcoin::bip32::ExtendedPrivKey::new_master(Network::Bitcoin, &[7; 32]).unwrap();
+
+        let create = |index: u32| {
+            let commit_key = crate::entropy::derive_commit_key(&secp, &xpriv, index).unwrap();
+            let (commit, _, artifacts) = InscriptionBuilder::new(rollup_name)
+                .with_body(body.clone())
+                .with_signature(signature.clone(), sequencer_public_key.clone())
+                .with_utxos(utxos.clone())
+                .with_recipient(address.clone())
+                .with_fee_rates(12.0, 10.0)
+                .with_commit_key(commit_key)
+                .build_with_artifacts()
+                .unwrap();
+            assert_eq!(artifacts.internal_key, commit_key.x_only_public_key().0);
+            commit
+        };
+
+        // the commit output can be found again from the xpriv and the index alone
+        assert_eq!(create(0).output[0], create(0).output[0]);
+        assert_ne!(create(0).output[0], create(1).output[0]);
+    }
+
+    #[test]
+    fn recover_commit_output() {
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
+        let secp = Secp256k1::new();
+        let commit_key = secp256k1::KeyPair::from_seckey_slice(&secp, &[3; 32]).unwrap();
+
+        let build = |internal_key| {
+            InscriptionBuilder::new(rollup_name)
+                .with_body(body.clone())
+                .with_signature(signature.clone(), sequencer_public_key.clone())
+                .with_utxos(utxos.clone())
+                .with_recipient(address.clone())
+                .with_fee_rates(12.0, 10.0)
+                .with_internal_key(internal_key)
+                .with_commit_key(commit_key)
+                .build()
+                .unwrap()
+        };
+
+        let (commit, reveal) = build(CommitInternalKey::CommitKey);
+        let artifacts = CommitArtifacts::from_reveal_tx(&reveal, Network::Bitcoin).unwrap();
+        let recover = |artifacts: &CommitArtifacts, commit_output: TxOut| {
+            super::build_recovery_transaction(
+                reveal.input[0].previous_output,
+                commit_output,
+                artifacts,
+                &commit_key,
+                address.clone(),
+                2.0,
+                &mut rand::thread_rng(),
+            )
+        };
+
+        let recovery = recover(&artifacts, commit.output[0].clone()).unwrap();
+        assert_eq!(
+            recovery.input[0].previous_output,
+            reveal.input[0].previous_output
+        );
+        assert!(recovery.output[0].value < commit.output[0].value);
+
+        // key path signature under the output key, tweaked with the reveal script
+        let sighash = SighashCache::new(&recovery)
+            .taproot_key_spend_signature_hash(
+                0,
+                &bitcoin::psbt::Prevouts::All(&[commit.output[0].clone()]),
+                bitcoin::sighash::TapSighashType::Default,
+            )
+            .unwrap();
+        let (output_key, _) = commit_key
+            .x_only_public_key()
+            .0
+            .tap_tweak(&secp, artifacts.merkle_root);
+        secp.verify_schnorr(
+            &Signature::from_slice(&recovery.input[0].witness[0]).unwrap(),
+            &secp256k1::Message::from_slice(sighash.as_byte_array()).unwrap(),
+            &output_key.to_inner(),
+        )
+        .unwrap();
+
+        // another commit output
+        assert!(matches!(
+            recover(&artifacts, commit.output[1].clone()),
+            Err(super::RecoveryError::CommitOutputMismatch)
+        ));
+
+        // only the reveal tx can spend a commit output behind a NUMS internal key
+        let (commit, reveal) = build(CommitInternalKey::Nums);
+        let artifacts = CommitArtifacts::from_reveal_tx(&reveal, Network::Bitcoin).unwrap();
+        assert!(matches!(
+            recover(&artifacts, commit.output[0].clone()),
+            Err(super::RecoveryError::NotCommitKey)
+        ));
+    }
+
+    #[test]
+    fn commit_psbt() {
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
+
+        let (psbt, reveal) = InscriptionBuilder::new(rollup_name)
+            .with_body(body)
+            .with_signature(signature, sequencer_public_key)
+            .with_utxos(utxos.clone())
+            .with_recipient(address)
+            .with_fee_rates(12.0, 10.0)
+            .build_psbt()
+            .unwrap();
+
+        assert_eq!(
+            reveal.input[0].previous_output.txid,
+            psbt.unsigned_tx.txid()
+        );
+        for (input, psbt_input) in psbt.unsigned_tx.input.iter().zip(&psbt.inputs) {
+            let utxo = utxos
+                .iter()
+                .find(|utxo| {
+                    utxo.tx_id == input.previous_output.txid
+                        && utxo.vout == input.previous_output.vout
+                })
+                .unwrap();
+            let witness_utxo = psbt_input.witness_utxo.as_ref().unwrap();
+            assert_eq!(witness_utxo.value, utxo.amount);
+            assert_eq!(
+                witness_utxo.script_pubkey.to_hex_string(),
+                utxo.script_pubkey
+            );
+        }
+
+        let psbt = bitcoin::psbt::Psbt::deserialize(&psbt.serialize()).unwrap();
+        assert!(matches!(
+            super::commit_psbt(&psbt.unsigned_tx, &utxos[..0]),
+            Err(super::PsbtError::MissingUtxo(_))
+        ));
+    }
+
+    #[test]
+    fn grind_batched_nonces() {
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
+
+        let (_, reveal) = InscriptionBuilder::new(rollup_name)
+            .with_body(body)
+            .with_signature(signature.clone(), sequencer_public_key.clone())
+            .with_batched_blob(SignedBlob {
+                body: vec![1; 700],
+                signature,
+                public_key: sequencer_public_key,
+                metadata: InscriptionMetadata::default(),
+            })
+            .with_utxos(utxos)
+            .with_recipient(address)
+            .with_fee_rates(12.0, 10.0)
+            .with_pow_prefix(&[0])
+            .with_pow_zero_bits(4)
+            .build()
+            .unwrap();
+
+        assert!(crate::spec::meets_reveal_pow(
+            &reveal.txid().to_byte_array(),
+            &[0],
+            4
+        ));
+
+        // every envelope carries the ground nonce
+        let script = reveal.input[0].witness.nth(1).unwrap().to_vec();
+        let offsets = super::nonce_offsets(&ScriptBuf::from_bytes(script.clone()));
+        assert_eq!(offsets.len(), 2);
+        assert_eq!(
+            script[offsets[0]..offsets[0] + 8],
+            script[offsets[1]..offsets[1] + 8]
+        );
+    }
+
+    #[test]
+    fn chunked_blob() {
+        let (rollup_name, _, _, _, address, utxos) = get_mock_data();
+        let blob = std::fs::read("test_data/blob.txt").unwrap();
+        let sequencer_key = SecretKey::from_slice(&[7; 32]).unwrap();
+
+        let chunks = split_blob(&blob, blob.len() / 3 + 1);
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks
+            .iter()
+            .enumerate()
+            .all(|(index, (_, manifest))| manifest.index as usize == index && manifest.total == 3));
+
+        let mut inscriptions: Vec<_> = chunks
+            .into_iter()
+            .map(|(chunk, manifest)| {
+                let body = compress_blob(&chunk);
+                let metadata = InscriptionMetadata {
+                    batch_number: Some(3),
+                    chunk: Some(manifest),
+                    ..Default::default()
+                };
+                let (signature, public_key) = sign_blob_with_private_key(
+                    &blob_signing_message(&body, &metadata, []),
+                    &sequencer_key,
+                );
+                let (_, reveal) = InscriptionBuilder::new(rollup_name)
+                    .with_body(body)
+                    .with_signature(signature, public_key)
+                    .with_metadata(metadata)
+                    .with_utxos(utxos.clone())
+                    .with_recipient(address.clone())
+                    .with_fee_rates(12.0, 10.0)
+                    .build()
+                    .unwrap();
+
+                parse_transaction(&reveal, rollup_name).unwrap()
+            })
+            .collect();
+
+        // chunks may be found in any order, e.g. across blocks
+        inscriptions.reverse();
+        let reassembled = reassemble_chunks(&inscriptions, None).unwrap();
+        assert_eq!(reassembled.blob, blob);
+        assert_eq!(reassembled.metadata.batch_number, Some(3));
+        assert_eq!(reassembled.metadata.chunk, None);
+
+        assert_eq!(
+            reassemble_chunks(&inscriptions[1..], None),
+            Err(ChunkError::MissingChunks)
+        );
+        // every chunk fits the cap, the blob they add up to does not
+        assert_eq!(
+            reassemble_chunks(&inscriptions, Some(blob.len() - 1)),
+            Err(ChunkError::TooLarge)
+        );
+
+        let mut duplicated = inscriptions.clone();
+        duplicated[0] = inscriptions[1].clone();
+        assert_eq!(
+            reassemble_chunks(&duplicated, None),
+            Err(ChunkError::DuplicateChunk(1))
+        );
+
+        // a chunk signed by someone else
+        let mut spoofed = inscriptions.clone();
+        spoofed[1].public_key = vec![2; 33];
+        assert_eq!(
+            reassemble_chunks(&spoofed, None),
+            Err(ChunkError::SenderMismatch)
+        );
+
+        let mut corrupted = inscriptions;
+        corrupted[1].body[0] ^= 1;
+        assert_eq!(
+            reassemble_chunks(&corrupted, None),
+            Err(ChunkError::InvalidChunk(1))
+        );
+    }
+
+    #[test]
+    fn commit_binding() {
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
+
+        for internal_key in [CommitInternalKey::CommitKey, CommitInternalKey::Nums] {
+            let (commit, reveal, artifacts) = InscriptionBuilder::new(rollup_name)
+                .with_body(body.clone())
+                .with_signature(signature.clone(), sequencer_public_key.clone())
+                .with_utxos(utxos.clone())
+                .with_recipient(address.clone())
+                .with_fee_rates(12.0, 12.0)
+                .with_internal_key(internal_key)
+                .build_with_artifacts()
+                .unwrap();
+
+            assert!(verify_commit_binding(&artifacts, &commit));
+            // an auditor holding only the reveal tx finds the same artifacts
+            assert_eq!(
+                CommitArtifacts::from_reveal_tx(&reveal, address.network),
+                Some(artifacts)
+            );
+
+            let mut other_commit = commit.clone();
+            other_commit.output[0].script_pubkey = address.script_pubkey();
+            assert!(!verify_commit_binding(&artifacts, &other_commit));
+        }
+    }
+
+    #[test]
+    fn nums_internal_key() {
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
+
+        let (commit, reveal) = InscriptionBuilder::new(rollup_name)
+            .with_body(body)
+            .with_signature(signature, sequencer_public_key)
+            .with_utxos(utxos)
+            .with_recipient(address)
+            .with_fee_rates(12.0, 12.0)
+            .with_internal_key(CommitInternalKey::Nums)
+            .build()
+            .unwrap();
+
+        assert!(commit.output[0].script_pubkey.is_v1_p2tr());
+
+        let control_block = ControlBlock::decode(reveal.input[0].witness.last().unwrap()).unwrap();
+        assert_eq!(
+            control_block.internal_key.serialize(),
+            super::NUMS_INTERNAL_KEY
+        );
+        crate::helpers::simulation::simulate_reveal_spend(&reveal, &commit).unwrap();
+    }
+
+    #[test]
+    fn chained_inscription() {
+        let (rollup_name, body, signature, sequencer_public_key, address, _) = get_mock_data();
+
+        let secp = bitcoin::secp256k1::Secp256k1::new();
+        let (slot_public_key, _) =
+            bitcoin::secp256k1::KeyPair::new(&secp, &mut rand::thread_rng()).x_only_public_key();
+        let slot_address = Address::p2tr(&secp, slot_public_key, None, address.network);
+        let slot_value = 3 * super::inscription_slot_value(body.len(), &address, 546, 5.0, 5.0);
+
+        let next_value =
+            super::chained_reveal_value(slot_value, body.len(), address.network, 5.0, 5.0).unwrap();
+        assert!(next_value > super::inscription_slot_value(body.len(), &address, 546, 5.0, 5.0));
+        assert_eq!(
+            super::chained_reveal_value(1_000, body.len(), address.network, 5.0, 5.0),
+            None
+        );
+
+        // the reveal output funds the next slot, the commit change goes to the sequencer
+        let (commit, reveal) = InscriptionBuilder::new(rollup_name)
+            .with_body(body)
+            .with_signature(signature, sequencer_public_key)
+            .with_utxos(vec![UTXO {
+                tx_id: Txid::all_zeros(),
+                vout: 0,
+                address: slot_address.to_string(),
+                script_pubkey: slot_address.script_pubkey().to_hex_string(),
+                amount: slot_value,
+                confirmations: 0,
+                spendable: true,
+                solvable: true,
+                ancestor_count: None,
+            }])
+            .with_recipient(slot_address.clone())
+            .with_change_address(address.clone())
+            .with_reveal_value(next_value)
+            .with_fee_rates(5.0, 5.0)
+            .build()
+            .unwrap();
+
+        assert_eq!(commit.input.len(), 1);
+        assert!(commit.output[1..]
+            .iter()
+            .all(|output| output.script_pubkey == address.script_pubkey()));
+        assert_eq!(reveal.output[0].value, next_value);
+        assert_eq!(reveal.output[0].script_pubkey, slot_address.script_pubkey());
+    }
+
+    #[test]
+    fn reveal_vsize_for_body_len() {
+        let (_, _, _, _, address, utxos) = get_mock_data();
+        let rollup_name = "a".repeat(MAX_ROLLUP_NAME_LEN);
+        // the builder does not check signatures
+        let signature = vec![1; bip322::MAX_SIMPLE_SIGNATURE_LEN];
+        let sequencer_public_key = vec![2; bip322::MAX_SCRIPT_PUBKEY_LEN];
+
+        // push opcodes change at 76 and 256 bytes, chunks are 520 bytes
+        for body_len in [1, 75, 76, 255, 256, 520, 521, 1_040, 1_041, 10_000, 100_000] {
+            let (_, reveal) = InscriptionBuilder::new(&rollup_name)
+                .with_body(vec![100; body_len])
+                .with_signature(signature.clone(), sequencer_public_key.clone())
+                .with_metadata(InscriptionMetadata {
+                    batch_number: Some(1),
+                    timestamp: Some(1694177029),
+                    kind: Some(BlobKind::Proof),
+                    params_fingerprint: Some([1; 8]),
+                    chunk: Some(ChunkManifest {
+                        index: 0,
+                        total: 2,
+                        blob_hash: [1; 32],
+                    }),
+                    signature_scheme: Some(SignatureScheme::Bip322),
+                    compression: Some(CompressionAlgorithm::Zstd),
+                    precommitment: Some([3; 32]),
+                })
+                .with_utxos(utxos.clone())
+                .with_recipient(address.clone())
+                .with_fee_rates(1.0, 1.0)
+                .build()
+                .unwrap();
+
+            assert_eq!(super::reveal_vsize_for_body_len(body_len), reveal.vsize());
+        }
+
+        // an upper bound for smaller envelopes
+        let (rollup_name, body, signature, sequencer_public_key, address, utxos) = get_mock_data();
+        let (_, reveal) = InscriptionBuilder::new(rollup_name)
+            .with_body(body.clone())
+            .with_signature(signature, sequencer_public_key)
+            .with_utxos(utxos)
+            .with_recipient(address)
+            .with_fee_rates(1.0, 1.0)
+            .build()
+            .unwrap();
+        assert!(super::reveal_vsize_for_body_len(body.len()) > reveal.vsize());
     }
 }
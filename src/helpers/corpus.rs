@@ -0,0 +1,229 @@
+// Block corpus to benchmark blob extraction amid unrelated inscriptions
+// Real blocks are loaded from a directory, e.g. mainnet blocks stuffed with ordinal inscriptions
+// saved with `bitcoin-cli getblock <hash> 0 > <hash>.hex`. Synthetic blocks with the same
+// kind of noise can be generated when no corpus is at hand
+
+use std::fs;
+use std::path::Path;
+
+use bitcoin::block::{Header, Version};
+use bitcoin::blockdata::opcodes::all::{OP_CHECKSIG, OP_ENDIF, OP_IF};
+use bitcoin::blockdata::opcodes::OP_FALSE;
+use bitcoin::blockdata::script;
+use bitcoin::consensus::encode;
+use bitcoin::hash_types::TxMerkleNode;
+use bitcoin::hashes::Hash;
+use bitcoin::script::PushBytesBuf;
+use bitcoin::{
+    absolute, Block, BlockHash, CompactTarget, OutPoint, ScriptBuf, Sequence, Transaction, TxIn,
+    TxOut, Witness,
+};
+use rand::Rng;
+
+use super::parsers::{parse_inscriptions, ParsedInscription};
+use super::{BODY_TAG, PUBLICKEY_TAG, RANDOM_TAG, ROLLUP_NAME_TAG, SIGNATURE_TAG};
+use crate::spec::RollupParams;
+
+// Loads the blocks of the *.hex files in dir, in file name order
+pub fn load_corpus(dir: &Path) -> Result<Vec<Block>, anyhow::Error> {
+    let mut paths = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.retain(|path| {
+        path.extension()
+            .map_or(false, |extension| extension == "hex")
+    });
+    paths.sort();
+
+    paths
+        .iter()
+        .map(|path| {
+            let block_hex = fs::read_to_string(path)?;
+            Ok(encode::deserialize(&hex::decode(block_hex.trim())?)?)
+        })
+        .collect()
+}
+
+// Inscriptions of the block that are relevant to the rollup, found the way the service
+// extracts them: txid proof of work, envelope, signature, chain params and decompression checks
+pub fn relevant_inscriptions(block: &Block, params: &RollupParams) -> Vec<ParsedInscription> {
+    let fingerprint = params.fingerprint();
+
+    block
+        .txdata
+        .iter()
+        .filter(|tx| params.meets_reveal_pow(&tx.txid().to_byte_array()))
+        .take(params.relevant_tx_cap())
+        .flat_map(|tx| parse_inscriptions(tx, &params.rollup_name).unwrap_or_default())
+        .filter(|inscription| {
+            inscription.metadata.matches_params(fingerprint)
+                && inscription.get_sig_verified_hash().is_some()
+                && inscription
+                    .decompressed_body_with_limit(params.max_decompressed_len)
+                    .is_ok()
+        })
+        .collect()
+}
+
+// Block of ordinal_count ordinal inscriptions of body_len bytes, followed by spoof_count
+// envelopes that carry rollup_name with a signature that does not verify
+pub fn ordinal_noise_block(
+    ordinal_count: usize,
+    spoof_count: usize,
+    body_len: usize,
+    rollup_name: &str,
+    rng: &mut impl Rng,
+) -> Block {
+    let mut txdata = Vec::with_capacity(ordinal_count + spoof_count);
+
+    for _ in 0..ordinal_count {
+        let fields = [
+            b"ord".to_vec(),
+            vec![1],
+            b"text/plain;charset=utf-8".to_vec(),
+            vec![],
+        ];
+        txdata.push(reveal_tx(&fields, &random_bytes(body_len, rng), rng));
+    }
+
+    for _ in 0..spoof_count {
+        // a well formed public key, so that the signature is actually checked
+        let mut public_key = vec![2];
+        public_key.extend_from_slice(&random_bytes(32, rng));
+
+        let fields = [
+            ROLLUP_NAME_TAG.to_vec(),
+            rollup_name.as_bytes().to_vec(),
+            SIGNATURE_TAG.to_vec(),
+            random_bytes(64, rng),
+            PUBLICKEY_TAG.to_vec(),
+            public_key,
+            RANDOM_TAG.to_vec(),
+            random_bytes(8, rng),
+            BODY_TAG.to_vec(),
+        ];
+        txdata.push(reveal_tx(&fields, &random_bytes(body_len, rng), rng));
+    }
+
+    Block {
+        header: Header {
+            version: Version::TWO,
+            prev_blockhash: BlockHash::all_zeros(),
+            merkle_root: TxMerkleNode::all_zeros(),
+            time: 0,
+            bits: CompactTarget::from_consensus(0),
+            nonce: 0,
+        },
+        txdata,
+    }
+}
+
+fn random_bytes(len: usize, rng: &mut impl Rng) -> Vec<u8> {
+    let mut bytes = vec![0; len];
+    rng.fill(bytes.as_mut_slice());
+    bytes
+}
+
+// Script path spend of an envelope of fields followed by the body in 520 byte chunks
+fn reveal_tx(fields: &[Vec<u8>], body: &[u8], rng: &mut impl Rng) -> Transaction {
+    let mut builder = script::Builder::new()
+        .push_slice(PushBytesBuf::try_from(random_bytes(32, rng)).unwrap())
+        .push_opcode(OP_CHECKSIG)
+        .push_opcode(OP_FALSE)
+        .push_opcode(OP_IF);
+    for field in fields.iter().map(Vec::as_slice).chain(body.chunks(520)) {
+        builder = builder.push_slice(PushBytesBuf::try_from(field.to_vec()).unwrap());
+    }
+    let tapscript = builder.push_opcode(OP_ENDIF).into_script();
+
+    // leaf version and internal key of a single leaf taptree
+    let mut control_block = vec![0xc0];
+    control_block.extend_from_slice(&random_bytes(32, rng));
+
+    let mut witness = Witness::new();
+    witness.push(random_bytes(64, rng));
+    witness.push(tapscript);
+    witness.push(control_block);
+
+    Transaction {
+        version: 2,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness,
+        }],
+        output: vec![TxOut {
+            value: 546,
+            script_pubkey: ScriptBuf::new(),
+        }],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::{load_corpus, ordinal_noise_block, relevant_inscriptions};
+    use crate::helpers::parsers::parse_transaction;
+    use crate::spec::RollupParams;
+
+    fn params() -> RollupParams {
+        RollupParams {
+            rollup_name: "sov-btc".to_string(),
+            reveal_tx_id_prefix: vec![],
+            reveal_tx_id_zero_bits: 0,
+            max_relevant_txs: None,
+            max_decompressed_len: None,
+            monotonic_batch_numbers: false,
+        }
+    }
+
+    #[test]
+    fn extraction_over_ordinal_noise() {
+        let block = ordinal_noise_block(200, 50, 4_000, "sov-btc", &mut StdRng::seed_from_u64(0));
+
+        // spoofs get through the envelope checks, only their signature fails
+        assert_eq!(
+            block
+                .txdata
+                .iter()
+                .filter(|tx| parse_transaction(tx, "sov-btc").is_ok())
+                .count(),
+            50
+        );
+
+        let time = Instant::now();
+        assert!(relevant_inscriptions(&block, &params()).is_empty());
+        println!(
+            "extracted {} txs of ordinal noise in {:?}",
+            block.txdata.len(),
+            time.elapsed()
+        );
+    }
+
+    // Set BITCOIN_DA_CORPUS to a directory of blocks saved by `bitcoin-cli getblock <hash> 0`
+    // cargo test --release extraction_benchmark -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn extraction_benchmark() {
+        let dir = std::env::var("BITCOIN_DA_CORPUS").expect("BITCOIN_DA_CORPUS is not set");
+        let blocks = load_corpus(dir.as_ref()).unwrap();
+
+        for block in &blocks {
+            let time = Instant::now();
+            let relevant = relevant_inscriptions(block, &params());
+            println!(
+                "block {}: {} txs, {} relevant, extracted in {:?}",
+                block.block_hash(),
+                block.txdata.len(),
+                relevant.len(),
+                time.elapsed()
+            );
+        }
+    }
+}
@@ -3,7 +3,28 @@ const ROLLUP_NAME_TAG: &[u8] = &[1];
 const SIGNATURE_TAG: &[u8] = &[2];
 const PUBLICKEY_TAG: &[u8] = &[3];
 const RANDOM_TAG: &[u8] = &[4];
+// Optional tags, written between the random number and the body
+const BATCH_NUMBER_TAG: &[u8] = &[5];
+const TIMESTAMP_TAG: &[u8] = &[6];
+const KIND_TAG: &[u8] = &[7];
+const PARAMS_TAG: &[u8] = &[8];
+const CHUNK_TAG: &[u8] = &[9];
+const SCHEME_TAG: &[u8] = &[10];
+const COMPRESSION_TAG: &[u8] = &[11];
+// Merkle root of the body chunks, see spec::body_root
+const BODY_ROOT_TAG: &[u8] = &[13];
+// Txid of the tx pre-committing to the hash of the body, see spec::precommitment
+const PRECOMMITMENT_TAG: &[u8] = &[14];
 const BODY_TAG: &[u8] = &[];
+// First push of the envelope, followed by the version as a single byte. Envelopes
+// predating it are of version 0, which has the layout of version 1 without the tag
+const VERSION_TAG: &[u8] = &[12];
 
 pub mod builders;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod corpus;
 pub mod parsers;
+pub mod policy;
+pub mod simulation;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod templates;
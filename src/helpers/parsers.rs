@@ -1,44 +1,130 @@
 use core::iter::Peekable;
+use std::collections::BTreeMap;
 
 use bitcoin::blockdata::opcodes::all::{OP_ENDIF, OP_IF};
 use bitcoin::blockdata::script::{Instruction, Instructions};
 use bitcoin::consensus::Decodable;
 use bitcoin::hashes::{sha256d, Hash};
 use bitcoin::opcodes::OP_FALSE;
-use bitcoin::secp256k1::{ecdsa, Message, Secp256k1};
+use bitcoin::secp256k1::{ecdsa, schnorr, Message, Secp256k1, XOnlyPublicKey};
 use bitcoin::{secp256k1, Script, Transaction};
 use serde::{Deserialize, Serialize};
 
-use super::{BODY_TAG, PUBLICKEY_TAG, RANDOM_TAG, ROLLUP_NAME_TAG, SIGNATURE_TAG};
+use super::builders::{
+    blob_signing_message, decompress_blob_with_limit, detect_compression, DecompressError,
+};
+use super::{
+    BATCH_NUMBER_TAG, BODY_ROOT_TAG, BODY_TAG, CHUNK_TAG, COMPRESSION_TAG, KIND_TAG, PARAMS_TAG,
+    PRECOMMITMENT_TAG, PUBLICKEY_TAG, RANDOM_TAG, ROLLUP_NAME_TAG, SCHEME_TAG, SIGNATURE_TAG,
+    TIMESTAMP_TAG, VERSION_TAG,
+};
+use crate::bip322;
+use crate::spec::blob::{
+    BlobKind, Checkpoint, ChunkManifest, CompressionAlgorithm, InscriptionMetadata, SignatureScheme,
+};
+use crate::spec::body_root::body_root;
+use crate::spec::DEFAULT_MAX_DECOMPRESSED_LEN;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ParsedInscription {
     pub body: Vec<u8>,
     pub signature: Vec<u8>,
     pub public_key: Vec<u8>,
+    pub metadata: InscriptionMetadata,
+    // merkle root of the body chunks, checked against the body by the parser
+    // Envelopes predating the root tag have none
+    #[serde(default)]
+    pub body_root: Option<[u8; 32]>,
+    // (tag, value) pushes of tags unknown to the parser, covered by the signature
+    #[serde(default)]
+    pub extra_tags: BTreeMap<Vec<u8>, Vec<u8>>,
 }
 
 impl ParsedInscription {
     /// Verifies the signature of the inscription and returns the hash of the body
+    /// The signature is over the body and the tags of the envelope, see blob_signing_message
+    /// The scheme tag selects ECDSA with a 33 byte public key, BIP340 Schnorr with a 32
+    /// byte x-only public key or a BIP-322 signature of the address of a script pubkey.
+    /// Without it the scheme follows the public key length, e.g. a MuSig2 aggregate key
+    /// carries a Schnorr signature
     pub fn get_sig_verified_hash(&self) -> Option<[u8; 32]> {
-        let public_key = secp256k1::PublicKey::from_slice(&self.public_key);
-        let signature = ecdsa::Signature::from_compact(&self.signature);
-        let hash = sha256d::Hash::hash(&self.body).to_byte_array();
-        let message = Message::from_slice(&hash).unwrap(); // cannot fail
-
-        let secp = Secp256k1::new();
-
-        if public_key.is_ok()
-            && signature.is_ok()
-            && secp
-                .verify_ecdsa(&message, &signature.unwrap(), &public_key.unwrap())
-                .is_ok()
-        {
-            Some(hash)
+        let signing_message = blob_signing_message(
+            &self.body,
+            &self.metadata,
+            self.extra_tags
+                .iter()
+                .map(|(tag, value)| (tag.as_slice(), value.as_slice())),
+        );
+        let signed_hash = sha256d::Hash::hash(&signing_message).to_byte_array();
+        let message = Message::from_slice(&signed_hash).unwrap(); // cannot fail
+
+        let secp = Secp256k1::verification_only();
+
+        let scheme = self
+            .metadata
+            .signature_scheme
+            .unwrap_or_else(|| SignatureScheme::from_public_key_len(self.public_key.len()));
+        let verified = match scheme {
+            SignatureScheme::Schnorr => {
+                let public_key = XOnlyPublicKey::from_slice(&self.public_key);
+                let signature = schnorr::Signature::from_slice(&self.signature);
+
+                public_key.is_ok()
+                    && signature.is_ok()
+                    && secp
+                        .verify_schnorr(&signature.unwrap(), &message, &public_key.unwrap())
+                        .is_ok()
+            }
+            SignatureScheme::Ecdsa => {
+                let public_key = secp256k1::PublicKey::from_slice(&self.public_key);
+                let signature = ecdsa::Signature::from_compact(&self.signature);
+
+                public_key.is_ok()
+                    && signature.is_ok()
+                    && secp
+                        .verify_ecdsa(&message, &signature.unwrap(), &public_key.unwrap())
+                        .is_ok()
+            }
+            // the message is the signed hash, signed by the address of the public key
+            SignatureScheme::Bip322 => bip322::verify_simple(
+                Script::from_bytes(&self.public_key),
+                &signed_hash,
+                &self.signature,
+            ),
+        };
+
+        if verified {
+            Some(sha256d::Hash::hash(&self.body).to_byte_array())
         } else {
             None
         }
     }
+
+    // Checkpoint carried by the inscription, None if it is of another kind or malformed
+    // The signature is not checked
+    pub fn checkpoint(&self) -> Option<Checkpoint> {
+        if self.metadata.kind != Some(BlobKind::Checkpoint) {
+            return None;
+        }
+
+        Checkpoint::from_bytes(&self.decompressed_body_with_limit(None).ok()?)
+    }
+
+    // Body decompressed with the algorithm of its compression tag, detected from the body if
+    // the envelope has none, as envelopes predating the tag are brotli. The body may not
+    // decompress to more than max_len bytes, DEFAULT_MAX_DECOMPRESSED_LEN if None
+    pub fn decompressed_body_with_limit(
+        &self,
+        max_len: Option<usize>,
+    ) -> Result<Vec<u8>, DecompressError> {
+        let max_len = max_len.unwrap_or(DEFAULT_MAX_DECOMPRESSED_LEN);
+        let compression = self
+            .metadata
+            .compression
+            .unwrap_or_else(|| detect_compression(&self.body));
+
+        decompress_blob_with_limit(&self.body, compression, max_len)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -48,15 +134,39 @@ pub enum ParserError {
     EnvelopeHasIncorrectFormat,
     NonTapscriptWitness,
     IncorrectSignature,
+    UnsupportedEnvelopeVersion(u8),
+    BodyRootMismatch,
 }
 
+// The rollup name is matched byte for byte, it does not have to be UTF-8
 pub fn parse_transaction(
     tx: &Transaction,
-    rollup_name: &str,
+    rollup_name: &(impl AsRef<[u8]> + ?Sized),
 ) -> Result<ParsedInscription, ParserError> {
     let script = get_script(tx)?;
     let mut instructions = script.instructions().peekable();
-    parse_relevant_inscriptions(&mut instructions, rollup_name)
+    parse_relevant_inscriptions(&mut instructions, rollup_name.as_ref())
+}
+
+// Parses every envelope of the tapscript, a batched reveal tx carries one blob per envelope
+// The first one is the inscription returned by parse_transaction. A malformed envelope
+// rejects the whole tx, so all blobs of a tx are accepted or none
+pub fn parse_inscriptions(
+    tx: &Transaction,
+    rollup_name: &(impl AsRef<[u8]> + ?Sized),
+) -> Result<Vec<ParsedInscription>, ParserError> {
+    let script = get_script(tx)?;
+    let mut instructions = script.instructions().peekable();
+
+    let mut inscriptions = vec![parse_relevant_inscriptions(
+        &mut instructions,
+        rollup_name.as_ref(),
+    )?];
+    while let Some(inscription) = parse_next_inscription(&mut instructions, rollup_name.as_ref())? {
+        inscriptions.push(inscription);
+    }
+
+    Ok(inscriptions)
 }
 
 // Returns the script from the first input of the transaction
@@ -71,71 +181,145 @@ fn get_script(tx: &Transaction) -> Result<&Script, ParserError> {
 // Parses the inscription from script if it is relevant to the rollup
 fn parse_relevant_inscriptions(
     instructions: &mut Peekable<Instructions>,
-    rollup_name: &str,
+    rollup_name: &[u8],
 ) -> Result<ParsedInscription, ParserError> {
+    parse_next_inscription(instructions, rollup_name)?
+        .ok_or(ParserError::EnvelopeHasIncorrectFormat)
+}
+
+// Parses the next envelope of the script, None if the script has no envelope left
+// The layout of the envelope is the one of its version
+fn parse_next_inscription(
+    instructions: &mut Peekable<Instructions>,
+    rollup_name: &[u8],
+) -> Result<Option<ParsedInscription>, ParserError> {
+    if !skip_to_envelope(instructions) {
+        return Ok(None);
+    }
+
+    match parse_envelope_version(instructions)? {
+        0 | 1 => parse_envelope_v1(instructions, rollup_name).map(Some),
+        version => Err(ParserError::UnsupportedEnvelopeVersion(version)),
+    }
+}
+
+// Consumes the instructions up to the OP_FALSE OP_IF opening the next envelope,
+// false if the script has none
+fn skip_to_envelope(instructions: &mut Peekable<Instructions>) -> bool {
     let mut last_op = None;
-    let mut inside_envelope = false;
+
+    while let Some(Ok(instruction)) = instructions.next() {
+        match instruction {
+            Instruction::Op(OP_IF) if last_op == Some(OP_FALSE) => return true,
+            Instruction::Op(another_op) => last_op = Some(another_op),
+            Instruction::PushBytes(bytes) => {
+                if bytes.is_empty() {
+                    last_op = Some(OP_FALSE); // rust bitcoin pushes [] instead of op_false
+                }
+            }
+        }
+    }
+
+    false
+}
+
+// Version of the envelope, consumes the version tag and value if the envelope has them
+fn parse_envelope_version(instructions: &mut Peekable<Instructions>) -> Result<u8, ParserError> {
+    match instructions.peek() {
+        Some(Ok(Instruction::PushBytes(tag))) if tag.as_bytes() == VERSION_TAG => {
+            instructions.next();
+        }
+        _ => return Ok(0),
+    }
+
+    match instructions.next() {
+        Some(Ok(Instruction::PushBytes(version))) => match version.as_bytes() {
+            [version] => Ok(*version),
+            _ => Err(ParserError::EnvelopeHasIncorrectFormat),
+        },
+        _ => Err(ParserError::EnvelopeHasIncorrectFormat),
+    }
+}
+
+// Parses the pushes of an envelope of version 0 or 1 up to its OP_ENDIF
+fn parse_envelope_v1(
+    instructions: &mut Peekable<Instructions>,
+    rollup_name: &[u8],
+) -> Result<ParsedInscription, ParserError> {
     let mut inside_envelope_index = 0;
 
     let mut body: Vec<u8> = Vec::new();
     let mut signature: Vec<u8> = Vec::new();
     let mut public_key: Vec<u8> = Vec::new();
+    let mut metadata = InscriptionMetadata::default();
+    let mut root: Option<[u8; 32]> = None;
+    let mut extra_tags = BTreeMap::new();
+
+    // optional tags come in (tag, value) pairs after the random number
+    // until the body tag is seen
+    let mut inside_body = false;
+    let mut metadata_tag: Option<&[u8]> = None;
+    let mut extra_tag: Option<&[u8]> = None;
 
     // this while loop is optimized for the least amount of iterations
     // for a strict envelope structure
     // nothing other than data pushes should be inside the envelope
-    // the loop will break after the first envelope is parsed
+    // the loop will break at the end of the envelope
     while let Some(Ok(instruction)) = instructions.next() {
         match instruction {
-            Instruction::Op(OP_IF) => {
-                if last_op == Some(OP_FALSE) {
-                    inside_envelope = true;
-                } else if inside_envelope {
-                    return Err(ParserError::EnvelopeHasNonPushOp);
-                }
-            }
-            Instruction::Op(OP_ENDIF) => {
-                if inside_envelope {
-                    break; // we are done parsing
-                }
-            }
-            Instruction::Op(another_op) => {
-                // don't allow anything except data pushes inside envelope
-                if inside_envelope {
-                    return Err(ParserError::EnvelopeHasNonPushOp);
-                }
-
-                last_op = Some(another_op);
-            }
+            Instruction::Op(OP_ENDIF) => break, // we are done parsing
+            // don't allow anything except data pushes inside envelope
+            Instruction::Op(_) => return Err(ParserError::EnvelopeHasNonPushOp),
             Instruction::PushBytes(bytes) => {
-                if inside_envelope {
-                    // this looks ugly but we need to have least amount of
-                    // iterations possible in a malicous case
-                    // so if any of the conditions does not hold
-                    // we return an error
-                    if (inside_envelope_index == 0 && bytes.as_bytes() != ROLLUP_NAME_TAG)
-                        || (inside_envelope_index == 2 && bytes.as_bytes() != SIGNATURE_TAG)
-                        || (inside_envelope_index == 4 && bytes.as_bytes() != PUBLICKEY_TAG)
-                        || (inside_envelope_index == 6 && bytes.as_bytes() != RANDOM_TAG)
-                        || (inside_envelope_index == 8 && bytes.as_bytes() != BODY_TAG)
+                // this looks ugly but we need to have least amount of
+                // iterations possible in a malicous case
+                // so if any of the conditions does not hold
+                // we return an error
+                if (inside_envelope_index == 0 && bytes.as_bytes() != ROLLUP_NAME_TAG)
+                    || (inside_envelope_index == 2 && bytes.as_bytes() != SIGNATURE_TAG)
+                    || (inside_envelope_index == 4 && bytes.as_bytes() != PUBLICKEY_TAG)
+                    || (inside_envelope_index == 6 && bytes.as_bytes() != RANDOM_TAG)
+                {
+                    return Err(ParserError::EnvelopeHasIncorrectFormat);
+                } else if inside_envelope_index == 1 && bytes.as_bytes() != rollup_name {
+                    return Err(ParserError::InvalidRollupName);
+                } else if inside_envelope_index == 3 {
+                    signature.extend(bytes.as_bytes());
+                } else if inside_envelope_index == 5 {
+                    public_key.extend(bytes.as_bytes());
+                } else if inside_body {
+                    body.extend(bytes.as_bytes());
+                } else if inside_envelope_index >= 8 && inside_envelope_index % 2 == 0 {
+                    let tag = bytes.as_bytes();
+
+                    if tag == BODY_TAG {
+                        inside_body = true;
+                    } else if is_unset_metadata_tag(&metadata, tag)
+                        || (tag == BODY_ROOT_TAG && root.is_none())
                     {
+                        metadata_tag = Some(tag);
+                    } else if is_envelope_tag(tag) || extra_tags.contains_key(tag) {
+                        // repeated tag
                         return Err(ParserError::EnvelopeHasIncorrectFormat);
-                    } else if inside_envelope_index == 1
-                        && bytes.as_bytes() != rollup_name.as_bytes()
-                    {
-                        return Err(ParserError::InvalidRollupName);
-                    } else if inside_envelope_index == 3 {
-                        signature.extend(bytes.as_bytes());
-                    } else if inside_envelope_index == 5 {
-                        public_key.extend(bytes.as_bytes());
-                    } else if inside_envelope_index >= 9 {
-                        body.extend(bytes.as_bytes());
+                    } else {
+                        extra_tag = Some(tag);
                     }
-
-                    inside_envelope_index += 1;
-                } else if bytes.is_empty() {
-                    last_op = Some(OP_FALSE); // rust bitcoin pushes [] instead of op_false
+                } else if let Some(tag) = metadata_tag.take() {
+                    if tag == BODY_ROOT_TAG {
+                        root = Some(
+                            bytes
+                                .as_bytes()
+                                .try_into()
+                                .map_err(|_| ParserError::EnvelopeHasIncorrectFormat)?,
+                        );
+                    } else {
+                        set_metadata_value(&mut metadata, tag, bytes.as_bytes())?;
+                    }
+                } else if let Some(tag) = extra_tag.take() {
+                    extra_tags.insert(tag.to_vec(), bytes.as_bytes().to_vec());
                 }
+
+                inside_envelope_index += 1;
             }
         }
     }
@@ -143,15 +327,220 @@ fn parse_relevant_inscriptions(
     if body.is_empty() || signature.is_empty() || public_key.is_empty() {
         return Err(ParserError::EnvelopeHasIncorrectFormat);
     }
+    if root.map_or(false, |root| root != body_root(&body)) {
+        return Err(ParserError::BodyRootMismatch);
+    }
 
     Ok(ParsedInscription {
         body,
         signature,
         public_key,
+        metadata,
+        body_root: root,
+        extra_tags,
+    })
+}
+
+// Whether tag is a known optional tag that was not seen yet
+fn is_unset_metadata_tag(metadata: &InscriptionMetadata, tag: &[u8]) -> bool {
+    (tag == BATCH_NUMBER_TAG && metadata.batch_number.is_none())
+        || (tag == TIMESTAMP_TAG && metadata.timestamp.is_none())
+        || (tag == KIND_TAG && metadata.kind.is_none())
+        || (tag == PARAMS_TAG && metadata.params_fingerprint.is_none())
+        || (tag == CHUNK_TAG && metadata.chunk.is_none())
+        || (tag == SCHEME_TAG && metadata.signature_scheme.is_none())
+        || (tag == COMPRESSION_TAG && metadata.compression.is_none())
+        || (tag == PRECOMMITMENT_TAG && metadata.precommitment.is_none())
+}
+
+// Whether tag is one of the tags of the envelope format, which cannot be extra tags
+fn is_envelope_tag(tag: &[u8]) -> bool {
+    [
+        VERSION_TAG,
+        ROLLUP_NAME_TAG,
+        SIGNATURE_TAG,
+        PUBLICKEY_TAG,
+        RANDOM_TAG,
+        BATCH_NUMBER_TAG,
+        TIMESTAMP_TAG,
+        KIND_TAG,
+        PARAMS_TAG,
+        CHUNK_TAG,
+        SCHEME_TAG,
+        COMPRESSION_TAG,
+        BODY_ROOT_TAG,
+        PRECOMMITMENT_TAG,
+    ]
+    .contains(&tag)
+}
+
+// Numbers are 8 bytes little endian, the kind, the signature scheme and the compression
+// algorithm are a single byte, the params fingerprint 8 bytes, the chunk manifest 40 bytes and
+// the pre-commitment txid 32 bytes
+fn set_metadata_value(
+    metadata: &mut InscriptionMetadata,
+    tag: &[u8],
+    value: &[u8],
+) -> Result<(), ParserError> {
+    if tag == KIND_TAG {
+        let kind = match value {
+            [kind] => BlobKind::from_u8(*kind),
+            _ => None,
+        };
+        metadata.kind = Some(kind.ok_or(ParserError::EnvelopeHasIncorrectFormat)?);
+        return Ok(());
+    }
+
+    if tag == SCHEME_TAG {
+        let scheme = match value {
+            [scheme] => SignatureScheme::from_u8(*scheme),
+            _ => None,
+        };
+        metadata.signature_scheme = Some(scheme.ok_or(ParserError::EnvelopeHasIncorrectFormat)?);
+        return Ok(());
+    }
+
+    if tag == COMPRESSION_TAG {
+        let compression = match value {
+            [compression] => CompressionAlgorithm::from_u8(*compression),
+            _ => None,
+        };
+        metadata.compression = Some(compression.ok_or(ParserError::EnvelopeHasIncorrectFormat)?);
+        return Ok(());
+    }
+
+    if tag == PARAMS_TAG {
+        metadata.params_fingerprint = Some(
+            value
+                .try_into()
+                .map_err(|_| ParserError::EnvelopeHasIncorrectFormat)?,
+        );
+        return Ok(());
+    }
+
+    if tag == PRECOMMITMENT_TAG {
+        metadata.precommitment = Some(
+            value
+                .try_into()
+                .map_err(|_| ParserError::EnvelopeHasIncorrectFormat)?,
+        );
+        return Ok(());
+    }
+
+    if tag == CHUNK_TAG {
+        metadata.chunk =
+            Some(ChunkManifest::from_bytes(value).ok_or(ParserError::EnvelopeHasIncorrectFormat)?);
+        return Ok(());
+    }
+
+    let value = u64::from_le_bytes(
+        value
+            .try_into()
+            .map_err(|_| ParserError::EnvelopeHasIncorrectFormat)?,
+    );
+
+    if tag == BATCH_NUMBER_TAG {
+        metadata.batch_number = Some(value);
+    } else {
+        metadata.timestamp = Some(value);
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ChunkError {
+    NotChunked,
+    ManifestMismatch,
+    SenderMismatch,
+    InvalidChunk(u32),
+    DuplicateChunk(u32),
+    MissingChunks,
+    BlobHashMismatch,
+    TooLarge,
+}
+
+// Blob put back together from its chunks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReassembledBlob {
+    // sha256d of the uncompressed blob, as in the chunk manifests
+    pub blob_hash: [u8; 32],
+    pub public_key: Vec<u8>,
+    // uncompressed blob
+    pub blob: Vec<u8>,
+    // metadata of the first chunk, without its manifest
+    pub metadata: InscriptionMetadata,
+}
+
+// Reassembles a blob from all of its chunks, given in any order
+// Every chunk must be signed by the same key and decompress, and the blob they add up to
+// must have the hash of the manifests and no more than max_len bytes, see
+// decompressed_body_with_limit
+pub fn reassemble_chunks(
+    chunks: &[ParsedInscription],
+    max_len: Option<usize>,
+) -> Result<ReassembledBlob, ChunkError> {
+    let max_len = max_len.unwrap_or(DEFAULT_MAX_DECOMPRESSED_LEN);
+    let first = chunks.first().ok_or(ChunkError::MissingChunks)?;
+    let manifest = first.metadata.chunk.ok_or(ChunkError::NotChunked)?;
+    // also bounds the allocation below by the number of chunks given
+    if manifest.total as usize > chunks.len() {
+        return Err(ChunkError::MissingChunks);
+    }
+
+    let mut parts: Vec<Option<(Vec<u8>, &ParsedInscription)>> = vec![None; manifest.total as usize];
+    let mut blob_len = 0;
+    for chunk in chunks {
+        let chunk_manifest = chunk.metadata.chunk.ok_or(ChunkError::NotChunked)?;
+        if chunk_manifest.total != manifest.total || chunk_manifest.blob_hash != manifest.blob_hash
+        {
+            return Err(ChunkError::ManifestMismatch);
+        }
+        if chunk.public_key != first.public_key {
+            return Err(ChunkError::SenderMismatch);
+        }
+
+        let index = chunk_manifest.index;
+        let part = chunk
+            .get_sig_verified_hash()
+            .and_then(|_| chunk.decompressed_body_with_limit(Some(max_len)).ok())
+            .ok_or(ChunkError::InvalidChunk(index))?;
+        blob_len += part.len();
+        if blob_len > max_len {
+            return Err(ChunkError::TooLarge);
+        }
+        // index < total is checked when the manifest is parsed
+        if parts[index as usize].replace((part, chunk)).is_some() {
+            return Err(ChunkError::DuplicateChunk(index));
+        }
+    }
+
+    let parts: Vec<(Vec<u8>, &ParsedInscription)> = parts
+        .into_iter()
+        .collect::<Option<_>>()
+        .ok_or(ChunkError::MissingChunks)?;
+    let blob = parts
+        .iter()
+        .flat_map(|(part, _)| part)
+        .copied()
+        .collect::<Vec<u8>>();
+
+    if sha256d::Hash::hash(&blob).to_byte_array() != manifest.blob_hash {
+        return Err(ChunkError::BlobHashMismatch);
+    }
+
+    Ok(ReassembledBlob {
+        blob_hash: manifest.blob_hash,
+        public_key: first.public_key.clone(),
+        blob,
+        metadata: InscriptionMetadata {
+            chunk: None,
+            ..parts[0].1.metadata
+        },
     })
 }
 
-pub fn parse_hex_transaction(
+pub(crate) fn parse_hex_transaction(
     tx_hex: &str,
 ) -> Result<Transaction, bitcoin::consensus::encode::Error> {
     if let Ok(reader) = hex::decode(tx_hex) {
@@ -168,13 +557,18 @@ mod tests {
     use bitcoin::opcodes::all::{OP_CHECKSIG, OP_ENDIF, OP_IF};
     use bitcoin::opcodes::{OP_FALSE, OP_TRUE};
     use bitcoin::script::{self, PushBytesBuf};
+    use bitcoin::secp256k1::SecretKey;
     use bitcoin::Transaction;
 
     use super::{
-        parse_relevant_inscriptions, BODY_TAG, PUBLICKEY_TAG, RANDOM_TAG, ROLLUP_NAME_TAG,
-        SIGNATURE_TAG,
+        parse_next_inscription, parse_relevant_inscriptions, BATCH_NUMBER_TAG, BODY_ROOT_TAG,
+        BODY_TAG, KIND_TAG, PARAMS_TAG, PRECOMMITMENT_TAG, PUBLICKEY_TAG, RANDOM_TAG,
+        ROLLUP_NAME_TAG, SIGNATURE_TAG, TIMESTAMP_TAG, VERSION_TAG,
     };
-    use crate::helpers::parsers::{parse_transaction, ParserError};
+    use crate::helpers::builders::{blob_signing_message, sign_blob_with_private_key};
+    use crate::helpers::parsers::{parse_transaction, ParsedInscription, ParserError};
+    use crate::spec::blob::{BlobKind, CompressionAlgorithm, InscriptionMetadata};
+    use crate::spec::body_root::body_root;
 
     #[test]
     fn correct() {
@@ -198,7 +592,7 @@ mod tests {
         let reveal_script = reveal_script_builder.into_script();
 
         let result =
-            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), "sov-btc");
+            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), b"sov-btc");
 
         assert!(result.is_ok());
 
@@ -209,6 +603,424 @@ mod tests {
         assert_eq!(result.public_key, vec![0u8; 64]);
     }
 
+    #[test]
+    fn envelope_version() {
+        let envelope = |version: &[u8]| {
+            script::Builder::new()
+                .push_x_only_key(&XOnlyPublicKey::from_slice(&[1; 32]).unwrap())
+                .push_opcode(OP_CHECKSIG)
+                .push_opcode(OP_FALSE)
+                .push_opcode(OP_IF)
+                .push_slice(PushBytesBuf::try_from(VERSION_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(version.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(ROLLUP_NAME_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from("sov-btc".as_bytes().to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(SIGNATURE_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+                .push_slice(PushBytesBuf::try_from(PUBLICKEY_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+                .push_slice(PushBytesBuf::try_from(RANDOM_TAG.to_vec()).unwrap())
+                .push_slice([0; 8])
+                .push_slice(PushBytesBuf::try_from(BODY_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(vec![0u8; 128]).unwrap())
+                .push_opcode(OP_ENDIF)
+                .into_script()
+        };
+
+        let reveal_script = envelope(&[1]);
+        let result =
+            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), b"sov-btc")
+                .unwrap();
+        assert_eq!(result.body, vec![0u8; 128]);
+        assert_eq!(result.signature, vec![0u8; 64]);
+
+        // a later layout is not parsed as the current one
+        let reveal_script = envelope(&[2]);
+        assert_eq!(
+            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), b"sov-btc")
+                .unwrap_err(),
+            ParserError::UnsupportedEnvelopeVersion(2)
+        );
+
+        let reveal_script = envelope(&[1, 0]);
+        assert_eq!(
+            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), b"sov-btc")
+                .unwrap_err(),
+            ParserError::EnvelopeHasIncorrectFormat
+        );
+    }
+
+    #[test]
+    fn byte_rollup_name() {
+        let rollup_name = [0xffu8, 0x00, 0xfe];
+        let reveal_script = script::Builder::new()
+            .push_opcode(OP_FALSE)
+            .push_opcode(OP_IF)
+            .push_slice(PushBytesBuf::try_from(ROLLUP_NAME_TAG.to_vec()).unwrap())
+            .push_slice(rollup_name)
+            .push_slice(PushBytesBuf::try_from(SIGNATURE_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+            .push_slice(PushBytesBuf::try_from(PUBLICKEY_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+            .push_slice(PushBytesBuf::try_from(RANDOM_TAG.to_vec()).unwrap())
+            .push_int(0)
+            .push_slice(PushBytesBuf::try_from(BODY_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(vec![0u8; 128]).unwrap())
+            .push_opcode(OP_ENDIF)
+            .into_script();
+
+        let result =
+            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), &rollup_name);
+        assert!(result.is_ok());
+
+        // non UTF-8 names of other rollups are rejected, not misread
+        let result =
+            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), b"sov-btc");
+        assert_eq!(result.unwrap_err(), ParserError::InvalidRollupName);
+    }
+
+    #[test]
+    fn metadata_tags() {
+        let reveal_script = script::Builder::new()
+            .push_x_only_key(&XOnlyPublicKey::from_slice(&[1; 32]).unwrap())
+            .push_opcode(OP_CHECKSIG)
+            .push_opcode(OP_FALSE)
+            .push_opcode(OP_IF)
+            .push_slice(PushBytesBuf::try_from(ROLLUP_NAME_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from("sov-btc".as_bytes().to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(SIGNATURE_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+            .push_slice(PushBytesBuf::try_from(PUBLICKEY_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+            .push_slice(PushBytesBuf::try_from(RANDOM_TAG.to_vec()).unwrap())
+            .push_int(0)
+            .push_slice(PushBytesBuf::try_from(BATCH_NUMBER_TAG.to_vec()).unwrap())
+            .push_slice(42u64.to_le_bytes())
+            .push_slice(PushBytesBuf::try_from(TIMESTAMP_TAG.to_vec()).unwrap())
+            .push_slice(1694177029u64.to_le_bytes())
+            .push_slice(PushBytesBuf::try_from(PARAMS_TAG.to_vec()).unwrap())
+            .push_slice([9u8; 8])
+            .push_slice(PushBytesBuf::try_from(BODY_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(vec![0u8; 128]).unwrap())
+            .push_opcode(OP_ENDIF)
+            .into_script();
+
+        let result =
+            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), b"sov-btc")
+                .unwrap();
+
+        assert_eq!(result.body, vec![0u8; 128]);
+        assert_eq!(result.metadata.batch_number, Some(42));
+        assert_eq!(result.metadata.timestamp, Some(1694177029));
+        assert_eq!(result.metadata.kind, None);
+        assert_eq!(result.metadata.params_fingerprint, Some([9; 8]));
+
+        // kind tag, a single byte
+        let reveal_script = script::Builder::new()
+            .push_opcode(OP_FALSE)
+            .push_opcode(OP_IF)
+            .push_slice(PushBytesBuf::try_from(ROLLUP_NAME_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from("sov-btc".as_bytes().to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(SIGNATURE_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+            .push_slice(PushBytesBuf::try_from(PUBLICKEY_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+            .push_slice(PushBytesBuf::try_from(RANDOM_TAG.to_vec()).unwrap())
+            .push_int(0)
+            .push_slice(PushBytesBuf::try_from(KIND_TAG.to_vec()).unwrap())
+            .push_slice([BlobKind::Proof.to_u8()])
+            .push_slice(PushBytesBuf::try_from(BODY_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(vec![0u8; 128]).unwrap())
+            .push_opcode(OP_ENDIF)
+            .into_script();
+
+        let result =
+            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), b"sov-btc")
+                .unwrap();
+
+        assert_eq!(result.metadata.kind, Some(BlobKind::Proof));
+        assert_eq!(result.metadata.batch_number, None);
+
+        // unknown kind
+        let reveal_script = script::Builder::new()
+            .push_opcode(OP_FALSE)
+            .push_opcode(OP_IF)
+            .push_slice(PushBytesBuf::try_from(ROLLUP_NAME_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from("sov-btc".as_bytes().to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(SIGNATURE_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+            .push_slice(PushBytesBuf::try_from(PUBLICKEY_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+            .push_slice(PushBytesBuf::try_from(RANDOM_TAG.to_vec()).unwrap())
+            .push_int(0)
+            .push_slice(PushBytesBuf::try_from(KIND_TAG.to_vec()).unwrap())
+            .push_slice([42u8])
+            .push_slice(PushBytesBuf::try_from(BODY_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(vec![0u8; 128]).unwrap())
+            .push_opcode(OP_ENDIF)
+            .into_script();
+
+        let result =
+            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), b"sov-btc");
+
+        assert_eq!(result.unwrap_err(), ParserError::EnvelopeHasIncorrectFormat);
+
+        // metadata value must be 8 bytes
+        let reveal_script = script::Builder::new()
+            .push_opcode(OP_FALSE)
+            .push_opcode(OP_IF)
+            .push_slice(PushBytesBuf::try_from(ROLLUP_NAME_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from("sov-btc".as_bytes().to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(SIGNATURE_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+            .push_slice(PushBytesBuf::try_from(PUBLICKEY_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+            .push_slice(PushBytesBuf::try_from(RANDOM_TAG.to_vec()).unwrap())
+            .push_int(0)
+            .push_slice(PushBytesBuf::try_from(BATCH_NUMBER_TAG.to_vec()).unwrap())
+            .push_slice([42u8; 4])
+            .push_slice(PushBytesBuf::try_from(BODY_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(vec![0u8; 128]).unwrap())
+            .push_opcode(OP_ENDIF)
+            .into_script();
+
+        let result =
+            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), b"sov-btc");
+
+        assert_eq!(result.unwrap_err(), ParserError::EnvelopeHasIncorrectFormat);
+
+        // repeated metadata tag
+        let reveal_script = script::Builder::new()
+            .push_opcode(OP_FALSE)
+            .push_opcode(OP_IF)
+            .push_slice(PushBytesBuf::try_from(ROLLUP_NAME_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from("sov-btc".as_bytes().to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(SIGNATURE_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+            .push_slice(PushBytesBuf::try_from(PUBLICKEY_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+            .push_slice(PushBytesBuf::try_from(RANDOM_TAG.to_vec()).unwrap())
+            .push_int(0)
+            .push_slice(PushBytesBuf::try_from(TIMESTAMP_TAG.to_vec()).unwrap())
+            .push_slice(1u64.to_le_bytes())
+            .push_slice(PushBytesBuf::try_from(TIMESTAMP_TAG.to_vec()).unwrap())
+            .push_slice(2u64.to_le_bytes())
+            .push_slice(PushBytesBuf::try_from(BODY_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from(vec![0u8; 128]).unwrap())
+            .push_opcode(OP_ENDIF)
+            .into_script();
+
+        let result =
+            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), b"sov-btc");
+
+        assert_eq!(result.unwrap_err(), ParserError::EnvelopeHasIncorrectFormat);
+    }
+
+    #[test]
+    fn extra_tags() {
+        let envelope = |tags: &[(&[u8], &[u8])]| {
+            let mut builder = script::Builder::new()
+                .push_opcode(OP_FALSE)
+                .push_opcode(OP_IF)
+                .push_slice(PushBytesBuf::try_from(ROLLUP_NAME_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from("sov-btc".as_bytes().to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(SIGNATURE_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+                .push_slice(PushBytesBuf::try_from(PUBLICKEY_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+                .push_slice(PushBytesBuf::try_from(RANDOM_TAG.to_vec()).unwrap())
+                .push_int(0);
+            for (tag, value) in tags {
+                builder = builder
+                    .push_slice(PushBytesBuf::try_from(tag.to_vec()).unwrap())
+                    .push_slice(PushBytesBuf::try_from(value.to_vec()).unwrap());
+            }
+            builder
+                .push_slice(PushBytesBuf::try_from(BODY_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(vec![0u8; 128]).unwrap())
+                .push_opcode(OP_ENDIF)
+                .into_script()
+        };
+
+        // unknown tags are returned next to the known ones
+        let reveal_script = envelope(&[
+            (BATCH_NUMBER_TAG, &42u64.to_le_bytes()),
+            (b"blocks", &[1, 2]),
+            (&[99], b""),
+        ]);
+        let result =
+            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), b"sov-btc")
+                .unwrap();
+        assert_eq!(result.body, vec![0u8; 128]);
+        assert_eq!(result.metadata.batch_number, Some(42));
+        assert_eq!(
+            result.extra_tags.into_iter().collect::<Vec<_>>(),
+            vec![(b"blocks".to_vec(), vec![1, 2]), (vec![99], vec![])]
+        );
+
+        // an extra tag is read once, and envelope tags are never extra tags
+        for tags in [
+            &[(b"blocks" as &[u8], &[1u8] as &[u8]), (b"blocks", &[2])][..],
+            &[(RANDOM_TAG, &[0; 8])],
+        ] {
+            let reveal_script = envelope(tags);
+            assert_eq!(
+                parse_relevant_inscriptions(
+                    &mut reveal_script.instructions().peekable(),
+                    b"sov-btc"
+                )
+                .unwrap_err(),
+                ParserError::EnvelopeHasIncorrectFormat
+            );
+        }
+    }
+
+    #[test]
+    fn body_root_tag() {
+        let body = vec![7u8; 1_200];
+        let envelope = |root: &[u8]| {
+            script::Builder::new()
+                .push_opcode(OP_FALSE)
+                .push_opcode(OP_IF)
+                .push_slice(PushBytesBuf::try_from(ROLLUP_NAME_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from("sov-btc".as_bytes().to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(SIGNATURE_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+                .push_slice(PushBytesBuf::try_from(PUBLICKEY_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+                .push_slice(PushBytesBuf::try_from(RANDOM_TAG.to_vec()).unwrap())
+                .push_int(0)
+                .push_slice(PushBytesBuf::try_from(BODY_ROOT_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(root.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(BODY_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(body[..520].to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(body[520..1_040].to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(body[1_040..].to_vec()).unwrap())
+                .push_opcode(OP_ENDIF)
+                .into_script()
+        };
+        let parse = |reveal_script: bitcoin::ScriptBuf| {
+            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), b"sov-btc")
+        };
+
+        let root = body_root(&body);
+        let result = parse(envelope(&root)).unwrap();
+        assert_eq!(result.body, body);
+        assert_eq!(result.body_root, Some(root));
+
+        assert_eq!(
+            parse(envelope(&body_root(&body[1..]))).unwrap_err(),
+            ParserError::BodyRootMismatch
+        );
+        assert_eq!(
+            parse(envelope(&root[1..])).unwrap_err(),
+            ParserError::EnvelopeHasIncorrectFormat
+        );
+    }
+
+    #[test]
+    fn precommitment_tag() {
+        let envelope = |precommitment: &[u8]| {
+            script::Builder::new()
+                .push_opcode(OP_FALSE)
+                .push_opcode(OP_IF)
+                .push_slice(PushBytesBuf::try_from(ROLLUP_NAME_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from("sov-btc".as_bytes().to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(SIGNATURE_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+                .push_slice(PushBytesBuf::try_from(PUBLICKEY_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(vec![0u8; 64]).unwrap())
+                .push_slice(PushBytesBuf::try_from(RANDOM_TAG.to_vec()).unwrap())
+                .push_int(0)
+                .push_slice(PushBytesBuf::try_from(PRECOMMITMENT_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(precommitment.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(BODY_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(vec![1u8; 16]).unwrap())
+                .push_opcode(OP_ENDIF)
+                .into_script()
+        };
+        let parse = |reveal_script: bitcoin::ScriptBuf| {
+            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), b"sov-btc")
+        };
+
+        let result = parse(envelope(&[3; 32])).unwrap();
+        assert_eq!(result.metadata.precommitment, Some([3; 32]));
+        assert!(result.extra_tags.is_empty());
+
+        assert_eq!(
+            parse(envelope(&[3; 31])).unwrap_err(),
+            ParserError::EnvelopeHasIncorrectFormat
+        );
+    }
+
+    #[test]
+    fn signed_metadata() {
+        let secret_key = SecretKey::from_slice(&[3; 32]).unwrap();
+        let sign = |body: &[u8], metadata: InscriptionMetadata, extra_tags: &[(&[u8], &[u8])]| {
+            let message = blob_signing_message(body, &metadata, extra_tags.iter().copied());
+            let (signature, public_key) = sign_blob_with_private_key(&message, &secret_key);
+            ParsedInscription {
+                body: body.to_vec(),
+                signature,
+                public_key,
+                metadata,
+                body_root: None,
+                extra_tags: extra_tags
+                    .iter()
+                    .map(|(tag, value)| (tag.to_vec(), value.to_vec()))
+                    .collect(),
+            }
+        };
+
+        // an envelope without tags signs its bare body
+        let legacy = sign(&[1; 16], InscriptionMetadata::default(), &[]);
+        assert_eq!(
+            blob_signing_message(&[1; 16], &InscriptionMetadata::default(), []),
+            vec![1; 16]
+        );
+        assert!(legacy.get_sig_verified_hash().is_some());
+
+        let metadata = InscriptionMetadata {
+            batch_number: Some(4),
+            timestamp: Some(1_700_000_000),
+            compression: Some(CompressionAlgorithm::None),
+            ..Default::default()
+        };
+        let inscription = sign(&[1; 16], metadata, &[(b"app", b"v1")]);
+        let hash = inscription.get_sig_verified_hash().unwrap();
+        // the blob hash is still the one of the body
+        assert_eq!(hash, legacy.get_sig_verified_hash().unwrap());
+
+        // no tag can be added, changed or stripped without the key
+        let tampered: Vec<fn(&mut ParsedInscription)> = vec![
+            |inscription| inscription.metadata.batch_number = Some(5),
+            |inscription| inscription.metadata.batch_number = None,
+            |inscription| inscription.metadata.timestamp = Some(0),
+            |inscription| inscription.metadata.kind = Some(BlobKind::Checkpoint),
+            |inscription| inscription.metadata.params_fingerprint = Some([0; 8]),
+            |inscription| inscription.metadata.compression = Some(CompressionAlgorithm::Zstd),
+            |inscription| inscription.metadata.compression = None,
+            |inscription| inscription.metadata.precommitment = Some([0; 32]),
+            |inscription| inscription.metadata = InscriptionMetadata::default(),
+            |inscription| inscription.extra_tags.clear(),
+            |inscription| {
+                inscription
+                    .extra_tags
+                    .insert(b"app".to_vec(), b"v2".to_vec());
+            },
+            |inscription| inscription.body[0] = 2,
+        ];
+        for tamper in tampered {
+            let mut tampered = inscription.clone();
+            tamper(&mut tampered);
+            assert_eq!(tampered.get_sig_verified_hash(), None);
+        }
+        // nor can a legacy signature be given tags
+        let mut tagged_legacy = legacy;
+        tagged_legacy.metadata.batch_number = Some(4);
+        assert_eq!(tagged_legacy.get_sig_verified_hash(), None);
+    }
+
     #[test]
     fn wrong_rollup_tag() {
         let reveal_script_builder = script::Builder::new()
@@ -231,7 +1043,7 @@ mod tests {
         let reveal_script = reveal_script_builder.into_script();
 
         let result =
-            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), "sov-btc");
+            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), b"sov-btc");
 
         assert!(result.is_err());
         assert_eq!(result.unwrap_err(), ParserError::InvalidRollupName);
@@ -258,7 +1070,7 @@ mod tests {
         let reveal_script = reveal_script_builder.into_script();
 
         let result =
-            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), "sov-btc");
+            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), b"sov-btc");
 
         assert!(result.is_err(), "Failed to error on no name tag.");
         assert_eq!(result.unwrap_err(), ParserError::EnvelopeHasIncorrectFormat);
@@ -282,7 +1094,7 @@ mod tests {
         let reveal_script = reveal_script_builder.into_script();
 
         let result =
-            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), "sov-btc");
+            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), b"sov-btc");
 
         assert!(result.is_err(), "Failed to error on no signature tag.");
         assert_eq!(result.unwrap_err(), ParserError::EnvelopeHasIncorrectFormat);
@@ -306,7 +1118,7 @@ mod tests {
         let reveal_script = reveal_script_builder.into_script();
 
         let result =
-            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), "sov-btc");
+            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), b"sov-btc");
 
         assert!(result.is_err(), "Failed to error on no publickey tag.");
         assert_eq!(result.unwrap_err(), ParserError::EnvelopeHasIncorrectFormat);
@@ -330,7 +1142,7 @@ mod tests {
         let reveal_script = reveal_script_builder.into_script();
 
         let result =
-            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), "sov-btc");
+            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), b"sov-btc");
 
         assert!(result.is_err(), "Failed to error on no body tag.");
 
@@ -353,7 +1165,7 @@ mod tests {
         let reveal_script = reveal_script_builder.into_script();
 
         let result =
-            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), "sov-btc");
+            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), b"sov-btc");
 
         assert!(result.is_err(), "Failed to error on no random tag.");
         assert_eq!(result.unwrap_err(), ParserError::EnvelopeHasIncorrectFormat);
@@ -380,7 +1192,7 @@ mod tests {
             .into_script();
 
         let result =
-            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), "sov-btc");
+            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), b"sov-btc");
 
         assert!(result.is_err());
         assert_eq!(result.unwrap_err(), ParserError::EnvelopeHasIncorrectFormat);
@@ -412,7 +1224,7 @@ mod tests {
             .into_script();
 
         let result =
-            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), "sov-btc");
+            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), b"sov-btc");
 
         assert!(result.is_err());
         assert_eq!(result.unwrap_err(), ParserError::EnvelopeHasNonPushOp);
@@ -452,7 +1264,7 @@ mod tests {
             .into_script();
 
         let result =
-            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), "sov-btc");
+            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), b"sov-btc");
 
         assert!(result.is_ok());
 
@@ -463,6 +1275,58 @@ mod tests {
         assert_eq!(result.public_key, vec![0u8; 64]);
     }
 
+    #[test]
+    fn batched_envelopes() {
+        let envelope = |builder: script::Builder, fill: u8| {
+            builder
+                .push_opcode(OP_FALSE)
+                .push_opcode(OP_IF)
+                .push_slice(PushBytesBuf::try_from(ROLLUP_NAME_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from("sov-btc".as_bytes().to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(SIGNATURE_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(vec![fill; 64]).unwrap())
+                .push_slice(PushBytesBuf::try_from(PUBLICKEY_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(vec![fill; 64]).unwrap())
+                .push_slice(PushBytesBuf::try_from(RANDOM_TAG.to_vec()).unwrap())
+                .push_int(0)
+                .push_slice(PushBytesBuf::try_from(BODY_TAG.to_vec()).unwrap())
+                .push_slice(PushBytesBuf::try_from(vec![fill; 128]).unwrap())
+                .push_opcode(OP_ENDIF)
+        };
+        let checksig = script::Builder::new()
+            .push_x_only_key(&XOnlyPublicKey::from_slice(&[1; 32]).unwrap())
+            .push_opcode(OP_CHECKSIG);
+
+        let reveal_script = envelope(envelope(checksig.clone(), 0), 1).into_script();
+        let mut instructions = reveal_script.instructions().peekable();
+
+        let first = parse_next_inscription(&mut instructions, b"sov-btc").unwrap();
+        assert_eq!(first.unwrap().body, vec![0u8; 128]);
+        let second = parse_next_inscription(&mut instructions, b"sov-btc").unwrap();
+        assert_eq!(second.unwrap().body, vec![1u8; 128]);
+        assert!(parse_next_inscription(&mut instructions, b"sov-btc")
+            .unwrap()
+            .is_none());
+
+        // an envelope of another rollup after a valid one
+        let reveal_script = envelope(checksig, 0)
+            .push_opcode(OP_FALSE)
+            .push_opcode(OP_IF)
+            .push_slice(PushBytesBuf::try_from(ROLLUP_NAME_TAG.to_vec()).unwrap())
+            .push_slice(PushBytesBuf::try_from("other".as_bytes().to_vec()).unwrap())
+            .push_opcode(OP_ENDIF)
+            .into_script();
+        let mut instructions = reveal_script.instructions().peekable();
+
+        assert!(parse_next_inscription(&mut instructions, b"sov-btc")
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            parse_next_inscription(&mut instructions, b"sov-btc").unwrap_err(),
+            ParserError::InvalidRollupName
+        );
+    }
+
     #[test]
     fn big_push() {
         let reveal_script = script::Builder::new()
@@ -489,7 +1353,7 @@ mod tests {
             .into_script();
 
         let result =
-            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), "sov-btc");
+            parse_relevant_inscriptions(&mut reveal_script.instructions().peekable(), b"sov-btc");
 
         assert!(result.is_ok());
 
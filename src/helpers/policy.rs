@@ -0,0 +1,177 @@
+use bitcoin::blockdata::constants::MAX_SCRIPT_ELEMENT_SIZE;
+use bitcoin::blockdata::script::Instruction;
+use bitcoin::{Script, Transaction};
+use thiserror::Error;
+
+use crate::helpers::builders::dust_threshold;
+
+// Standardness limits of bitcoind, a tx breaking them is valid but not relayed
+pub const MAX_STANDARD_TX_WEIGHT: usize = 400_000;
+const MIN_STANDARD_TX_NONWITNESS_SIZE: usize = 65;
+// witness items of a tapscript spend besides the script and the control block
+const MAX_STANDARD_TAPSCRIPT_STACK_ITEM_SIZE: usize = 80;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PolicyError {
+    #[error("weight of {weight} WU is above the standard limit of {MAX_STANDARD_TX_WEIGHT} WU")]
+    Weight { weight: usize },
+    #[error("size of {size} bytes without witness is below the standard minimum")]
+    TooSmall { size: usize },
+    #[error("output {vout} of {value} sats is below the dust threshold of {threshold} sats")]
+    Dust {
+        vout: usize,
+        value: u64,
+        threshold: u64,
+    },
+    #[error("script push of {len} bytes is above the limit of {MAX_SCRIPT_ELEMENT_SIZE} bytes")]
+    PushSize { len: usize },
+    #[error("witness item of {len} bytes is above the tapscript limit of 80 bytes")]
+    StackItemSize { len: usize },
+    #[error("script cannot be decoded")]
+    Script,
+}
+
+// Weight, size and output rules of a tx with its witness, or placeholders of the same size
+// Outputs below dust_limit are dust, or below the threshold of their script at the dust
+// relay fee if it is not set. OP_RETURN outputs are never dust
+pub fn check_tx_standardness(
+    tx: &Transaction,
+    dust_limit: Option<u64>,
+    dust_relay_fee_rate: f64,
+) -> Result<(), PolicyError> {
+    let weight = tx.weight().to_wu() as usize;
+    if weight > MAX_STANDARD_TX_WEIGHT {
+        return Err(PolicyError::Weight { weight });
+    }
+
+    let size = tx.strippedsize();
+    if size < MIN_STANDARD_TX_NONWITNESS_SIZE {
+        return Err(PolicyError::TooSmall { size });
+    }
+
+    for (vout, output) in tx.output.iter().enumerate() {
+        if output.script_pubkey.is_op_return() {
+            continue;
+        }
+        let threshold = dust_limit
+            .unwrap_or_else(|| dust_threshold(&output.script_pubkey, dust_relay_fee_rate));
+        if output.value < threshold {
+            return Err(PolicyError::Dust {
+                vout,
+                value: output.value,
+                threshold,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+// Push and stack item limits of a script path spend of script, whose witness stack holds
+// stack_items before the script and the control block
+pub fn check_tapscript_spend(script: &Script, stack_items: &[&[u8]]) -> Result<(), PolicyError> {
+    for instruction in script.instructions() {
+        match instruction.map_err(|_| PolicyError::Script)? {
+            Instruction::PushBytes(push) if push.len() > MAX_SCRIPT_ELEMENT_SIZE => {
+                return Err(PolicyError::PushSize { len: push.len() });
+            }
+            _ => {}
+        }
+    }
+
+    match stack_items
+        .iter()
+        .find(|item| item.len() > MAX_STANDARD_TAPSCRIPT_STACK_ITEM_SIZE)
+    {
+        Some(item) => Err(PolicyError::StackItemSize { len: item.len() }),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+
+    use bitcoin::absolute::LockTime;
+    use bitcoin::blockdata::opcodes::all::OP_CHECKSIG;
+    use bitcoin::script::Builder;
+    use bitcoin::{OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness};
+
+    use super::{check_tapscript_spend, check_tx_standardness, PolicyError};
+
+    fn tx(values: &[u64], witness_len: usize) -> Transaction {
+        let mut witness = Witness::new();
+        witness.push(vec![0; witness_len]);
+        Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::from_str(&format!("{:064x}", 1)).unwrap(), 0),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness,
+            }],
+            output: values
+                .iter()
+                .map(|value| TxOut {
+                    value: *value,
+                    script_pubkey: ScriptBuf::from_hex(
+                        "512009c03e3d99ac46e29929db5a70b1995de11c1b5da2f5bc0de64ccb8098cab2aa",
+                    )
+                    .unwrap(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn tx_standardness() {
+        assert_eq!(check_tx_standardness(&tx(&[546], 64), None, 3.0), Ok(()));
+
+        // P2TR outputs are dust below 330 sats at 3 sat/vB
+        assert_eq!(
+            check_tx_standardness(&tx(&[546, 329], 64), None, 3.0),
+            Err(PolicyError::Dust {
+                vout: 1,
+                value: 329,
+                threshold: 330
+            })
+        );
+        assert_eq!(
+            check_tx_standardness(&tx(&[546, 329], 64), Some(0), 3.0),
+            Ok(())
+        );
+
+        // the witness is a quarter of its size in weight
+        assert!(matches!(
+            check_tx_standardness(&tx(&[546], 1_600_000), None, 3.0),
+            Err(PolicyError::Weight { .. })
+        ));
+
+        assert!(matches!(
+            check_tx_standardness(&tx(&[], 64), None, 3.0),
+            Err(PolicyError::TooSmall { .. })
+        ));
+    }
+
+    #[test]
+    fn tapscript_spend() {
+        let script = Builder::new()
+            .push_slice([1; 32])
+            .push_opcode(OP_CHECKSIG)
+            .into_script();
+        assert_eq!(check_tapscript_spend(&script, &[&[0; 64]]), Ok(()));
+        assert_eq!(
+            check_tapscript_spend(&script, &[&[0; 81]]),
+            Err(PolicyError::StackItemSize { len: 81 })
+        );
+
+        // a push above 520 bytes cannot be built, only decoded
+        let mut bytes = vec![0x4d, 0x09, 0x02];
+        bytes.extend([0; 521]);
+        assert_eq!(
+            check_tapscript_spend(&ScriptBuf::from_bytes(bytes), &[]),
+            Err(PolicyError::PushSize { len: 521 })
+        );
+    }
+}
@@ -0,0 +1,339 @@
+use bitcoin::blockdata::constants::MAX_SCRIPT_ELEMENT_SIZE;
+use bitcoin::blockdata::opcodes::all::{OP_CHECKSIG, OP_ENDIF, OP_IF};
+use bitcoin::blockdata::script::Instruction;
+use bitcoin::hashes::Hash;
+use bitcoin::opcodes::OP_FALSE;
+use bitcoin::psbt::Prevouts;
+use bitcoin::secp256k1::{schnorr, Message, Secp256k1, XOnlyPublicKey};
+use bitcoin::sighash::{SighashCache, TapSighashType};
+use bitcoin::taproot::{ControlBlock, LeafVersion, TapLeafHash};
+use bitcoin::{Script, Transaction, TxOut};
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum RevealSimulationError {
+    #[error("reveal tx must have a single input")]
+    InputCount,
+    #[error("reveal tx must spend the commit output")]
+    WrongPrevout,
+    #[error("reveal outputs exceed the commit output value")]
+    ValueOverflow,
+    #[error("commit output is not a taproot output")]
+    NotTaproot,
+    #[error("witness must be a signature, the reveal script and a control block")]
+    WitnessShape,
+    #[error("control block does not commit the reveal script to the commit output")]
+    ControlBlock,
+    #[error("reveal script does not match the inscription template: {0}")]
+    Script(&'static str),
+    #[error("signature does not verify against the key of the reveal script")]
+    Signature,
+}
+
+// Simulates the script path spend of a reveal tx against the commit output it spends,
+// following the consensus rules the inscription template relies on. This is not a general
+// script interpreter: scripts other than `<key> OP_CHECKSIG` followed by one or more
+// `OP_FALSE OP_IF <pushes> OP_ENDIF` envelopes are rejected, which covers every script
+// build_reveal_script produces. The libbitcoinconsensus the bitcoin crate binds predates
+// taproot and cannot verify a script path spend, so there is no interpreter to defer to
+pub(crate) fn simulate_reveal_spend(
+    reveal_tx: &Transaction,
+    commit_tx: &Transaction,
+) -> Result<(), RevealSimulationError> {
+    let [input] = reveal_tx.input.as_slice() else {
+        return Err(RevealSimulationError::InputCount);
+    };
+
+    if input.previous_output.txid != commit_tx.txid() {
+        return Err(RevealSimulationError::WrongPrevout);
+    }
+    let prevout: &TxOut = commit_tx
+        .output
+        .get(input.previous_output.vout as usize)
+        .ok_or(RevealSimulationError::WrongPrevout)?;
+
+    let output_total: u64 = reveal_tx.output.iter().map(|output| output.value).sum();
+    if output_total > prevout.value {
+        return Err(RevealSimulationError::ValueOverflow);
+    }
+
+    if !prevout.script_pubkey.is_v1_p2tr() {
+        return Err(RevealSimulationError::NotTaproot);
+    }
+    let output_key = XOnlyPublicKey::from_slice(&prevout.script_pubkey.as_bytes()[2..])
+        .map_err(|_| RevealSimulationError::NotTaproot)?;
+
+    if input.witness.len() != 3 {
+        return Err(RevealSimulationError::WitnessShape);
+    }
+    let signature = input.witness.nth(0).expect("witness has 3 elements");
+    let script = Script::from_bytes(input.witness.nth(1).expect("witness has 3 elements"));
+    let control_block = ControlBlock::decode(input.witness.nth(2).expect("witness has 3 elements"))
+        .map_err(|_| RevealSimulationError::ControlBlock)?;
+
+    let secp = Secp256k1::verification_only();
+
+    if control_block.leaf_version != LeafVersion::TapScript
+        || !control_block.verify_taproot_commitment(&secp, output_key, script)
+    {
+        return Err(RevealSimulationError::ControlBlock);
+    }
+
+    let public_key = check_script_template(script)?;
+
+    let (signature, sighash_type) = match signature.len() {
+        64 => (signature, TapSighashType::Default),
+        65 => (
+            &signature[..64],
+            TapSighashType::from_consensus_u8(signature[64])
+                .map_err(|_| RevealSimulationError::Signature)?,
+        ),
+        _ => return Err(RevealSimulationError::Signature),
+    };
+    let signature =
+        schnorr::Signature::from_slice(signature).map_err(|_| RevealSimulationError::Signature)?;
+
+    let sighash = SighashCache::new(reveal_tx)
+        .taproot_script_spend_signature_hash(
+            0,
+            &Prevouts::All(&[prevout]),
+            TapLeafHash::from_script(script, LeafVersion::TapScript),
+            sighash_type,
+        )
+        .map_err(|_| RevealSimulationError::Signature)?;
+
+    secp.verify_schnorr(
+        &signature,
+        &Message::from_slice(sighash.as_byte_array()).expect("sighash is 32 bytes"),
+        &public_key,
+    )
+    .map_err(|_| RevealSimulationError::Signature)
+}
+
+// Checks the script is `<key> OP_CHECKSIG` followed by envelopes
+// `OP_FALSE OP_IF <pushes> OP_ENDIF`, one per blob of a batched reveal tx, with pushes within
+// the stack element size limit, and returns the key
+fn check_script_template(script: &Script) -> Result<XOnlyPublicKey, RevealSimulationError> {
+    let mut instructions = script.instructions();
+    let mut next = || {
+        instructions
+            .next()
+            .transpose()
+            .map_err(|_| RevealSimulationError::Script("malformed push"))
+    };
+
+    let public_key = match next()? {
+        Some(Instruction::PushBytes(bytes)) => XOnlyPublicKey::from_slice(bytes.as_bytes())
+            .map_err(|_| RevealSimulationError::Script("invalid key"))?,
+        _ => return Err(RevealSimulationError::Script("missing key")),
+    };
+
+    if next()? != Some(Instruction::Op(OP_CHECKSIG)) {
+        return Err(RevealSimulationError::Script("missing OP_CHECKSIG"));
+    }
+
+    let mut envelope = next()?;
+    if envelope.is_none() {
+        return Err(RevealSimulationError::Script("missing OP_FALSE"));
+    }
+    while let Some(instruction) = envelope {
+        // OP_FALSE is an empty push
+        match instruction {
+            Instruction::PushBytes(bytes) if bytes.is_empty() => {}
+            Instruction::Op(op) if op == OP_FALSE => {}
+            _ => return Err(RevealSimulationError::Script("missing OP_FALSE")),
+        }
+
+        if next()? != Some(Instruction::Op(OP_IF)) {
+            return Err(RevealSimulationError::Script("missing OP_IF"));
+        }
+
+        loop {
+            match next()? {
+                Some(Instruction::PushBytes(bytes)) if bytes.len() <= MAX_SCRIPT_ELEMENT_SIZE => {}
+                Some(Instruction::PushBytes(_)) => {
+                    return Err(RevealSimulationError::Script("push exceeds 520 bytes"))
+                }
+                Some(Instruction::Op(OP_ENDIF)) => break,
+                _ => return Err(RevealSimulationError::Script("non push in envelope")),
+            }
+        }
+
+        envelope = next()?;
+    }
+
+    Ok(public_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+
+    use bitcoin::{Address, Txid, Witness};
+
+    use super::{simulate_reveal_spend, RevealSimulationError};
+    use crate::helpers::builders::{InscriptionBuilder, SignedBlob};
+    use crate::spec::blob::{
+        BlobKind, ChunkManifest, CompressionAlgorithm, InscriptionMetadata, SignatureScheme,
+    };
+    use crate::spec::utxo::UTXO;
+
+    #[test]
+    fn simulate_reveal() {
+        let address =
+            Address::from_str("bc1pp8qru0ve43rw9xffmdd8pvveths3cx6a5t6mcr0xfn9cpxx2k24qf70xq9")
+                .unwrap()
+                .require_network(bitcoin::Network::Bitcoin)
+                .unwrap();
+        let utxo = UTXO {
+            tx_id: Txid::from_str(
+                "4cfbec13cf1510545f285cceceb6229bd7b6a918a8f6eba1dbee64d26226a3b7",
+            )
+            .unwrap(),
+            vout: 0,
+            address: address.to_string(),
+            script_pubkey: address.script_pubkey().to_hex_string(),
+            amount: 1_000_000,
+            confirmations: 100,
+            spendable: true,
+            solvable: true,
+            ancestor_count: None,
+        };
+
+        let (commit, reveal) = InscriptionBuilder::new("test_rollup")
+            .with_body(vec![100; 2000])
+            .with_signature(vec![100; 64], vec![100; 33])
+            .with_utxos(vec![utxo.clone()])
+            .with_recipient(address.clone())
+            .with_fee_rates(10.0, 10.0)
+            .build()
+            .unwrap();
+
+        assert_eq!(simulate_reveal_spend(&reveal, &commit), Ok(()));
+
+        // another commit tx
+        let mut other_commit = commit.clone();
+        other_commit.lock_time = bitcoin::absolute::LockTime::from_consensus(1);
+        assert_eq!(
+            simulate_reveal_spend(&reveal, &other_commit),
+            Err(RevealSimulationError::WrongPrevout)
+        );
+
+        let witness: Vec<Vec<u8>> = reveal.input[0].witness.to_vec();
+
+        // corrupted signature
+        let mut tampered = reveal.clone();
+        let mut signature = witness[0].clone();
+        signature[10] ^= 1;
+        tampered.input[0].witness =
+            Witness::from_slice(&[signature, witness[1].clone(), witness[2].clone()]);
+        assert_eq!(
+            simulate_reveal_spend(&tampered, &commit),
+            Err(RevealSimulationError::Signature)
+        );
+
+        // control block of another internal key
+        let mut control_block = witness[2].clone();
+        control_block[5] ^= 1;
+        tampered.input[0].witness =
+            Witness::from_slice(&[witness[0].clone(), witness[1].clone(), control_block]);
+        assert_eq!(
+            simulate_reveal_spend(&tampered, &commit),
+            Err(RevealSimulationError::ControlBlock)
+        );
+
+        // outputs spending more than the commit output
+        let mut tampered = reveal.clone();
+        tampered.output[0].value = commit.output[0].value + 1;
+        assert_eq!(
+            simulate_reveal_spend(&tampered, &commit),
+            Err(RevealSimulationError::ValueOverflow)
+        );
+
+        // a batched reveal tx carries an envelope per blob
+        let batched = InscriptionBuilder::new("test_rollup")
+            .with_body(vec![100; 2000])
+            .with_signature(vec![100; 64], vec![100; 33])
+            .with_batched_blob(SignedBlob {
+                body: vec![101; 700],
+                signature: vec![101; 64],
+                public_key: vec![101; 33],
+                metadata: Default::default(),
+            })
+            .with_batched_blob(SignedBlob {
+                body: vec![102; 10],
+                signature: vec![102; 64],
+                public_key: vec![102; 33],
+                metadata: Default::default(),
+            })
+            .with_utxos(vec![utxo])
+            .with_recipient(address)
+            .with_fee_rates(10.0, 10.0)
+            .build()
+            .unwrap();
+        assert_eq!(simulate_reveal_spend(&batched.1, &batched.0), Ok(()));
+    }
+
+    #[test]
+    fn simulate_builder_scripts() {
+        let address =
+            Address::from_str("bc1pp8qru0ve43rw9xffmdd8pvveths3cx6a5t6mcr0xfn9cpxx2k24qf70xq9")
+                .unwrap()
+                .require_network(bitcoin::Network::Bitcoin)
+                .unwrap();
+        let utxo = UTXO {
+            tx_id: Txid::from_str(
+                "4cfbec13cf1510545f285cceceb6229bd7b6a918a8f6eba1dbee64d26226a3b7",
+            )
+            .unwrap(),
+            vout: 0,
+            address: address.to_string(),
+            script_pubkey: address.script_pubkey().to_hex_string(),
+            amount: 1_000_000,
+            confirmations: 100,
+            spendable: true,
+            solvable: true,
+            ancestor_count: None,
+        };
+        let metadata = InscriptionMetadata {
+            batch_number: Some(7),
+            timestamp: Some(1_700_000_000),
+            kind: Some(BlobKind::Checkpoint),
+            params_fingerprint: Some([3; 8]),
+            chunk: Some(ChunkManifest {
+                index: 1,
+                total: 2,
+                blob_hash: [4; 32],
+            }),
+            signature_scheme: Some(SignatureScheme::Schnorr),
+            compression: Some(CompressionAlgorithm::None),
+            precommitment: Some([5; 32]),
+        };
+
+        // every metadata tag, extra tags of the largest size and bodies around the chunk size
+        for body_len in [1, 520, 521, 1_040, 5_000] {
+            let (commit, reveal) = InscriptionBuilder::new("test_rollup")
+                .with_body(vec![100; body_len])
+                .with_signature(vec![100; 64], vec![100; 32])
+                .with_metadata(metadata)
+                .with_extra_tags(vec![(vec![1; 520], vec![2; 520]), (vec![3; 2], vec![])])
+                .with_batched_blob(SignedBlob {
+                    body: vec![101; body_len],
+                    signature: vec![101; 64],
+                    public_key: vec![101; 33],
+                    metadata,
+                })
+                .with_utxos(vec![utxo.clone()])
+                .with_recipient(address.clone())
+                .with_fee_rates(10.0, 10.0)
+                .build()
+                .unwrap();
+
+            assert_eq!(
+                simulate_reveal_spend(&reveal, &commit),
+                Ok(()),
+                "{body_len}"
+            );
+        }
+    }
+}
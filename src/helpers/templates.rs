@@ -0,0 +1,294 @@
+// Transaction templates with placeholder signatures of the correct length
+// They let tests check sizes and fee logic without real keys or a bitcoin node
+
+use bitcoin::hashes::Hash;
+use bitcoin::{absolute, Network, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid};
+
+use super::builders::InscriptionBuilder;
+pub use crate::spec::utxo::InputScriptType;
+use crate::spec::utxo::UTXO;
+
+// schnorr signature with default sighash
+const SCHNORR_SIGNATURE_SIZE: usize = 64;
+
+impl InputScriptType {
+    pub fn utxo(&self, amount: u64, vout: u32) -> UTXO {
+        let address = self.address(Network::Regtest);
+
+        UTXO {
+            tx_id: Txid::all_zeros(),
+            vout,
+            address: address.to_string(),
+            script_pubkey: address.script_pubkey().to_hex_string(),
+            amount,
+            confirmations: 100,
+            spendable: true,
+            solvable: true,
+            ancestor_count: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TxTemplate {
+    inputs: Vec<(InputScriptType, u64)>,
+    outputs: Vec<TxOut>,
+}
+
+impl TxTemplate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_input(mut self, script_type: InputScriptType, value: u64) -> Self {
+        self.inputs.push((script_type, value));
+        self
+    }
+
+    pub fn with_output(mut self, script_pubkey: ScriptBuf, value: u64) -> Self {
+        self.outputs.push(TxOut {
+            value,
+            script_pubkey,
+        });
+        self
+    }
+
+    // Builds the transaction with placeholder signatures, so its size equals the signed one
+    pub fn build(&self) -> Transaction {
+        let input = self
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(vout, (script_type, _))| TxIn {
+                previous_output: OutPoint {
+                    txid: Txid::all_zeros(),
+                    vout: vout as u32,
+                },
+                script_sig: script_type.script_sig(),
+                witness: script_type.witness(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            })
+            .collect();
+
+        Transaction {
+            version: 2,
+            lock_time: absolute::LockTime::ZERO,
+            input,
+            output: self.outputs.clone(),
+        }
+    }
+
+    pub fn fee(&self) -> u64 {
+        let input_total: u64 = self.inputs.iter().map(|(_, value)| value).sum();
+        let output_total: u64 = self.outputs.iter().map(|output| output.value).sum();
+
+        input_total - output_total
+    }
+
+    pub fn fee_rate(&self) -> f64 {
+        self.fee() as f64 / self.build().vsize() as f64
+    }
+}
+
+// Fills the inputs of an unsigned transaction with placeholder signatures of the given type
+pub fn with_placeholder_signatures(
+    mut tx: Transaction,
+    script_type: InputScriptType,
+) -> Transaction {
+    for input in tx.input.iter_mut() {
+        input.script_sig = script_type.script_sig();
+        input.witness = script_type.witness();
+    }
+
+    tx
+}
+
+// Builds a commit/reveal pair for a body of the given length funded by a single utxo
+// Commit tx carries placeholder signatures, reveal tx is signed with a throwaway key
+pub fn inscription_pair(
+    body_len: usize,
+    funding_type: InputScriptType,
+    funding_amount: u64,
+    commit_fee_rate: f64,
+    reveal_fee_rate: f64,
+) -> Result<(Transaction, Transaction), anyhow::Error> {
+    let recipient = InputScriptType::P2tr.address(Network::Regtest);
+
+    let (commit, reveal) = InscriptionBuilder::new("template")
+        .with_body(vec![1; body_len])
+        .with_signature(vec![0; SCHNORR_SIGNATURE_SIZE], vec![2; 33])
+        .with_utxos(vec![funding_type.utxo(funding_amount, 0)])
+        .with_recipient(recipient)
+        .with_fee_rates(commit_fee_rate, reveal_fee_rate)
+        .with_network(Network::Regtest)
+        .build()?;
+
+    Ok((with_placeholder_signatures(commit, funding_type), reveal))
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::Network;
+
+    use super::{
+        inscription_pair, with_placeholder_signatures, InputScriptType, InscriptionBuilder,
+        TxTemplate, SCHNORR_SIGNATURE_SIZE,
+    };
+    use crate::helpers::builders::{
+        build_consolidation_transaction, estimate_inscription_cost,
+        with_placeholder_signatures as with_utxo_placeholder_signatures, ConsolidationError,
+    };
+
+    #[test]
+    fn template_sizes() {
+        let output = InputScriptType::P2tr
+            .address(Network::Regtest)
+            .script_pubkey();
+
+        let sizes = [
+            (InputScriptType::P2tr, 111),
+            (InputScriptType::P2wpkh, 122),
+            (InputScriptType::P2shP2wpkh, 145),
+            (InputScriptType::P2pkh, 201),
+        ]
+        .map(|(script_type, expected)| {
+            let tx = TxTemplate::new()
+                .with_input(script_type, 10_000)
+                .with_output(output.clone(), 9_000)
+                .build();
+
+            (tx.vsize(), expected)
+        });
+
+        for (vsize, expected) in sizes {
+            assert_eq!(vsize, expected);
+        }
+    }
+
+    #[test]
+    fn template_fee_rate() {
+        let template = TxTemplate::new()
+            .with_input(InputScriptType::P2tr, 10_000)
+            .with_output(
+                InputScriptType::P2tr
+                    .address(Network::Regtest)
+                    .script_pubkey(),
+                8_890,
+            );
+
+        assert_eq!(template.fee(), 1_110);
+        assert_eq!(template.fee_rate(), 10.0);
+    }
+
+    #[test]
+    fn inscription_pair_fee_rates() {
+        for funding_type in [
+            InputScriptType::P2tr,
+            InputScriptType::P2wpkh,
+            InputScriptType::P2shP2wpkh,
+            InputScriptType::P2pkh,
+        ] {
+            let (commit, reveal) =
+                inscription_pair(1000, funding_type, 1_000_000, 12.0, 10.0).unwrap();
+
+            let commit_fee = 1_000_000 - commit.output.iter().map(|o| o.value).sum::<u64>();
+            let reveal_fee = commit.output[0].value - reveal.output[0].value;
+
+            assert!(
+                commit_fee as f64 / commit.vsize() as f64 >= 12.0,
+                "{:?}",
+                funding_type
+            );
+            assert!(reveal_fee as f64 / reveal.vsize() as f64 >= 10.0);
+        }
+    }
+
+    #[test]
+    fn inscription_cost() {
+        for body_len in [1, 1_000, 100_000] {
+            let cost = estimate_inscription_cost(body_len, 12.0, 10.0).unwrap();
+            let (commit, reveal) =
+                inscription_pair(body_len, InputScriptType::P2tr, 10_000_000, 12.0, 10.0).unwrap();
+
+            assert_eq!(cost.commit_vsize, commit.vsize());
+            // the template envelope is smaller than the worst case one
+            assert!(cost.reveal_vsize >= reveal.vsize());
+            assert_eq!(cost.reveal_value, reveal.output[0].value);
+
+            let commit_fee = 10_000_000 - commit.output.iter().map(|o| o.value).sum::<u64>();
+            assert_eq!(cost.commit_fee, commit_fee);
+            assert!(cost.total() >= commit_fee + commit.output[0].value);
+        }
+
+        assert!(estimate_inscription_cost(1, 0.0, 10.0).is_err());
+    }
+
+    #[test]
+    fn mixed_funding_fee_rate() {
+        let utxos: Vec<_> = [
+            InputScriptType::P2tr,
+            InputScriptType::P2wpkh,
+            InputScriptType::P2shP2wpkh,
+            InputScriptType::P2pkh,
+        ]
+        .iter()
+        .enumerate()
+        .map(|(vout, script_type)| script_type.utxo(3_000, vout as u32))
+        .collect();
+        let recipient = InputScriptType::P2tr.address(Network::Regtest);
+
+        // the reveal value takes all four utxos
+        let (commit, _) = InscriptionBuilder::new("template")
+            .with_body(vec![1; 100])
+            .with_signature(vec![0; SCHNORR_SIGNATURE_SIZE], vec![2; 33])
+            .with_utxos(utxos.clone())
+            .with_recipient(recipient)
+            .with_reveal_value(9_000)
+            .with_fee_rates(2.0, 2.0)
+            .with_network(Network::Regtest)
+            .build()
+            .unwrap();
+        assert_eq!(commit.input.len(), 4);
+        assert_eq!(commit.output.len(), 2);
+
+        // each input is sized by its own script type, not as a taproot key path spend
+        let signed = with_utxo_placeholder_signatures(&commit, &utxos);
+        let as_taproot = with_placeholder_signatures(commit.clone(), InputScriptType::P2tr);
+        assert!(signed.vsize() > as_taproot.vsize());
+
+        let commit_fee = 12_000 - commit.output.iter().map(|o| o.value).sum::<u64>();
+        assert_eq!(commit_fee, (signed.vsize() as f64 * 2.0).ceil() as u64);
+    }
+
+    #[test]
+    fn consolidation() {
+        let target = InputScriptType::P2tr.address(Network::Regtest);
+        let mut utxos: Vec<_> = (0..4)
+            .map(|vout| InputScriptType::P2tr.utxo(546, vout))
+            .collect();
+        utxos.push(InputScriptType::P2pkh.utxo(546, 4));
+
+        let tx = build_consolidation_transaction(&utxos, target.clone(), 2.0).unwrap();
+        assert_eq!(tx.input.len(), 5);
+        assert_eq!(tx.output.len(), 1);
+        let signed = with_utxo_placeholder_signatures(&tx, &utxos);
+        let fee = 5 * 546 - tx.output[0].value;
+        assert_eq!(fee, (signed.vsize() as f64 * 2.0).ceil() as u64);
+
+        // a taproot input costs 57.5 vB, reveal outputs are not worth sweeping at 10 sat/vB
+        assert_eq!(
+            build_consolidation_transaction(&utxos, target.clone(), 10.0),
+            Err(ConsolidationError::NothingToSweep)
+        );
+        utxos.push(InputScriptType::P2tr.utxo(10_000, 5));
+        let tx = build_consolidation_transaction(&utxos, target.clone(), 10.0).unwrap();
+        assert_eq!(tx.input.len(), 1);
+        assert_eq!(tx.input[0].previous_output.vout, 5);
+
+        // the output would be dust
+        assert!(matches!(
+            build_consolidation_transaction(&[InputScriptType::P2tr.utxo(400, 0)], target, 2.0),
+            Err(ConsolidationError::InsufficientValue { value: 400, .. })
+        ));
+    }
+}
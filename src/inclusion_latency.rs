@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+use bitcoin::Txid;
+use serde::{Deserialize, Serialize};
+
+/// Time to inclusion of a blob sent by the service. Times are unix seconds,
+/// block latencies are counted from the tip height at submission
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InclusionRecord {
+    pub reveal_txid: Txid,
+    pub submitted_at: u64,
+    pub submitted_tip: u64,
+    // first block the reveal tx was seen in, it moves if that block is reorged out
+    pub confirmed_at: Option<u64>,
+    pub confirmed_height: Option<u64>,
+    // tip height at which the service first saw the confirmation block finalized
+    pub finalized_at: Option<u64>,
+    pub finalized_tip: Option<u64>,
+}
+
+impl InclusionRecord {
+    pub fn seconds_to_confirmation(&self) -> Option<u64> {
+        Some(self.confirmed_at?.saturating_sub(self.submitted_at))
+    }
+
+    pub fn blocks_to_confirmation(&self) -> Option<u64> {
+        Some(self.confirmed_height?.saturating_sub(self.submitted_tip))
+    }
+
+    pub fn seconds_to_finality(&self) -> Option<u64> {
+        Some(self.finalized_at?.saturating_sub(self.submitted_at))
+    }
+
+    pub fn blocks_to_finality(&self) -> Option<u64> {
+        Some(self.finalized_tip?.saturating_sub(self.submitted_tip))
+    }
+}
+
+// Nearest rank percentiles of a latency
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Percentiles {
+    pub count: usize,
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+impl Percentiles {
+    fn from_values(mut values: Vec<u64>) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+
+        values.sort_unstable();
+        let rank = |percentile: usize| values[(values.len() * percentile + 99) / 100 - 1];
+
+        Self {
+            count: values.len(),
+            p50: rank(50),
+            p90: rank(90),
+            p99: rank(99),
+            max: values[values.len() - 1],
+        }
+    }
+}
+
+/// Latency percentiles of the submissions in a time window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct InclusionLatency {
+    pub seconds_to_confirmation: Percentiles,
+    pub blocks_to_confirmation: Percentiles,
+    pub seconds_to_finality: Percentiles,
+    pub blocks_to_finality: Percentiles,
+}
+
+// InclusionTracker follows submissions until their finality
+// With a path, records are appended to a JSON lines file on every change and
+// the latest record of a reveal tx wins when the file is loaded again
+#[derive(Debug, Default)]
+pub struct InclusionTracker {
+    path: Option<PathBuf>,
+    records: HashMap<Txid, InclusionRecord>,
+}
+
+impl InclusionTracker {
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, anyhow::Error> {
+        let path = path.into();
+        let mut records = HashMap::new();
+
+        if path.exists() {
+            for line in BufReader::new(File::open(&path)?).lines() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                let record: InclusionRecord = serde_json::from_str(&line)?;
+                records.insert(record.reveal_txid, record);
+            }
+        }
+
+        Ok(Self {
+            path: Some(path),
+            records,
+        })
+    }
+
+    pub fn get(&self, reveal_txid: &Txid) -> Option<InclusionRecord> {
+        self.records.get(reveal_txid).copied()
+    }
+
+    pub fn record_submission(
+        &mut self,
+        reveal_txid: Txid,
+        submitted_at: u64,
+        submitted_tip: u64,
+    ) -> Result<(), anyhow::Error> {
+        self.insert(InclusionRecord {
+            reveal_txid,
+            submitted_at,
+            submitted_tip,
+            confirmed_at: None,
+            confirmed_height: None,
+            finalized_at: None,
+            finalized_tip: None,
+        })
+    }
+
+    // Only submissions of the service are tracked, other txs are ignored
+    pub fn record_confirmation(
+        &mut self,
+        reveal_txid: &Txid,
+        height: u64,
+        now: u64,
+    ) -> Result<(), anyhow::Error> {
+        let Some(record) = self.get(reveal_txid) else {
+            return Ok(());
+        };
+        if record.finalized_at.is_some() || record.confirmed_height == Some(height) {
+            return Ok(());
+        }
+
+        // after a reorg the first confirmation time is kept, only the block changes
+        self.insert(InclusionRecord {
+            confirmed_at: Some(record.confirmed_at.unwrap_or(now)),
+            confirmed_height: Some(height),
+            ..record
+        })
+    }
+
+    // Marks the submissions confirmed at or below finalized_height as final
+    // Returns the newly finalized records
+    pub fn record_finality(
+        &mut self,
+        finalized_height: u64,
+        tip: u64,
+        now: u64,
+    ) -> Result<Vec<InclusionRecord>, anyhow::Error> {
+        let finalized: Vec<InclusionRecord> = self
+            .records
+            .values()
+            .filter(|record| {
+                record.finalized_at.is_none()
+                    && record
+                        .confirmed_height
+                        .map_or(false, |height| height <= finalized_height)
+            })
+            .map(|record| InclusionRecord {
+                finalized_at: Some(now),
+                finalized_tip: Some(tip),
+                ..*record
+            })
+            .collect();
+
+        for record in &finalized {
+            self.insert(*record)?;
+        }
+
+        Ok(finalized)
+    }
+
+    // Percentiles over the submissions sent at or after `since`
+    pub fn latency(&self, since: u64) -> InclusionLatency {
+        let window: Vec<&InclusionRecord> = self
+            .records
+            .values()
+            .filter(|record| record.submitted_at >= since)
+            .collect();
+        let percentiles = |latency: fn(&InclusionRecord) -> Option<u64>| {
+            Percentiles::from_values(window.iter().filter_map(|record| latency(record)).collect())
+        };
+
+        InclusionLatency {
+            seconds_to_confirmation: percentiles(InclusionRecord::seconds_to_confirmation),
+            blocks_to_confirmation: percentiles(InclusionRecord::blocks_to_confirmation),
+            seconds_to_finality: percentiles(InclusionRecord::seconds_to_finality),
+            blocks_to_finality: percentiles(InclusionRecord::blocks_to_finality),
+        }
+    }
+
+    fn insert(&mut self, record: InclusionRecord) -> Result<(), anyhow::Error> {
+        if let Some(path) = &self.path {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(file, "{}", serde_json::to_string(&record)?)?;
+        }
+
+        self.records.insert(record.reveal_txid, record);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+    use bitcoin::Txid;
+
+    use super::{InclusionTracker, Percentiles};
+
+    #[test]
+    fn percentiles() {
+        assert_eq!(Percentiles::from_values(vec![]), Percentiles::default());
+        assert_eq!(
+            Percentiles::from_values((1..=100).rev().collect()),
+            Percentiles {
+                count: 100,
+                p50: 50,
+                p90: 90,
+                p99: 99,
+                max: 100,
+            }
+        );
+        assert_eq!(
+            Percentiles::from_values(vec![7]),
+            Percentiles {
+                count: 1,
+                p50: 7,
+                p90: 7,
+                p99: 7,
+                max: 7,
+            }
+        );
+    }
+
+    #[test]
+    fn inclusion_tracker_persists() {
+        let path = std::env::temp_dir().join(format!("inclusion_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let first = Txid::from_byte_array([1; 32]);
+        let second = Txid::from_byte_array([2; 32]);
+
+        let mut tracker = InclusionTracker::open(&path).unwrap();
+        tracker.record_submission(first, 1_000, 100).unwrap();
+        tracker.record_submission(second, 2_000, 105).unwrap();
+        // not sent by the service
+        tracker
+            .record_confirmation(&Txid::from_byte_array([3; 32]), 101, 1_600)
+            .unwrap();
+
+        tracker.record_confirmation(&first, 101, 1_600).unwrap();
+        // reorged into the next block
+        tracker.record_confirmation(&first, 102, 2_200).unwrap();
+        tracker.record_confirmation(&second, 106, 2_300).unwrap();
+
+        let finalized = tracker.record_finality(102, 106, 4_000).unwrap();
+        assert_eq!(finalized.len(), 1);
+        assert_eq!(finalized[0].reveal_txid, first);
+
+        let tracker = InclusionTracker::open(&path).unwrap();
+        let record = tracker.get(&first).unwrap();
+        assert_eq!(record.seconds_to_confirmation(), Some(600));
+        assert_eq!(record.blocks_to_confirmation(), Some(2));
+        assert_eq!(record.seconds_to_finality(), Some(3_000));
+        assert_eq!(record.blocks_to_finality(), Some(6));
+
+        let latency = tracker.latency(0);
+        assert_eq!(latency.seconds_to_confirmation.count, 2);
+        assert_eq!(latency.seconds_to_confirmation.max, 600);
+        assert_eq!(latency.seconds_to_finality.count, 1);
+        assert_eq!(tracker.latency(1_500).blocks_to_confirmation.p50, 1);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,10 +1,62 @@
+#[cfg(feature = "native")]
+pub mod audit;
+#[cfg(feature = "native")]
+pub mod batch_registry;
+#[cfg(feature = "native")]
+pub mod batch_window;
+#[cfg(feature = "native")]
+pub mod beacon;
+pub mod bip322;
+#[cfg(feature = "native")]
+pub mod blob_index;
+#[cfg(feature = "native")]
+pub mod broadcast_log;
+pub mod coin_selection;
+pub mod entropy;
+#[cfg(all(feature = "native", any(test, feature = "test-utils")))]
+pub mod fault;
+#[cfg(feature = "native")]
+pub mod fee;
+#[cfg(feature = "native")]
+pub mod handoff;
 mod helpers;
 #[cfg(feature = "native")]
+pub mod inclusion_latency;
+#[cfg(feature = "native")]
+pub mod musig;
+pub mod prelude;
+#[cfg(feature = "native")]
+pub mod quorum;
+#[cfg(feature = "native")]
+pub mod quota;
+#[cfg(feature = "native")]
+pub mod replay;
+#[cfg(feature = "native")]
+pub mod retention;
+#[cfg(feature = "native")]
 mod rpc;
 pub mod spec;
 
 #[cfg(feature = "native")]
 pub mod service;
+#[cfg(feature = "native")]
+pub mod signer;
+#[cfg(feature = "native")]
+pub mod slot;
+#[cfg(feature = "native")]
+pub mod tip_monitor;
+pub mod tx_diff;
+#[cfg(feature = "native")]
+pub mod tx_store;
+#[cfg(feature = "native")]
+pub mod utxo_filter;
+#[cfg(feature = "native")]
+pub mod utxo_reservation;
 pub mod verifier;
+#[cfg(feature = "native")]
+pub mod webhook;
+
+#[cfg(any(test, feature = "test-utils"))]
+pub use helpers::{corpus, templates};
 
 const REVEAL_OUTPUT_AMOUNT: u64 = 546;
@@ -0,0 +1,760 @@
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::secp256k1::constants::CURVE_ORDER;
+use bitcoin::secp256k1::schnorr::Signature;
+use bitcoin::secp256k1::{
+    Message, PublicKey, Scalar, Secp256k1, SecretKey, Signing, Verification, XOnlyPublicKey,
+};
+use thiserror::Error;
+
+use crate::entropy::{random_bytes, EntropySource};
+
+// MuSig2 (BIP327) key aggregation and signing, so that a committee of sequencers can
+// sign a blob with a single Schnorr signature under an aggregate key. Key tweaking is
+// not supported, the aggregate key is used as is as the blob sender
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MusigError {
+    #[error("no public keys to aggregate")]
+    NoPublicKeys,
+    #[error("signer is not part of the aggregate key")]
+    UnknownSigner,
+    #[error("number of partial signatures does not match the number of signers")]
+    MissingPartialSignatures,
+    #[error("partial signature of signer {0} is invalid")]
+    InvalidPartialSignature(usize),
+    // a nonce, a signature or a point came out zero or at infinity, this is negligibly rare
+    #[error("scalar out of range")]
+    ScalarOutOfRange,
+}
+
+fn tagged_hash(tag: &str, data: &[&[u8]]) -> [u8; 32] {
+    let tag_hash = sha256::Hash::hash(tag.as_bytes());
+
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag_hash.as_byte_array());
+    engine.input(tag_hash.as_byte_array());
+    for bytes in data {
+        engine.input(bytes);
+    }
+
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+// Hashes are reduced mod n as in BIP327, a hash of n or more is not an error
+fn hash_to_scalar(tag: &str, data: &[&[u8]]) -> Result<Scalar, MusigError> {
+    scalar_mod_n(tagged_hash(tag, data))
+}
+
+fn scalar_mod_n(mut bytes: [u8; 32]) -> Result<Scalar, MusigError> {
+    if Scalar::from_be_bytes(bytes).is_err() {
+        // 2^256 < 2n, a single subtraction reduces it
+        let mut borrow = 0;
+        for (byte, order_byte) in bytes.iter_mut().zip(CURVE_ORDER.iter()).rev() {
+            let difference = *byte as i16 - *order_byte as i16 - borrow;
+            *byte = difference.rem_euclid(256) as u8;
+            borrow = (difference < 0) as i16;
+        }
+    }
+
+    Scalar::from_be_bytes(bytes).map_err(|_| MusigError::ScalarOutOfRange)
+}
+
+fn has_even_y(point: &PublicKey) -> bool {
+    point.serialize()[0] == 0x02
+}
+
+fn x_bytes(point: &PublicKey) -> [u8; 32] {
+    point.x_only_public_key().0.serialize()
+}
+
+/// Aggregate public key of a fixed, ordered, set of signers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyAggContext {
+    public_keys: Vec<PublicKey>,
+    coefficients: Vec<Scalar>,
+    aggregate: PublicKey,
+}
+
+impl KeyAggContext {
+    pub fn new(public_keys: Vec<PublicKey>) -> Result<Self, MusigError> {
+        let first = *public_keys.first().ok_or(MusigError::NoPublicKeys)?;
+        let second = public_keys.iter().find(|key| **key != first).copied();
+
+        let serialized: Vec<[u8; 33]> = public_keys.iter().map(PublicKey::serialize).collect();
+        let list_hash = tagged_hash(
+            "KeyAgg list",
+            &serialized
+                .iter()
+                .map(|key| key.as_slice())
+                .collect::<Vec<_>>(),
+        );
+
+        let secp = Secp256k1::verification_only();
+        let mut coefficients = Vec::with_capacity(public_keys.len());
+        let mut terms = Vec::with_capacity(public_keys.len());
+        for (key, serialized_key) in public_keys.iter().zip(serialized.iter()) {
+            // the second distinct key gets coefficient 1, it saves a multiplication
+            let coefficient = if Some(*key) == second {
+                Scalar::ONE
+            } else {
+                hash_to_scalar("KeyAgg coefficient", &[&list_hash, serialized_key])?
+            };
+
+            terms.push(
+                key.mul_tweak(&secp, &coefficient)
+                    .map_err(|_| MusigError::ScalarOutOfRange)?,
+            );
+            coefficients.push(coefficient);
+        }
+
+        let aggregate = PublicKey::combine_keys(&terms.iter().collect::<Vec<_>>())
+            .map_err(|_| MusigError::ScalarOutOfRange)?;
+
+        Ok(Self {
+            public_keys,
+            coefficients,
+            aggregate,
+        })
+    }
+
+    pub fn aggregate_public_key(&self) -> XOnlyPublicKey {
+        self.aggregate.x_only_public_key().0
+    }
+
+    pub fn public_keys(&self) -> &[PublicKey] {
+        &self.public_keys
+    }
+
+    fn coefficient(&self, public_key: &PublicKey) -> Result<Scalar, MusigError> {
+        self.public_keys
+            .iter()
+            .position(|key| key == public_key)
+            .map(|index| self.coefficients[index])
+            .ok_or(MusigError::UnknownSigner)
+    }
+}
+
+/// Secret nonce of a signing session, consumed by signing so it cannot be reused
+#[derive(Debug)]
+pub struct SecretNonce(SecretKey, SecretKey);
+
+/// Public nonce a signer shares with the other signers before signing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublicNonce(pub PublicKey, pub PublicKey);
+
+/// Sum of the public nonces of all signers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AggregateNonce(pub PublicKey, pub PublicKey);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartialSignature(pub SecretKey);
+
+impl SecretNonce {
+    // NonceGen of BIP327: the random bytes are mixed with the secret key of the signer and,
+    // when known, the aggregate key and the message, so that a weak entropy source alone
+    // does not leak the key
+    pub fn generate<C: Signing>(
+        secp: &Secp256k1<C>,
+        entropy: &mut dyn EntropySource,
+        secret_key: &SecretKey,
+        aggregate_public_key: Option<&XOnlyPublicKey>,
+        message: Option<&[u8; 32]>,
+    ) -> Result<(SecretNonce, PublicNonce), MusigError> {
+        nonce_gen(
+            secp,
+            random_bytes(entropy),
+            Some(secret_key),
+            &secret_key.public_key(secp),
+            aggregate_public_key.map(XOnlyPublicKey::serialize).as_ref(),
+            message.map(|message| message.as_slice()),
+            None,
+        )
+    }
+}
+
+fn nonce_gen<C: Signing>(
+    secp: &Secp256k1<C>,
+    rand: [u8; 32],
+    secret_key: Option<&SecretKey>,
+    public_key: &PublicKey,
+    aggregate_public_key: Option<&[u8; 32]>,
+    message: Option<&[u8]>,
+    extra_in: Option<&[u8]>,
+) -> Result<(SecretNonce, PublicNonce), MusigError> {
+    let mut rand = rand;
+    if let Some(secret_key) = secret_key {
+        let aux = tagged_hash("MuSig/aux", &[&rand]);
+        for ((byte, key_byte), aux_byte) in rand
+            .iter_mut()
+            .zip(secret_key.secret_bytes())
+            .zip(aux.iter())
+        {
+            *byte = key_byte ^ aux_byte;
+        }
+    }
+
+    let aggregate_public_key: &[u8] = aggregate_public_key.map_or(&[], |key| key.as_slice());
+    // an absent message differs from an empty one
+    let message_prefixed = match message {
+        None => vec![0],
+        Some(message) => {
+            let mut prefixed = vec![1];
+            prefixed.extend_from_slice(&(message.len() as u64).to_be_bytes());
+            prefixed.extend_from_slice(message);
+            prefixed
+        }
+    };
+    let extra_in = extra_in.unwrap_or_default();
+    let extra_in_len = u32::try_from(extra_in.len()).map_err(|_| MusigError::ScalarOutOfRange)?;
+    let public_key = public_key.serialize();
+
+    let nonce = |index: u8| {
+        let k = hash_to_scalar(
+            "MuSig/nonce",
+            &[
+                &rand,
+                &[public_key.len() as u8],
+                &public_key,
+                &[aggregate_public_key.len() as u8],
+                aggregate_public_key,
+                &message_prefixed,
+                &extra_in_len.to_be_bytes(),
+                extra_in,
+                &[index],
+            ],
+        )?;
+        SecretKey::from_slice(&k.to_be_bytes()).map_err(|_| MusigError::ScalarOutOfRange)
+    };
+    let (k1, k2) = (nonce(0)?, nonce(1)?);
+
+    Ok((
+        SecretNonce(k1, k2),
+        PublicNonce(k1.public_key(secp), k2.public_key(secp)),
+    ))
+}
+
+impl AggregateNonce {
+    pub fn new(public_nonces: &[PublicNonce]) -> Result<Self, MusigError> {
+        let r1: Vec<&PublicKey> = public_nonces.iter().map(|nonce| &nonce.0).collect();
+        let r2: Vec<&PublicKey> = public_nonces.iter().map(|nonce| &nonce.1).collect();
+
+        Ok(Self(
+            PublicKey::combine_keys(&r1).map_err(|_| MusigError::ScalarOutOfRange)?,
+            PublicKey::combine_keys(&r2).map_err(|_| MusigError::ScalarOutOfRange)?,
+        ))
+    }
+}
+
+// Values every signer derives the same way from the aggregate nonce and the message
+struct SessionValues {
+    b: Scalar,
+    e: Scalar,
+    r: PublicKey,
+}
+
+impl SessionValues {
+    fn new<C: Verification>(
+        secp: &Secp256k1<C>,
+        key_agg: &KeyAggContext,
+        aggregate_nonce: &AggregateNonce,
+        message: &[u8; 32],
+    ) -> Result<Self, MusigError> {
+        let aggregate_key = x_bytes(&key_agg.aggregate);
+
+        let b = hash_to_scalar(
+            "MuSig/noncecoef",
+            &[
+                &aggregate_nonce.0.serialize(),
+                &aggregate_nonce.1.serialize(),
+                &aggregate_key,
+                message,
+            ],
+        )?;
+        let r = aggregate_nonce
+            .0
+            .combine(
+                &aggregate_nonce
+                    .1
+                    .mul_tweak(secp, &b)
+                    .map_err(|_| MusigError::ScalarOutOfRange)?,
+            )
+            .map_err(|_| MusigError::ScalarOutOfRange)?;
+        let e = hash_to_scalar(
+            "BIP0340/challenge",
+            &[&x_bytes(&r), &aggregate_key, message],
+        )?;
+
+        Ok(Self { b, e, r })
+    }
+}
+
+pub fn partial_sign<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    secret_nonce: SecretNonce,
+    secret_key: &SecretKey,
+    key_agg: &KeyAggContext,
+    aggregate_nonce: &AggregateNonce,
+    message: &[u8; 32],
+) -> Result<PartialSignature, MusigError> {
+    let coefficient = key_agg.coefficient(&secret_key.public_key(secp))?;
+    let session = SessionValues::new(secp, key_agg, aggregate_nonce, message)?;
+
+    let SecretNonce(mut k1, mut k2) = secret_nonce;
+    if !has_even_y(&session.r) {
+        k1 = k1.negate();
+        k2 = k2.negate();
+    }
+
+    let mut d = *secret_key;
+    if !has_even_y(&key_agg.aggregate) {
+        d = d.negate();
+    }
+
+    // s = k1 + b * k2 + e * a * d
+    let k2_b = k2
+        .mul_tweak(&session.b)
+        .map_err(|_| MusigError::ScalarOutOfRange)?;
+    let e_a_d = d
+        .mul_tweak(&coefficient)
+        .and_then(|d| d.mul_tweak(&session.e))
+        .map_err(|_| MusigError::ScalarOutOfRange)?;
+    let s = k1
+        .add_tweak(&Scalar::from(k2_b))
+        .and_then(|s| s.add_tweak(&Scalar::from(e_a_d)))
+        .map_err(|_| MusigError::ScalarOutOfRange)?;
+
+    Ok(PartialSignature(s))
+}
+
+// Checks the partial signature of a single signer, so a misbehaving signer can be identified
+pub fn verify_partial_signature<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    partial_signature: &PartialSignature,
+    public_nonce: &PublicNonce,
+    public_key: &PublicKey,
+    key_agg: &KeyAggContext,
+    aggregate_nonce: &AggregateNonce,
+    message: &[u8; 32],
+) -> Result<bool, MusigError> {
+    let coefficient = key_agg.coefficient(public_key)?;
+    let session = SessionValues::new(secp, key_agg, aggregate_nonce, message)?;
+
+    // R1 + b * R2, negated if the final nonce has odd y
+    let mut nonce = public_nonce
+        .0
+        .combine(
+            &public_nonce
+                .1
+                .mul_tweak(secp, &session.b)
+                .map_err(|_| MusigError::ScalarOutOfRange)?,
+        )
+        .map_err(|_| MusigError::ScalarOutOfRange)?;
+    if !has_even_y(&session.r) {
+        nonce = nonce.negate(secp);
+    }
+
+    let mut key = *public_key;
+    if !has_even_y(&key_agg.aggregate) {
+        key = key.negate(secp);
+    }
+    let key = key
+        .mul_tweak(secp, &coefficient)
+        .and_then(|key| key.mul_tweak(secp, &session.e))
+        .map_err(|_| MusigError::ScalarOutOfRange)?;
+
+    let expected = nonce
+        .combine(&key)
+        .map_err(|_| MusigError::ScalarOutOfRange)?;
+
+    Ok(partial_signature.0.public_key(secp) == expected)
+}
+
+// Sums the partial signatures of all signers, in the order of the signers' public keys,
+// into a BIP340 signature under the aggregate key. Invalid partial signatures are reported
+pub fn aggregate_partial_signatures<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    key_agg: &KeyAggContext,
+    public_nonces: &[PublicNonce],
+    partial_signatures: &[PartialSignature],
+    message: &[u8; 32],
+) -> Result<Signature, MusigError> {
+    if partial_signatures.len() != key_agg.public_keys.len()
+        || public_nonces.len() != key_agg.public_keys.len()
+    {
+        return Err(MusigError::MissingPartialSignatures);
+    }
+
+    let aggregate_nonce = AggregateNonce::new(public_nonces)?;
+
+    for (index, (public_key, (public_nonce, partial_signature))) in key_agg
+        .public_keys
+        .iter()
+        .zip(public_nonces.iter().zip(partial_signatures.iter()))
+        .enumerate()
+    {
+        if !verify_partial_signature(
+            secp,
+            partial_signature,
+            public_nonce,
+            public_key,
+            key_agg,
+            &aggregate_nonce,
+            message,
+        )? {
+            return Err(MusigError::InvalidPartialSignature(index));
+        }
+    }
+
+    let session = SessionValues::new(secp, key_agg, &aggregate_nonce, message)?;
+
+    let mut s = partial_signatures[0].0;
+    for partial_signature in &partial_signatures[1..] {
+        s = s
+            .add_tweak(&Scalar::from(partial_signature.0))
+            .map_err(|_| MusigError::ScalarOutOfRange)?;
+    }
+
+    let mut signature = [0u8; 64];
+    signature[..32].copy_from_slice(&x_bytes(&session.r));
+    signature[32..].copy_from_slice(&s.secret_bytes());
+
+    let signature = Signature::from_slice(&signature).map_err(|_| MusigError::ScalarOutOfRange)?;
+
+    // the sum of valid partial signatures is always valid, this only guards against bugs
+    secp.verify_schnorr(
+        &signature,
+        &Message::from_slice(message).expect("message is 32 bytes"),
+        &key_agg.aggregate_public_key(),
+    )
+    .map_err(|_| MusigError::ScalarOutOfRange)?;
+
+    Ok(signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+
+    use bitcoin::hashes::hex::FromHex;
+    use bitcoin::hashes::{sha256d, Hash};
+    use bitcoin::secp256k1::constants::CURVE_ORDER;
+    use bitcoin::secp256k1::{Message, PublicKey, Scalar, Secp256k1, SecretKey};
+
+    use super::{
+        aggregate_partial_signatures, nonce_gen, partial_sign, scalar_mod_n,
+        verify_partial_signature, AggregateNonce, KeyAggContext, MusigError, PartialSignature,
+        PublicNonce, SecretNonce,
+    };
+    use crate::helpers::parsers::ParsedInscription;
+    use crate::spec::blob::InscriptionMetadata;
+
+    #[test]
+    fn musig_session() {
+        let secp = Secp256k1::new();
+        let mut rng = rand::thread_rng();
+
+        let secret_keys: Vec<SecretKey> = (0..3).map(|_| SecretKey::new(&mut rng)).collect();
+        let key_agg = KeyAggContext::new(
+            secret_keys
+                .iter()
+                .map(|secret_key| secret_key.public_key(&secp))
+                .collect(),
+        )
+        .unwrap();
+
+        let body = vec![7u8; 100];
+        let message = sha256d::Hash::hash(&body).to_byte_array();
+
+        // first round, nonces are exchanged
+        let (secret_nonces, public_nonces): (Vec<_>, Vec<_>) = secret_keys
+            .iter()
+            .map(|secret_key| {
+                SecretNonce::generate(
+                    &secp,
+                    &mut rng,
+                    secret_key,
+                    Some(&key_agg.aggregate_public_key()),
+                    Some(&message),
+                )
+                .unwrap()
+            })
+            .unzip();
+        let aggregate_nonce = AggregateNonce::new(&public_nonces).unwrap();
+
+        // second round, partial signatures are exchanged
+        let partial_signatures: Vec<PartialSignature> = secret_nonces
+            .into_iter()
+            .zip(secret_keys.iter())
+            .map(|(secret_nonce, secret_key)| {
+                partial_sign(
+                    &secp,
+                    secret_nonce,
+                    secret_key,
+                    &key_agg,
+                    &aggregate_nonce,
+                    &message,
+                )
+                .unwrap()
+            })
+            .collect();
+
+        let signature = aggregate_partial_signatures(
+            &secp,
+            &key_agg,
+            &public_nonces,
+            &partial_signatures,
+            &message,
+        )
+        .unwrap();
+
+        secp.verify_schnorr(
+            &signature,
+            &Message::from_slice(&message).unwrap(),
+            &key_agg.aggregate_public_key(),
+        )
+        .unwrap();
+
+        // the verifier accepts the aggregate signature as a single signature
+        let inscription = ParsedInscription {
+            body,
+            signature: signature.as_ref().to_vec(),
+            public_key: key_agg.aggregate_public_key().serialize().to_vec(),
+            metadata: InscriptionMetadata::default(),
+            body_root: None,
+            extra_tags: Default::default(),
+        };
+        assert_eq!(inscription.get_sig_verified_hash(), Some(message));
+
+        // a wrong partial signature is attributed to its signer
+        let mut wrong_partial_signatures = partial_signatures.clone();
+        wrong_partial_signatures[1] = partial_signatures[2];
+        assert_eq!(
+            aggregate_partial_signatures(
+                &secp,
+                &key_agg,
+                &public_nonces,
+                &wrong_partial_signatures,
+                &message,
+            ),
+            Err(MusigError::InvalidPartialSignature(1))
+        );
+
+        // only signers of the aggregate key can sign
+        let outsider = SecretKey::new(&mut rng);
+        let (secret_nonce, _) =
+            SecretNonce::generate(&secp, &mut rng, &outsider, None, None).unwrap();
+        assert_eq!(
+            partial_sign(
+                &secp,
+                secret_nonce,
+                &outsider,
+                &key_agg,
+                &aggregate_nonce,
+                &message,
+            ),
+            Err(MusigError::UnknownSigner)
+        );
+    }
+
+    fn public_key(hex: &str) -> PublicKey {
+        PublicKey::from_str(hex).unwrap()
+    }
+
+    fn bytes<const N: usize>(hex: &str) -> [u8; N] {
+        Vec::from_hex(hex).unwrap().try_into().unwrap()
+    }
+
+    #[test]
+    fn hashes_reduced_mod_n() {
+        assert_eq!(scalar_mod_n(CURVE_ORDER), Ok(Scalar::ZERO));
+        let mut n_plus_one = CURVE_ORDER;
+        n_plus_one[31] += 1;
+        assert_eq!(scalar_mod_n(n_plus_one), Ok(Scalar::ONE));
+        // 2^256 - 1 - n
+        assert_eq!(
+            scalar_mod_n([0xff; 32]).unwrap().to_be_bytes(),
+            bytes::<32>("000000000000000000000000000000014551231950B75FC4402DA1732FC9BEBE")
+        );
+    }
+
+    // Vectors of BIP327, bip-0327/vectors/key_agg_vectors.json
+    #[test]
+    fn key_agg_vectors() {
+        let public_keys = [
+            public_key("02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9"),
+            public_key("03DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659"),
+            public_key("023590A94E768F8E1815C2F24B4D80A8E3149316C3518CE7B7AD338368D038CA66"),
+        ];
+
+        for (key_indices, expected) in [
+            (
+                vec![0, 1, 2],
+                "90539EEDE565F5D054F32CC0C220126889ED1E5D193BAF15AEF344FE59D4610C",
+            ),
+            (
+                vec![2, 1, 0],
+                "6204DE8B083426DC6EAF9502D27024D53FC826BF7D2012148A0575435DF54B2B",
+            ),
+            (
+                vec![0, 0, 0],
+                "B436E3BAD62B8CD409969A224731C193D051162D8C5AE8B109306127DA3AA935",
+            ),
+            (
+                vec![0, 0, 1, 1],
+                "69BC22BFA5D106306E48A20679DE1D7389386124D07571D0D872686028C26A3E",
+            ),
+        ] {
+            let key_agg =
+                KeyAggContext::new(key_indices.iter().map(|i| public_keys[*i]).collect()).unwrap();
+            assert_eq!(
+                key_agg.aggregate_public_key().serialize(),
+                bytes::<32>(expected),
+                "{key_indices:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn nonce_generation() {
+        let secp = Secp256k1::new();
+        let secret_key = SecretKey::from_slice(&[2; 32]).unwrap();
+        let public_key = secret_key.public_key(&secp);
+        let nonce = |rand: [u8; 32],
+                     secret_key: &SecretKey,
+                     aggregate_public_key: Option<&[u8; 32]>,
+                     message: Option<&[u8]>| {
+            let (secret_nonce, public_nonce) = nonce_gen(
+                &secp,
+                rand,
+                Some(secret_key),
+                &secret_key.public_key(&secp),
+                aggregate_public_key,
+                message,
+                None,
+            )
+            .unwrap();
+            assert_eq!(public_nonce.0, secret_nonce.0.public_key(&secp));
+            assert_eq!(public_nonce.1, secret_nonce.1.public_key(&secp));
+            public_nonce
+        };
+
+        let public_nonce = nonce([0; 32], &secret_key, Some(&[7; 32]), Some(&[1; 32]));
+        assert_ne!(public_nonce.0, public_nonce.1);
+        assert_eq!(
+            public_nonce,
+            nonce([0; 32], &secret_key, Some(&[7; 32]), Some(&[1; 32]))
+        );
+
+        // a broken entropy source still gives every signer and every session its own nonce
+        let other_key = SecretKey::from_slice(&[3; 32]).unwrap();
+        assert_ne!(
+            public_nonce,
+            nonce([0; 32], &other_key, Some(&[7; 32]), Some(&[1; 32]))
+        );
+        assert_ne!(
+            public_nonce,
+            nonce([0; 32], &secret_key, None, Some(&[1; 32]))
+        );
+        assert_ne!(
+            public_nonce,
+            nonce([0; 32], &secret_key, Some(&[7; 32]), Some(&[2; 32]))
+        );
+        // an empty message is not an absent one
+        assert_ne!(
+            nonce([0; 32], &secret_key, None, Some(&[])),
+            nonce([0; 32], &secret_key, None, None)
+        );
+        assert_ne!(
+            public_nonce,
+            nonce([1; 32], &secret_key, Some(&[7; 32]), Some(&[1; 32]))
+        );
+
+        // without the secret key the random bytes are used as is
+        let (_, unkeyed) = nonce_gen(&secp, [0; 32], None, &public_key, None, None, None).unwrap();
+        assert_ne!(unkeyed, nonce([0; 32], &secret_key, None, None));
+    }
+
+    // bip-0327/vectors/sign_verify_vectors.json, the partial signatures of the valid cases
+    // are produced by Sign and accepted by PartialSigVerify
+    #[test]
+    fn sign_verify_vectors() {
+        let secp = Secp256k1::new();
+        let secret_key =
+            SecretKey::from_str("7FB9E0E687ADA1EEBF7ECFE2F21E73EBDB51A7D450948DFE8D76D7F2D1007671")
+                .unwrap();
+        let public_keys = [
+            public_key("03935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9"),
+            public_key("02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9"),
+            public_key("02DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA661"),
+        ];
+        let secret_nonce = || {
+            SecretNonce(
+                SecretKey::from_str(
+                    "508B81A611F100A6B2B6B29656590898AF488BCF2E1F55CF22E5CFB84421FE61",
+                )
+                .unwrap(),
+                SecretKey::from_str(
+                    "FA27FD49B1D50085B481285E1CA205D55C82CC1B31FF5CD54A489829355901F7",
+                )
+                .unwrap(),
+            )
+        };
+        let public_nonce = PublicNonce(
+            public_key("0337C87821AFD50A8644D820A8F3E02E499C931865C2360FB43D0A0D20DAFE07EA"),
+            public_key("0287BF891D2A6DEAEBADC909352AA9405D1428C15F4B75F04DAE642A95C2548480"),
+        );
+        let aggregate_nonce = AggregateNonce(
+            public_key("028465FCF0BBDBCF443AABCCE533D42B4B5A10966AC09A49655E8C42DAAB8FCD61"),
+            public_key("037496A3CC86926D452CAFCFD55D25972CA1675D549310DE296BFF42F72EEEA8C9"),
+        );
+        let message =
+            bytes::<32>("F95466D086770E689964664219266FE5ED215C92AE20BAB5C9D79ADDDDF3C0CF");
+
+        assert_eq!(secret_nonce().0.public_key(&secp), public_nonce.0);
+        assert_eq!(secret_nonce().1.public_key(&secp), public_nonce.1);
+
+        for (key_indices, expected) in [
+            (
+                [0, 1, 2],
+                "012ABBCB52B3016AC03AD82395A1A415C48B93DEF78718E62A7A90052FE224FB",
+            ),
+            (
+                [1, 0, 2],
+                "9FF2F7AAA856150CC8819254218D3ADEEB0535269051897724F9DB3789513A52",
+            ),
+            (
+                [1, 2, 0],
+                "FA23C359F6FAC4E7796BB93BC9F0532A95468C539BA20FF86D7C76ED92227900",
+            ),
+        ] {
+            let key_agg =
+                KeyAggContext::new(key_indices.iter().map(|i| public_keys[*i]).collect()).unwrap();
+
+            let partial_signature = partial_sign(
+                &secp,
+                secret_nonce(),
+                &secret_key,
+                &key_agg,
+                &aggregate_nonce,
+                &message,
+            )
+            .unwrap();
+            assert_eq!(
+                partial_signature.0.secret_bytes(),
+                bytes::<32>(expected),
+                "{key_indices:?}"
+            );
+
+            assert!(verify_partial_signature(
+                &secp,
+                &partial_signature,
+                &public_nonce,
+                &public_keys[0],
+                &key_agg,
+                &aggregate_nonce,
+                &message,
+            )
+            .unwrap());
+        }
+    }
+}
@@ -0,0 +1,44 @@
+// Stable public surface of the crate, everything else may change between releases
+// use bitcoin_da::prelude::*; covers submitting, extracting and verifying blobs
+
+pub use crate::coin_selection::{
+    BranchAndBound, CoinSelection, CoinSelectionStrategy, LargestFirst, OldestFirst,
+    SingleUtxoFirst,
+};
+pub use crate::helpers::builders::{
+    build_consolidation_transaction, build_precommitment_transaction, build_recovery_transaction,
+    commit_psbt, compress_blob, compress_blob_or_store, compress_blob_with, compress_stream,
+    decompress_blob, decompress_blob_with, decompress_blob_with_limit, decompress_stream,
+    detect_compression, dust_threshold, estimate_inscription_cost, inscription_fee,
+    reveal_vsize_for_body_len, split_blob, try_compress_blob, try_compress_blob_or_store,
+    try_decompress_blob, verify_commit_binding, CancellationToken, CommitArtifacts,
+    CommitInternalKey, ConsolidationError, DecompressError, FeeCap, FeeCapError,
+    InscriptionBuilder, InscriptionCost, InscriptionError, InscriptionReceipt, PsbtError,
+    RecoveryError, SignedBlob, UnsignedInscription, DEFAULT_DUST_RELAY_FEE_RATE, MAX_CHUNK_LEN,
+};
+pub use crate::helpers::parsers::{
+    parse_inscriptions, parse_transaction, reassemble_chunks, ChunkError, ParsedInscription,
+    ParserError, ReassembledBlob,
+};
+pub use crate::helpers::policy::{PolicyError, MAX_STANDARD_TX_WEIGHT};
+pub use crate::spec::batch_sequence::BatchSequence;
+pub use crate::spec::blob::{
+    BlobKind, BlobWithSender, Checkpoint, ChunkManifest, CompressionAlgorithm, InscriptionMetadata,
+    SignatureScheme,
+};
+pub use crate::spec::body_root::{body_chunk_proof, body_root, verify_body_chunk};
+pub use crate::spec::precommitment::{
+    parse_precommitments, precommitment_script, verify_precommitment, PRECOMMITMENT_MAGIC,
+};
+pub use crate::spec::{BitcoinSpec, RollupNameError, RollupParams, ENVELOPE_VERSION};
+pub use crate::verifier::{
+    verify_blob_tx, verify_checkpoint_tx, verify_chunked_blob_txs, BitcoinVerifier,
+    ValidationError, VerifiedBlob, VerifiedCheckpoint,
+};
+#[cfg(feature = "native")]
+pub use crate::{
+    fee::{FallbackFeeEstimator, FeeBounds, FeeEstimator, MempoolSpaceFeeSource},
+    service::{BitcoinService, DaServiceConfig, SecretString},
+    signer::{LocalSigner, Signer},
+    tx_store::{FsTxStore, InMemoryTxStore, TxStore},
+};
@@ -0,0 +1,227 @@
+use core::time::Duration;
+
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{error, warn};
+
+use crate::rpc::{is_block_not_found, BitcoinNode};
+
+// time to wait for a quorum node before counting it as absent
+const QUORUM_NODE_TIMEOUT: u64 = 30; // seconds
+                                     // time between asking a node behind the main node again for the block
+const QUORUM_LAGGING_NODE_RETRY: u64 = 2; // seconds
+
+/// Connection details of an additional node that takes part in quorum reads
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct QuorumNodeConfig {
+    pub node_url: String,
+    pub node_username: String,
+    pub node_password: String,
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum QuorumError {
+    #[error(
+        "nodes disagree on block hash at height {height}: {hashes:?}, potential eclipse attack"
+    )]
+    Divergence { height: u64, hashes: Vec<String> },
+    #[error("only {agreed} of required {threshold} nodes agree on block hash at height {height}")]
+    NotEnoughAgreement {
+        height: u64,
+        agreed: usize,
+        threshold: usize,
+    },
+    #[error("quorum threshold of {threshold} is not within 1 and the {nodes} nodes")]
+    InvalidThreshold { threshold: usize, nodes: usize },
+}
+
+// NodeQuorum cross checks block hashes returned by the main node against other nodes
+#[derive(Debug, Clone)]
+pub struct NodeQuorum {
+    nodes: Vec<BitcoinNode>,
+    // number of nodes (main node included) that must agree on a block hash
+    threshold: usize,
+}
+
+impl NodeQuorum {
+    pub fn new(nodes: Vec<BitcoinNode>, threshold: usize) -> Result<Self, QuorumError> {
+        check_threshold(threshold, nodes.len())?;
+
+        Ok(Self { nodes, threshold })
+    }
+
+    // Checks that enough nodes agree with the block hash the main node reported at height
+    pub async fn verify_block_hash(
+        &self,
+        height: u64,
+        block_hash: &str,
+    ) -> Result<(), QuorumError> {
+        let responses = join_all(self.nodes.iter().map(|node| async move {
+            // a node behind the main node does not have the block yet, it is asked again
+            // until it catches up or times out
+            let block_hash = async {
+                loop {
+                    match node.get_block_hash(height).await {
+                        Err(e) if is_block_not_found(&e) => {
+                            tokio::time::sleep(Duration::from_secs(QUORUM_LAGGING_NODE_RETRY)).await
+                        }
+                        result => return result,
+                    }
+                }
+            };
+
+            match tokio::time::timeout(Duration::from_secs(QUORUM_NODE_TIMEOUT), block_hash).await {
+                Ok(Ok(hash)) => Some(hash),
+                Ok(Err(e)) => {
+                    warn!(
+                        "Quorum node failed to return block hash at {}: {}",
+                        height, e
+                    );
+                    None
+                }
+                Err(_) => {
+                    warn!("Quorum node timed out at height {}", height);
+                    None
+                }
+            }
+        }))
+        .await;
+
+        let result = tally_block_hashes(height, block_hash, &responses, self.threshold);
+
+        if let Err(e) = &result {
+            error!("{}", e);
+        }
+
+        result
+    }
+}
+
+// The threshold counts the main node, so it is at least 1 and at most every node
+pub fn check_threshold(threshold: usize, nodes: usize) -> Result<(), QuorumError> {
+    if !(1..=nodes + 1).contains(&threshold) {
+        return Err(QuorumError::InvalidThreshold {
+            threshold,
+            nodes: nodes + 1,
+        });
+    }
+
+    Ok(())
+}
+
+// Counts the votes for the main node's block hash, nodes that did not respond have no vote
+// Any node reporting a different hash is treated as a divergence regardless of the threshold
+fn tally_block_hashes(
+    height: u64,
+    block_hash: &str,
+    responses: &[Option<String>],
+    threshold: usize,
+) -> Result<(), QuorumError> {
+    let mut agreed = 1; // main node
+
+    let mut hashes = vec![block_hash.to_string()];
+
+    for hash in responses.iter().flatten() {
+        if hash == block_hash {
+            agreed += 1;
+        } else if !hashes.contains(hash) {
+            hashes.push(hash.clone());
+        }
+    }
+
+    if hashes.len() > 1 {
+        return Err(QuorumError::Divergence { height, hashes });
+    }
+
+    if agreed < threshold {
+        return Err(QuorumError::NotEnoughAgreement {
+            height,
+            agreed,
+            threshold,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::QuorumError;
+    use crate::rpc::{is_block_not_found, RPCError};
+
+    #[test]
+    fn tally_block_hashes() {
+        let hash = "6b15a2e4b17b0aabbd418634ae9410b46feaabf693eea4c8621ffe71435d24b0";
+        let other = "7750076b3b5498aad3e2e7da55618c66394d1368dc08f19f0b13d1e5b83ae056";
+
+        assert!(super::tally_block_hashes(
+            10,
+            hash,
+            &[Some(hash.to_string()), Some(hash.to_string())],
+            3
+        )
+        .is_ok());
+
+        // absent node is fine as long as the threshold is met
+        assert!(super::tally_block_hashes(10, hash, &[Some(hash.to_string()), None], 2).is_ok());
+
+        assert_eq!(
+            super::tally_block_hashes(10, hash, &[Some(hash.to_string()), None], 3),
+            Err(QuorumError::NotEnoughAgreement {
+                height: 10,
+                agreed: 2,
+                threshold: 3
+            })
+        );
+
+        assert_eq!(
+            super::tally_block_hashes(
+                10,
+                hash,
+                &[Some(hash.to_string()), Some(other.to_string())],
+                2
+            ),
+            Err(QuorumError::Divergence {
+                height: 10,
+                hashes: vec![hash.to_string(), other.to_string()]
+            })
+        );
+    }
+
+    #[test]
+    fn check_threshold() {
+        assert!(super::check_threshold(1, 0).is_ok());
+        assert!(super::check_threshold(3, 2).is_ok());
+        assert_eq!(
+            super::check_threshold(0, 2),
+            Err(QuorumError::InvalidThreshold {
+                threshold: 0,
+                nodes: 3
+            })
+        );
+        assert_eq!(
+            super::check_threshold(4, 2),
+            Err(QuorumError::InvalidThreshold {
+                threshold: 4,
+                nodes: 3
+            })
+        );
+    }
+
+    #[test]
+    fn lagging_node() {
+        let lagging = anyhow::anyhow!(RPCError {
+            code: -8,
+            message: "Block height out of range".to_string(),
+        });
+        assert!(is_block_not_found(&lagging));
+
+        let failed = anyhow::anyhow!(RPCError {
+            code: -28,
+            message: "Loading block index...".to_string(),
+        });
+        assert!(!is_block_not_found(&failed));
+        assert!(!is_block_not_found(&anyhow::anyhow!("connection refused")));
+    }
+}
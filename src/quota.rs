@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+// Daily limits of a namespace, i.e. of a rollup name. Days are UTC days
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quota {
+    // compressed blob bytes
+    pub max_bytes_per_day: Option<u64>,
+    // fees of the commit and reveal txs
+    pub max_sats_per_day: Option<u64>,
+}
+
+/// Usage of a namespace, over a day or since the ledger was created
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NamespaceUsage {
+    pub bytes: u64,
+    pub sats: u64,
+    pub submissions: u64,
+    // submissions refused for exceeding the quota
+    pub rejected: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum QuotaExceeded {
+    #[error("namespace {namespace} cannot post {requested} more bytes, {used} of its {limit} bytes per day are used")]
+    Bytes {
+        namespace: String,
+        used: u64,
+        requested: u64,
+        limit: u64,
+    },
+    #[error("namespace {namespace} cannot spend {requested} more sats, {used} of its {limit} sats per day are spent")]
+    Sats {
+        namespace: String,
+        used: u64,
+        requested: u64,
+        limit: u64,
+    },
+}
+
+// QuotaLedger accounts the submissions of each namespace separately, so that services of
+// several rollups sharing a wallet can share a ledger and one rollup's batch storm cannot
+// exhaust the wallet of the others
+#[derive(Debug, Default)]
+pub struct QuotaLedger {
+    // quota of the namespaces without one of their own
+    default_quota: Quota,
+    quotas: HashMap<String, Quota>,
+    // usage of the current day, keyed by namespace
+    today: HashMap<String, (u64, NamespaceUsage)>,
+    totals: HashMap<String, NamespaceUsage>,
+}
+
+impl QuotaLedger {
+    pub fn new(default_quota: Quota) -> Self {
+        Self {
+            default_quota,
+            ..Default::default()
+        }
+    }
+
+    pub fn set_quota(&mut self, namespace: &str, quota: Quota) {
+        self.quotas.insert(namespace.to_string(), quota);
+    }
+
+    pub fn quota(&self, namespace: &str) -> Quota {
+        self.quotas
+            .get(namespace)
+            .copied()
+            .unwrap_or(self.default_quota)
+    }
+
+    // Checks that the namespace can post bytes and spend sats more today, a refusal is counted
+    pub fn check(
+        &mut self,
+        namespace: &str,
+        bytes: u64,
+        sats: u64,
+        now: u64,
+    ) -> Result<(), QuotaExceeded> {
+        let quota = self.quota(namespace);
+        let used = self.usage_today(namespace, now);
+
+        let result = match (quota.max_bytes_per_day, quota.max_sats_per_day) {
+            (Some(limit), _) if used.bytes.saturating_add(bytes) > limit => {
+                Err(QuotaExceeded::Bytes {
+                    namespace: namespace.to_string(),
+                    used: used.bytes,
+                    requested: bytes,
+                    limit,
+                })
+            }
+            (_, Some(limit)) if used.sats.saturating_add(sats) > limit => {
+                Err(QuotaExceeded::Sats {
+                    namespace: namespace.to_string(),
+                    used: used.sats,
+                    requested: sats,
+                    limit,
+                })
+            }
+            _ => Ok(()),
+        };
+
+        if result.is_err() {
+            self.update(namespace, now, |usage| usage.rejected += 1);
+        }
+
+        result
+    }
+
+    // Accounts a submission sent by the namespace
+    pub fn record(&mut self, namespace: &str, bytes: u64, sats: u64, now: u64) {
+        self.update(namespace, now, |usage| {
+            usage.bytes += bytes;
+            usage.sats += sats;
+            usage.submissions += 1;
+        });
+    }
+
+    pub fn usage_today(&self, namespace: &str, now: u64) -> NamespaceUsage {
+        match self.today.get(namespace) {
+            Some((day, usage)) if *day == now / SECONDS_PER_DAY => *usage,
+            _ => NamespaceUsage::default(),
+        }
+    }
+
+    // Usage of every namespace since the ledger was created, e.g. to export as metrics
+    pub fn totals(&self) -> Vec<(String, NamespaceUsage)> {
+        let mut totals: Vec<_> = self
+            .totals
+            .iter()
+            .map(|(namespace, usage)| (namespace.clone(), *usage))
+            .collect();
+        totals.sort_by(|a, b| a.0.cmp(&b.0));
+        totals
+    }
+
+    fn update(&mut self, namespace: &str, now: u64, update: impl Fn(&mut NamespaceUsage)) {
+        let day = now / SECONDS_PER_DAY;
+        let today = self
+            .today
+            .entry(namespace.to_string())
+            .or_insert((day, NamespaceUsage::default()));
+        if today.0 != day {
+            *today = (day, NamespaceUsage::default());
+        }
+
+        update(&mut today.1);
+        update(self.totals.entry(namespace.to_string()).or_default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NamespaceUsage, Quota, QuotaExceeded, QuotaLedger, SECONDS_PER_DAY};
+
+    #[test]
+    fn namespace_quotas() {
+        let mut ledger = QuotaLedger::new(Quota {
+            max_bytes_per_day: Some(1_000),
+            max_sats_per_day: None,
+        });
+        ledger.set_quota(
+            "storm",
+            Quota {
+                max_bytes_per_day: None,
+                max_sats_per_day: Some(10_000),
+            },
+        );
+        let now = 10 * SECONDS_PER_DAY;
+
+        ledger.check("storm", 100_000, 6_000, now).unwrap();
+        ledger.record("storm", 100_000, 6_000, now);
+        assert_eq!(
+            ledger.check("storm", 100_000, 6_000, now + 60),
+            Err(QuotaExceeded::Sats {
+                namespace: "storm".to_string(),
+                used: 6_000,
+                requested: 6_000,
+                limit: 10_000,
+            })
+        );
+
+        // the other namespace is not affected
+        ledger.check("quiet", 600, 50_000, now).unwrap();
+        ledger.record("quiet", 600, 50_000, now);
+        assert!(matches!(
+            ledger.check("quiet", 600, 0, now),
+            Err(QuotaExceeded::Bytes { used: 600, .. })
+        ));
+
+        // a new day
+        ledger
+            .check("storm", 100_000, 6_000, now + SECONDS_PER_DAY)
+            .unwrap();
+        assert_eq!(
+            ledger.usage_today("storm", now + SECONDS_PER_DAY),
+            NamespaceUsage::default()
+        );
+
+        assert_eq!(
+            ledger.totals(),
+            vec![
+                (
+                    "quiet".to_string(),
+                    NamespaceUsage {
+                        bytes: 600,
+                        sats: 50_000,
+                        submissions: 1,
+                        rejected: 1,
+                    }
+                ),
+                (
+                    "storm".to_string(),
+                    NamespaceUsage {
+                        bytes: 100_000,
+                        sats: 6_000,
+                        submissions: 1,
+                        rejected: 1,
+                    }
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,205 @@
+use core::any::Any;
+
+use bitcoin::Txid;
+use serde::{Deserialize, Serialize};
+
+use crate::batch_registry::{BatchRecord, BatchRegistry};
+use crate::blob_index::{BlobIndex, BlobLocation};
+
+/// A blob found again while replaying a block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayedBlob {
+    pub reveal_txid: Txid,
+    pub sender: Vec<u8>,
+    pub blob_hash: [u8; 32],
+    pub batch_number: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ReplayDivergence {
+    // the verifier rejected the extraction of the block
+    VerificationFailed {
+        height: u64,
+        error: String,
+    },
+    // the blob index points to another tx at this height or to a later block
+    IndexMismatch {
+        height: u64,
+        reveal_txid: Txid,
+        indexed: BlobLocation,
+    },
+    // the batch registry records another tx at this height or this tx at another height
+    BatchMismatch {
+        height: u64,
+        reveal_txid: Txid,
+        recorded: BatchRecord,
+    },
+}
+
+/// Result of re-extracting and re-verifying a range of blocks
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplayReport {
+    pub from: u64,
+    pub to: u64,
+    pub blobs: usize,
+    // blobs the index has no location for, e.g. extracted before the last restart
+    pub unindexed: usize,
+    pub divergences: Vec<ReplayDivergence>,
+}
+
+impl ReplayReport {
+    pub fn new(from: u64, to: u64) -> Self {
+        Self {
+            from,
+            to,
+            ..Default::default()
+        }
+    }
+
+    pub fn verification_failed(&mut self, height: u64, error: String) {
+        self.divergences
+            .push(ReplayDivergence::VerificationFailed { height, error });
+    }
+
+    // Compares the blobs found at height with what the index and the registry stored
+    pub fn check_blobs(
+        &mut self,
+        height: u64,
+        blobs: &[ReplayedBlob],
+        index: &BlobIndex,
+        registry: &BatchRegistry,
+    ) {
+        for blob in blobs {
+            self.blobs += 1;
+
+            match index.get(&blob.blob_hash) {
+                None => self.unindexed += 1,
+                // the index keeps the first location of a blob inscribed more than once
+                Some(indexed)
+                    if indexed.height > height
+                        || (indexed.height == height && indexed.txid != blob.reveal_txid) =>
+                {
+                    self.divergences.push(ReplayDivergence::IndexMismatch {
+                        height,
+                        reveal_txid: blob.reveal_txid,
+                        indexed,
+                    })
+                }
+                Some(_) => {}
+            }
+
+            let Some(recorded) = blob
+                .batch_number
+                .and_then(|batch| registry.get(&blob.sender, batch))
+            else {
+                continue;
+            };
+
+            let other_tx =
+                recorded.height == Some(height) && recorded.reveal_txid != blob.reveal_txid;
+            let other_height = recorded.reveal_txid == blob.reveal_txid
+                && recorded
+                    .height
+                    .map_or(false, |recorded_height| recorded_height != height);
+
+            if other_tx || other_height {
+                self.divergences.push(ReplayDivergence::BatchMismatch {
+                    height,
+                    reveal_txid: blob.reveal_txid,
+                    recorded,
+                });
+            }
+        }
+    }
+}
+
+// Message of a caught panic, the verifier asserts on some malformed inputs
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<String>()
+        .cloned()
+        .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+    use bitcoin::Txid;
+
+    use super::{ReplayDivergence, ReplayReport, ReplayedBlob};
+    use crate::batch_registry::BatchRegistry;
+    use crate::blob_index::{BlobIndex, BlobLocation};
+
+    #[test]
+    fn replay_report() {
+        let reveal_txid = Txid::from_byte_array([1; 32]);
+        let other_txid = Txid::from_byte_array([2; 32]);
+
+        let mut index = BlobIndex::new();
+        index.insert(
+            [1; 32],
+            BlobLocation {
+                height: 10,
+                txid: reveal_txid,
+            },
+        );
+        index.insert(
+            [2; 32],
+            BlobLocation {
+                height: 10,
+                txid: other_txid,
+            },
+        );
+
+        let mut registry = BatchRegistry::in_memory();
+        registry
+            .record_inclusion(b"alice", 7, Txid::all_zeros(), reveal_txid, 10)
+            .unwrap();
+        registry
+            .record_inclusion(b"alice", 8, Txid::all_zeros(), reveal_txid, 11)
+            .unwrap();
+
+        let mut report = ReplayReport::new(10, 10);
+        report.check_blobs(
+            10,
+            &[
+                // consistent
+                ReplayedBlob {
+                    reveal_txid,
+                    sender: b"alice".to_vec(),
+                    blob_hash: [1; 32],
+                    batch_number: Some(7),
+                },
+                // index points to another tx, batch recorded at another height
+                ReplayedBlob {
+                    reveal_txid,
+                    sender: b"alice".to_vec(),
+                    blob_hash: [2; 32],
+                    batch_number: Some(8),
+                },
+                ReplayedBlob {
+                    reveal_txid,
+                    sender: b"alice".to_vec(),
+                    blob_hash: [3; 32],
+                    batch_number: None,
+                },
+            ],
+            &index,
+            &registry,
+        );
+
+        assert_eq!(report.blobs, 3);
+        assert_eq!(report.unindexed, 1);
+        assert_eq!(report.divergences.len(), 2);
+        assert!(matches!(
+            report.divergences[0],
+            ReplayDivergence::IndexMismatch { .. }
+        ));
+        assert!(matches!(
+            report.divergences[1],
+            ReplayDivergence::BatchMismatch { .. }
+        ));
+    }
+}
@@ -0,0 +1,221 @@
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use bitcoin::consensus::encode;
+use bitcoin::{Transaction, Txid};
+use serde::{Deserialize, Serialize};
+
+// Blocks mined per day on average
+const BLOCKS_PER_DAY: u64 = 144;
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// How much history the service keeps in its blob index and its archive of reveal txs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    // blobs of older blocks and older archived txs are pruned, a day is 144 blocks
+    // blobs of blocks that are not final are kept regardless
+    pub keep_days: Option<u64>,
+    // archived reveal txs are deleted, oldest first, until the archive is below this size
+    pub max_archive_bytes: Option<u64>,
+    // archived reveal txs are deleted once the reveal is in a final block
+    pub prune_finalized_reveals: bool,
+}
+
+/// Outcome of a pruning pass
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct PruneStats {
+    pub index_pruned: usize,
+    pub index_len: usize,
+    pub archive_pruned: usize,
+    pub archive_bytes: u64,
+}
+
+// Archived reveal tx, written by tx_store::FsTxStore as reveal_<commit txid>.tx
+struct ArchivedTx {
+    path: std::path::PathBuf,
+    modified: SystemTime,
+    len: u64,
+}
+
+impl ArchivedTx {
+    // Deletes the reveal tx and the index of its commit key, if any
+    fn remove(&self) -> Result<(), anyhow::Error> {
+        fs::remove_file(&self.path)?;
+        match fs::remove_file(self.path.with_extension("index")) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl RetentionPolicy {
+    // Blobs indexed below the returned height can be pruned
+    pub fn index_cutoff(&self, tip_height: u64, finalized_height: u64) -> Option<u64> {
+        let keep_blocks = self.keep_days?.saturating_mul(BLOCKS_PER_DAY);
+
+        Some(
+            tip_height
+                .saturating_sub(keep_blocks)
+                .min(finalized_height.saturating_add(1)),
+        )
+    }
+
+    // Deletes archived reveal txs in dir according to the policy
+    // Returns how many were deleted and the size of the remaining ones
+    pub fn prune_archive(
+        &self,
+        dir: &Path,
+        now: SystemTime,
+        is_finalized: impl Fn(&Txid) -> bool,
+    ) -> Result<(usize, u64), anyhow::Error> {
+        let mut archived = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if !(name.starts_with("reveal_") && name.ends_with(".tx")) {
+                continue;
+            }
+
+            let metadata = entry.metadata()?;
+            archived.push(ArchivedTx {
+                path: entry.path(),
+                modified: metadata.modified()?,
+                len: metadata.len(),
+            });
+        }
+
+        // oldest first
+        archived.sort_by_key(|tx| tx.modified);
+
+        let max_age = self
+            .keep_days
+            .map(|days| Duration::from_secs(days.saturating_mul(SECONDS_PER_DAY)));
+
+        let mut kept = Vec::new();
+        let mut pruned = 0;
+        for tx in archived {
+            let expired = max_age.map_or(false, |max_age| {
+                now.duration_since(tx.modified).unwrap_or_default() > max_age
+            });
+            let finalized = self.prune_finalized_reveals
+                && fs::read(&tx.path)
+                    .ok()
+                    .and_then(|bytes| encode::deserialize::<Transaction>(&bytes).ok())
+                    .map_or(false, |reveal_tx| is_finalized(&reveal_tx.txid()));
+
+            if expired || finalized {
+                tx.remove()?;
+                pruned += 1;
+            } else {
+                kept.push(tx);
+            }
+        }
+
+        let mut archive_bytes: u64 = kept.iter().map(|tx| tx.len).sum();
+        if let Some(max_archive_bytes) = self.max_archive_bytes {
+            for tx in kept {
+                if archive_bytes <= max_archive_bytes {
+                    break;
+                }
+
+                tx.remove()?;
+                archive_bytes -= tx.len;
+                pruned += 1;
+            }
+        }
+
+        Ok((pruned, archive_bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use bitcoin::consensus::encode;
+    use bitcoin::{absolute, Transaction};
+
+    use super::RetentionPolicy;
+
+    #[test]
+    fn index_cutoff() {
+        let policy = RetentionPolicy {
+            keep_days: Some(1),
+            ..Default::default()
+        };
+
+        assert_eq!(policy.index_cutoff(1_000, 996), Some(856));
+        // blocks that are not final are kept
+        assert_eq!(policy.index_cutoff(100, 96), Some(0));
+        assert_eq!(
+            RetentionPolicy {
+                keep_days: Some(0),
+                ..Default::default()
+            }
+            .index_cutoff(1_000, 996),
+            Some(997)
+        );
+        assert_eq!(RetentionPolicy::default().index_cutoff(1_000, 996), None);
+    }
+
+    #[test]
+    fn prune_archive() {
+        let dir = std::env::temp_dir().join(format!("archive_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let reveal_tx = |lock_time: u32| Transaction {
+            version: 2,
+            lock_time: absolute::LockTime::from_consensus(lock_time),
+            input: vec![],
+            output: vec![],
+        };
+        for lock_time in 0..3 {
+            std::fs::write(
+                dir.join(format!("reveal_{}.tx", lock_time)),
+                encode::serialize(&reveal_tx(lock_time)),
+            )
+            .unwrap();
+        }
+        std::fs::write(dir.join("other.tx"), [0; 100]).unwrap();
+        let tx_len = encode::serialize(&reveal_tx(0)).len() as u64;
+
+        // nothing to prune
+        let now = SystemTime::now();
+        let policy = RetentionPolicy {
+            keep_days: Some(1),
+            max_archive_bytes: Some(3 * tx_len),
+            prune_finalized_reveals: true,
+        };
+        assert_eq!(
+            policy.prune_archive(&dir, now, |_| false).unwrap(),
+            (0, 3 * tx_len)
+        );
+
+        // the finalized reveal and then the oldest one to fit the size budget
+        let finalized_txid = reveal_tx(1).txid();
+        let policy = RetentionPolicy {
+            max_archive_bytes: Some(tx_len),
+            ..policy
+        };
+        assert_eq!(
+            policy
+                .prune_archive(&dir, now, |txid| *txid == finalized_txid)
+                .unwrap(),
+            (2, tx_len)
+        );
+        assert!(!dir.join("reveal_1.tx").exists());
+
+        // expired
+        let later = now + Duration::from_secs(2 * 24 * 60 * 60);
+        assert_eq!(
+            policy.prune_archive(&dir, later, |_| false).unwrap(),
+            (1, 0)
+        );
+        assert!(dir.join("other.tx").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
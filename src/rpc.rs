@@ -1,22 +1,29 @@
 use core::fmt::Display;
 use core::str::FromStr;
+use std::collections::HashMap;
 
 use anyhow::anyhow;
 use async_recursion::async_recursion;
 use bitcoin::block::{Header, Version};
 use bitcoin::hash_types::TxMerkleNode;
-use bitcoin::{Address, BlockHash, CompactTarget, Network};
+use bitcoin::{Address, BlockHash, CompactTarget, Network, OutPoint, Txid};
 use reqwest::header::HeaderMap;
 use serde::{Deserialize, Serialize};
 use serde_json::value::RawValue;
 use serde_json::{json, to_value};
 
+#[cfg(any(test, feature = "test-utils"))]
+use crate::fault::FaultInjector;
 use crate::helpers::parsers::parse_hex_transaction;
 use crate::spec::block::BitcoinBlock;
 use crate::spec::header::HeaderWrapper;
 use crate::spec::transaction::Transaction;
 use crate::spec::utxo::UTXO;
 
+const RPC_INVALID_ADDRESS_OR_KEY: i32 = -5;
+// getblockhash error of a height above the tip of the node
+const RPC_INVALID_PARAMETER: i32 = -8;
+
 // RPCError is a struct that represents an error returned by the Bitcoin RPC
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct RPCError {
@@ -29,6 +36,11 @@ impl Display for RPCError {
     }
 }
 
+// Whether error is a node not having the block yet, as a node behind the others answers
+pub(crate) fn is_block_not_found(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<RPCError>().map(|e| e.code) == Some(RPC_INVALID_PARAMETER)
+}
+
 // Response is a struct that represents a response returned by the Bitcoin RPC
 // It is generic over the type of the result field, which is usually a String in Bitcoin Core
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
@@ -44,6 +56,8 @@ pub struct BitcoinNode {
     url: String,
     client: reqwest::Client,
     network: Network,
+    #[cfg(any(test, feature = "test-utils"))]
+    faults: FaultInjector,
 }
 impl BitcoinNode {
     pub fn new(url: String, username: String, password: String, network: Network) -> Self {
@@ -72,9 +86,17 @@ impl BitcoinNode {
             url,
             client,
             network,
+            #[cfg(any(test, feature = "test-utils"))]
+            faults: FaultInjector::default(),
         }
     }
 
+    #[cfg(any(test, feature = "test-utils"))]
+    pub fn with_fault_injector(mut self, faults: FaultInjector) -> Self {
+        self.faults = faults;
+        self
+    }
+
     // TODO: add max retries
     #[async_recursion]
     async fn call<T: serde::de::DeserializeOwned>(
@@ -82,6 +104,9 @@ impl BitcoinNode {
         method: &str,
         params: Vec<serde_json::Value>,
     ) -> Result<T, anyhow::Error> {
+        #[cfg(any(test, feature = "test-utils"))]
+        self.faults.delay().await;
+
         let response = self
             .client
             .post(&self.url)
@@ -117,17 +142,35 @@ impl BitcoinNode {
 
     // get_block_count returns the current block height
     pub async fn get_block_count(&self) -> Result<u64, anyhow::Error> {
-        self.call::<u64>("getblockcount", vec![]).await
+        let height = self.call::<u64>("getblockcount", vec![]).await?;
+
+        #[cfg(any(test, feature = "test-utils"))]
+        let height = self.faults.tip_height(height);
+
+        Ok(height)
     }
 
     // get_block_hash returns the block hash of the block at the given height
     pub async fn get_block_hash(&self, height: u64) -> Result<String, anyhow::Error> {
+        #[cfg(any(test, feature = "test-utils"))]
+        if self.faults.reorg_depth() > 0 && height > self.get_block_count().await? {
+            return Err(anyhow!(RPCError {
+                code: RPC_INVALID_PARAMETER,
+                message: "Block height out of range".to_string(),
+            }));
+        }
+
         self.call::<String>("getblockhash", vec![to_value(height)?])
             .await
     }
 
     // get_best_blockhash returns the best blockhash of the chain
     pub async fn get_best_blockhash(&self) -> Result<String, anyhow::Error> {
+        #[cfg(any(test, feature = "test-utils"))]
+        if self.faults.reorg_depth() > 0 {
+            return self.get_block_hash(self.get_block_count().await?).await;
+        }
+
         self.call::<String>("getbestblockhash", vec![]).await
     }
 
@@ -205,10 +248,14 @@ impl BitcoinNode {
 
     // get_utxos returns all unspent transaction outputs for the wallets of bitcoind
     pub async fn get_utxos(&self) -> Result<Vec<UTXO>, anyhow::Error> {
-        let utxos = self
+        #[allow(unused_mut)]
+        let mut utxos = self
             .call::<Vec<UTXO>>("listunspent", vec![to_value(0)?, to_value(9999999)?])
             .await?;
 
+        #[cfg(any(test, feature = "test-utils"))]
+        self.faults.corrupt_utxos(&mut utxos);
+
         if utxos.is_empty() {
             return Err(anyhow!("No UTXOs found"));
         }
@@ -217,7 +264,7 @@ impl BitcoinNode {
     }
 
     // get_change_address returns a change address for the wallet of bitcoind
-    async fn get_change_address(&self) -> Result<Address, anyhow::Error> {
+    pub async fn get_change_address(&self) -> Result<Address, anyhow::Error> {
         let address_string = self.call::<String>("getrawchangeaddress", vec![]).await?;
         Ok(Address::from_str(&address_string)?.require_network(self.network)?)
     }
@@ -229,10 +276,10 @@ impl BitcoinNode {
         Ok([change_address, change_address_2])
     }
 
-    // estimate_smart_fee estimates the fee to confirm a transaction in the next block
-    pub async fn estimate_smart_fee(&self) -> Result<f64, anyhow::Error> {
+    // estimate_smart_fee estimates the fee to confirm a transaction within conf_target blocks
+    pub async fn estimate_smart_fee(&self, conf_target: u16) -> Result<f64, anyhow::Error> {
         let result = self
-            .call::<Box<RawValue>>("estimatesmartfee", vec![to_value(1)?])
+            .call::<Box<RawValue>>("estimatesmartfee", vec![to_value(conf_target)?])
             .await?
             .to_string();
 
@@ -266,10 +313,155 @@ impl BitcoinNode {
 
     // send_raw_transaction sends a raw transaction to the network
     pub async fn send_raw_transaction(&self, tx: String) -> Result<String, anyhow::Error> {
+        // a dropped broadcast looks accepted, the tx never reaches the node
+        #[cfg(any(test, feature = "test-utils"))]
+        if self.faults.should_drop_broadcast() {
+            return Ok(parse_hex_transaction(&tx)?.txid().to_string());
+        }
+
         self.call::<String>("sendrawtransaction", vec![to_value(tx)?])
             .await
     }
 
+    // lock_unspent locks the outputs in the wallet of bitcoind, or unlocks them, so that
+    // other users of the wallet do not fund their txs with them
+    pub async fn lock_unspent(
+        &self,
+        unlock: bool,
+        outpoints: &[OutPoint],
+    ) -> Result<(), anyhow::Error> {
+        let outpoints: Vec<serde_json::Value> = outpoints
+            .iter()
+            .map(|outpoint| json!({ "txid": outpoint.txid, "vout": outpoint.vout }))
+            .collect();
+
+        let locked = self
+            .call::<bool>("lockunspent", vec![to_value(unlock)?, to_value(outpoints)?])
+            .await?;
+        if !locked {
+            return Err(anyhow!("lockunspent did not update the outputs"));
+        }
+
+        Ok(())
+    }
+
+    // get_mempool_fee_rates returns the fee rate (sat/vB) and vsize of every mempool transaction
+    pub async fn get_mempool_fee_rates(&self) -> Result<Vec<(f64, u64)>, anyhow::Error> {
+        let mempool = self
+            .call::<HashMap<String, serde_json::Value>>("getrawmempool", vec![to_value(true)?])
+            .await?;
+
+        Ok(mempool
+            .values()
+            .filter_map(|entry| {
+                let vsize = entry["vsize"].as_u64().filter(|vsize| *vsize > 0)?;
+                let fee = entry["fees"]["base"].as_f64()?;
+
+                Some((fee * 100_000_000.0 / vsize as f64, vsize))
+            })
+            .collect())
+    }
+
+    // get_mempool_ancestor_count returns the number of unconfirmed ancestors of a mempool
+    // transaction, itself included, or None if the transaction is not in the mempool
+    pub async fn get_mempool_ancestor_count(
+        &self,
+        txid: &Txid,
+    ) -> Result<Option<u64>, anyhow::Error> {
+        let entry = match self
+            .call::<serde_json::Value>("getmempoolentry", vec![to_value(txid.to_string())?])
+            .await
+        {
+            Ok(entry) => entry,
+            // the transaction is confirmed or unknown
+            Err(error)
+                if error.downcast_ref::<RPCError>().map(|e| e.code)
+                    == Some(RPC_INVALID_ADDRESS_OR_KEY) =>
+            {
+                return Ok(None)
+            }
+            Err(error) => return Err(error),
+        };
+
+        entry["ancestorcount"]
+            .as_u64()
+            .map(Some)
+            .ok_or_else(|| anyhow!("getmempoolentry did not return an ancestor count"))
+    }
+
+    // get_raw_mempool returns the txids of the mempool transactions
+    pub async fn get_raw_mempool(&self) -> Result<Vec<Txid>, anyhow::Error> {
+        self.call::<Vec<Txid>>("getrawmempool", vec![]).await
+    }
+
+    // get_raw_transaction returns a mempool transaction, or a confirmed one if the node
+    // indexes transactions
+    pub async fn get_raw_transaction(&self, txid: &Txid) -> Result<Transaction, anyhow::Error> {
+        let tx_hex = self
+            .call::<String>("getrawtransaction", vec![to_value(txid.to_string())?])
+            .await?;
+
+        Ok(parse_hex_transaction(&tx_hex)?)
+    }
+
+    // get_wallet_tx_confirmations returns the confirmations of a wallet transaction, 0 while
+    // it is unconfirmed or conflicted, or None if the wallet does not know the transaction
+    pub async fn get_wallet_tx_confirmations(
+        &self,
+        txid: &Txid,
+    ) -> Result<Option<u64>, anyhow::Error> {
+        let tx = match self
+            .call::<serde_json::Value>("gettransaction", vec![to_value(txid.to_string())?])
+            .await
+        {
+            Ok(tx) => tx,
+            Err(error)
+                if error.downcast_ref::<RPCError>().map(|e| e.code)
+                    == Some(RPC_INVALID_ADDRESS_OR_KEY) =>
+            {
+                return Ok(None)
+            }
+            Err(error) => return Err(error),
+        };
+
+        // conflicted transactions have negative confirmations
+        tx["confirmations"]
+            .as_i64()
+            .map(|confirmations| Some(confirmations.max(0) as u64))
+            .ok_or_else(|| anyhow!("gettransaction did not return confirmations"))
+    }
+
+    // get_network returns the chain the node is running on
+    pub async fn get_network(&self) -> Result<Network, anyhow::Error> {
+        let info = self
+            .call::<serde_json::Value>("getblockchaininfo", vec![])
+            .await?;
+
+        let chain = info["chain"]
+            .as_str()
+            .ok_or_else(|| anyhow!("getblockchaininfo did not return a chain"))?;
+
+        Ok(Network::from_core_arg(chain)?)
+    }
+
+    // get_relay_fee_rates returns the minimum and the incremental relay fee rates (sat/vB)
+    // of the node
+    pub async fn get_relay_fee_rates(&self) -> Result<(f64, f64), anyhow::Error> {
+        let info = self
+            .call::<serde_json::Value>("getnetworkinfo", vec![])
+            .await?;
+
+        // reported in BTC/kvB
+        let fee_rate = |field: &str| {
+            info[field]
+                .as_f64()
+                .map(|btc_per_kvb| btc_per_kvb * 100_000.0)
+                .ok_or_else(|| anyhow!("getnetworkinfo did not return {}", field))
+        };
+
+        Ok((fee_rate("relayfee")?, fee_rate("incrementalfee")?))
+    }
+
     pub async fn list_wallets(&self) -> Result<Vec<String>, anyhow::Error> {
         self.call::<Vec<String>>("listwallets", vec![]).await
     }
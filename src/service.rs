@@ -1,33 +1,86 @@
+use core::fmt;
 use core::result::Result::Ok;
 use core::str::FromStr;
 use core::time::Duration;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 use async_trait::async_trait;
 use bitcoin::address::NetworkUnchecked;
+use bitcoin::bip32::ExtendedPrivKey;
 use bitcoin::consensus::encode;
 use bitcoin::hashes::{sha256d, Hash};
-use bitcoin::secp256k1::SecretKey;
-use bitcoin::{Address, Txid};
+use bitcoin::secp256k1::{KeyPair, Secp256k1, SecretKey, XOnlyPublicKey};
+use bitcoin::sighash::TapSighashType;
+use bitcoin::{Address, OutPoint, ScriptBuf, TxOut, Txid};
 use hex::ToHex;
 use serde::{Deserialize, Serialize};
-use sov_rollup_interface::da::DaSpec;
+use sov_rollup_interface::da::{BlockHeaderTrait, DaSpec, DaVerifier};
 use sov_rollup_interface::services::da::DaService;
-use tracing::info;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, error, info, trace, warn};
+use zeroize::Zeroize;
 
+use crate::audit::{AuditEvent, TxAuditRecord, TxPurpose};
+use crate::batch_registry::{BatchRecord, BatchRegistry};
+use crate::batch_window::{group_by_size, BatchWindow, BatchWindowConfig};
+use crate::beacon::CheckpointBeacon;
+use crate::blob_index::{BlobIndex, BlobLocation};
+use crate::broadcast_log::{BroadcastIntent, BroadcastLog, BroadcastStatus};
+use crate::coin_selection::{CoinSelection, CoinSelectionStrategy, SingleUtxoFirst};
+use crate::entropy::{derive_commit_key, random_key_pair, EntropySource};
+use crate::fee::{
+    FallbackFeeEstimator, FeeBounds, FeeEstimator, MempoolHistogram, MempoolSpaceFeeSource,
+    RelayPolicy,
+};
+use crate::handoff::InscriptionHandoff;
 use crate::helpers::builders::{
-    compress_blob, create_inscription_transactions, decompress_blob, sign_blob_with_private_key,
-    write_reveal_tx,
+    blob_signing_message, build_commit_transaction, build_consolidation_transaction,
+    build_precommitment_transaction, build_recovery_transaction, chained_reveal_value,
+    dust_threshold, estimate_inscription_cost, inscription_slot_value, reveal_vsize_for_body_len,
+    sign_blob_schnorr_with_private_key, sign_blob_with_private_key, sign_input, split_blob,
+    try_compress_blob, try_compress_blob_or_store, with_placeholder_signatures, CancellationToken,
+    CommitArtifacts, CommitInternalKey, FeeCap, InscriptionBuilder, InscriptionCost,
+    InscriptionReceipt, SignedBlob, UnsignedInscription, DEFAULT_DUST_RELAY_FEE_RATE,
+    MAX_CHUNK_LEN,
+};
+use crate::helpers::parsers::parse_inscriptions;
+use crate::helpers::simulation::simulate_reveal_spend;
+use crate::inclusion_latency::{InclusionLatency, InclusionTracker};
+use crate::musig::{
+    aggregate_partial_signatures, partial_sign, AggregateNonce, KeyAggContext, PartialSignature,
+    PublicNonce, SecretNonce,
 };
-use crate::helpers::parsers::parse_transaction;
+use crate::quorum::{check_threshold as check_quorum_threshold, NodeQuorum, QuorumNodeConfig};
+use crate::quota::{NamespaceUsage, Quota, QuotaLedger};
+use crate::replay::{panic_message, ReplayReport, ReplayedBlob};
+use crate::retention::{PruneStats, RetentionPolicy};
 use crate::rpc::{BitcoinNode, RPCError};
-use crate::spec::blob::BlobWithSender;
+use crate::signer::Signer;
+use crate::slot::{slot_address, InscriptionSlot};
+use crate::spec::batch_sequence::{BatchSequence, BatchSequenceHistory};
+use crate::spec::blob::{
+    BlobKind, BlobWithSender, Checkpoint, CompressionAlgorithm, InscriptionMetadata,
+    SignatureScheme,
+};
 use crate::spec::block::BitcoinBlock;
+use crate::spec::header::HeaderWrapper;
 use crate::spec::header_stream::BitcoinHeaderStream;
-use crate::spec::proof::InclusionMultiProof;
+use crate::spec::proof::{InclusionMultiProof, ProofInput};
 use crate::spec::utxo::UTXO;
-use crate::spec::{BitcoinSpec, RollupParams};
+use crate::spec::{meets_reveal_pow, relevant_tx_cap, BitcoinSpec, RollupParams};
+use crate::tip_monitor::{ChainTipMonitor, TipAlarm, TipStatus};
+use crate::tx_diff::TxDiff;
+use crate::tx_store::{FsTxStore, TxStore};
+use crate::utxo_filter::{
+    AncestorCountFilter, MinConfirmationsFilter, OutPointDenylist, PostageAmountFilter, UtxoFilter,
+};
+use crate::utxo_reservation::{UtxoReservation, UtxoReservations};
 use crate::verifier::BitcoinVerifier;
-use crate::REVEAL_OUTPUT_AMOUNT;
+use crate::webhook::{BlobStatus, BlobStatusNotification, WebhookNotifier};
+use crate::{bip322, REVEAL_OUTPUT_AMOUNT};
 
 /// A service that provides data and data availability proofs for Bitcoin
 #[derive(Debug, Clone)]
@@ -35,13 +88,158 @@ pub struct BitcoinService {
     client: BitcoinNode,
     rollup_name: String,
     network: bitcoin::Network,
-    address: Address<NetworkUnchecked>,
-    sequencer_da_private_key: SecretKey,
+    // None in watch-only mode
+    address: Option<Address<NetworkUnchecked>>,
+    sequencer_da_private_key: Option<SecretKey>,
     reveal_tx_id_prefix: Vec<u8>,
+    reveal_tx_id_zero_bits: u8,
+    max_relevant_txs: Option<u32>,
+    max_decompressed_len: Option<usize>,
+    // batch sequences after the synced blocks, if batch numbers must increase
+    batch_sequence: Option<Arc<Mutex<BatchSequenceHistory>>>,
+    // last batch number the service numbered a blob with, see number_batch
+    last_batch_number: Arc<Mutex<Option<u64>>>,
+    webhook_url: Option<String>,
+    // signs the webhook notifications instead of the sequencer da key
+    webhook_key: Option<SecretKey>,
+    quorum: Option<NodeQuorum>,
+    fee_cap: FeeCap,
+    entropy: SharedEntropy,
+    tip_monitor: Option<Arc<Mutex<ChainTipMonitor>>>,
+    blob_index: Arc<Mutex<BlobIndex>>,
+    blob_rescan_depth: Option<u64>,
+    batch_registry: Arc<Mutex<BatchRegistry>>,
+    broadcast_log: Arc<Mutex<BroadcastLog>>,
+    inclusion_tracker: Arc<Mutex<InclusionTracker>>,
+    utxo_filters: Vec<Arc<dyn UtxoFilter>>,
+    coin_selection: Arc<dyn CoinSelection>,
+    utxo_reservations: UtxoReservations,
+    lock_unspent: bool,
+    change_address: ChangeAddress,
+    batch_queue: Option<mpsc::UnboundedSender<QueuedBlob>>,
+    fee_estimator: Arc<dyn FeeEstimator>,
+    fee_conf_target: u16,
+    inclusion_estimate_blocks: Option<u64>,
+    commit_internal_key: CommitInternalKey,
+    commit_xpriv: Option<ExtendedPrivKey>,
+    // the tx store hands out the indexes from there
+    commit_key_start_index: u32,
+    sibling_public_keys: Vec<Vec<u8>>,
+    double_post_window_blocks: u64,
+    relay_policy: RelayPolicy,
+    quota_ledger: Arc<Mutex<QuotaLedger>>,
+    // signs blobs and reveal txs in place of the in-memory keys
+    signer: Option<Arc<dyn Signer>>,
+    signature_scheme: SignatureScheme,
+    compression: CompressionAlgorithm,
+    checkpoint_beacon: Option<Arc<Mutex<CheckpointBeacon>>>,
+    grind_timeout: Option<Duration>,
+    anti_fee_sniping: bool,
+    reveal_sighash_type: TapSighashType,
+    min_change: Option<u64>,
+    reveal_value: u64,
+    // signed reveal txs, stored before their commit tx is broadcast
+    tx_store: Arc<dyn TxStore>,
+    // dir of the archived reveal txs and of the handoffs, pruned by the retention policy
+    reveal_tx_dir: PathBuf,
+}
+
+// Signed commit and reveal txs of a submission, ready to broadcast
+struct PreparedInscription {
+    signed_commit_tx: bitcoin::Transaction,
+    reveal_tx: bitcoin::Transaction,
+    commit_input_total: u64,
+    // sender and number of the batch
+    batch: Option<(Vec<u8>, u64)>,
+    // index of the derived commit key, None if the key is random or held by the signer
+    commit_key_index: Option<u32>,
+    receipt: InscriptionReceipt,
+    // released when the inscription is dropped
+    reservation: UtxoReservation,
+}
+
+// Txs of an inscription, the reveal tx is still to be signed if a signer holds the commit key
+// A signed one carries the index of its commit key if the key was derived
+enum BuiltInscription {
+    Unsigned(Box<UnsignedInscription>),
+    Signed(bitcoin::Transaction, bitcoin::Transaction, Option<u32>),
+}
+
+impl BuiltInscription {
+    fn commit_tx(&self) -> &bitcoin::Transaction {
+        match self {
+            Self::Unsigned(unsigned) => &unsigned.commit_tx,
+            Self::Signed(commit_tx, ..) => commit_tx,
+        }
+    }
+}
+
+// Blob waiting for the batch window, with the channel its reveal txid is sent to
+type QueuedBlob = (Vec<u8>, oneshot::Sender<Result<Txid, anyhow::Error>>);
+
+// Entropy source shared by the clones of the service
+#[derive(Clone)]
+struct SharedEntropy(Arc<Mutex<Box<dyn EntropySource + Send>>>);
+
+impl fmt::Debug for SharedEntropy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedEntropy")
+    }
+}
+
+/// Receiver of the change of commit txs
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ChangeAddress {
+    // the sequencer address, which receives the reveal outputs too
+    #[default]
+    Sequencer,
+    Fixed(Address),
+    // a fresh change address of the node wallet for every commit tx
+    Wallet,
+}
+
+/// Secret of the config, e.g. the sequencer key in hex. It is left out of Debug output and
+/// wiped from memory when dropped
+#[derive(Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct SecretString(String);
+
+impl SecretString {
+    pub fn new(secret: String) -> Self {
+        Self(secret)
+    }
+
+    pub fn expose_secret(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(secret: String) -> Self {
+        Self(secret)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(secret: &str) -> Self {
+        Self(secret.to_string())
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretString(<redacted>)")
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
 }
 
 /// Runtime configuration for the DA service
-#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
 pub struct DaServiceConfig {
     /// The URL of the Bitcoin node to connect to
     pub node_url: String,
@@ -52,18 +250,309 @@ pub struct DaServiceConfig {
     pub network: String,
 
     // taproot address that holds the funds of the sequencer
-    // will be used as the change address for the inscribe transaction
+    // receives the reveal outputs and, unless change_address is set, the change of commit txs
     pub address: String,
 
+    // receives the change of commit txs, so that the funding wallet is not the inscription
+    // destination. "wallet" takes a fresh change address of the node wallet for every tx
+    pub change_address: Option<String>,
+
     // da private key of the sequencer
-    pub sequencer_da_private_key: Option<String>,
+    pub sequencer_da_private_key: Option<SecretString>,
+    // scheme the sequencer key signs blobs with: ecdsa (default), schnorr or bip322, the
+    // latter by the P2TR address of the key
+    pub blob_signature_scheme: Option<SignatureScheme>,
+    // algorithm blobs are compressed with unless a submission picks another: brotli (default),
+    // zstd, gzip or none (store). Blobs it does not make smaller are stored
+    pub blob_compression: Option<CompressionAlgorithm>,
+
+    // only extract and verify blobs, no wallet or keys are needed and address is ignored
+    pub watch_only: Option<bool>,
 
     // number of last paid fee rates to average if estimation fails
     pub fee_rates_to_avg: Option<usize>,
+
+    // url to POST signed blob inclusion and finality notifications to
+    pub webhook_url: Option<String>,
+
+    // private key signing the webhook notifications, the da private key by default
+    pub webhook_private_key: Option<SecretString>,
+
+    // additional nodes that must agree with the main node on block hashes before extraction
+    pub quorum_nodes: Option<Vec<QuorumNodeConfig>>,
+
+    // number of nodes, main node included, that must agree. defaults to all nodes
+    pub quorum_threshold: Option<usize>,
+
+    // fee rate (sat/vB) the service refuses to exceed
+    pub max_fee_rate: Option<f64>,
+
+    // total fee (sats) of a blob submission the service refuses to exceed
+    pub max_fee_per_submission: Option<u64>,
+
+    // blocks the node tip may diverge from the validated headers before finality stops
+    pub tip_divergence_max_blocks: Option<u64>,
+
+    // minutes the node tip may diverge from the validated headers before finality stops
+    pub tip_divergence_max_minutes: Option<u64>,
+
+    // minutes the node tip may stay at the same height before finality stops
+    pub tip_stall_max_minutes: Option<u64>,
+
+    // number of blocks below the tip to rescan for blobs missing from the index
+    pub blob_rescan_depth: Option<u64>,
+
+    // outpoints ("txid:vout") that must never fund a commit tx, e.g. inscribed or asset utxos
+    pub excluded_utxos: Option<Vec<String>>,
+
+    // utxos up to this amount (sats) are not used to fund commit txs
+    pub postage_max_amount: Option<u64>,
+
+    // confirmations a utxo needs to fund commit txs, 0 (unconfirmed change too) by default
+    pub min_confirmations: Option<u64>,
+
+    // most unconfirmed ancestors a utxo may have to fund commit txs
+    pub max_ancestor_count: Option<u32>,
+
+    // how utxos are picked to fund commit txs: single_utxo_first (default), largest_first,
+    // oldest_first or branch_and_bound (no change output when possible)
+    pub coin_selection: Option<CoinSelectionStrategy>,
+
+    // also lock the utxos of commit txs in flight in the wallet (lockunspent), for other
+    // processes funding txs from the same wallet
+    pub lock_unspent: Option<bool>,
+
+    // minimum number of blocks between posts of queued blobs
+    pub batch_min_blocks: Option<u64>,
+
+    // queued blobs are posted right away once this many bytes are pending
+    pub batch_max_pending_bytes: Option<usize>,
+
+    // mempool.space compatible fee API, the node is used if it fails or returns bogus rates
+    pub fee_api_url: Option<String>,
+
+    // seconds to cache the fee API response
+    pub fee_api_cache_secs: Option<u64>,
+
+    // fee rates (sat/vB) from the fee API below this one or above max_fee_rate are ignored
+    pub fee_api_min_rate: Option<f64>,
+
+    // blocks the txs of a submission are meant to confirm within, the target of the fee
+    // estimates. Next block by default
+    pub fee_conf_target: Option<u16>,
+
+    // JSON lines file persisting the mapping of rollup batch numbers to txids
+    pub batch_registry_path: Option<String>,
+
+    // log the probability of each submission being included within this many blocks
+    pub inclusion_estimate_blocks: Option<u64>,
+
+    // JSON lines write-ahead log of broadcasts, pending ones are sent again at startup
+    pub broadcast_log_path: Option<String>,
+
+    // JSON lines file persisting the time to confirmation and finality of submissions
+    pub inclusion_log_path: Option<String>,
+
+    // commit outputs use a NUMS internal key, so they can only be spent by the reveal tx
+    pub nums_internal_key: Option<bool>,
+
+    // xpriv the commit keys are derived from, at m/<index>' from commit_key_start_index up
+    // Without it commit keys are random and lost if the service stops before the reveal is sent
+    pub commit_xpriv: Option<String>,
+    pub commit_key_start_index: Option<u32>,
+
+    // public keys (hex) of the other sequencer replicas posting the same batches
+    // A blob one of them already posted in the mempool or in the last double_post_window_blocks
+    // blocks is not sent again
+    pub sibling_sequencer_public_keys: Option<Vec<String>>,
+    pub double_post_window_blocks: Option<u64>,
+
+    // relay policy (sat/vB) of the node, the relay fees are fetched from the node if not set
+    // the dust relay fee must match the node's -dustrelayfee, 3 sat/vB by default
+    pub min_relay_fee_rate: Option<f64>,
+    pub incremental_relay_fee_rate: Option<f64>,
+    pub dust_relay_fee_rate: Option<f64>,
+
+    // daily quota of the rollup, in compressed blob bytes and in fees (sats)
+    // see with_quota_ledger to share the accounting with the services of other rollups
+    pub quota_bytes_per_day: Option<u64>,
+    pub quota_sats_per_day: Option<u64>,
+
+    // directory the signed reveal txs are archived in, and the handoffs of the reveal txs
+    // that could not be broadcast. When set, the archived reveal txs of a previous run are
+    // broadcast again at startup. The working directory by default
+    pub reveal_tx_dir: Option<String>,
+
+    // retention of the blob index and of the reveal txs archived in reveal_tx_dir
+    // pruning runs in the background every retention_interval_secs once any of them is set
+    pub retention_keep_days: Option<u64>,
+    pub retention_max_archive_bytes: Option<u64>,
+    pub retention_prune_finalized_reveals: Option<bool>,
+    pub retention_interval_secs: Option<u64>,
+
+    // posts the latest state root reported with update_checkpoint every that many blocks
+    pub checkpoint_interval_blocks: Option<u64>,
+
+    // seconds the reveal nonce grinding may take before the submission fails, so that it can
+    // be retried with other parameters. Unbounded by default
+    pub pow_grind_timeout_secs: Option<u64>,
+
+    // lock commit and reveal txs to the tip height like the bitcoind wallet, so that they
+    // cannot be mined in a reorg of the tip (anti fee sniping). Off by default
+    pub anti_fee_sniping: Option<bool>,
+
+    // sighash type of the reveal signature, e.g. "SIGHASH_ALL|SIGHASH_ANYONECANPAY" to let fee
+    // inputs be added to the reveal tx later. SIGHASH_DEFAULT by default
+    pub reveal_sighash_type: Option<TapSighashType>,
+
+    // smallest change output of commit txs, utxos leaving no change or at least that much are
+    // preferred and smaller change is left as fee. The dust threshold of the change by default
+    pub min_change: Option<u64>,
+
+    // value of the reveal output paid to the sequencer address, at least its dust threshold
+    // Larger outputs can pay to CPFP the reveal tx or be worth consolidating. 546 by default
+    pub reveal_value: Option<u64>,
+}
+
+// Presets fill in the network and the defaults of the node's RPC port, the node credentials,
+// address and sequencer key are left for the caller to set
+impl DaServiceConfig {
+    pub fn mainnet_defaults() -> Self {
+        Self {
+            node_url: "http://localhost:8332".to_string(),
+            network: "bitcoin".to_string(),
+            max_fee_rate: Some(500.0),
+            // inscriptions and assets on postage sized outputs are worth far more than their amount
+            postage_max_amount: Some(10_000),
+            ..Default::default()
+        }
+    }
+
+    pub fn signet_defaults() -> Self {
+        Self {
+            node_url: "http://localhost:38332".to_string(),
+            network: "signet".to_string(),
+            max_fee_rate: Some(100.0),
+            ..Default::default()
+        }
+    }
+
+    pub fn regtest_defaults() -> Self {
+        Self {
+            node_url: "http://localhost:18443".to_string(),
+            network: "regtest".to_string(),
+            ..Default::default()
+        }
+    }
+
+    // Cross-checks the configuration against the chain the node runs on, so the service
+    // fails at startup instead of building transactions for the wrong chain
+    pub fn check(&self, node_network: bitcoin::Network) -> Result<(), anyhow::Error> {
+        let network = bitcoin::Network::from_str(&self.network)
+            .map_err(|_| anyhow::anyhow!("Invalid bitcoin network name {}", self.network))?;
+
+        if network != node_network {
+            return Err(anyhow::anyhow!(
+                "Configured for {} but the node runs on {}",
+                network,
+                node_network
+            ));
+        }
+
+        if let Some(threshold) = self.quorum_threshold {
+            check_quorum_threshold(threshold, self.quorum_nodes.as_ref().map_or(0, Vec::len))?;
+        }
+
+        if self.watch_only.unwrap_or(false) {
+            return Ok(());
+        }
+
+        let address = Address::from_str(&self.address)
+            .map_err(|e| anyhow::anyhow!("Invalid bitcoin address {}: {}", self.address, e))?;
+        if !address.is_valid_for_network(network) {
+            return Err(anyhow::anyhow!(
+                "Address {} is not valid for {}",
+                self.address,
+                network
+            ));
+        }
+
+        if let Some(reveal_value) = self.reveal_value {
+            let threshold = dust_threshold(
+                &address.payload.script_pubkey(),
+                self.dust_relay_fee_rate
+                    .unwrap_or(DEFAULT_DUST_RELAY_FEE_RATE),
+            );
+            if reveal_value < threshold {
+                return Err(anyhow::anyhow!(
+                    "Reveal value of {} sats is below the dust threshold of {} sats",
+                    reveal_value,
+                    threshold
+                ));
+            }
+        }
+
+        if let Some(change_address) = self.change_address.as_deref() {
+            if change_address != WALLET_CHANGE_ADDRESS
+                && !Address::from_str(change_address)
+                    .map_or(false, |address| address.is_valid_for_network(network))
+            {
+                return Err(anyhow::anyhow!(
+                    "Change address {} is not valid for {}",
+                    change_address,
+                    network
+                ));
+            }
+        }
+
+        if let Some(private_key) = &self.sequencer_da_private_key {
+            SecretKey::from_str(private_key.expose_secret())
+                .map_err(|_| anyhow::anyhow!("Invalid sequencer private key"))?;
+        }
+        if let Some(private_key) = &self.webhook_private_key {
+            SecretKey::from_str(private_key.expose_secret())
+                .map_err(|_| anyhow::anyhow!("Invalid webhook private key"))?;
+        }
+
+        if let Some(fee_conf_target) = self.fee_conf_target {
+            if !(1..=MAX_FEE_CONF_TARGET).contains(&fee_conf_target) {
+                return Err(anyhow::anyhow!(
+                    "Fee confirmation target of {} blocks is not within 1 and {}",
+                    fee_conf_target,
+                    MAX_FEE_CONF_TARGET
+                ));
+            }
+        }
+
+        Ok(())
+    }
 }
 
+const WALLET_CHANGE_ADDRESS: &str = "wallet";
 const FINALITY_DEPTH: u64 = 4; // blocks
 const POLLING_INTERVAL: u64 = 10; // seconds
+const DEFAULT_TIP_DIVERGENCE_MAX_BLOCKS: u64 = 6;
+const DEFAULT_TIP_DIVERGENCE_MAX_MINUTES: u64 = 60;
+// a two hour gap between blocks happens about once a year on mainnet
+const DEFAULT_TIP_STALL_MAX_MINUTES: u64 = 120;
+const DEFAULT_FEE_API_CACHE_SECS: u64 = 60;
+const DEFAULT_FEE_CONF_TARGET: u16 = 1;
+// largest confirmation target of estimatesmartfee
+const MAX_FEE_CONF_TARGET: u16 = 1008;
+const DEFAULT_DOUBLE_POST_WINDOW_BLOCKS: u64 = 6;
+// sendrawtransaction error of a tx that is already in a block
+const RPC_VERIFY_ALREADY_IN_CHAIN: i32 = -27;
+// sendrawtransaction error of a tx the node rejects, e.g. for missing or spent inputs
+const RPC_VERIFY_ERROR: i32 = -25;
+// blobs posted together by the batch window, a reveal tx must stay below the standard
+// tx weight of 400k weight units
+const MAX_BATCHED_BYTES: usize = 300_000;
+// default -limitancestorcount of bitcoind, a tx and its unconfirmed ancestors
+const MEMPOOL_ANCESTOR_LIMIT: u64 = 25;
+const DEFAULT_RETENTION_INTERVAL_SECS: u64 = 60 * 60;
+// nonces tried between two logs of the reveal nonce grinding
+const GRIND_PROGRESS_ATTEMPTS: u64 = 1_000_000;
+const DEFAULT_REVEAL_TX_DIR: &str = ".";
 
 impl BitcoinService {
     // Create a new instance of the DA service from the given configuration.
@@ -72,143 +561,3245 @@ impl BitcoinService {
             bitcoin::Network::from_str(&config.network).expect("Invalid bitcoin network name");
 
         let client = BitcoinNode::new(
-            config.node_url,
-            config.node_username,
-            config.node_password,
+            config.node_url.clone(),
+            config.node_username.clone(),
+            config.node_password.clone(),
+            network,
+        );
+
+        let node_network = client
+            .get_network()
+            .await
+            .expect("Failed to get the network of the node");
+        config
+            .check(node_network)
+            .expect("Invalid DA service configuration");
+        chain_params.check().expect("Invalid rollup name");
+
+        // the watch-only feature leaves out the constructor and the setter that take keys, the
+        // service never holds one and every submission fails
+        #[cfg(feature = "watch-only")]
+        let mut service = Self::watch_only(
+            client,
+            chain_params.rollup_name,
             network,
+            chain_params.reveal_tx_id_prefix,
         );
 
-        let address = Address::from_str(&config.address).expect("Invalid bitcoin address");
+        #[cfg(not(feature = "watch-only"))]
+        let mut service = if config.watch_only.unwrap_or(false) {
+            Self::watch_only(
+                client,
+                chain_params.rollup_name,
+                network,
+                chain_params.reveal_tx_id_prefix,
+            )
+        } else {
+            let address = Address::from_str(&config.address).expect("Invalid bitcoin address");
+
+            let private_key = SecretKey::from_str(
+                config
+                    .sequencer_da_private_key
+                    .as_ref()
+                    .map_or("", SecretString::expose_secret),
+            )
+            .expect("Invalid private key");
+
+            Self::with_client(
+                client,
+                chain_params.rollup_name,
+                network,
+                address,
+                private_key,
+                chain_params.reveal_tx_id_prefix,
+            )
+            .await
+        };
+
+        service.reveal_tx_id_zero_bits = chain_params.reveal_tx_id_zero_bits;
+        service.max_relevant_txs = chain_params.max_relevant_txs;
+        service.max_decompressed_len = chain_params.max_decompressed_len;
+        service = service.with_monotonic_batch_numbers(chain_params.monotonic_batch_numbers);
+        service.webhook_url = config.webhook_url;
+        if let Some(private_key) = &config.webhook_private_key {
+            service = service.with_webhook_key(
+                SecretKey::from_str(private_key.expose_secret())
+                    .expect("Invalid webhook private key"),
+            );
+        }
+
+        service.fee_cap = FeeCap {
+            max_fee_rate: config.max_fee_rate,
+            max_fee: config.max_fee_per_submission,
+        };
+
+        service.blob_rescan_depth = config.blob_rescan_depth;
+        service.inclusion_estimate_blocks = config.inclusion_estimate_blocks;
+
+        if config.nums_internal_key.unwrap_or(false) {
+            service.commit_internal_key = CommitInternalKey::Nums;
+        }
+        service.signature_scheme = config.blob_signature_scheme.unwrap_or_default();
+        service.compression = config.blob_compression.unwrap_or_default();
+
+        let mut relay_policy = RelayPolicy::default();
+        match service.client.get_relay_fee_rates().await {
+            Ok((min_relay_fee_rate, incremental_relay_fee_rate)) => {
+                relay_policy.min_relay_fee_rate = min_relay_fee_rate;
+                relay_policy.incremental_relay_fee_rate = incremental_relay_fee_rate;
+            }
+            Err(e) => warn!("Failed to get the relay fees of the node: {}", e),
+        }
+        service.relay_policy = RelayPolicy {
+            min_relay_fee_rate: config
+                .min_relay_fee_rate
+                .unwrap_or(relay_policy.min_relay_fee_rate),
+            incremental_relay_fee_rate: config
+                .incremental_relay_fee_rate
+                .unwrap_or(relay_policy.incremental_relay_fee_rate),
+            dust_relay_fee_rate: config
+                .dust_relay_fee_rate
+                .unwrap_or(relay_policy.dust_relay_fee_rate),
+        };
+
+        if config.quota_bytes_per_day.is_some() || config.quota_sats_per_day.is_some() {
+            service
+                .quota_ledger()
+                .expect("Quota ledger lock is poisoned")
+                .set_quota(
+                    &service.rollup_name,
+                    Quota {
+                        max_bytes_per_day: config.quota_bytes_per_day,
+                        max_sats_per_day: config.quota_sats_per_day,
+                    },
+                );
+        }
+
+        if let Some(sibling_public_keys) = config.sibling_sequencer_public_keys {
+            service = service.with_sibling_sequencers(
+                sibling_public_keys
+                    .iter()
+                    .map(|key| hex::decode(key).expect("Invalid sibling sequencer public key"))
+                    .collect(),
+                config
+                    .double_post_window_blocks
+                    .unwrap_or(DEFAULT_DOUBLE_POST_WINDOW_BLOCKS),
+            );
+        }
+
+        if let Some(commit_xpriv) = config.commit_xpriv {
+            service = service.with_commit_xpriv(
+                ExtendedPrivKey::from_str(&commit_xpriv).expect("Invalid commit xpriv"),
+                config.commit_key_start_index.unwrap_or(0),
+            );
+        }
+
+        service.anti_fee_sniping = config.anti_fee_sniping.unwrap_or(false);
+        service.reveal_sighash_type = config
+            .reveal_sighash_type
+            .unwrap_or(TapSighashType::Default);
+        service.min_change = config.min_change;
+        service.reveal_value = config.reveal_value.unwrap_or(REVEAL_OUTPUT_AMOUNT);
+
+        if let Some(timeout_secs) = config.pow_grind_timeout_secs {
+            service = service.with_grind_timeout(Duration::from_secs(timeout_secs));
+        }
+
+        if let Some(batch_registry_path) = config.batch_registry_path {
+            service.batch_registry = Arc::new(Mutex::new(
+                BatchRegistry::open(batch_registry_path).expect("Cannot open batch registry"),
+            ));
+        }
+
+        if let Some(inclusion_log_path) = config.inclusion_log_path {
+            service.inclusion_tracker = Arc::new(Mutex::new(
+                InclusionTracker::open(inclusion_log_path).expect("Cannot open inclusion log"),
+            ));
+        }
+
+        if let Some(broadcast_log_path) = config.broadcast_log_path {
+            service.broadcast_log = Arc::new(Mutex::new(
+                BroadcastLog::open(broadcast_log_path).expect("Cannot open broadcast log"),
+            ));
+
+            // txs of a previous run that may not have reached the network
+            match service.reconcile_broadcasts().await {
+                Ok(outcomes) => {
+                    for (intent, status) in outcomes {
+                        info!(
+                            "Reconciled {:?} tx {}: {:?}",
+                            intent.purpose, intent.txid, status
+                        );
+                    }
+                }
+                Err(e) => warn!("Failed to reconcile pending broadcasts: {}", e),
+            }
+        }
+
+        if let Some(reveal_tx_dir) = config.reveal_tx_dir {
+            service = service.with_reveal_tx_dir(reveal_tx_dir);
+
+            // reveal txs of a previous run whose commit tx may be out
+            match service.recover_reveals().await {
+                Ok(outcomes) => {
+                    for (commit_txid, status) in outcomes {
+                        info!(
+                            "Recovered reveal tx of commit {}: {:?}",
+                            commit_txid, status
+                        );
+                    }
+                }
+                Err(e) => warn!("Failed to recover archived reveal txs: {}", e),
+            }
+        }
+
+        if let Some(fee_api_url) = config.fee_api_url {
+            let default_bounds = FeeBounds::default();
+
+            service.fee_estimator = Arc::new(FallbackFeeEstimator::new(
+                Arc::new(MempoolSpaceFeeSource::new(
+                    fee_api_url,
+                    Duration::from_secs(
+                        config
+                            .fee_api_cache_secs
+                            .unwrap_or(DEFAULT_FEE_API_CACHE_SECS),
+                    ),
+                )),
+                service.fee_estimator.clone(),
+                FeeBounds {
+                    min_fee_rate: config
+                        .fee_api_min_rate
+                        .unwrap_or(default_bounds.min_fee_rate),
+                    fee_cap: service.fee_cap,
+                },
+            ));
+        }
+        if let Some(fee_conf_target) = config.fee_conf_target {
+            service.fee_conf_target = fee_conf_target;
+        }
+
+        if let Some(excluded_utxos) = config.excluded_utxos {
+            let outpoints = excluded_utxos
+                .iter()
+                .map(|outpoint| OutPoint::from_str(outpoint).expect("Invalid excluded utxo"));
+            service
+                .utxo_filters
+                .push(Arc::new(OutPointDenylist::new(outpoints)));
+        }
+
+        if let Some(max_amount) = config.postage_max_amount {
+            service
+                .utxo_filters
+                .push(Arc::new(PostageAmountFilter { max_amount }));
+        }
+        if let Some(min_confirmations) = config.min_confirmations {
+            service
+                .utxo_filters
+                .push(Arc::new(MinConfirmationsFilter { min_confirmations }));
+        }
+
+        if let Some(max_ancestor_count) = config.max_ancestor_count {
+            service
+                .utxo_filters
+                .push(Arc::new(AncestorCountFilter { max_ancestor_count }));
+        }
+        service.coin_selection = config.coin_selection.unwrap_or_default().coin_selection();
+        service.lock_unspent = config.lock_unspent.unwrap_or(false);
+        service.change_address = match config.change_address.as_deref() {
+            None => ChangeAddress::Sequencer,
+            Some(WALLET_CHANGE_ADDRESS) => ChangeAddress::Wallet,
+            Some(address) => ChangeAddress::Fixed(
+                Address::from_str(address)
+                    .expect("Invalid change address")
+                    .require_network(network)
+                    .expect("Invalid network for change address"),
+            ),
+        };
+
+        if config.tip_divergence_max_blocks.is_some()
+            || config.tip_divergence_max_minutes.is_some()
+            || config.tip_stall_max_minutes.is_some()
+        {
+            service.tip_monitor = Some(Arc::new(Mutex::new(ChainTipMonitor::new(
+                config
+                    .tip_divergence_max_blocks
+                    .unwrap_or(DEFAULT_TIP_DIVERGENCE_MAX_BLOCKS),
+                Duration::from_secs(
+                    config
+                        .tip_divergence_max_minutes
+                        .unwrap_or(DEFAULT_TIP_DIVERGENCE_MAX_MINUTES)
+                        * 60,
+                ),
+                Duration::from_secs(
+                    config
+                        .tip_stall_max_minutes
+                        .unwrap_or(DEFAULT_TIP_STALL_MAX_MINUTES)
+                        * 60,
+                ),
+                network,
+            ))));
+        }
+
+        service.quorum = config.quorum_nodes.map(|nodes| {
+            let nodes = nodes
+                .into_iter()
+                .map(|node| {
+                    BitcoinNode::new(
+                        node.node_url,
+                        node.node_username,
+                        node.node_password,
+                        network,
+                    )
+                })
+                .collect::<Vec<_>>();
+            let threshold = config.quorum_threshold.unwrap_or(nodes.len() + 1);
+
+            NodeQuorum::new(nodes, threshold).expect("Invalid quorum configuration")
+        });
+
+        if config.batch_min_blocks.is_some() || config.batch_max_pending_bytes.is_some() {
+            let window = BatchWindow::new(BatchWindowConfig {
+                min_blocks_between_posts: config.batch_min_blocks.unwrap_or(0),
+                max_pending_bytes: config.batch_max_pending_bytes.unwrap_or(usize::MAX),
+            });
+            let (sender, receiver) = mpsc::unbounded_channel();
+
+            // the posting service has no queue, so it sends directly
+            tokio::spawn(service.clone().run_batch_window(window, receiver));
+
+            service.batch_queue = Some(sender);
+        }
+
+        if config.retention_keep_days.is_some()
+            || config.retention_max_archive_bytes.is_some()
+            || config.retention_prune_finalized_reveals.unwrap_or(false)
+        {
+            let policy = RetentionPolicy {
+                keep_days: config.retention_keep_days,
+                max_archive_bytes: config.retention_max_archive_bytes,
+                prune_finalized_reveals: config.retention_prune_finalized_reveals.unwrap_or(false),
+            };
+            let interval = Duration::from_secs(
+                config
+                    .retention_interval_secs
+                    .unwrap_or(DEFAULT_RETENTION_INTERVAL_SECS),
+            );
+
+            tokio::spawn(service.clone().run_pruning(policy, interval));
+        }
+
+        if let Some(interval_blocks) = config.checkpoint_interval_blocks {
+            service.checkpoint_beacon =
+                Some(Arc::new(Mutex::new(CheckpointBeacon::new(interval_blocks))));
+
+            tokio::spawn(service.clone().run_checkpoint_beacon());
+        }
+
+        service
+    }
+
+    #[cfg(not(feature = "watch-only"))]
+    pub async fn with_client(
+        client: BitcoinNode,
+        rollup_name: String,
+        network: bitcoin::Network,
+        address: Address<NetworkUnchecked>,
+        sequencer_da_private_key: SecretKey,
+        reveal_tx_id_prefix: Vec<u8>,
+    ) -> Self {
+        // We can't store address with the network check because it's not serializable
+        address
+            .clone()
+            .require_network(network)
+            .expect("Invalid address for network!");
+
+        let wallets = client
+            .list_wallets()
+            .await
+            .expect("Failed to list loaded wallets");
+
+        if wallets.is_empty() {
+            panic!("No loaded wallet found!");
+        }
+
+        Self::build(
+            client,
+            rollup_name,
+            network,
+            Some(address),
+            Some(sequencer_da_private_key),
+            reveal_tx_id_prefix,
+        )
+    }
+
+    // Service for rollup full nodes that never post: blocks are streamed, blobs extracted and
+    // proofs generated, submission fails since there is no wallet and no sequencer key
+    pub fn watch_only(
+        client: BitcoinNode,
+        rollup_name: String,
+        network: bitcoin::Network,
+        reveal_tx_id_prefix: Vec<u8>,
+    ) -> Self {
+        Self::build(
+            client,
+            rollup_name,
+            network,
+            None,
+            None,
+            reveal_tx_id_prefix,
+        )
+    }
+
+    fn build(
+        client: BitcoinNode,
+        rollup_name: String,
+        network: bitcoin::Network,
+        address: Option<Address<NetworkUnchecked>>,
+        sequencer_da_private_key: Option<SecretKey>,
+        reveal_tx_id_prefix: Vec<u8>,
+    ) -> Self {
+        let fee_estimator: Arc<dyn FeeEstimator> = Arc::new(client.clone());
+
+        Self {
+            client,
+            rollup_name,
+            network,
+            address,
+            sequencer_da_private_key,
+            reveal_tx_id_prefix,
+            reveal_tx_id_zero_bits: 0,
+            max_relevant_txs: None,
+            max_decompressed_len: None,
+            batch_sequence: None,
+            last_batch_number: Arc::new(Mutex::new(None)),
+            webhook_url: None,
+            webhook_key: None,
+            quorum: None,
+            fee_cap: FeeCap::default(),
+            entropy: SharedEntropy(Arc::new(Mutex::new(Box::new(rand::rngs::OsRng)))),
+            tip_monitor: None,
+            blob_index: Arc::new(Mutex::new(BlobIndex::new())),
+            blob_rescan_depth: None,
+            batch_registry: Arc::new(Mutex::new(BatchRegistry::in_memory())),
+            broadcast_log: Arc::new(Mutex::new(BroadcastLog::in_memory())),
+            inclusion_tracker: Arc::new(Mutex::new(InclusionTracker::in_memory())),
+            utxo_filters: vec![],
+            coin_selection: Arc::new(SingleUtxoFirst),
+            utxo_reservations: UtxoReservations::new(),
+            lock_unspent: false,
+            change_address: ChangeAddress::default(),
+            batch_queue: None,
+            fee_estimator,
+            fee_conf_target: DEFAULT_FEE_CONF_TARGET,
+            inclusion_estimate_blocks: None,
+            commit_internal_key: CommitInternalKey::default(),
+            commit_xpriv: None,
+            commit_key_start_index: 0,
+            sibling_public_keys: vec![],
+            double_post_window_blocks: DEFAULT_DOUBLE_POST_WINDOW_BLOCKS,
+            relay_policy: RelayPolicy::default(),
+            quota_ledger: Arc::new(Mutex::new(QuotaLedger::default())),
+            signer: None,
+            signature_scheme: SignatureScheme::default(),
+            compression: CompressionAlgorithm::default(),
+            checkpoint_beacon: None,
+            grind_timeout: None,
+            anti_fee_sniping: false,
+            reveal_sighash_type: TapSighashType::Default,
+            min_change: None,
+            reveal_value: REVEAL_OUTPUT_AMOUNT,
+            tx_store: Arc::new(FsTxStore::new(DEFAULT_REVEAL_TX_DIR)),
+            reveal_tx_dir: PathBuf::from(DEFAULT_REVEAL_TX_DIR),
+        }
+    }
+
+    // Posts the checkpoints of the beacon as they fall due, whatever the batch flow
+    async fn run_checkpoint_beacon(self) {
+        loop {
+            tokio::time::sleep(Duration::from_secs(POLLING_INTERVAL)).await;
+
+            let height = match self.client.get_block_count().await {
+                Ok(height) => height,
+                Err(e) => {
+                    error!("Failed to get block count for checkpoint beacon: {}", e);
+                    continue;
+                }
+            };
+
+            let checkpoint = match self.checkpoint_beacon() {
+                Ok(beacon) => beacon.due(height),
+                Err(e) => {
+                    error!("{}", e);
+                    return;
+                }
+            };
+            let Some(checkpoint) = checkpoint else {
+                continue;
+            };
+
+            let result = match self.get_fee_rate().await {
+                Ok(fee_rate) => {
+                    self.send_transaction_of_kind(
+                        &checkpoint.to_bytes(),
+                        BlobKind::Checkpoint,
+                        fee_rate,
+                    )
+                    .await
+                }
+                Err(e) => Err(e),
+            };
+
+            match result {
+                Ok(txid) => {
+                    info!(
+                        "Checkpoint {} posted at height {} in tx {}",
+                        hex::encode(checkpoint.state_root),
+                        height,
+                        txid
+                    );
+                    if let Ok(mut beacon) = self.checkpoint_beacon() {
+                        beacon.record_post(height);
+                    }
+                }
+                // retried at the next poll
+                Err(e) => warn!("Failed to post checkpoint at height {}: {}", height, e),
+            }
+        }
+    }
+
+    fn checkpoint_beacon(&self) -> Result<MutexGuard<'_, CheckpointBeacon>, anyhow::Error> {
+        self.checkpoint_beacon
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Checkpoint beacon is disabled"))?
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Checkpoint beacon lock is poisoned"))
+    }
+
+    // Latest state root of the rollup, posted by the beacon at its next interval
+    pub fn update_checkpoint(&self, state_root: [u8; 32]) -> Result<(), anyhow::Error> {
+        self.checkpoint_beacon()?.update(Checkpoint { state_root });
+        Ok(())
+    }
+
+    // Prunes the blob index and the reveal tx archive every interval
+    async fn run_pruning(self, policy: RetentionPolicy, interval: Duration) {
+        loop {
+            match self.prune(&policy).await {
+                Ok(stats) => info!(
+                    "Pruned {} indexed blobs ({} left) and {} archived txs ({} bytes left)",
+                    stats.index_pruned, stats.index_len, stats.archive_pruned, stats.archive_bytes
+                ),
+                Err(e) => warn!("Failed to prune: {}", e),
+            }
+
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    // Drops the history the policy does not keep, blobs of blocks that are not final are kept
+    pub async fn prune(&self, policy: &RetentionPolicy) -> Result<PruneStats, anyhow::Error> {
+        let tip_height = self.client.get_block_count().await?;
+        let finalized_height = tip_height.saturating_sub(FINALITY_DEPTH);
+
+        let mut blob_index = self
+            .blob_index
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Blob index lock is poisoned"))?;
+
+        // the archive is checked first, pruning the index forgets where the reveals are
+        let (archive_pruned, archive_bytes) =
+            policy.prune_archive(&self.reveal_tx_dir, SystemTime::now(), |txid| {
+                blob_index
+                    .find_txid(txid)
+                    .map_or(false, |location| location.height <= finalized_height)
+            })?;
+
+        let index_pruned = policy
+            .index_cutoff(tip_height, finalized_height)
+            .map_or(0, |cutoff| blob_index.prune_below(cutoff));
+
+        Ok(PruneStats {
+            index_pruned,
+            index_len: blob_index.len(),
+            archive_pruned,
+            archive_bytes,
+        })
+    }
+
+    // Collects blobs sent through send_transaction and posts them together, in as few reveal
+    // txs as possible, when the batch window opens
+    async fn run_batch_window(
+        self,
+        mut window: BatchWindow,
+        mut receiver: mpsc::UnboundedReceiver<QueuedBlob>,
+    ) {
+        let mut pending: Vec<QueuedBlob> = vec![];
+        let mut closed = false;
+
+        loop {
+            if closed {
+                if pending.is_empty() {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_secs(POLLING_INTERVAL)).await;
+            } else {
+                match tokio::time::timeout(Duration::from_secs(POLLING_INTERVAL), receiver.recv())
+                    .await
+                {
+                    Ok(Some(queued)) => {
+                        window.add_pending(queued.0.len());
+                        pending.push(queued);
+                    }
+                    Ok(None) => closed = true,
+                    Err(_) => {} // poll the window
+                }
+            }
+
+            if pending.is_empty() {
+                continue;
+            }
+
+            let height = match self.client.get_block_count().await {
+                Ok(height) => height,
+                Err(e) => {
+                    error!("Failed to get block count for batch window: {}", e);
+                    continue;
+                }
+            };
+
+            if !window.is_open(height) {
+                continue;
+            }
+
+            info!(
+                "Posting {} queued blobs ({} bytes) at height {}",
+                pending.len(),
+                window.pending_bytes(),
+                height
+            );
+
+            let sizes: Vec<usize> = pending.iter().map(|(blob, _)| blob.len()).collect();
+            let mut posted = false;
+            for group_len in group_by_size(&sizes, MAX_BATCHED_BYTES) {
+                let (blobs, replies): (Vec<_>, Vec<_>) = pending.drain(..group_len).unzip();
+
+                let result = match self.get_fee_rate().await {
+                    Ok(fee_rate) => self.send_transactions_batched(&blobs, fee_rate).await,
+                    Err(e) => Err(e),
+                };
+                posted |= result.is_ok();
+
+                for reply in replies {
+                    // the caller may have stopped waiting, the blob is posted anyway
+                    let _ = reply.send(match &result {
+                        Ok(txid) => Ok(*txid),
+                        Err(e) => Err(anyhow::anyhow!("{}", e)),
+                    });
+                }
+            }
+
+            // the blobs of failed groups are answered with the error and not queued again
+            if posted {
+                window.record_post(height);
+            } else {
+                window.record_failed_post();
+            }
+        }
+    }
+
+    pub fn is_watch_only(&self) -> bool {
+        self.sequencer_da_private_key.is_none() && self.signer.is_none()
+    }
+
+    // Address holding the funds of the sequencer
+    fn sequencer_address(&self) -> Result<Address, anyhow::Error> {
+        let address = self
+            .address
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Submission is disabled in watch-only mode"))?;
+
+        Ok(address
+            .require_network(self.network)
+            .expect("Invalid network for address"))
+    }
+
+    // Receives the change of the next commit tx
+    async fn change_address(&self) -> Result<Address, anyhow::Error> {
+        match &self.change_address {
+            ChangeAddress::Sequencer => self.sequencer_address(),
+            ChangeAddress::Fixed(address) => Ok(address.clone()),
+            ChangeAddress::Wallet => self.client.get_change_address().await,
+        }
+    }
+
+    // Sends the change of commit txs elsewhere than to the sequencer address
+    pub fn with_change_address(mut self, change_address: ChangeAddress) -> Self {
+        self.change_address = change_address;
+        self
+    }
+
+    fn chain_params(&self) -> RollupParams {
+        RollupParams {
+            rollup_name: self.rollup_name.clone(),
+            reveal_tx_id_prefix: self.reveal_tx_id_prefix.clone(),
+            reveal_tx_id_zero_bits: self.reveal_tx_id_zero_bits,
+            max_relevant_txs: self.max_relevant_txs,
+            max_decompressed_len: self.max_decompressed_len,
+            monotonic_batch_numbers: self.batch_sequence.is_some(),
+        }
+    }
+
+    // Txs of the block meeting the reveal proof of work, in block order and up to the cap
+    fn relevant_txs<'a>(
+        &self,
+        block: &'a BitcoinBlock,
+    ) -> impl Iterator<Item = &'a bitcoin::Transaction> + 'a {
+        let prefix = self.reveal_tx_id_prefix.clone();
+        let zero_bits = self.reveal_tx_id_zero_bits;
+
+        block
+            .txdata
+            .iter()
+            .filter(move |tx| meets_reveal_pow(&tx.txid().to_byte_array(), &prefix, zero_bits))
+            .take(relevant_tx_cap(self.max_relevant_txs))
+    }
+
+    fn params_fingerprint(&self) -> [u8; 8] {
+        self.chain_params().fingerprint()
+    }
+
+    // Leading zero bits reveal txids need on top of the prefix, see RollupParams
+    pub fn with_reveal_tx_id_zero_bits(mut self, zero_bits: u8) -> Self {
+        self.reveal_tx_id_zero_bits = zero_bits;
+        self
+    }
+
+    // Cap on the relevant txs read per block, see RollupParams
+    pub fn with_max_relevant_txs(mut self, max_relevant_txs: Option<u32>) -> Self {
+        self.max_relevant_txs = max_relevant_txs;
+        self
+    }
+
+    // Cap on the decompressed size of blobs, larger ones are dropped, see RollupParams
+    pub fn with_max_decompressed_len(mut self, max_decompressed_len: Option<usize>) -> Self {
+        self.max_decompressed_len = max_decompressed_len;
+        self
+    }
+
+    // Drops blobs whose batch number is not above the previous ones of their sender, see
+    // RollupParams
+    pub fn with_monotonic_batch_numbers(mut self, enabled: bool) -> Self {
+        self.batch_sequence = enabled.then(|| Arc::new(Mutex::new(BatchSequenceHistory::new())));
+        self
+    }
+
+    // Batch sequence the block at height is read from, if batch numbers must increase
+    fn batch_sequence_before(&self, height: u64) -> Option<BatchSequence> {
+        let history = self.batch_sequence.as_ref()?;
+
+        Some(
+            history
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .before(height),
+        )
+    }
+
+    // Signs the webhook notifications with key rather than the sequencer da key
+    pub fn with_webhook_key(mut self, key: SecretKey) -> Self {
+        self.webhook_key = Some(key);
+        self
+    }
+
+    fn sequencer_key(&self) -> Result<SecretKey, anyhow::Error> {
+        self.sequencer_da_private_key
+            .ok_or_else(|| anyhow::anyhow!("Submission is disabled in watch-only mode"))
+    }
+
+    // Signs blobs and reveal txs with the signer instead of the sequencer key and the
+    // commit keys, e.g. to keep the keys in an HSM. Commit keys are no longer derived
+    #[cfg(not(feature = "watch-only"))]
+    pub fn with_signer(mut self, signer: Arc<dyn Signer>) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    // Scheme the sequencer key signs blobs with, it does not apply to a signer
+    pub fn with_signature_scheme(mut self, scheme: SignatureScheme) -> Self {
+        self.signature_scheme = scheme;
+        self
+    }
+
+    // Algorithm blobs are compressed with unless a submission picks another
+    pub fn with_compression(mut self, compression: CompressionAlgorithm) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    // Compresses the blob with the algorithm of the metadata, or the one of the service
+    // The algorithm of the service falls back to storing blobs it does not make smaller, one
+    // picked for the submission is kept
+    // Brotli bodies are not tagged so that parsers predating the compression tag read them
+    fn compress(
+        &self,
+        blob: &[u8],
+        metadata: &mut InscriptionMetadata,
+    ) -> Result<Vec<u8>, anyhow::Error> {
+        let (body, compression) = match metadata.compression {
+            Some(compression) => (try_compress_blob(blob, compression)?, compression),
+            None => try_compress_blob_or_store(blob, self.compression)?,
+        };
+        metadata.compression = Some(compression).filter(|c| *c != CompressionAlgorithm::Brotli);
+
+        Ok(body)
+    }
+
+    // Signs the compressed blob along with its metadata, stamped with the current timestamp
+    // and the params fingerprint, and numbered if needed, first so that the signature covers
+    // them
+    // Schnorr and BIP-322 signatures are tagged with their scheme, ECDSA ones are not so that
+    // parsers predating the scheme tag still read them
+    // BIP-322 signatures are by the P2TR key path address of the sequencer key
+    async fn sign_blob(
+        &self,
+        body: Vec<u8>,
+        mut metadata: InscriptionMetadata,
+    ) -> Result<SignedBlob, anyhow::Error> {
+        self.number_batch(&mut metadata)?;
+        let scheme = match &self.signer {
+            Some(signer) => signer.signature_scheme(),
+            None => self.signature_scheme,
+        };
+        let metadata = InscriptionMetadata {
+            timestamp: Some(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs()),
+            params_fingerprint: Some(self.params_fingerprint()),
+            signature_scheme: Some(scheme).filter(|scheme| *scheme != SignatureScheme::Ecdsa),
+            ..metadata
+        };
+        let message = blob_signing_message(&body, &metadata, []);
+
+        let (signature, public_key) = match &self.signer {
+            Some(signer) => signer.sign_blob(&message).await?,
+            None => {
+                let sequencer_key = self.sequencer_key()?;
+                match scheme {
+                    SignatureScheme::Ecdsa => sign_blob_with_private_key(&message, &sequencer_key),
+                    SignatureScheme::Schnorr => {
+                        sign_blob_schnorr_with_private_key(&message, &sequencer_key)
+                    }
+                    SignatureScheme::Bip322 => bip322::sign_simple_p2tr(
+                        &sha256d::Hash::hash(&message).to_byte_array(),
+                        &sequencer_key,
+                    ),
+                }
+            }
+        };
+
+        Ok(SignedBlob {
+            body,
+            signature,
+            public_key,
+            metadata,
+        })
+    }
+
+    // Numbers the blob after the last batch of the service if batch numbers must increase and
+    // the caller gave none, only checkpoints go unnumbered. The last batch is the highest one
+    // the service numbered, sent or found on chain from any of its senders
+    fn number_batch(&self, metadata: &mut InscriptionMetadata) -> Result<(), anyhow::Error> {
+        let Some(history) = &self.batch_sequence else {
+            return Ok(());
+        };
+        if metadata.batch_number.is_none() && metadata.kind == Some(BlobKind::Checkpoint) {
+            return Ok(());
+        }
+
+        let mut last_batch_number = self
+            .last_batch_number
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let batch_number = match metadata.batch_number {
+            Some(batch_number) => batch_number,
+            None => {
+                let registry = self
+                    .batch_registry
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner);
+                let on_chain = history
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .before(u64::MAX)
+                    .last_of(|sender| self.is_own_sender(sender, &registry));
+
+                match [*last_batch_number, registry.last_batch_number(), on_chain]
+                    .into_iter()
+                    .flatten()
+                    .max()
+                {
+                    Some(last) => last
+                        .checked_add(1)
+                        .ok_or_else(|| anyhow::anyhow!("Batch numbers are exhausted"))?,
+                    None => 0,
+                }
+            }
+        };
+
+        *last_batch_number =
+            Some(last_batch_number.map_or(batch_number, |last| last.max(batch_number)));
+        metadata.batch_number = Some(batch_number);
+
+        Ok(())
+    }
+
+    // Blobs that must increase the batch number of the sequencer need one, only checkpoints
+    // go unnumbered. The service and the verifier would drop them, see BatchSequence
+    // The committee signs the same metadata, so the service cannot number its blobs
+    fn check_batch_number(&self, metadata: &InscriptionMetadata) -> Result<(), anyhow::Error> {
+        if self.batch_sequence.is_some()
+            && metadata.batch_number.is_none()
+            && metadata.kind != Some(BlobKind::Checkpoint)
+        {
+            return Err(anyhow::anyhow!(
+                "Batch numbers must increase for the rollup, the blob needs one"
+            ));
+        }
+
+        Ok(())
+    }
+
+    // Commit key of the signer for the next inscription, None if the builder draws one
+    async fn signer_commit_key(&self) -> Result<Option<XOnlyPublicKey>, anyhow::Error> {
+        match &self.signer {
+            Some(signer) => Ok(Some(signer.commit_public_key().await?)),
+            None => Ok(None),
+        }
+    }
+
+    // Signs the reveal tx with the signer that holds the commit key
+    async fn sign_reveal(
+        &self,
+        unsigned: UnsignedInscription,
+    ) -> Result<(bitcoin::Transaction, bitcoin::Transaction), anyhow::Error> {
+        let signer = self
+            .signer
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("No signer holds commit key"))?;
+        let signature = signer
+            .sign_reveal(&unsigned.commit_public_key, unsigned.reveal_sighash())
+            .await?;
+        let (commit_tx, reveal_tx, _) = unsigned.finalize(&signature)?;
+
+        Ok((commit_tx, reveal_tx))
+    }
+
+    // Adds a filter for utxos that must not fund commit transactions
+    pub fn with_utxo_filter(mut self, filter: Arc<dyn UtxoFilter>) -> Self {
+        self.utxo_filters.push(filter);
+        self
+    }
+
+    // Picks the utxos funding commit txs
+    pub fn with_coin_selection(mut self, coin_selection: Arc<dyn CoinSelection>) -> Self {
+        self.coin_selection = coin_selection;
+        self
+    }
+
+    // Raised chain tip divergence alarm, finality does not advance while it is set
+    pub fn tip_alarm(&self) -> Option<TipAlarm> {
+        self.tip_monitor
+            .as_ref()
+            .and_then(|monitor| monitor.lock().ok()?.alarm().cloned())
+    }
+
+    // Clears the chain tip divergence alarm after the operator has checked the node
+    pub fn clear_tip_alarm(&self) {
+        if let Some(monitor) = &self.tip_monitor {
+            if let Ok(mut monitor) = monitor.lock() {
+                monitor.clear_alarm();
+            }
+        }
+    }
+
+    // Compares the node tip with the last validated header
+    // Returns an error while they diverge, so finality does not advance
+    async fn check_chain_tip(&self, node_height: u64) -> Result<(), anyhow::Error> {
+        let Some(monitor) = &self.tip_monitor else {
+            return Ok(());
+        };
+
+        let local_tip = monitor
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Tip monitor lock is poisoned"))?
+            .local_tip();
+
+        let node_hash_at_local_tip = match local_tip {
+            Some((local_height, _)) if local_height <= node_height => {
+                Some(self.client.get_block_hash(local_height).await?)
+            }
+            _ => None,
+        };
+
+        let status = monitor
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Tip monitor lock is poisoned"))?
+            .observe_node_tip(
+                node_height,
+                node_hash_at_local_tip.as_deref(),
+                Instant::now(),
+            );
+
+        match status {
+            Ok(TipStatus::InSync) => Ok(()),
+            Ok(TipStatus::Diverged { blocks, .. }) => {
+                warn!(
+                    "Node tip at {} diverged from validated headers by {} blocks",
+                    node_height, blocks
+                );
+                Err(anyhow::anyhow!(
+                    "Node tip diverged from validated headers, not advancing finality"
+                ))
+            }
+            Err(alarm) => {
+                error!("Chain tip alarm: {}", alarm);
+                Err(alarm.into())
+            }
+        }
+    }
+
+    // Moves the validated tip to header through the headers between them, each checked for
+    // proof of work and linkage rather than trusting the hashes reported by the node
+    async fn validate_headers(&self, header: HeaderWrapper) -> Result<(), anyhow::Error> {
+        let Some(monitor) = &self.tip_monitor else {
+            return Ok(());
+        };
+
+        let local_tip = monitor
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Tip monitor lock is poisoned"))?
+            .local_tip();
+        let local_height = match local_tip {
+            Some((local_height, _)) if header.height <= local_height => return Ok(()),
+            Some((local_height, _)) => local_height,
+            // the first header is only checked on its own
+            None => header.height.saturating_sub(1),
+        };
+
+        let mut headers = vec![header];
+        while headers.last().expect("headers are not empty").height > local_height + 1 {
+            let child = headers.last().expect("headers are not empty");
+            let parent = self
+                .client
+                .get_block_header(child.prev_hash().0.to_string())
+                .await?;
+            headers.push(parent);
+        }
+        headers.reverse();
+
+        monitor
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Tip monitor lock is poisoned"))?
+            .record_validated_tip(&headers)
+            .map_err(|alarm| {
+                error!("Chain tip alarm: {}", alarm);
+                alarm.into()
+            })
+    }
+
+    // Last validated finalized header as (height, hash), the node's finalized tip
+    // when no header was validated yet
+    async fn validated_tip(&self) -> Result<(u64, String), anyhow::Error> {
+        if let Some(monitor) = &self.tip_monitor {
+            let local_tip = monitor
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Tip monitor lock is poisoned"))?
+                .local_tip();
+            if let Some(local_tip) = local_tip {
+                return Ok(local_tip);
+            }
+        }
+
+        let finalized_height = self
+            .client
+            .get_block_count()
+            .await?
+            .saturating_sub(FINALITY_DEPTH);
+
+        Ok((
+            finalized_height,
+            self.client.get_block_hash(finalized_height).await?,
+        ))
+    }
+
+    // Whether the block hash at height is an ancestor of (or is) the validated tip,
+    // following the header chain back from the tip instead of trusting the node's
+    // current block at that height. Heights above the validated tip are never ancestors
+    pub async fn is_ancestor_of_validated_tip(
+        &self,
+        height: u64,
+        hash: &str,
+    ) -> Result<bool, anyhow::Error> {
+        self.is_ancestor_of(self.validated_tip().await?, height, hash)
+            .await
+    }
+
+    async fn is_ancestor_of(
+        &self,
+        (tip_height, tip_hash): (u64, String),
+        height: u64,
+        hash: &str,
+    ) -> Result<bool, anyhow::Error> {
+        if height > tip_height {
+            return Ok(false);
+        }
+
+        let mut header = self.client.get_block_header(tip_hash).await?;
+        while header.height > height {
+            let parent = self
+                .client
+                .get_block_header(header.prev_hash().0.to_string())
+                .await?;
+            if !header.links_to(&parent) {
+                return Err(anyhow::anyhow!(
+                    "Header at height {} does not link to its parent",
+                    header.height
+                ));
+            }
+            header = parent;
+        }
+
+        Ok(header.block_hash().to_string() == hash)
+    }
+
+    // Derives the commit keys from the xpriv, one hardened index per inscription from start_index
+    // The next index is kept in the tx store, a restart does not use an index again
+    pub fn with_commit_xpriv(mut self, xpriv: ExtendedPrivKey, start_index: u32) -> Self {
+        self.commit_xpriv = Some(xpriv);
+        self.commit_key_start_index = start_index;
+        self
+    }
+
+    // Time the reveal nonce grinding of a submission may take before it fails
+    pub fn with_grind_timeout(mut self, timeout: Duration) -> Self {
+        self.grind_timeout = Some(timeout);
+        self
+    }
+
+    // Store of the signed reveal txs, FsTxStore of the reveal tx dir by default
+    pub fn with_tx_store(mut self, tx_store: Arc<dyn TxStore>) -> Self {
+        self.tx_store = tx_store;
+        self
+    }
+
+    // Archives the reveal txs in dir, see DaServiceConfig::reveal_tx_dir
+    pub fn with_reveal_tx_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.reveal_tx_dir = dir.into();
+        self.tx_store = Arc::new(FsTxStore::new(self.reveal_tx_dir.clone()));
+        self
+    }
+
+    // Source of the fee rates of the submissions, the node by default
+    pub fn with_fee_estimator(mut self, fee_estimator: Arc<dyn FeeEstimator>) -> Self {
+        self.fee_estimator = fee_estimator;
+        self
+    }
+
+    // Blocks the txs of a submission are meant to confirm within
+    pub fn with_fee_conf_target(mut self, fee_conf_target: u16) -> Self {
+        self.fee_conf_target = fee_conf_target;
+        self
+    }
+
+    // Locks commit and reveal txs to the tip height, see DaServiceConfig::anti_fee_sniping
+    pub fn with_anti_fee_sniping(mut self, enabled: bool) -> Self {
+        self.anti_fee_sniping = enabled;
+        self
+    }
+
+    // Sighash type of the reveal signature, see DaServiceConfig::reveal_sighash_type
+    pub fn with_reveal_sighash_type(mut self, sighash_type: TapSighashType) -> Self {
+        self.reveal_sighash_type = sighash_type;
+        self
+    }
+
+    // Smallest change output of commit txs, see DaServiceConfig::min_change
+    pub fn with_min_change(mut self, min_change: u64) -> Self {
+        self.min_change = Some(min_change);
+        self
+    }
+
+    // Value of the reveal output, see DaServiceConfig::reveal_value
+    pub fn with_reveal_value(mut self, reveal_value: u64) -> Self {
+        self.reveal_value = reveal_value;
+        self
+    }
+
+    // Tip height the txs of a submission are locked to, if anti fee sniping is enabled
+    async fn lock_time_height(&self) -> Result<Option<u32>, anyhow::Error> {
+        if !self.anti_fee_sniping {
+            return Ok(None);
+        }
+
+        Ok(Some(u32::try_from(self.client.get_block_count().await?)?))
+    }
+
+    fn grind_cancellation(&self) -> CancellationToken {
+        self.grind_timeout
+            .map_or_else(CancellationToken::new, CancellationToken::with_timeout)
+    }
+
+    // Public keys of the other sequencer replicas, and the number of recent blocks searched
+    // for their posts besides the mempool
+    pub fn with_sibling_sequencers(
+        mut self,
+        public_keys: Vec<Vec<u8>>,
+        window_blocks: u64,
+    ) -> Self {
+        self.sibling_public_keys = public_keys;
+        self.double_post_window_blocks = window_blocks;
+        self
+    }
+
+    // Reveal tx of a sibling sequencer that already inscribes one of the blobs, in the mempool
+    // or in the last blocks of the window. Blobs are matched by the hash their signature covers
+    async fn find_sibling_post(
+        &self,
+        blob_hashes: &[[u8; 32]],
+    ) -> Result<Option<Txid>, anyhow::Error> {
+        if self.sibling_public_keys.is_empty() {
+            return Ok(None);
+        }
+
+        let is_sibling_post = |tx: &bitcoin::Transaction| {
+            parse_inscriptions(tx, &self.rollup_name)
+                .unwrap_or_default()
+                .iter()
+                .any(|inscription| {
+                    self.sibling_public_keys.contains(&inscription.public_key)
+                        && inscription
+                            .get_sig_verified_hash()
+                            .map_or(false, |hash| blob_hashes.contains(&hash))
+                })
+        };
+
+        for txid in self.client.get_raw_mempool().await? {
+            if !meets_reveal_pow(
+                &txid.to_byte_array(),
+                &self.reveal_tx_id_prefix,
+                self.reveal_tx_id_zero_bits,
+            ) {
+                continue;
+            }
+
+            // the tx may have been mined or evicted since the mempool was listed
+            match self.client.get_raw_transaction(&txid).await {
+                Ok(tx) if is_sibling_post(&tx) => return Ok(Some(txid)),
+                Ok(_) => {}
+                Err(e) => warn!("Failed to get mempool tx {}: {}", txid, e),
+            }
+        }
+
+        let tip_height = self.client.get_block_count().await?;
+        for height in
+            (tip_height.saturating_sub(self.double_post_window_blocks) + 1..=tip_height).rev()
+        {
+            let block = self
+                .client
+                .get_block(self.client.get_block_hash(height).await?)
+                .await?;
+
+            let sibling_post = self.relevant_txs(&block).find(|tx| is_sibling_post(tx));
+            if let Some(tx) = sibling_post {
+                return Ok(Some(tx.txid()));
+            }
+        }
+
+        Ok(None)
+    }
+
+    // Sweeps the commit output of a stuck reveal tx back to the sequencer address, with the
+    // commit key derived at commit_key_index, which the tx store keeps with the reveal tx
+    // until the node accepts it
+    // Returns the txid of the recovery tx
+    // The commit tx must still be in the mempool or the node must index transactions
+    pub async fn recover_commit_output(
+        &self,
+        reveal_tx: &bitcoin::Transaction,
+        commit_key_index: u32,
+        fee_sat_per_vbyte: f64,
+    ) -> Result<Txid, anyhow::Error> {
+        let xpriv = self.commit_xpriv.as_ref().ok_or_else(|| {
+            anyhow::anyhow!("Commit keys are not derived from an xpriv, they cannot be recovered")
+        })?;
+        self.fee_cap.check_fee_rate(fee_sat_per_vbyte)?;
+
+        let artifacts = CommitArtifacts::from_reveal_tx(reveal_tx, self.network)
+            .ok_or_else(|| anyhow::anyhow!("Reveal tx does not spend a commit output"))?;
+        let commit_outpoint = reveal_tx.input[0].previous_output;
+        let commit_tx = self
+            .client
+            .get_raw_transaction(&commit_outpoint.txid)
+            .await?;
+        let commit_output = commit_tx
+            .output
+            .get(commit_outpoint.vout as usize)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Commit output {} does not exist", commit_outpoint))?;
+
+        let commit_key = derive_commit_key(&Secp256k1::new(), xpriv, commit_key_index)?;
+        let recovery_tx = {
+            let mut entropy = self
+                .entropy
+                .0
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Entropy source lock is poisoned"))?;
+
+            build_recovery_transaction(
+                commit_outpoint,
+                commit_output.clone(),
+                &artifacts,
+                &commit_key,
+                self.sequencer_address()?,
+                fee_sat_per_vbyte,
+                entropy.as_mut(),
+            )?
+        };
+
+        TxAuditRecord::new(
+            &recovery_tx,
+            TxPurpose::Recovery,
+            AuditEvent::Signed,
+            commit_output.value,
+        )
+        .log();
+        self.broadcast(&recovery_tx, TxPurpose::Recovery).await?;
+        TxAuditRecord::new(
+            &recovery_tx,
+            TxPurpose::Recovery,
+            AuditEvent::Broadcast,
+            commit_output.value,
+        )
+        .log();
+
+        Ok(recovery_tx.txid())
+    }
+
+    // Next derived commit key and its index, None if commit keys are random
+    // The index is stored with the reveal tx so that the key can be derived again to recover
+    // the commit output
+    fn next_commit_key(&self) -> Result<Option<(u32, KeyPair)>, anyhow::Error> {
+        let Some(xpriv) = &self.commit_xpriv else {
+            return Ok(None);
+        };
+
+        let index = self
+            .tx_store
+            .reserve_commit_key_index(self.commit_key_start_index)?;
+        let key_pair = derive_commit_key(&Secp256k1::new(), xpriv, index)?;
+        info!(
+            "Commit key derived at index {}: {}",
+            index,
+            key_pair.x_only_public_key().0
+        );
+
+        Ok(Some((index, key_pair)))
+    }
+
+    // Replaces the default (OS) randomness used for commit keys and signature nonces
+    pub fn with_entropy_source(mut self, entropy: Box<dyn EntropySource + Send>) -> Self {
+        self.entropy = SharedEntropy(Arc::new(Mutex::new(entropy)));
+        self
+    }
+
+    // Routes the node RPCs through the fault injector, for resilience testing only
+    #[cfg(any(test, feature = "test-utils"))]
+    pub fn with_fault_injector(mut self, faults: crate::fault::FaultInjector) -> Self {
+        self.client = self.client.with_fault_injector(faults);
+        self
+    }
+
+    pub async fn send_transaction_with_fee_rate(
+        &self,
+        blob: &[u8],
+        fee_sat_per_vbyte: f64,
+    ) -> Result<<Self as DaService>::TransactionId, anyhow::Error> {
+        self.send_transaction_with_metadata(blob, fee_sat_per_vbyte, None)
+            .await
+    }
+
+    // Sends the blob tagged with the rollup batch number and the current timestamp
+    pub async fn send_transaction_with_metadata(
+        &self,
+        blob: &[u8],
+        fee_sat_per_vbyte: f64,
+        batch_number: Option<u64>,
+    ) -> Result<<Self as DaService>::TransactionId, anyhow::Error> {
+        self.send_sequencer_blob(
+            blob,
+            fee_sat_per_vbyte,
+            InscriptionMetadata {
+                batch_number,
+                ..Default::default()
+            },
+        )
+        .await
+    }
+
+    // Sends a blob tagged with the kind of artifact it carries, e.g. a zk proof
+    pub async fn send_transaction_of_kind(
+        &self,
+        blob: &[u8],
+        kind: BlobKind,
+        fee_sat_per_vbyte: f64,
+    ) -> Result<Txid, anyhow::Error> {
+        self.send_sequencer_blob(
+            blob,
+            fee_sat_per_vbyte,
+            InscriptionMetadata {
+                kind: Some(kind),
+                ..Default::default()
+            },
+        )
+        .await
+    }
+
+    // Sends the blob compressed with the given algorithm instead of the one of the service,
+    // e.g. none for a payload that is already compressed or encrypted
+    pub async fn send_transaction_with_compression(
+        &self,
+        blob: &[u8],
+        compression: CompressionAlgorithm,
+        fee_sat_per_vbyte: f64,
+    ) -> Result<Txid, anyhow::Error> {
+        self.send_sequencer_blob(
+            blob,
+            fee_sat_per_vbyte,
+            InscriptionMetadata {
+                compression: Some(compression),
+                ..Default::default()
+            },
+        )
+        .await
+    }
+
+    // Sends a blob too large for a single reveal tx as chunks, one commit and reveal pair
+    // each. Returns the reveal txids in chunk order, the rollup reassembles the blob with
+    // parsers::reassemble_chunks once all of them are found
+    pub async fn send_transaction_chunked(
+        &self,
+        blob: &[u8],
+        fee_sat_per_vbyte: f64,
+    ) -> Result<Vec<Txid>, anyhow::Error> {
+        let mut txids = vec![];
+        for (chunk, manifest) in split_blob(blob, MAX_CHUNK_LEN) {
+            let txid = self
+                .send_sequencer_blob(
+                    &chunk,
+                    fee_sat_per_vbyte,
+                    InscriptionMetadata {
+                        chunk: Some(manifest),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+            txids.push(txid);
+        }
+
+        Ok(txids)
+    }
+
+    // Compresses the blob and signs it with the sequencer key
+    async fn send_sequencer_blob(
+        &self,
+        blob: &[u8],
+        fee_sat_per_vbyte: f64,
+        mut metadata: InscriptionMetadata,
+    ) -> Result<Txid, anyhow::Error> {
+        // Compress the blob and sign it for authentication of the sequencer
+        let body = self.compress(blob, &mut metadata)?;
+        let signed = self.sign_blob(body, metadata).await?;
+
+        self.send_signed_blob(
+            signed.body,
+            signed.signature,
+            signed.public_key,
+            fee_sat_per_vbyte,
+            signed.metadata,
+            vec![],
+        )
+        .await
+    }
+
+    // Sends the blobs through a single commit and reveal pair, one envelope per blob, so the
+    // overhead of the two txs is paid once. All blobs share the returned reveal txid
+    pub async fn send_transactions_batched(
+        &self,
+        blobs: &[Vec<u8>],
+        fee_sat_per_vbyte: f64,
+    ) -> Result<Txid, anyhow::Error> {
+        let mut signed_blobs = Vec::with_capacity(blobs.len());
+        for blob in blobs {
+            let mut metadata = InscriptionMetadata::default();
+            let body = self.compress(blob, &mut metadata)?;
+            signed_blobs.push(self.sign_blob(body, metadata).await?);
+        }
+        let mut signed_blobs = signed_blobs.into_iter();
+
+        let first = signed_blobs
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("No blobs to send"))?;
+
+        self.send_signed_blob(
+            first.body,
+            first.signature,
+            first.public_key,
+            fee_sat_per_vbyte,
+            first.metadata,
+            signed_blobs.collect(),
+        )
+        .await
+    }
+
+    // Sends an already compressed and signed blob, and the batched ones after it in the same
+    // reveal tx
+    async fn send_signed_blob(
+        &self,
+        blob: Vec<u8>,
+        signature: Vec<u8>,
+        public_key: Vec<u8>,
+        fee_sat_per_vbyte: f64,
+        metadata: InscriptionMetadata,
+        batched_blobs: Vec<SignedBlob>,
+    ) -> Result<Txid, anyhow::Error> {
+        let prepared = self
+            .prepare_signed_blob(
+                blob,
+                signature,
+                public_key,
+                fee_sat_per_vbyte,
+                metadata,
+                batched_blobs,
+            )
+            .await?;
+        let receipt = &prepared.receipt;
+
+        self.lock_wallet_utxos(&prepared.reservation).await?;
+        let result = self
+            .broadcast_inscription(
+                &prepared.signed_commit_tx,
+                prepared.commit_input_total,
+                &prepared.reveal_tx,
+                prepared.batch.clone(),
+                prepared.commit_key_index,
+            )
+            .await;
+        self.unlock_wallet_utxos(&prepared.reservation).await;
+
+        if result.is_ok() {
+            info!(
+                "Inscription paid {} sats ({} commit, {} reveal) for {} vB ({} commit, {} reveal) at {:.2} sat/vB, {} utxos spent, commit address {}, nonce {}",
+                receipt.fee(),
+                receipt.commit_fee,
+                receipt.reveal_fee,
+                receipt.vsize(),
+                receipt.commit_vsize,
+                receipt.reveal_vsize,
+                receipt.effective_fee_rate,
+                receipt.utxos.len(),
+                receipt.commit_address,
+                receipt.nonce
+            );
+        }
+
+        result
+    }
+
+    // Builds the commit and reveal txs of the blob and signs both, nothing is broadcast yet
+    // The utxos of the commit tx stay reserved until the returned inscription is dropped
+    async fn prepare_signed_blob(
+        &self,
+        blob: Vec<u8>,
+        signature: Vec<u8>,
+        public_key: Vec<u8>,
+        fee_sat_per_vbyte: f64,
+        metadata: InscriptionMetadata,
+        batched_blobs: Vec<SignedBlob>,
+    ) -> Result<PreparedInscription, anyhow::Error> {
+        self.fee_cap.check_fee_rate(fee_sat_per_vbyte)?;
+        // the reveal alone may already exceed the fee cap, no need to look at the wallet
+        let reveal_vsize: usize = batched_blobs
+            .iter()
+            .map(|blob| reveal_vsize_for_body_len(blob.body.len()))
+            .sum::<usize>()
+            + reveal_vsize_for_body_len(blob.len());
+        self.fee_cap
+            .check_fee((reveal_vsize as f64 * fee_sat_per_vbyte).ceil() as u64)?;
+        // the byte quota is known to be exceeded before any tx is built
+        let bytes = batched_blobs
+            .iter()
+            .map(|blob| blob.body.len() as u64)
+            .sum::<u64>()
+            + blob.len() as u64;
+        self.quota_ledger()?
+            .check(&self.rollup_name, bytes, 0, unix_now())?;
+
+        // an active and a passive replica may both post the same batch
+        let blob_hashes: Vec<[u8; 32]> = std::iter::once(&blob)
+            .chain(batched_blobs.iter().map(|blob| &blob.body))
+            .map(|body| sha256d::Hash::hash(body).to_byte_array())
+            .collect();
+        if let Some(txid) = self.find_sibling_post(&blob_hashes).await? {
+            return Err(anyhow::anyhow!(
+                "Blob is already posted by a sibling sequencer in tx {}",
+                txid
+            ));
+        }
+
+        if let Some(blocks) = self.inclusion_estimate_blocks {
+            match self.inclusion_probability(fee_sat_per_vbyte, blocks).await {
+                Ok(probability) => info!(
+                    "Sending blob at {} sat/vB, inclusion probability within {} blocks: {:.2}",
+                    fee_sat_per_vbyte, blocks, probability
+                ),
+                Err(e) => warn!("Failed to estimate inclusion probability: {}", e),
+            }
+        }
+
+        let client = self.client.clone();
+
+        let network = self.network;
+        let address = self.sequencer_address()?;
+        let rollup_name = self.rollup_name.clone();
+
+        // get all available utxos, except the ones that must not be spent
+        let mut utxos: Vec<UTXO> = client.get_utxos().await?;
+        utxos.retain(|utxo| {
+            !self
+                .utxo_filters
+                .iter()
+                .any(|filter| filter.is_excluded(utxo))
+        });
+
+        // create inscribe transactions
+        let signer_commit_key = self.signer_commit_key().await?;
+        let change_address = self.change_address().await?;
+        let batch = metadata
+            .batch_number
+            .map(|batch_number| (public_key.clone(), batch_number));
+        let mut builder = InscriptionBuilder::new(&rollup_name)
+            .with_body(blob)
+            .with_signature(signature, public_key)
+            .with_metadata(metadata);
+        for batched_blob in batched_blobs {
+            builder = builder.with_batched_blob(batched_blob);
+        }
+        let builder = builder
+            .with_coin_selection(self.coin_selection.clone())
+            .with_dust_limit(
+                self.relay_policy
+                    .dust_threshold(&change_address.script_pubkey()),
+            )
+            .with_change_address(change_address)
+            .with_recipient(address)
+            .with_reveal_value(self.reveal_value)
+            .with_fee_rates(fee_sat_per_vbyte, fee_sat_per_vbyte)
+            .with_fee_cap(self.fee_cap)
+            .with_network(network)
+            .with_internal_key(self.commit_internal_key)
+            .with_pow_prefix(&self.reveal_tx_id_prefix)
+            .with_pow_zero_bits(self.reveal_tx_id_zero_bits)
+            .with_skip_pow(!self.chain_params().requires_reveal_pow())
+            .with_reveal_sighash_type(self.reveal_sighash_type)
+            .with_grind_progress(GRIND_PROGRESS_ATTEMPTS, &log_grind_progress)
+            .with_grind_cancellation(self.grind_cancellation());
+        let builder = match self.min_change {
+            Some(min_change) => builder.with_min_change(min_change),
+            None => builder,
+        };
+        let builder = match self.lock_time_height().await? {
+            Some(tip_height) => builder.with_tip_height(tip_height),
+            None => builder,
+        };
+
+        // the utxos of concurrent submissions are left out and the ones of the commit tx
+        // reserved in one go, the signer is only awaited after
+        let (built, receipt, reservation) = {
+            let reservations = self.utxo_reservations.lock()?;
+            let builder = builder.with_utxos(reservations.available(utxos.clone()));
+
+            let (built, receipt) = match signer_commit_key {
+                Some(commit_public_key) => {
+                    let unsigned = builder.build_unsigned(commit_public_key)?;
+                    let receipt = unsigned.receipt.clone();
+                    (BuiltInscription::Unsigned(Box::new(unsigned)), receipt)
+                }
+                None => {
+                    let commit_key = self.next_commit_key()?;
+                    let mut entropy = self
+                        .entropy
+                        .0
+                        .lock()
+                        .map_err(|_| anyhow::anyhow!("Entropy source lock is poisoned"))?;
+
+                    let mut builder = builder.with_entropy(entropy.as_mut());
+                    if let Some((_, commit_key)) = commit_key {
+                        builder = builder.with_commit_key(commit_key);
+                    }
+                    let (commit_tx, reveal_tx, receipt) = builder.build_with_receipt()?;
+                    (
+                        BuiltInscription::Signed(
+                            commit_tx,
+                            reveal_tx,
+                            commit_key.map(|(index, _)| index),
+                        ),
+                        receipt,
+                    )
+                }
+            };
+            let reservation = reservations.reserve(built.commit_tx());
+
+            (built, receipt, reservation)
+        };
+        let (unsigned_commit_tx, reveal_tx, commit_key_index) = match built {
+            BuiltInscription::Unsigned(unsigned) => {
+                let (commit_tx, reveal_tx) = self.sign_reveal(*unsigned).await?;
+                (commit_tx, reveal_tx, None)
+            }
+            BuiltInscription::Signed(commit_tx, reveal_tx, commit_key_index) => {
+                (commit_tx, reveal_tx, commit_key_index)
+            }
+        };
+
+        let commit_input_total = utxos_total(&unsigned_commit_tx, &utxos);
+
+        // sign inscribe transactions
+        let serialized_unsigned_commit_tx = &encode::serialize(&unsigned_commit_tx);
+        let signed_raw_commit_tx = client
+            .sign_raw_transaction_with_wallet(serialized_unsigned_commit_tx.encode_hex())
+            .await?;
+
+        let signed_commit_tx: bitcoin::Transaction =
+            encode::deserialize(&hex::decode(&signed_raw_commit_tx)?)?;
+
+        if tracing::enabled!(tracing::Level::TRACE) {
+            trace!(
+                "Commit tx {} as estimated and as signed by the wallet:\n{}",
+                signed_commit_tx.txid(),
+                TxDiff::new(
+                    &with_placeholder_signatures(&unsigned_commit_tx, &utxos),
+                    &signed_commit_tx
+                )
+            );
+        }
+
+        audit_signed_inscription(&signed_commit_tx, commit_input_total, &reveal_tx);
+
+        Ok(PreparedInscription {
+            signed_commit_tx,
+            reveal_tx,
+            commit_input_total,
+            batch,
+            commit_key_index,
+            receipt,
+            reservation,
+        })
+    }
+
+    // Broadcasts the commit tx of the blob and returns the pair with the fully signed reveal
+    // tx, which is not broadcast, e.g. to reveal on a schedule. The handoff carries the
+    // consensus encoded txs, so broadcast_presigned_reveal can send the reveal tx later, from
+    // this service or another one. The reveal tx is kept out of the tx store, recover_reveals
+    // does not broadcast it early
+    pub async fn send_commit_with_presigned_reveal(
+        &self,
+        blob: &[u8],
+        fee_sat_per_vbyte: f64,
+    ) -> Result<InscriptionHandoff, anyhow::Error> {
+        let mut metadata = InscriptionMetadata::default();
+        let body = self.compress(blob, &mut metadata)?;
+        let signed = self.sign_blob(body, metadata).await?;
+        let prepared = self
+            .prepare_signed_blob(
+                signed.body,
+                signed.signature,
+                signed.public_key,
+                fee_sat_per_vbyte,
+                signed.metadata,
+                vec![],
+            )
+            .await?;
+
+        self.lock_wallet_utxos(&prepared.reservation).await?;
+        let result = self
+            .broadcast_commit(
+                &prepared.signed_commit_tx,
+                prepared.commit_input_total,
+                &prepared.reveal_tx,
+                None,
+            )
+            .await;
+        self.unlock_wallet_utxos(&prepared.reservation).await;
+        result?;
+
+        info!(
+            "Commit tx {} sent, reveal tx {} is held back",
+            prepared.signed_commit_tx.txid(),
+            prepared.reveal_tx.txid()
+        );
+
+        Ok(InscriptionHandoff::new(
+            &prepared.signed_commit_tx,
+            &prepared.reveal_tx,
+            prepared.commit_input_total,
+            &self.rollup_name,
+        ))
+    }
+
+    // Broadcasts the reveal tx of a handoff, e.g. of send_commit_with_presigned_reveal
+    // The commit tx is sent again first, in case the node never saw it
+    pub async fn broadcast_presigned_reveal(
+        &self,
+        handoff: &InscriptionHandoff,
+    ) -> Result<Txid, anyhow::Error> {
+        let (commit_tx, reveal_tx) = handoff.transactions()?;
+
+        // the commit tx is usually in the mempool or confirmed already
+        if let Err(e) = self
+            .client
+            .send_raw_transaction(encode::serialize(&commit_tx).encode_hex())
+            .await
+        {
+            debug!("Commit tx {} was not sent again: {}", commit_tx.txid(), e);
+        }
+
+        self.broadcast(&reveal_tx, TxPurpose::Reveal).await?;
+        TxAuditRecord::new(
+            &reveal_tx,
+            TxPurpose::Reveal,
+            AuditEvent::Broadcast,
+            commit_tx.output[0].value,
+        )
+        .log();
+
+        let reveal_txid = reveal_tx.txid();
+        if let Err(e) = self.track_submission(reveal_txid).await {
+            error!("Failed to track inclusion of {}: {}", reveal_txid, e);
+        }
+
+        Ok(reveal_txid)
+    }
+
+    fn quota_ledger(&self) -> Result<MutexGuard<'_, QuotaLedger>, anyhow::Error> {
+        self.quota_ledger
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Quota ledger lock is poisoned"))
+    }
+
+    // Shares the utxo reservations with the services of other rollups funded by the same wallet
+    pub fn with_utxo_reservations(mut self, utxo_reservations: UtxoReservations) -> Self {
+        self.utxo_reservations = utxo_reservations;
+        self
+    }
+
+    // Locks the reserved utxos in the wallet, a failure releases the reservation
+    async fn lock_wallet_utxos(&self, reservation: &UtxoReservation) -> Result<(), anyhow::Error> {
+        if self.lock_unspent && !reservation.outpoints().is_empty() {
+            self.client
+                .lock_unspent(false, reservation.outpoints())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    // Unlocks the utxos once their commit tx is out or failed
+    async fn unlock_wallet_utxos(&self, reservation: &UtxoReservation) {
+        if self.lock_unspent && !reservation.outpoints().is_empty() {
+            if let Err(e) = self
+                .client
+                .lock_unspent(true, reservation.outpoints())
+                .await
+            {
+                warn!("Failed to unlock utxos in the wallet: {}", e);
+            }
+        }
+    }
+
+    // Shares the quota ledger with the services of other rollups funded by the same wallet
+    pub fn with_quota_ledger(mut self, quota_ledger: Arc<Mutex<QuotaLedger>>) -> Self {
+        self.quota_ledger = quota_ledger;
+        self
+    }
+
+    // Usage of every namespace accounted in the quota ledger, today and since startup
+    pub fn namespace_usage(
+        &self,
+    ) -> Result<Vec<(String, NamespaceUsage, NamespaceUsage)>, anyhow::Error> {
+        let ledger = self.quota_ledger()?;
+        let now = unix_now();
+
+        Ok(ledger
+            .totals()
+            .into_iter()
+            .map(|(namespace, total)| {
+                let today = ledger.usage_today(&namespace, now);
+                (namespace, today, total)
+            })
+            .collect())
+    }
+
+    // Sends the signed commit and the reveal tx, returns the reveal txid
+    async fn broadcast_inscription(
+        &self,
+        signed_commit_tx: &bitcoin::Transaction,
+        commit_input_total: u64,
+        reveal_tx: &bitcoin::Transaction,
+        batch: Option<(Vec<u8>, u64)>,
+        commit_key_index: Option<u32>,
+    ) -> Result<Txid, anyhow::Error> {
+        self.broadcast_commit(
+            signed_commit_tx,
+            commit_input_total,
+            reveal_tx,
+            Some(commit_key_index),
+        )
+        .await?;
+
+        // send reveal tx, on failure the pair is left for a third party service to broadcast
+        let reveal_tx_hash = match self.broadcast(reveal_tx, TxPurpose::Reveal).await {
+            Ok(reveal_tx_hash) => reveal_tx_hash,
+            Err(e) => {
+                self.write_handoff(signed_commit_tx, reveal_tx, commit_input_total);
+                return Err(e);
+            }
+        };
+
+        info!("Blob inscribe tx sent. Hash: {}", reveal_tx_hash);
+
+        // the node has the reveal tx, recover_reveals has nothing left to do for it
+        if let Err(e) = self.tx_store.remove_reveal(&signed_commit_tx.txid()) {
+            error!(
+                "Failed to remove reveal tx {} from the tx store: {}",
+                reveal_tx_hash, e
+            );
+        }
+
+        TxAuditRecord::new(
+            reveal_tx,
+            TxPurpose::Reveal,
+            AuditEvent::Broadcast,
+            signed_commit_tx.output[0].value,
+        )
+        .log();
+
+        let reveal_txid = Txid::from_str(reveal_tx_hash.as_str())
+            .expect("Failed to parse txid from reveal tx hash");
+
+        if let Err(e) = self.track_submission(reveal_txid).await {
+            error!("Failed to track inclusion of {}: {}", reveal_txid, e);
+        }
+
+        if let Some((sender, batch_number)) = batch {
+            // the blob is already sent, a failure to record it must not fail the submission
+            if let Err(e) = self
+                .batch_registry
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Batch registry lock is poisoned"))
+                .and_then(|mut registry| {
+                    registry.record_submission(
+                        &sender,
+                        batch_number,
+                        signed_commit_tx.txid(),
+                        reveal_txid,
+                    )
+                })
+            {
+                error!("Failed to record batch {}: {}", batch_number, e);
+            }
+        }
+
+        if let Some(webhook_url) = &self.webhook_url {
+            self.watch_blob(reveal_txid, webhook_url.clone());
+        }
+
+        Ok(reveal_txid)
+    }
+
+    // Sends the signed commit tx, the fees of the pair are spent once it is out
+    // With store_reveal, the reveal tx is kept in the tx store for recover_reveals, with the
+    // index of its commit key if the key was derived
+    async fn broadcast_commit(
+        &self,
+        signed_commit_tx: &bitcoin::Transaction,
+        commit_input_total: u64,
+        reveal_tx: &bitcoin::Transaction,
+        store_reveal: Option<Option<u32>>,
+    ) -> Result<(), anyhow::Error> {
+        // never broadcast a commit whose output the reveal cannot spend
+        simulate_reveal_spend(reveal_tx, signed_commit_tx)?;
+
+        // the fees are spent once the commit is out, whether the reveal follows or not
+        let bytes: u64 = parse_inscriptions(reveal_tx, &self.rollup_name)
+            .unwrap_or_default()
+            .iter()
+            .map(|inscription| inscription.body.len() as u64)
+            .sum();
+        let fee = commit_input_total
+            .saturating_sub(signed_commit_tx.output[1..].iter().map(|o| o.value).sum())
+            .saturating_sub(reveal_tx.output.iter().map(|o| o.value).sum());
+        self.quota_ledger()?
+            .check(&self.rollup_name, bytes, fee, unix_now())?;
+
+        // the reveal tx is stored first, it can still be broadcast if something goes wrong
+        // once the commit tx is out
+        if let Some(commit_key_index) = store_reveal {
+            self.tx_store
+                .put_reveal(&signed_commit_tx.txid(), reveal_tx, commit_key_index)?;
+        }
+
+        self.broadcast(signed_commit_tx, TxPurpose::Commit).await?;
+
+        self.quota_ledger()?
+            .record(&self.rollup_name, bytes, fee, unix_now());
+
+        TxAuditRecord::new(
+            signed_commit_tx,
+            TxPurpose::Commit,
+            AuditEvent::Broadcast,
+            commit_input_total,
+        )
+        .log();
+
+        Ok(())
+    }
+
+    // Writes the commit and reveal pair as an ord compatible handoff next to the reveal archive
+    fn write_handoff(
+        &self,
+        signed_commit_tx: &bitcoin::Transaction,
+        reveal_tx: &bitcoin::Transaction,
+        commit_input_total: u64,
+    ) {
+        let handoff = InscriptionHandoff::new(
+            signed_commit_tx,
+            reveal_tx,
+            commit_input_total,
+            &self.rollup_name,
+        );
+        let path = self
+            .reveal_tx_dir
+            .join(format!("handoff_{}.json", handoff.reveal));
+
+        match handoff
+            .to_json()
+            .map_err(anyhow::Error::from)
+            .and_then(|json| Ok(std::fs::write(&path, json)?))
+        {
+            Ok(()) => warn!(
+                "Reveal tx {} was not broadcast, handoff written to {}",
+                handoff.reveal,
+                path.display()
+            ),
+            Err(e) => error!("Failed to write handoff of {}: {}", handoff.reveal, e),
+        }
+    }
+
+    // Sends tx through the broadcast log, the intent is persisted before the node sees the tx
+    async fn broadcast(
+        &self,
+        tx: &bitcoin::Transaction,
+        purpose: TxPurpose,
+    ) -> Result<String, anyhow::Error> {
+        let raw_tx: String = encode::serialize(tx).encode_hex();
+
+        self.broadcast_log
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Broadcast log lock is poisoned"))?
+            .record_intent(BroadcastIntent {
+                txid: tx.txid(),
+                raw_tx: raw_tx.clone(),
+                purpose,
+            })?;
+
+        let txid = self.client.send_raw_transaction(raw_tx).await?;
+
+        // the tx is already sent, a pending intent is only broadcast again at startup
+        if let Err(e) = self
+            .broadcast_log
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Broadcast log lock is poisoned"))
+            .and_then(|mut log| log.record_done(tx.txid()))
+        {
+            error!("Failed to mark the broadcast of {} as done: {}", txid, e);
+        }
+
+        Ok(txid)
+    }
+
+    // Broadcasts the pending intents of the log again, in their original order
+    // Txs the node accepts or already confirmed are marked done, rejected ones stay pending
+    pub async fn reconcile_broadcasts(
+        &self,
+    ) -> Result<Vec<(BroadcastIntent, BroadcastStatus)>, anyhow::Error> {
+        let pending = self
+            .broadcast_log
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Broadcast log lock is poisoned"))?
+            .pending()
+            .to_vec();
+
+        let mut outcomes = Vec::with_capacity(pending.len());
+        for intent in pending {
+            let status = match self
+                .client
+                .send_raw_transaction(intent.raw_tx.clone())
+                .await
+            {
+                Ok(_) => BroadcastStatus::Accepted,
+                Err(error) => match error.downcast_ref::<RPCError>() {
+                    Some(error) if error.code == RPC_VERIFY_ALREADY_IN_CHAIN => {
+                        BroadcastStatus::AlreadyConfirmed
+                    }
+                    _ => BroadcastStatus::Rejected(error.to_string()),
+                },
+            };
+
+            if !matches!(status, BroadcastStatus::Rejected(_)) {
+                self.broadcast_log
+                    .lock()
+                    .map_err(|_| anyhow::anyhow!("Broadcast log lock is poisoned"))?
+                    .record_done(intent.txid)?;
+            }
+
+            outcomes.push((intent, status));
+        }
+
+        Ok(outcomes)
+    }
+
+    // Broadcasts the reveal txs in the tx store again, e.g. after a crash between the
+    // broadcast of a commit tx and the one of its reveal tx. Accepted and confirmed ones are
+    // removed, and so are ones whose confirmed commit output is already spent, by the reveal
+    // tx or a recovery tx. Other rejected ones are kept, e.g. a commit tx that never made it
+    // out is rejected
+    pub async fn recover_reveals(&self) -> Result<Vec<(Txid, BroadcastStatus)>, anyhow::Error> {
+        let mut outcomes = vec![];
+        for (commit_txid, reveal_tx) in self.tx_store.reveals()? {
+            let status = match self
+                .client
+                .send_raw_transaction(encode::serialize(&reveal_tx).encode_hex())
+                .await
+            {
+                Ok(_) => BroadcastStatus::Accepted,
+                Err(error) => match error.downcast_ref::<RPCError>() {
+                    Some(error) if error.code == RPC_VERIFY_ALREADY_IN_CHAIN => {
+                        BroadcastStatus::AlreadyConfirmed
+                    }
+                    Some(error)
+                        if is_missing_inputs(error)
+                            && self
+                                .client
+                                .get_wallet_tx_confirmations(&commit_txid)
+                                .await?
+                                .map_or(false, |confirmations| confirmations > 0) =>
+                    {
+                        BroadcastStatus::InputsSpent
+                    }
+                    _ => BroadcastStatus::Rejected(error.to_string()),
+                },
+            };
+
+            if !matches!(status, BroadcastStatus::Rejected(_)) {
+                self.tx_store.remove_reveal(&commit_txid)?;
+            }
+
+            outcomes.push((commit_txid, status));
+        }
+
+        Ok(outcomes)
+    }
+
+    // Commit and reveal txids of a rollup batch of sender and, once included, its height
+    pub fn get_batch(&self, sender: &[u8], batch_number: u64) -> Option<BatchRecord> {
+        self.batch_registry.lock().ok()?.get(sender, batch_number)
+    }
+
+    // Whether sender is a key of the service: the sequencer key, in any of the signature
+    // schemes, or a key the service sent batches with, e.g. the key of the signer
+    fn is_own_sender(&self, sender: &[u8], registry: &BatchRegistry) -> bool {
+        if let Some(sequencer_key) = &self.sequencer_da_private_key {
+            let secp = Secp256k1::new();
+            let public_key = sequencer_key.public_key(&secp);
+            let x_only_public_key = public_key.x_only_public_key().0;
+            if sender == public_key.serialize().as_slice()
+                || sender == x_only_public_key.serialize().as_slice()
+                || sender == ScriptBuf::new_v1_p2tr(&secp, x_only_public_key, None).as_bytes()
+            {
+                return true;
+            }
+        }
+
+        registry.has_sender(sender)
+    }
+
+    // Time to confirmation and to finality percentiles of the submissions sent at or after
+    // `since` (unix seconds), to check DA SLOs
+    pub fn inclusion_latency(&self, since: u64) -> Option<InclusionLatency> {
+        Some(self.inclusion_tracker.lock().ok()?.latency(since))
+    }
+
+    // Starts tracking the time to inclusion of a submission from the current tip
+    async fn track_submission(&self, reveal_txid: Txid) -> Result<(), anyhow::Error> {
+        let tip = self.client.get_block_count().await?;
+
+        self.inclusion_tracker
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Inclusion tracker lock is poisoned"))?
+            .record_submission(reveal_txid, unix_now(), tip)
+    }
+
+    fn track_finality(&self, finalized_height: u64, tip: u64) -> Result<(), anyhow::Error> {
+        let finalized = self
+            .inclusion_tracker
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Inclusion tracker lock is poisoned"))?
+            .record_finality(finalized_height, tip, unix_now())?;
+
+        for record in finalized {
+            info!(
+                "Blob {} finalized {}s and {} blocks after submission, confirmed after {}s",
+                record.reveal_txid,
+                record.seconds_to_finality().unwrap_or_default(),
+                record.blocks_to_finality().unwrap_or_default(),
+                record.seconds_to_confirmation().unwrap_or_default(),
+            );
+        }
+
+        Ok(())
+    }
+
+    // Funds count inscription slots for compressed bodies of up to max_body_len bytes,
+    // meant to be called while fees are low. Funding txs are broadcast right away, the slots
+    // are returned to the caller who must persist them until they are used
+    pub async fn fund_inscription_slots(
+        &self,
+        count: usize,
+        max_body_len: usize,
+        fee_sat_per_vbyte: f64,
+    ) -> Result<Vec<InscriptionSlot>, anyhow::Error> {
+        self.fee_cap.check_fee_rate(fee_sat_per_vbyte)?;
+
+        let address = self.sequencer_address()?;
+
+        let mut utxos: Vec<UTXO> = self.client.get_utxos().await?;
+        utxos.retain(|utxo| {
+            !self
+                .utxo_filters
+                .iter()
+                .any(|filter| filter.is_excluded(utxo))
+        });
+
+        let value = inscription_slot_value(
+            max_body_len,
+            &address,
+            self.reveal_value,
+            fee_sat_per_vbyte,
+            fee_sat_per_vbyte,
+        );
+
+        let mut slots = Vec::with_capacity(count);
+        for _ in 0..count {
+            match self
+                .fund_inscription_slot(&mut utxos, value, fee_sat_per_vbyte)
+                .await
+            {
+                Ok((outpoint, secret_key)) => {
+                    slots.push(InscriptionSlot {
+                        outpoint,
+                        value,
+                        secret_key,
+                        max_body_len,
+                        commit_fee_rate: fee_sat_per_vbyte,
+                        reveal_fee_rate: fee_sat_per_vbyte,
+                    });
+                }
+                // slots funded so far must be returned, their keys exist nowhere else
+                Err(e) if !slots.is_empty() => {
+                    error!(
+                        "Funded {} of {} inscription slots: {}",
+                        slots.len(),
+                        count,
+                        e
+                    );
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(slots)
+    }
+
+    // Funds a single slot from utxos, spent utxos are removed so they are not reused
+    async fn fund_inscription_slot(
+        &self,
+        utxos: &mut Vec<UTXO>,
+        value: u64,
+        fee_sat_per_vbyte: f64,
+    ) -> Result<(OutPoint, SecretKey), anyhow::Error> {
+        let change_address = self.change_address().await?;
+        let key_pair = {
+            let mut entropy = self
+                .entropy
+                .0
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Entropy source lock is poisoned"))?;
+
+            random_key_pair(&Secp256k1::new(), entropy.as_mut())
+        };
+
+        let reservations = self.utxo_reservations.lock()?;
+        let unsigned_funding_tx = build_commit_transaction(
+            reservations.available(utxos.clone()),
+            slot_address(&key_pair, self.network),
+            change_address.clone(),
+            value,
+            fee_sat_per_vbyte,
+            self.relay_policy
+                .dust_threshold(&change_address.script_pubkey()),
+            self.min_change,
+            self.coin_selection.as_ref(),
+        )?;
+        let reservation = reservations.reserve(&unsigned_funding_tx);
+
+        let input_total = utxos_total(&unsigned_funding_tx, utxos);
+        let output_total: u64 = unsigned_funding_tx.output.iter().map(|o| o.value).sum();
+        self.fee_cap.check_fee(input_total - output_total)?;
+
+        let signed_raw_funding_tx = self
+            .client
+            .sign_raw_transaction_with_wallet(encode::serialize(&unsigned_funding_tx).encode_hex())
+            .await?;
+        let signed_funding_tx: bitcoin::Transaction =
+            encode::deserialize(&hex::decode(&signed_raw_funding_tx)?)?;
+
+        TxAuditRecord::new(
+            &signed_funding_tx,
+            TxPurpose::Slot,
+            AuditEvent::Signed,
+            input_total,
+        )
+        .log();
+
+        self.lock_wallet_utxos(&reservation).await?;
+        let result = self.broadcast(&signed_funding_tx, TxPurpose::Slot).await;
+        self.unlock_wallet_utxos(&reservation).await;
+        result?;
+
+        TxAuditRecord::new(
+            &signed_funding_tx,
+            TxPurpose::Slot,
+            AuditEvent::Broadcast,
+            input_total,
+        )
+        .log();
+
+        utxos.retain(|utxo| {
+            !unsigned_funding_tx.input.iter().any(|input| {
+                input.previous_output.txid == utxo.tx_id && input.previous_output.vout == utxo.vout
+            })
+        });
+
+        Ok((
+            OutPoint {
+                txid: signed_funding_tx.txid(),
+                vout: 0,
+            },
+            key_pair.secret_key(),
+        ))
+    }
+
+    // Sweeps the wallet utxos of up to max_amount sats, e.g. the reveal outputs paid to the
+    // sequencer address, into a single output to the sequencer address. Meant to be called
+    // while fees are low, utxos not worth their input at fee_sat_per_vbyte are kept
+    pub async fn consolidate_utxos(
+        &self,
+        max_amount: u64,
+        fee_sat_per_vbyte: f64,
+    ) -> Result<Txid, anyhow::Error> {
+        self.fee_cap.check_fee_rate(fee_sat_per_vbyte)?;
+
+        let mut utxos: Vec<UTXO> = self.client.get_utxos().await?;
+        utxos.retain(|utxo| {
+            utxo.amount <= max_amount
+                && !self
+                    .utxo_filters
+                    .iter()
+                    .any(|filter| filter.is_excluded(utxo))
+        });
+
+        let reservations = self.utxo_reservations.lock()?;
+        let utxos = reservations.available(utxos);
+        let unsigned_consolidation_tx =
+            build_consolidation_transaction(&utxos, self.sequencer_address()?, fee_sat_per_vbyte)?;
+        let reservation = reservations.reserve(&unsigned_consolidation_tx);
+
+        let input_total = utxos_total(&unsigned_consolidation_tx, &utxos);
+        self.fee_cap
+            .check_fee(input_total - unsigned_consolidation_tx.output[0].value)?;
+
+        let signed_raw_consolidation_tx = self
+            .client
+            .sign_raw_transaction_with_wallet(
+                encode::serialize(&unsigned_consolidation_tx).encode_hex(),
+            )
+            .await?;
+        let signed_consolidation_tx: bitcoin::Transaction =
+            encode::deserialize(&hex::decode(&signed_raw_consolidation_tx)?)?;
+
+        TxAuditRecord::new(
+            &signed_consolidation_tx,
+            TxPurpose::Consolidation,
+            AuditEvent::Signed,
+            input_total,
+        )
+        .log();
+
+        self.lock_wallet_utxos(&reservation).await?;
+        let result = self
+            .broadcast(&signed_consolidation_tx, TxPurpose::Consolidation)
+            .await;
+        self.unlock_wallet_utxos(&reservation).await;
+        result?;
+
+        TxAuditRecord::new(
+            &signed_consolidation_tx,
+            TxPurpose::Consolidation,
+            AuditEvent::Broadcast,
+            input_total,
+        )
+        .log();
+
+        Ok(signed_consolidation_tx.txid())
+    }
+
+    // Posts a cheap tx committing to the hash of the blob with an OP_RETURN output, see
+    // spec::precommitment. The blob is inscribed later with send_transaction_with_precommitment,
+    // e.g. once fees are lower, and the rollup can order it at the block of the pre-commitment
+    pub async fn precommit_blob(
+        &self,
+        blob: &[u8],
+        fee_sat_per_vbyte: f64,
+    ) -> Result<Txid, anyhow::Error> {
+        self.fee_cap.check_fee_rate(fee_sat_per_vbyte)?;
+
+        // the inscription compresses the blob the same way, so its body has this hash
+        let body = self.compress(blob, &mut InscriptionMetadata::default())?;
+        let blob_hash = sha256d::Hash::hash(&body).to_byte_array();
+
+        let mut utxos: Vec<UTXO> = self.client.get_utxos().await?;
+        utxos.retain(|utxo| {
+            !self
+                .utxo_filters
+                .iter()
+                .any(|filter| filter.is_excluded(utxo))
+        });
+        let change_address = self.change_address().await?;
+        let dust_limit = self
+            .relay_policy
+            .dust_threshold(&change_address.script_pubkey());
+
+        let reservations = self.utxo_reservations.lock()?;
+        let utxos = reservations.available(utxos);
+        let unsigned_precommitment_tx = build_precommitment_transaction(
+            utxos.clone(),
+            blob_hash,
+            change_address,
+            fee_sat_per_vbyte,
+            dust_limit,
+            self.coin_selection.as_ref(),
+        )?;
+        let reservation = reservations.reserve(&unsigned_precommitment_tx);
+
+        let input_total = utxos_total(&unsigned_precommitment_tx, &utxos);
+        let output_total: u64 = unsigned_precommitment_tx
+            .output
+            .iter()
+            .map(|output| output.value)
+            .sum();
+        self.fee_cap.check_fee(input_total - output_total)?;
+
+        let signed_raw_precommitment_tx = self
+            .client
+            .sign_raw_transaction_with_wallet(
+                encode::serialize(&unsigned_precommitment_tx).encode_hex(),
+            )
+            .await?;
+        let signed_precommitment_tx: bitcoin::Transaction =
+            encode::deserialize(&hex::decode(&signed_raw_precommitment_tx)?)?;
+
+        TxAuditRecord::new(
+            &signed_precommitment_tx,
+            TxPurpose::Precommitment,
+            AuditEvent::Signed,
+            input_total,
+        )
+        .log();
+
+        self.lock_wallet_utxos(&reservation).await?;
+        let result = self
+            .broadcast(&signed_precommitment_tx, TxPurpose::Precommitment)
+            .await;
+        self.unlock_wallet_utxos(&reservation).await;
+        result?;
+
+        TxAuditRecord::new(
+            &signed_precommitment_tx,
+            TxPurpose::Precommitment,
+            AuditEvent::Broadcast,
+            input_total,
+        )
+        .log();
+
+        Ok(signed_precommitment_tx.txid())
+    }
+
+    // Inscribes a blob pre-committed to by precommit_blob, its envelope names the txid of the
+    // pre-commitment so the rollup can match the two
+    pub async fn send_transaction_with_precommitment(
+        &self,
+        blob: &[u8],
+        precommitment: Txid,
+        fee_sat_per_vbyte: f64,
+    ) -> Result<Txid, anyhow::Error> {
+        self.send_sequencer_blob(
+            blob,
+            fee_sat_per_vbyte,
+            InscriptionMetadata {
+                precommitment: Some(precommitment.to_byte_array()),
+                ..Default::default()
+            },
+        )
+        .await
+    }
+
+    // Sends the blob through a previously funded slot, at the fee rates the slot was funded for
+    pub async fn send_transaction_with_slot(
+        &self,
+        blob: &[u8],
+        slot: &InscriptionSlot,
+        batch_number: Option<u64>,
+    ) -> Result<Txid, anyhow::Error> {
+        let (reveal_txid, _) = self
+            .send_through_slot(blob, slot, batch_number, false)
+            .await?;
+
+        Ok(reveal_txid)
+    }
+
+    // Sends the blob through the slot and funds the next slot with the reveal output, so
+    // a sequencer can keep inscribing from a single output without waiting for confirmations.
+    // The next slot is returned while it can still pay for a body of max_body_len bytes,
+    // after that the last reveal pays the sequencer address and None is returned.
+    // The chain is a line of unconfirmed txs: it stops before the mempool ancestor limit,
+    // and bumping the fee of any of its txs (RBF) evicts every tx after it
+    pub async fn send_transaction_chained(
+        &self,
+        blob: &[u8],
+        slot: &InscriptionSlot,
+        batch_number: Option<u64>,
+    ) -> Result<(Txid, Option<InscriptionSlot>), anyhow::Error> {
+        // the slot tx and its ancestors, plus the commit and the reveal
+        if let Some(ancestors) = self
+            .client
+            .get_mempool_ancestor_count(&slot.outpoint.txid)
+            .await?
+        {
+            if ancestors + 2 > MEMPOOL_ANCESTOR_LIMIT {
+                return Err(anyhow::anyhow!(
+                    "Slot {} has {} unconfirmed ancestors, the chain must wait for a confirmation",
+                    slot.outpoint,
+                    ancestors
+                ));
+            }
+        }
+
+        self.send_through_slot(blob, slot, batch_number, true).await
+    }
+
+    async fn send_through_slot(
+        &self,
+        blob: &[u8],
+        slot: &InscriptionSlot,
+        batch_number: Option<u64>,
+        chain: bool,
+    ) -> Result<(Txid, Option<InscriptionSlot>), anyhow::Error> {
+        let network = self.network;
+        let address = self.sequencer_address()?;
+
+        let mut metadata = InscriptionMetadata {
+            batch_number,
+            ..Default::default()
+        };
+        let blob = self.compress(blob, &mut metadata)?;
+        if blob.len() > slot.max_body_len {
+            return Err(anyhow::anyhow!(
+                "Compressed blob of {} bytes does not fit the slot of {} bytes",
+                blob.len(),
+                slot.max_body_len
+            ));
+        }
+        let SignedBlob {
+            body: blob,
+            signature,
+            public_key,
+            metadata,
+        } = self.sign_blob(blob, metadata).await?;
+        let batch = metadata
+            .batch_number
+            .map(|batch_number| (public_key.clone(), batch_number));
+
+        let slot_utxo = slot.utxo(network);
+        let slot_output = TxOut {
+            value: slot.value,
+            script_pubkey: slot.address(network).script_pubkey(),
+        };
+
+        // the reveal output funds the next slot while it is large enough for one
+        let next_slot_value = chained_reveal_value(
+            slot.value,
+            blob.len(),
+            network,
+            slot.commit_fee_rate,
+            slot.reveal_fee_rate,
+        )
+        .filter(|value| {
+            chain
+                && *value
+                    >= inscription_slot_value(
+                        slot.max_body_len,
+                        &address,
+                        self.reveal_value,
+                        slot.commit_fee_rate,
+                        slot.reveal_fee_rate,
+                    )
+        });
+        let next_secret_key = slot.next_secret_key();
+        let (recipient, reveal_value) = match next_slot_value {
+            Some(value) => (
+                slot_address(
+                    &KeyPair::from_secret_key(&Secp256k1::new(), &next_secret_key),
+                    network,
+                ),
+                value,
+            ),
+            None => (address.clone(), self.reveal_value),
+        };
+
+        // create inscribe transactions, the commit spends the slot by key path
+        let signer_commit_key = self.signer_commit_key().await?;
+        let change_address = self.change_address().await?;
+        let builder = InscriptionBuilder::new(&self.rollup_name)
+            .with_body(blob)
+            .with_signature(signature, public_key)
+            .with_metadata(metadata)
+            .with_utxos(vec![slot_utxo])
+            .with_coin_selection(self.coin_selection.clone())
+            .with_recipient(recipient)
+            .with_dust_limit(
+                self.relay_policy
+                    .dust_threshold(&change_address.script_pubkey()),
+            )
+            .with_change_address(change_address)
+            .with_reveal_value(reveal_value)
+            .with_fee_rates(slot.commit_fee_rate, slot.reveal_fee_rate)
+            .with_fee_cap(self.fee_cap)
+            .with_network(network)
+            .with_internal_key(self.commit_internal_key)
+            .with_pow_prefix(&self.reveal_tx_id_prefix)
+            .with_pow_zero_bits(self.reveal_tx_id_zero_bits)
+            .with_skip_pow(!self.chain_params().requires_reveal_pow())
+            .with_reveal_sighash_type(self.reveal_sighash_type)
+            .with_grind_progress(GRIND_PROGRESS_ATTEMPTS, &log_grind_progress)
+            .with_grind_cancellation(self.grind_cancellation());
+        let builder = match self.min_change {
+            Some(min_change) => builder.with_min_change(min_change),
+            None => builder,
+        };
+        let builder = match self.lock_time_height().await? {
+            Some(tip_height) => builder.with_tip_height(tip_height),
+            None => builder,
+        };
+
+        let (commit_tx, reveal_tx, commit_key_index) = match signer_commit_key {
+            Some(commit_public_key) => {
+                let (commit_tx, reveal_tx) = self
+                    .sign_reveal(builder.build_unsigned(commit_public_key)?)
+                    .await?;
+                (commit_tx, reveal_tx, None)
+            }
+            None => {
+                let commit_key = self.next_commit_key()?;
+                let mut entropy = self
+                    .entropy
+                    .0
+                    .lock()
+                    .map_err(|_| anyhow::anyhow!("Entropy source lock is poisoned"))?;
+
+                let mut builder = builder.with_entropy(entropy.as_mut());
+                if let Some((_, commit_key)) = commit_key {
+                    builder = builder.with_commit_key(commit_key);
+                }
+                let (commit_tx, reveal_tx) = builder.build()?;
+                (commit_tx, reveal_tx, commit_key.map(|(index, _)| index))
+            }
+        };
+
+        // the commit tx does not commit to its witness, signing it keeps the reveal valid
+        let commit_tx = {
+            let mut commit_tx = commit_tx;
+            let mut entropy = self
+                .entropy
+                .0
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Entropy source lock is poisoned"))?;
+
+            sign_input(
+                &mut commit_tx,
+                0,
+                &[slot_output],
+                &slot.secret_key,
+                entropy.as_mut(),
+            )?;
+
+            commit_tx
+        };
+
+        audit_signed_inscription(&commit_tx, slot.value, &reveal_tx);
+
+        let reveal_txid = self
+            .broadcast_inscription(&commit_tx, slot.value, &reveal_tx, batch, commit_key_index)
+            .await?;
+
+        let next_slot = next_slot_value.map(|value| InscriptionSlot {
+            outpoint: OutPoint {
+                txid: reveal_txid,
+                vout: 0,
+            },
+            value,
+            secret_key: next_secret_key,
+            max_body_len: slot.max_body_len,
+            commit_fee_rate: slot.commit_fee_rate,
+            reveal_fee_rate: slot.reveal_fee_rate,
+        });
+
+        Ok((reveal_txid, next_slot))
+    }
+
+    // First round of a MuSig2 session of the sequencer committee: the public nonce is sent
+    // to the coordinator, the secret nonce is kept for signing and must not be reused
+    pub fn musig_nonce(&self) -> Result<(SecretNonce, PublicNonce), anyhow::Error> {
+        let sequencer_key = self.sequencer_key()?;
+        let mut entropy = self
+            .entropy
+            .0
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Entropy source lock is poisoned"))?;
+
+        Ok(SecretNonce::generate(
+            &Secp256k1::new(),
+            entropy.as_mut(),
+            &sequencer_key,
+            None,
+            None,
+        )?)
+    }
+
+    // Metadata of the blobs signed by the committee, every member derives the same one
+    // The aggregate key is x-only, so the Schnorr scheme is not tagged
+    fn musig_metadata(&self) -> InscriptionMetadata {
+        InscriptionMetadata {
+            params_fingerprint: Some(self.params_fingerprint()),
+            ..Default::default()
+        }
+    }
+
+    // Hash the committee signs for the brotli compressed blob
+    fn musig_message(&self, body: &[u8]) -> [u8; 32] {
+        sha256d::Hash::hash(&blob_signing_message(body, &self.musig_metadata(), [])).to_byte_array()
+    }
+
+    // Second round of a MuSig2 session: signs the blob with the sequencer key
+    pub fn musig_partial_sign(
+        &self,
+        blob: &[u8],
+        secret_nonce: SecretNonce,
+        key_agg: &KeyAggContext,
+        aggregate_nonce: &AggregateNonce,
+    ) -> Result<PartialSignature, anyhow::Error> {
+        let message = self.musig_message(&try_compress_blob(blob, CompressionAlgorithm::Brotli)?);
+
+        Ok(partial_sign(
+            &Secp256k1::new(),
+            secret_nonce,
+            &self.sequencer_key()?,
+            key_agg,
+            aggregate_nonce,
+            &message,
+        )?)
+    }
+
+    // Sends the blob signed by the whole committee, the aggregate key is the blob sender
+    // Nonces and partial signatures are given in the order of the committee public keys
+    pub async fn send_transaction_with_musig(
+        &self,
+        blob: &[u8],
+        key_agg: &KeyAggContext,
+        public_nonces: &[PublicNonce],
+        partial_signatures: &[PartialSignature],
+        fee_sat_per_vbyte: f64,
+    ) -> Result<Txid, anyhow::Error> {
+        self.check_batch_number(&self.musig_metadata())?;
+        let blob = try_compress_blob(blob, CompressionAlgorithm::Brotli)?;
+        let message = self.musig_message(&blob);
+
+        let signature = aggregate_partial_signatures(
+            &Secp256k1::new(),
+            key_agg,
+            public_nonces,
+            partial_signatures,
+            &message,
+        )?;
+
+        self.send_signed_blob(
+            blob,
+            signature.as_ref().to_vec(),
+            key_agg.aggregate_public_key().serialize().to_vec(),
+            fee_sat_per_vbyte,
+            self.musig_metadata(),
+            vec![],
+        )
+        .await
+    }
+
+    // Watches the reveal tx of a blob in the background and notifies the given webhook url
+    // when the blob is included in a block and when that block is finalized
+    pub fn watch_blob(&self, reveal_txid: Txid, webhook_url: String) {
+        let service = self.clone();
+        let Some(webhook_key) = self.webhook_key.or(self.sequencer_da_private_key) else {
+            error!("Cannot sign webhook notifications without a webhook key in watch-only mode");
+            return;
+        };
+        let notifier = WebhookNotifier::new(webhook_key);
+
+        tokio::spawn(async move {
+            if let Err(e) = service
+                .watch_blob_confirmations(&notifier, &webhook_url, reveal_txid)
+                .await
+            {
+                error!("Failed to notify webhook for tx {}: {}", reveal_txid, e);
+            }
+        });
+    }
+
+    async fn watch_blob_confirmations(
+        &self,
+        notifier: &WebhookNotifier,
+        webhook_url: &str,
+        reveal_txid: Txid,
+    ) -> Result<(), anyhow::Error> {
+        let mut height = self.client.get_block_count().await?;
+
+        loop {
+            if height > self.client.get_block_count().await? {
+                // the tx is in none of the blocks so far, it must still be in the mempool
+                let in_mempool = self.client.get_raw_mempool().await?.contains(&reveal_txid);
+                // unless it was mined meanwhile
+                if !in_mempool && height > self.client.get_block_count().await? {
+                    return Err(anyhow::anyhow!(
+                        "Reveal tx {} was dropped from the mempool",
+                        reveal_txid
+                    ));
+                }
+                tokio::time::sleep(Duration::from_secs(POLLING_INTERVAL)).await;
+                continue;
+            }
+
+            let block = self.get_block_at(height).await?;
+
+            if !block.txdata.iter().any(|tx| tx.txid() == reveal_txid) {
+                height += 1;
+                continue;
+            }
+
+            let block_hash = block.header.block_hash().to_string();
+
+            notifier
+                .notify(
+                    webhook_url,
+                    &BlobStatusNotification {
+                        txid: reveal_txid,
+                        status: BlobStatus::Included,
+                        block_hash: block_hash.clone(),
+                        height,
+                    },
+                )
+                .await?;
+
+            // wait until the block is FINALITY_DEPTH deep
+            while self.client.get_block_count().await? < height + FINALITY_DEPTH {
+                tokio::time::sleep(Duration::from_secs(POLLING_INTERVAL)).await;
+            }
+
+            // the block may have been reorged out while waiting, look for the tx again
+            if self.client.get_block_hash(height).await? != block_hash {
+                continue;
+            }
+
+            notifier
+                .notify(
+                    webhook_url,
+                    &BlobStatusNotification {
+                        txid: reveal_txid,
+                        status: BlobStatus::Finalized,
+                        block_hash,
+                        height,
+                    },
+                )
+                .await?;
+
+            return Ok(());
+        }
+    }
+
+    // Packages everything the verifier needs for the block at the given height
+    pub async fn build_proof_input(&self, height: u64) -> Result<ProofInput, anyhow::Error> {
+        let block = self.get_block_at(height).await?;
+
+        let (blobs, inclusion_proof, completeness_proof) =
+            self.extract_relevant_blobs_with_proof(&block).await;
+
+        Ok(ProofInput {
+            header: block.header,
+            blobs,
+            inclusion_proof,
+            completeness_proof,
+            batch_sequence: self.batch_sequence_before(height),
+        })
+    }
+
+    // Fetches a blob by its hash (sha256d of the inscribed body) from the block the index
+    // points to. Blobs missing from the index are searched in the last blob_rescan_depth blocks
+    pub async fn get_blob_by_hash(
+        &self,
+        blob_hash: [u8; 32],
+    ) -> Result<Option<(u64, Txid, BlobWithSender)>, anyhow::Error> {
+        let location = self
+            .blob_index
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Blob index lock is poisoned"))?
+            .get(&blob_hash);
+
+        if let Some(location) = location {
+            let block = self.get_block_at(location.height).await?;
+
+            return Ok(self.find_blob_in_block(&block, blob_hash));
+        }
+
+        let Some(rescan_depth) = self.blob_rescan_depth else {
+            return Ok(None);
+        };
+
+        let block_count = self.client.get_block_count().await?;
+
+        for height in (block_count.saturating_sub(rescan_depth)..=block_count).rev() {
+            let block = self.get_block_at(height).await?;
+
+            if let Some(blob) = self.find_blob_in_block(&block, blob_hash) {
+                return Ok(Some(blob));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn find_blob_in_block(
+        &self,
+        block: &BitcoinBlock,
+        blob_hash: [u8; 32],
+    ) -> Option<(u64, Txid, BlobWithSender)> {
+        let (tx, blob) = self
+            .read_relevant_blobs(block)
+            .into_iter()
+            .find(|(_, blob)| blob.hash == blob_hash)?;
+
+        let location = BlobLocation {
+            height: block.header.height,
+            txid: tx.txid(),
+        };
+        if let Ok(mut blob_index) = self.blob_index.lock() {
+            blob_index.insert(blob_hash, location);
+        }
+
+        Some((location.height, location.txid, blob))
+    }
+
+    // Relevant blobs of the block carrying the given kind of artifact
+    pub fn extract_relevant_blobs_of_kind(
+        &self,
+        block: &BitcoinBlock,
+        kind: BlobKind,
+    ) -> Vec<BlobWithSender> {
+        self.read_relevant_blobs(block)
+            .into_iter()
+            .map(|(_, blob)| blob)
+            .filter(|blob| blob.kind() == kind)
+            .collect()
+    }
+
+    // Checkpoints of the rollup in the block, in block order
+    pub fn extract_checkpoints(&self, block: &BitcoinBlock) -> Vec<Checkpoint> {
+        self.extract_relevant_blobs_of_kind(block, BlobKind::Checkpoint)
+            .iter()
+            .filter_map(|blob| Checkpoint::from_bytes(blob.full_content()))
+            .collect()
+    }
+
+    // Blobs of the rollup in the block with their reveal txs, nothing is indexed and the
+    // batch sequence of the service is left as is
+    fn read_relevant_blobs<'a>(
+        &self,
+        block: &'a BitcoinBlock,
+    ) -> Vec<(&'a bitcoin::Transaction, BlobWithSender)> {
+        let mut batch_sequence = self.batch_sequence_before(block.header.height);
+        self.find_relevant_blobs(block, batch_sequence.as_mut())
+    }
+
+    // Blobs of the rollup in the block with their reveal txs, nothing is indexed
+    // The batch sequence, if batch numbers must increase, is advanced past the block
+    fn find_relevant_blobs<'a>(
+        &self,
+        block: &'a BitcoinBlock,
+        mut batch_sequence: Option<&mut BatchSequence>,
+    ) -> Vec<(&'a bitcoin::Transaction, BlobWithSender)> {
+        let mut blobs = Vec::new();
+        let params_fingerprint = self.params_fingerprint();
+
+        // iterate over the relevant transactions of the block
+        for tx in self.relevant_txs(block) {
+            // check if the inscriptions in script are relevant to the rollup
+            // a batched reveal tx carries one blob per envelope
+            let parsed_inscriptions = parse_inscriptions(tx, &self.rollup_name).unwrap_or_default();
 
-        let private_key =
-            SecretKey::from_str(&config.sequencer_da_private_key.unwrap_or("".to_owned()))
-                .expect("Invalid private key");
+            for inscription in parsed_inscriptions {
+                // the verifier drops these blobs too, the sender runs with other chain params
+                if !inscription.metadata.matches_params(params_fingerprint) {
+                    error!(
+                        "Blob in tx {} was sent with chain params {} but this service uses {}: \
+                         check the rollup name, reveal txid prefix and version of both sides",
+                        tx.txid(),
+                        hex::encode(inscription.metadata.params_fingerprint.unwrap_or_default()),
+                        hex::encode(params_fingerprint)
+                    );
+                    continue;
+                }
 
-        Self::with_client(
-            client,
-            chain_params.rollup_name,
-            network,
-            address,
-            private_key,
-            chain_params.reveal_tx_id_prefix,
-        )
-        .await
-    }
+                if inscription.get_sig_verified_hash().is_some() {
+                    // the verifier drops blobs that do not decompress as well
+                    let decompressed_blob =
+                        match inscription.decompressed_body_with_limit(self.max_decompressed_len) {
+                            Ok(decompressed_blob) => decompressed_blob,
+                            Err(e) => {
+                                error!("Blob in tx {} is rejected: {}", tx.txid(), e);
+                                continue;
+                            }
+                        };
 
-    pub async fn with_client(
-        client: BitcoinNode,
-        rollup_name: String,
-        network: bitcoin::Network,
-        address: Address<NetworkUnchecked>,
-        sequencer_da_private_key: SecretKey,
-        reveal_tx_id_prefix: Vec<u8>,
-    ) -> Self {
-        // We can't store address with the network check because it's not serializable
-        address
-            .clone()
-            .require_network(network)
-            .expect("Invalid address for network!");
+                    // the verifier drops replayed batches as well
+                    if let Some(batch_sequence) = batch_sequence.as_mut() {
+                        if !batch_sequence.accept(
+                            &inscription.public_key,
+                            inscription.metadata.batch_number,
+                            inscription.metadata.kind.unwrap_or_default(),
+                        ) {
+                            warn!(
+                                "Blob in tx {} is rejected: batch {:?} is not above batch {:?} \
+                                 of its sender",
+                                tx.txid(),
+                                inscription.metadata.batch_number,
+                                batch_sequence.last(&inscription.public_key)
+                            );
+                            continue;
+                        }
+                    }
 
-        let wallets = client
-            .list_wallets()
-            .await
-            .expect("Failed to list loaded wallets");
+                    let relevant_tx = BlobWithSender::new(
+                        decompressed_blob,
+                        inscription.public_key,
+                        sha256d::Hash::hash(&inscription.body).to_byte_array(),
+                    )
+                    .with_metadata(inscription.metadata);
 
-        if wallets.is_empty() {
-            panic!("No loaded wallet found!");
+                    blobs.push((tx, relevant_tx));
+                }
+            }
         }
 
-        Self {
-            client,
-            rollup_name,
-            network,
-            address,
-            sequencer_da_private_key,
-            reveal_tx_id_prefix,
-        }
+        blobs
     }
 
-    pub async fn send_transaction_with_fee_rate(
-        &self,
-        blob: &[u8],
-        fee_sat_per_vbyte: f64,
-    ) -> Result<<Self as DaService>::TransactionId, anyhow::Error> {
-        let client = self.client.clone();
-
-        let blob = blob.to_vec();
-        let network = self.network;
-        let address = self
-            .address
-            .clone()
-            .require_network(network)
-            .expect("Invalid network for address");
-        let rollup_name = self.rollup_name.clone();
-        let sequencer_da_private_key = self.sequencer_da_private_key;
+    // Re-extracts and re-verifies the blobs of blocks from..=to and compares them with the
+    // blob index and the batch registry, e.g. to audit an upgrade of the parser or verifier
+    pub async fn replay(&self, from: u64, to: u64) -> Result<ReplayReport, anyhow::Error> {
+        let verifier = BitcoinVerifier::new(self.chain_params());
+        // the replay and the verifier start from the sequence the service read the block from
+        let mut replayed_sequence = self.batch_sequence_before(from);
+        let mut verified_sequence = replayed_sequence.clone();
 
-        // Compress the blob
-        let blob = compress_blob(&blob);
+        let mut report = ReplayReport::new(from, to);
 
-        // get all available utxos
-        let utxos: Vec<UTXO> = client.get_utxos().await?;
+        for height in from..=to {
+            let block = self.get_block_at(height).await?;
 
-        // sign the blob for authentication of the sequencer
-        let (signature, public_key) = sign_blob_with_private_key(&blob, &sequencer_da_private_key)
-            .expect("Sequencer sign the blob");
+            let found = self.find_relevant_blobs(&block, replayed_sequence.as_mut());
+            let blobs: Vec<BlobWithSender> = found.iter().map(|(_, blob)| blob.clone()).collect();
+            let (inclusion_proof, completeness_proof) =
+                self.get_extraction_proof(&block, &blobs).await;
 
-        // create inscribe transactions
-        let (unsigned_commit_tx, reveal_tx) = create_inscription_transactions(
-            &rollup_name,
-            blob,
-            signature,
-            public_key,
-            utxos,
-            address,
-            REVEAL_OUTPUT_AMOUNT,
-            fee_sat_per_vbyte,
-            fee_sat_per_vbyte,
-            network,
-            self.reveal_tx_id_prefix.as_slice(),
-        )?;
+            let verification = panic::catch_unwind(AssertUnwindSafe(|| {
+                verifier.verify_relevant_tx_list_from(
+                    &block.header,
+                    &blobs,
+                    inclusion_proof,
+                    completeness_proof,
+                    verified_sequence.as_mut(),
+                )
+            }));
+            match verification {
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => report.verification_failed(height, format!("{:?}", e)),
+                Err(payload) => report.verification_failed(height, panic_message(payload.as_ref())),
+            }
 
-        // sign inscribe transactions
-        let serialized_unsigned_commit_tx = &encode::serialize(&unsigned_commit_tx);
-        let signed_raw_commit_tx = client
-            .sign_raw_transaction_with_wallet(serialized_unsigned_commit_tx.encode_hex())
-            .await?;
+            let replayed: Vec<ReplayedBlob> = found
+                .iter()
+                .map(|(tx, blob)| ReplayedBlob {
+                    reveal_txid: tx.txid(),
+                    sender: blob.sender.0.clone(),
+                    blob_hash: blob.hash,
+                    batch_number: blob.metadata.batch_number,
+                })
+                .collect();
 
-        // send inscribe transactions
-        client.send_raw_transaction(signed_raw_commit_tx).await?;
+            let index = self
+                .blob_index
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Blob index lock is poisoned"))?;
+            let registry = self
+                .batch_registry
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Batch registry lock is poisoned"))?;
+            report.check_blobs(height, &replayed, &index, &registry);
+        }
 
-        // serialize reveal tx
-        let serialized_reveal_tx = &encode::serialize(&reveal_tx);
+        Ok(report)
+    }
 
-        // write reveal tx to file, it can be used to continue revealing blob if something goes wrong
-        write_reveal_tx(
-            serialized_reveal_tx,
-            unsigned_commit_tx.txid().to_raw_hash().to_string(),
-        );
+    // Heuristic probability that a tx paying fee_rate is included within the next blocks,
+    // given the current mempool of the node
+    pub async fn inclusion_probability(
+        &self,
+        fee_rate: f64,
+        blocks: u64,
+    ) -> Result<f64, anyhow::Error> {
+        let histogram = MempoolHistogram::new(self.client.get_mempool_fee_rates().await?);
 
-        // send reveal tx
-        let reveal_tx_hash = client
-            .send_raw_transaction(serialized_reveal_tx.encode_hex())
-            .await?;
+        Ok(histogram.inclusion_probability(fee_rate, blocks))
+    }
 
-        info!("Blob inscribe tx sent. Hash: {}", reveal_tx_hash);
+    // Cost of inscribing a compressed blob of blob_len bytes at the current fee rate, e.g. to
+    // check the wallet balance or to quote it before a submission
+    pub async fn estimate_inscription_cost(
+        &self,
+        blob_len: usize,
+    ) -> Result<InscriptionCost, anyhow::Error> {
+        let fee_rate = self.get_fee_rate().await?;
 
-        Ok(Txid::from_str(reveal_tx_hash.as_str())
-            .expect("Failed to parse txid from reveal tx hash"))
+        Ok(InscriptionCost {
+            reveal_value: self.reveal_value,
+            ..estimate_inscription_cost(blob_len, fee_rate, fee_rate)?
+        })
     }
 
     pub async fn get_fee_rate(&self) -> Result<f64, anyhow::Error> {
         if self.network == bitcoin::Network::Regtest {
             // sometimes local mempool is empty, node cannot estimate
-            return Ok(2.0);
+            return Ok(self.relay_policy.floor_fee_rate(2.0));
         }
 
-        self.client.estimate_smart_fee().await
+        // the node does not relay txs paying less, whatever the estimate
+        Ok(self.relay_policy.floor_fee_rate(
+            self.fee_estimator
+                .estimate_fee_rate(self.fee_conf_target)
+                .await?,
+        ))
     }
 }
 
+// Seconds since the unix epoch, 0 if the clock is set before it
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+// Whether the node rejected a tx for inputs it does not have, missing or already spent
+// Nodes before 0.21 say "Missing inputs"
+fn is_missing_inputs(error: &RPCError) -> bool {
+    error.code == RPC_VERIFY_ERROR
+        && (error.message.contains("bad-txns-inputs-missingorspent")
+            || error.message.contains("Missing inputs"))
+}
+
+fn log_grind_progress(attempts: u64) {
+    debug!("Tried {} reveal nonces", attempts);
+}
+
+// Audit records of a signed commit tx and its reveal tx, before either is broadcast
+fn audit_signed_inscription(
+    commit_tx: &bitcoin::Transaction,
+    commit_input_total: u64,
+    reveal_tx: &bitcoin::Transaction,
+) {
+    TxAuditRecord::new(
+        commit_tx,
+        TxPurpose::Commit,
+        AuditEvent::Signed,
+        commit_input_total,
+    )
+    .log();
+    TxAuditRecord::new(
+        reveal_tx,
+        TxPurpose::Reveal,
+        AuditEvent::Signed,
+        commit_tx.output[0].value,
+    )
+    .log();
+}
+
+// Sums the amounts of the utxos spent by tx
+fn utxos_total(tx: &bitcoin::Transaction, utxos: &[UTXO]) -> u64 {
+    tx.input
+        .iter()
+        .filter_map(|input| {
+            utxos.iter().find(|utxo| {
+                utxo.tx_id == input.previous_output.txid && utxo.vout == input.previous_output.vout
+            })
+        })
+        .map(|utxo| utxo.amount)
+        .sum()
+}
+
 #[async_trait]
 impl DaService for BitcoinService {
     type Spec = BitcoinSpec;
@@ -254,6 +3845,26 @@ impl DaService for BitcoinService {
 
             break;
         }
+
+        if let Some(quorum) = &self.quorum {
+            quorum.verify_block_hash(height, &block_hash).await?;
+        }
+
+        // a finalized block must be on the chain of the validated tip, not merely the
+        // block the node currently has at that height
+        let validated_tip = self.validated_tip().await?;
+        if height <= validated_tip.0
+            && !self
+                .is_ancestor_of(validated_tip, height, &block_hash)
+                .await?
+        {
+            return Err(anyhow::anyhow!(
+                "Block {} at height {} is not an ancestor of the validated tip",
+                block_hash,
+                height
+            ));
+        }
+
         let block = client.get_block(block_hash).await?;
 
         Ok(block)
@@ -265,12 +3876,35 @@ impl DaService for BitcoinService {
     ) -> Result<<Self::Spec as DaSpec>::BlockHeader, Self::Error> {
         let block_count = self.client.get_block_count().await?;
 
-        let finalized_blockhash = self
+        self.check_chain_tip(block_count).await?;
+
+        let finalized_height = block_count - FINALITY_DEPTH;
+
+        let finalized_blockhash = self.client.get_block_hash(finalized_height).await?;
+
+        if let Some(quorum) = &self.quorum {
+            quorum
+                .verify_block_hash(finalized_height, &finalized_blockhash)
+                .await?;
+        }
+
+        let finalized_block_header = self
             .client
-            .get_block_hash(block_count - FINALITY_DEPTH)
+            .get_block_header(finalized_blockhash.clone())
+            .await?;
+        if finalized_block_header.block_hash().to_string() != finalized_blockhash {
+            return Err(anyhow::anyhow!(
+                "Node returned another header for block {}",
+                finalized_blockhash
+            ));
+        }
+
+        self.validate_headers(finalized_block_header.clone())
             .await?;
 
-        let finalized_block_header = self.client.get_block_header(finalized_blockhash).await?;
+        if let Err(e) = self.track_finality(finalized_height, block_count) {
+            error!("Failed to track finality at {}: {}", finalized_height, e);
+        }
 
         Ok(finalized_block_header)
     }
@@ -295,43 +3929,63 @@ impl DaService for BitcoinService {
         &self,
         block: &Self::FilteredBlock,
     ) -> Vec<<Self::Spec as sov_rollup_interface::da::DaSpec>::BlobTransaction> {
-        let mut txs = Vec::new();
-
         info!(
             "Extracting relevant txs from block {:?}",
             block.header.block_hash()
         );
 
-        // iterate over all transactions in the block
-        for tx in block.txdata.iter() {
-            if !tx
-                .txid()
-                .to_byte_array()
-                .as_slice()
-                .starts_with(self.reveal_tx_id_prefix.as_slice())
-            {
-                continue;
-            }
-
-            // check if the inscription in script is relevant to the rollup
-            let parsed_inscription = parse_transaction(tx, &self.rollup_name);
+        // the sync path: the block is read from the sequence after the previous one and the
+        // sequence after it is recorded for the next one, replacing the one of a reorged block
+        let mut batch_sequence = self.batch_sequence_before(block.header.height);
+        let blobs = self.find_relevant_blobs(block, batch_sequence.as_mut());
+        if let (Some(history), Some(batch_sequence)) = (&self.batch_sequence, batch_sequence) {
+            history
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .record(block.header.height, batch_sequence);
+        }
 
-            if let Ok(inscription) = parsed_inscription {
-                if inscription.get_sig_verified_hash().is_some() {
-                    // Decompress the blob
-                    let decompressed_blob = decompress_blob(&inscription.body);
+        for (tx, blob) in &blobs {
+            if let Ok(mut blob_index) = self.blob_index.lock() {
+                blob_index.insert(
+                    blob.hash,
+                    BlobLocation {
+                        height: block.header.height,
+                        txid: tx.txid(),
+                    },
+                );
+            }
 
-                    let relevant_tx = BlobWithSender::new(
-                        decompressed_blob,
-                        inscription.public_key,
-                        sha256d::Hash::hash(&inscription.body).to_byte_array(),
-                    );
+            if let Ok(mut inclusion_tracker) = self.inclusion_tracker.lock() {
+                if let Err(e) = inclusion_tracker.record_confirmation(
+                    &tx.txid(),
+                    block.header.height,
+                    unix_now(),
+                ) {
+                    error!("Failed to track confirmation of {}: {}", tx.txid(), e);
+                }
+            }
 
-                    txs.push(relevant_tx);
+            // batches of other senders are no business of the registry
+            if let Some(batch_number) = blob.metadata.batch_number {
+                if let Ok(mut batch_registry) = self.batch_registry.lock() {
+                    if !self.is_own_sender(&blob.sender.0, &batch_registry) {
+                        continue;
+                    }
+                    if let Err(e) = batch_registry.record_inclusion(
+                        &blob.sender.0,
+                        batch_number,
+                        tx.input[0].previous_output.txid,
+                        tx.txid(),
+                        block.header.height,
+                    ) {
+                        error!("Failed to record batch {}: {}", batch_number, e);
+                    }
                 }
             }
         }
-        txs
+
+        blobs.into_iter().map(|(_, blob)| blob).collect()
     }
 
     async fn get_extraction_proof(
@@ -347,21 +4001,13 @@ impl DaService for BitcoinService {
             block.header.block_hash()
         );
 
-        let mut completeness_proof = Vec::with_capacity(block.txdata.len());
+        // relevant txs are in the completeness proof
+        let completeness_proof = self.relevant_txs(block).cloned().collect();
 
         let block_txs = block
             .txdata
             .iter()
-            .map(|tx| {
-                let tx_hash = tx.txid().to_raw_hash().to_byte_array();
-
-                // if tx_hash has two leading zeros, it is in the completeness proof
-                if tx_hash[0..2] == [0, 0] {
-                    completeness_proof.push(tx.clone());
-                }
-
-                tx_hash
-            })
+            .map(|tx| tx.txid().to_raw_hash().to_byte_array())
             .collect::<Vec<_>>();
 
         let inclusion_proof = InclusionMultiProof { txs: block_txs };
@@ -395,6 +4041,16 @@ impl DaService for BitcoinService {
         &self,
         blob: &[u8],
     ) -> Result<<Self as DaService>::TransactionId, Self::Error> {
+        // wait for the batch window if one is configured
+        if let Some(batch_queue) = &self.batch_queue {
+            let (reply, result) = oneshot::channel();
+            batch_queue
+                .send((blob.to_vec(), reply))
+                .map_err(|_| anyhow::anyhow!("Batch window is not running"))?;
+
+            return result.await?;
+        }
+
         let fee_sat_per_vbyte = self.get_fee_rate().await?;
         self.send_transaction_with_fee_rate(blob, fee_sat_per_vbyte)
             .await
@@ -416,19 +4072,104 @@ impl DaService for BitcoinService {
 mod tests {
     use core::str::FromStr;
     use std::collections::HashSet;
+    use std::time::Duration;
 
     use bitcoin::hashes::{sha256d, Hash};
     use bitcoin::secp256k1::KeyPair;
     use bitcoin::{merkle_tree, Address, Txid};
     use sov_rollup_interface::services::da::DaService;
 
-    use super::{BitcoinService, FINALITY_DEPTH};
+    use super::{is_missing_inputs, BitcoinService, FeeCap, FINALITY_DEPTH, POLLING_INTERVAL};
+    use crate::handoff::InscriptionHandoff;
     use crate::helpers::parsers::parse_transaction;
-    use crate::rpc::BitcoinNode;
+    use crate::rpc::{BitcoinNode, RPCError};
     use crate::service::DaServiceConfig;
     use crate::spec::RollupParams;
 
+    fn test_params() -> RollupParams {
+        RollupParams {
+            rollup_name: "sov-btc".to_string(),
+            reveal_tx_id_prefix: vec![],
+            reveal_tx_id_zero_bits: 0,
+            max_relevant_txs: None,
+            max_decompressed_len: None,
+            monotonic_batch_numbers: false,
+        }
+    }
+
+    fn test_config() -> DaServiceConfig {
+        DaServiceConfig {
+            node_url: "http://localhost:38332".to_string(),
+            node_username: "chainway".to_string(),
+            node_password: "topsecret".to_string(),
+            network: "regtest".to_string(),
+            address: "bcrt1qy85zdv5se9d9ceg9nvay36t6j86z95fny4rdzu".to_string(),
+            change_address: None,
+            blob_signature_scheme: None,
+            blob_compression: None,
+            min_confirmations: None,
+            max_ancestor_count: None,
+            coin_selection: None,
+            lock_unspent: None,
+            sequencer_da_private_key: Some(
+                "E9873D79C6D87DC0FB6A5778633389F4453213303DA61F20BD67FC233AA33262".into(), // Test key, safe to publish
+            ),
+            watch_only: None,
+            fee_rates_to_avg: Some(2), // small to speed up tests
+            webhook_url: None,
+            webhook_private_key: None,
+            quorum_nodes: None,
+            quorum_threshold: None,
+            max_fee_rate: None,
+            max_fee_per_submission: None,
+            tip_divergence_max_blocks: None,
+            tip_divergence_max_minutes: None,
+            tip_stall_max_minutes: None,
+            blob_rescan_depth: None,
+            excluded_utxos: None,
+            postage_max_amount: None,
+            batch_min_blocks: None,
+            batch_max_pending_bytes: None,
+            fee_api_url: None,
+            fee_api_cache_secs: None,
+            fee_api_min_rate: None,
+            fee_conf_target: None,
+            batch_registry_path: None,
+            inclusion_estimate_blocks: None,
+            broadcast_log_path: None,
+            inclusion_log_path: None,
+            nums_internal_key: None,
+            commit_xpriv: None,
+            commit_key_start_index: None,
+            sibling_sequencer_public_keys: None,
+            double_post_window_blocks: None,
+            min_relay_fee_rate: None,
+            incremental_relay_fee_rate: None,
+            dust_relay_fee_rate: None,
+            quota_bytes_per_day: None,
+            quota_sats_per_day: None,
+            reveal_tx_dir: None,
+            retention_keep_days: None,
+            retention_max_archive_bytes: None,
+            retention_prune_finalized_reveals: None,
+            retention_interval_secs: None,
+            checkpoint_interval_blocks: None,
+            pow_grind_timeout_secs: None,
+            anti_fee_sniping: None,
+            reveal_sighash_type: None,
+            min_change: None,
+            reveal_value: None,
+        }
+    }
+
     async fn get_service() -> BitcoinService {
+        get_service_with(test_config(), test_params()).await
+    }
+
+    async fn get_service_with(
+        runtime_config: DaServiceConfig,
+        rollup_params: RollupParams,
+    ) -> BitcoinService {
         let rpc = BitcoinNode::new(
             "http://localhost:38332".to_string(),
             "chainway".to_string(),
@@ -447,26 +4188,7 @@ mod tests {
         .await
         .unwrap();
 
-        let runtime_config = DaServiceConfig {
-            node_url: "http://localhost:38332".to_string(),
-            node_username: "chainway".to_string(),
-            node_password: "topsecret".to_string(),
-            network: "regtest".to_string(),
-            address: "bcrt1qy85zdv5se9d9ceg9nvay36t6j86z95fny4rdzu".to_string(),
-            sequencer_da_private_key: Some(
-                "E9873D79C6D87DC0FB6A5778633389F4453213303DA61F20BD67FC233AA33262".to_string(), // Test key, safe to publish
-            ),
-            fee_rates_to_avg: Some(2), // small to speed up tests
-        };
-
-        BitcoinService::new(
-            runtime_config,
-            RollupParams {
-                rollup_name: "sov-btc".to_string(),
-                reveal_tx_id_prefix: vec![],
-            },
-        )
-        .await
+        BitcoinService::new(runtime_config, rollup_params).await
     }
 
     #[tokio::test]
@@ -615,12 +4337,83 @@ mod tests {
             .expect("Failed to send transaction");
     }
 
+    #[tokio::test]
+    async fn send_transaction_with_monotonic_batch_numbers() {
+        let da_service = get_service_with(
+            test_config(),
+            RollupParams {
+                monotonic_batch_numbers: true,
+                ..test_params()
+            },
+        )
+        .await;
+
+        // the service numbers the blobs of the rollup
+        for blob in [b"first batch", b"later batch"] {
+            da_service
+                .send_transaction(blob)
+                .await
+                .expect("Failed to send transaction");
+        }
+
+        let hashes = da_service
+            .client
+            .generate_to_address(
+                Address::from_str("bcrt1qxuds94z3pqwqea2p4f4ev4f25s6uu7y3avljrl")
+                    .unwrap()
+                    .require_network(bitcoin::Network::Regtest)
+                    .unwrap(),
+                1,
+            )
+            .await
+            .unwrap();
+        let block = da_service
+            .client
+            .get_block(hashes[0].to_string())
+            .await
+            .unwrap();
+        let block = da_service.get_block_at(block.header.height).await.unwrap();
+
+        let txs = da_service.extract_relevant_blobs(&block);
+        let mut batch_numbers: Vec<u64> = txs
+            .iter()
+            .map(|blob| blob.metadata.batch_number.unwrap())
+            .collect();
+        assert_eq!(batch_numbers.len(), 2);
+        batch_numbers.sort();
+        assert_eq!(batch_numbers[1], batch_numbers[0] + 1);
+    }
+
+    #[tokio::test]
+    async fn batch_window_failed_post() {
+        // every post fails, regtest fee rates are at least 2 sat/vB
+        let da_service = get_service_with(
+            DaServiceConfig {
+                batch_min_blocks: Some(100),
+                max_fee_rate: Some(1.0),
+                ..test_config()
+            },
+            test_params(),
+        )
+        .await;
+
+        for blob in [b"first batch", b"later batch"] {
+            let result = tokio::time::timeout(
+                Duration::from_secs(4 * POLLING_INTERVAL),
+                da_service.send_transaction(blob),
+            )
+            .await
+            .expect("Blob was held back by the failed post");
+            assert!(result.is_err());
+        }
+    }
+
     #[tokio::test]
     async fn send_transaction_with_fee_rate() {
         let da_service = get_service().await;
         let fee_rate = da_service
             .client
-            .estimate_smart_fee()
+            .estimate_smart_fee(1)
             .await
             .expect("Failed to get fee");
 
@@ -635,6 +4428,30 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn send_commit_with_presigned_reveal() {
+        let da_service = get_service().await;
+
+        let handoff = da_service
+            .send_commit_with_presigned_reveal(b"revealed later", 1.0)
+            .await
+            .expect("Failed to send commit tx");
+        assert!(!handoff.reveal_broadcast);
+        // recover_reveals must not broadcast it before its time
+        assert_eq!(
+            da_service.tx_store.get_reveal(&handoff.commit).unwrap(),
+            None
+        );
+
+        // e.g. by another service, from the handoff JSON
+        let handoff = InscriptionHandoff::from_json(&handoff.to_json().unwrap()).unwrap();
+        let reveal_txid = da_service
+            .broadcast_presigned_reveal(&handoff)
+            .await
+            .expect("Failed to broadcast reveal tx");
+        assert_eq!(reveal_txid, handoff.reveal);
+    }
+
     #[tokio::test]
     async fn check_signature() {
         let rpc = BitcoinNode::new(
@@ -646,10 +4463,11 @@ mod tests {
 
         let da_service = get_service().await;
         let secp = bitcoin::secp256k1::Secp256k1::new();
-        let da_pubkey = KeyPair::from_secret_key(&secp, &da_service.sequencer_da_private_key)
-            .public_key()
-            .serialize()
-            .to_vec();
+        let da_pubkey =
+            KeyPair::from_secret_key(&secp, &da_service.sequencer_da_private_key.unwrap())
+                .public_key()
+                .serialize()
+                .to_vec();
 
         // incorrect private key
 
@@ -684,4 +4502,133 @@ mod tests {
             "Publickey recovered incorrectly!"
         );
     }
+
+    #[test]
+    fn config_check() {
+        let config = DaServiceConfig {
+            address: "bcrt1qy85zdv5se9d9ceg9nvay36t6j86z95fny4rdzu".to_string(),
+            ..DaServiceConfig::regtest_defaults()
+        };
+
+        assert!(config.check(bitcoin::Network::Regtest).is_ok());
+        // node runs on another chain
+        assert!(config.check(bitcoin::Network::Signet).is_err());
+
+        // address of another network
+        let config = DaServiceConfig {
+            address: "bcrt1qy85zdv5se9d9ceg9nvay36t6j86z95fny4rdzu".to_string(),
+            ..DaServiceConfig::mainnet_defaults()
+        };
+        assert!(config.check(bitcoin::Network::Bitcoin).is_err());
+
+        // the change goes to another address of the same network, or to the wallet
+        for (change_address, valid) in [
+            ("bcrt1qxuds94z3pqwqea2p4f4ev4f25s6uu7y3avljrl", true),
+            ("wallet", true),
+            (
+                "bc1pp8qru0ve43rw9xffmdd8pvveths3cx6a5t6mcr0xfn9cpxx2k24qf70xq9",
+                false,
+            ),
+            ("not an address", false),
+        ] {
+            let config = DaServiceConfig {
+                address: "bcrt1qy85zdv5se9d9ceg9nvay36t6j86z95fny4rdzu".to_string(),
+                change_address: Some(change_address.to_string()),
+                ..DaServiceConfig::regtest_defaults()
+            };
+            assert_eq!(config.check(bitcoin::Network::Regtest).is_ok(), valid);
+        }
+
+        // the quorum threshold counts the main node
+        for (quorum_threshold, valid) in [(0, false), (1, true), (2, false)] {
+            let config = DaServiceConfig {
+                address: "bcrt1qy85zdv5se9d9ceg9nvay36t6j86z95fny4rdzu".to_string(),
+                quorum_threshold: Some(quorum_threshold),
+                ..DaServiceConfig::regtest_defaults()
+            };
+            assert_eq!(config.check(bitcoin::Network::Regtest).is_ok(), valid);
+        }
+
+        // estimatesmartfee takes targets of 1 to 1008 blocks
+        for (fee_conf_target, valid) in [(0, false), (6, true), (1009, false)] {
+            let config = DaServiceConfig {
+                address: "bcrt1qy85zdv5se9d9ceg9nvay36t6j86z95fny4rdzu".to_string(),
+                fee_conf_target: Some(fee_conf_target),
+                ..DaServiceConfig::regtest_defaults()
+            };
+            assert_eq!(config.check(bitcoin::Network::Regtest).is_ok(), valid);
+        }
+
+        // P2WPKH outputs are dust below 294 sats
+        for (reveal_value, valid) in [(293, false), (294, true)] {
+            let config = DaServiceConfig {
+                address: "bcrt1qy85zdv5se9d9ceg9nvay36t6j86z95fny4rdzu".to_string(),
+                reveal_value: Some(reveal_value),
+                ..DaServiceConfig::regtest_defaults()
+            };
+            assert_eq!(config.check(bitcoin::Network::Regtest).is_ok(), valid);
+        }
+
+        // address is not needed in watch-only mode
+        let config = DaServiceConfig {
+            watch_only: Some(true),
+            ..DaServiceConfig::signet_defaults()
+        };
+        assert!(config.check(bitcoin::Network::Signet).is_ok());
+    }
+
+    #[test]
+    fn secret_string() {
+        let key = "E9873D79C6D87DC0FB6A5778633389F4453213303DA61F20BD67FC233AA33262";
+        let config = DaServiceConfig {
+            sequencer_da_private_key: Some(key.into()),
+            ..DaServiceConfig::regtest_defaults()
+        };
+        assert!(!format!("{config:?}").contains(key));
+
+        // the key is a plain string in the config file
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(json.contains(&format!("\"sequencer_da_private_key\":\"{key}\"")));
+        let parsed: DaServiceConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            parsed.sequencer_da_private_key.unwrap().expose_secret(),
+            key
+        );
+    }
+
+    #[test]
+    fn missing_inputs() {
+        let error = |code, message: &str| RPCError {
+            code,
+            message: message.to_string(),
+        };
+
+        assert!(is_missing_inputs(&error(
+            -25,
+            "bad-txns-inputs-missingorspent"
+        )));
+        assert!(is_missing_inputs(&error(-25, "Missing inputs")));
+        assert!(!is_missing_inputs(&error(-25, "non-final")));
+        assert!(!is_missing_inputs(&error(
+            -27,
+            "Transaction already in block chain"
+        )));
+    }
+
+    #[test]
+    fn fee_cap() {
+        let fee_cap = FeeCap {
+            max_fee_rate: Some(50.0),
+            max_fee: Some(100_000),
+        };
+
+        assert!(fee_cap.check_fee_rate(50.0).is_ok());
+        assert!(fee_cap.check_fee_rate(50.1).is_err());
+        assert!(fee_cap.check_fee(100_000).is_ok());
+        assert!(fee_cap.check_fee(100_001).is_err());
+
+        // no cap by default
+        assert!(FeeCap::default().check_fee_rate(10_000.0).is_ok());
+        assert!(FeeCap::default().check_fee(u64::MAX).is_ok());
+    }
 }
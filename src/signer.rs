@@ -0,0 +1,157 @@
+// Signing of the blob and of the reveal tx behind a trait, so that the keys can live in an
+// HSM, a remote signer or a multi party setup instead of the service memory
+// The commit txs are still signed by the node wallet
+
+use core::fmt::Debug;
+
+use async_trait::async_trait;
+use bitcoin::secp256k1::schnorr::Signature;
+use bitcoin::secp256k1::{KeyPair, Message, Secp256k1, SecretKey, XOnlyPublicKey};
+
+use crate::helpers::builders::sign_blob_with_private_key;
+use crate::spec::blob::SignatureScheme;
+
+#[async_trait]
+pub trait Signer: Debug + Send + Sync {
+    // Signs the sha256d of the signing message of a blob, see blob_signing_message, returns
+    // the signature and the public key it verifies under, see
+    // ParsedInscription::get_sig_verified_hash for the accepted schemes
+    async fn sign_blob(&self, message: &[u8]) -> Result<(Vec<u8>, Vec<u8>), anyhow::Error>;
+
+    // Scheme of the blob signatures, written in the envelope unless it is ECDSA
+    fn signature_scheme(&self) -> SignatureScheme {
+        SignatureScheme::Ecdsa
+    }
+
+    // Key the reveal script of the next inscription is locked to
+    async fn commit_public_key(&self) -> Result<XOnlyPublicKey, anyhow::Error>;
+
+    // BIP340 signature of the reveal tx sighash, under a key returned by commit_public_key
+    async fn sign_reveal(
+        &self,
+        commit_public_key: &XOnlyPublicKey,
+        sighash: [u8; 32],
+    ) -> Result<Signature, anyhow::Error>;
+}
+
+/// Signer holding its keys in memory, every inscription is locked to the same commit key
+#[derive(Debug)]
+pub struct LocalSigner {
+    sequencer_key: SecretKey,
+    commit_key: KeyPair,
+}
+
+impl LocalSigner {
+    pub fn new(sequencer_key: SecretKey, commit_key: KeyPair) -> Self {
+        Self {
+            sequencer_key,
+            commit_key,
+        }
+    }
+}
+
+#[async_trait]
+impl Signer for LocalSigner {
+    async fn sign_blob(&self, message: &[u8]) -> Result<(Vec<u8>, Vec<u8>), anyhow::Error> {
+        Ok(sign_blob_with_private_key(message, &self.sequencer_key))
+    }
+
+    async fn commit_public_key(&self) -> Result<XOnlyPublicKey, anyhow::Error> {
+        Ok(self.commit_key.x_only_public_key().0)
+    }
+
+    async fn sign_reveal(
+        &self,
+        commit_public_key: &XOnlyPublicKey,
+        sighash: [u8; 32],
+    ) -> Result<Signature, anyhow::Error> {
+        if *commit_public_key != self.commit_key.x_only_public_key().0 {
+            return Err(anyhow::anyhow!(
+                "Commit key {} is not held by the signer",
+                commit_public_key
+            ));
+        }
+
+        Ok(Secp256k1::signing_only()
+            .sign_schnorr_no_aux_rand(&Message::from_slice(&sighash)?, &self.commit_key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+
+    use bitcoin::hashes::Hash;
+    use bitcoin::secp256k1::{KeyPair, Secp256k1, SecretKey};
+    use bitcoin::{Address, Txid};
+
+    use super::{LocalSigner, Signer};
+    use crate::helpers::builders::{compress_blob, InscriptionBuilder, InscriptionError};
+    use crate::helpers::parsers::parse_transaction;
+    use crate::helpers::simulation::simulate_reveal_spend;
+    use crate::spec::utxo::UTXO;
+
+    #[tokio::test]
+    async fn external_signer() {
+        let secp = Secp256k1::new();
+        let sequencer_key = SecretKey::from_slice(&[1; 32]).unwrap();
+        let commit_key = KeyPair::from_seckey_slice(&secp, &[2; 32]).unwrap();
+        let signer = LocalSigner::new(sequencer_key, commit_key);
+
+        let address =
+            Address::from_str("bc1pp8qru0ve43rw9xffmdd8pvveths3cx6a5t6mcr0xfn9cpxx2k24qf70xq9")
+                .unwrap()
+                .assume_checked();
+        let body = compress_blob(b"signed elsewhere");
+        let (signature, public_key) = signer.sign_blob(&body).await.unwrap();
+
+        let builder = || {
+            InscriptionBuilder::new("sov-btc")
+                .with_body(body.clone())
+                .with_signature(signature.clone(), public_key.clone())
+                .with_utxos(vec![UTXO {
+                    tx_id: Txid::all_zeros(),
+                    vout: 0,
+                    address: address.to_string(),
+                    script_pubkey: address.script_pubkey().to_hex_string(),
+                    amount: 1_000_000,
+                    confirmations: 1,
+                    spendable: true,
+                    solvable: true,
+                    ancestor_count: None,
+                }])
+                .with_recipient(address.clone())
+                .with_fee_rates(2.0, 2.0)
+        };
+
+        let commit_public_key = signer.commit_public_key().await.unwrap();
+        let unsigned = builder().build_unsigned(commit_public_key).unwrap();
+        assert!(unsigned.reveal_tx.input[0].witness.is_empty());
+
+        let reveal_signature = signer
+            .sign_reveal(&commit_public_key, unsigned.reveal_sighash())
+            .await
+            .unwrap();
+        let (commit_tx, reveal_tx, _) = unsigned.clone().finalize(&reveal_signature).unwrap();
+        simulate_reveal_spend(&reveal_tx, &commit_tx).unwrap();
+
+        let parsed = parse_transaction(&reveal_tx, "sov-btc").unwrap();
+        assert_eq!(parsed.body, body);
+        assert!(parsed.get_sig_verified_hash().is_some());
+
+        // a signature of another key, or of another message, is refused
+        let other_key = KeyPair::from_seckey_slice(&secp, &[3; 32]).unwrap();
+        assert!(LocalSigner::new(sequencer_key, other_key)
+            .sign_reveal(&commit_public_key, unsigned.reveal_sighash())
+            .await
+            .is_err());
+        let wrong_signature = signer
+            .sign_reveal(&commit_public_key, [7; 32])
+            .await
+            .unwrap();
+        assert!(matches!(
+            unsigned.finalize(&wrong_signature),
+            Err(InscriptionError::InvalidRevealSignature)
+        ));
+    }
+}
@@ -0,0 +1,71 @@
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::secp256k1::{KeyPair, Secp256k1, SecretKey};
+use bitcoin::{Address, Network, OutPoint};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+use crate::spec::utxo::UTXO;
+
+/// An output funded ahead of time, e.g. while fees are low, that pays for one future
+/// inscription of a body of up to max_body_len bytes. It is locked to a key path only P2TR
+/// address of secret_key, which the node wallet does not know: the slot has to be persisted
+/// by the caller until it is used, losing it loses the funds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InscriptionSlot {
+    pub outpoint: OutPoint,
+    pub value: u64,
+    pub secret_key: SecretKey,
+    pub max_body_len: usize,
+    pub commit_fee_rate: f64,
+    pub reveal_fee_rate: f64,
+}
+
+impl InscriptionSlot {
+    pub fn key_pair(&self) -> KeyPair {
+        KeyPair::from_secret_key(&Secp256k1::new(), &self.secret_key)
+    }
+
+    pub fn address(&self, network: Network) -> Address {
+        slot_address(&self.key_pair(), network)
+    }
+
+    // Key of the slot funded by the reveal output of this one when inscriptions are chained.
+    // It is derived from the slot key, so a lost next slot can be recovered from this one
+    pub fn next_secret_key(&self) -> SecretKey {
+        let mut secret_bytes = self.secret_key.secret_bytes();
+        let mut engine = sha256::Hash::engine();
+        engine.input(b"bitcoin-da/chained-slot");
+        engine.input(&secret_bytes);
+        secret_bytes.zeroize();
+
+        let mut next_secret_bytes = sha256::Hash::from_engine(engine).to_byte_array();
+        let next_secret_key =
+            SecretKey::from_slice(&next_secret_bytes).expect("Hash is a valid secret key");
+        next_secret_bytes.zeroize();
+
+        next_secret_key
+    }
+
+    // The slot as the only utxo funding a commit transaction
+    pub fn utxo(&self, network: Network) -> UTXO {
+        let address = self.address(network);
+
+        UTXO {
+            tx_id: self.outpoint.txid,
+            vout: self.outpoint.vout,
+            address: address.to_string(),
+            script_pubkey: address.script_pubkey().to_hex_string(),
+            amount: self.value,
+            confirmations: 0,
+            spendable: true,
+            solvable: true,
+            ancestor_count: None,
+        }
+    }
+}
+
+pub(crate) fn slot_address(key_pair: &KeyPair, network: Network) -> Address {
+    let (public_key, _parity) = key_pair.x_only_public_key();
+
+    Address::p2tr(&Secp256k1::new(), public_key, None, network)
+}
@@ -0,0 +1,177 @@
+// Batch numbers accepted from each sender, for deployments whose batch numbers must strictly
+// increase (RollupParams::monotonic_batch_numbers)
+// The sequence is explicit state: a block is read starting from the sequence after the
+// previous block, and the validity condition of the block commits to the digests of the
+// sequence before and after it, so that the proofs of consecutive blocks only combine if the
+// prover carried the sequence over. Reading a block never changes the sequence it started from
+// Batch numbers are covered by the blob signature. Every blob but a checkpoint must be
+// numbered, a number may not jump more than MAX_BATCH_NUMBER_GAP past the last one
+
+use std::collections::BTreeMap;
+
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+
+use super::blob::BlobKind;
+
+// Largest step from the last batch number of a sender, or from zero for its first batch, so
+// that a single blob cannot number itself u64::MAX and end the sequence of its sender
+pub const MAX_BATCH_NUMBER_GAP: u64 = 1 << 20;
+
+// Blocks below the last one whose sequences the service keeps to read them again
+const HISTORY_DEPTH: u64 = 1_000;
+
+#[derive(
+    Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize,
+)]
+pub struct BatchSequence {
+    // highest batch number accepted from each sender
+    last: BTreeMap<Vec<u8>, u64>,
+}
+
+impl BatchSequence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Highest batch number accepted from sender so far
+    pub fn last(&self, sender: &[u8]) -> Option<u64> {
+        self.last.get(sender).copied()
+    }
+
+    // Highest batch number accepted from the senders is_own holds for
+    pub fn last_of(&self, mut is_own: impl FnMut(&[u8]) -> bool) -> Option<u64> {
+        self.last
+            .iter()
+            .filter(|(sender, _)| is_own(sender))
+            .map(|(_, batch_number)| *batch_number)
+            .max()
+    }
+
+    // Whether a blob of sender is accepted, and records its batch number if so
+    pub fn accept(&mut self, sender: &[u8], batch_number: Option<u64>, kind: BlobKind) -> bool {
+        let Some(batch_number) = batch_number else {
+            return kind == BlobKind::Checkpoint;
+        };
+        let accepted = match self.last(sender) {
+            Some(last) => batch_number > last && batch_number - last <= MAX_BATCH_NUMBER_GAP,
+            None => batch_number <= MAX_BATCH_NUMBER_GAP,
+        };
+
+        if accepted {
+            self.last.insert(sender.to_vec(), batch_number);
+        }
+        accepted
+    }
+
+    // Commitment to the sequence carried by the validity condition
+    pub fn digest(&self) -> [u8; 32] {
+        let mut engine = sha256::Hash::engine();
+        engine.input(b"bitcoin-da/batch-sequence");
+        engine.input(&self.try_to_vec().expect("Cannot serialize batch sequence"));
+
+        sha256::Hash::from_engine(engine).to_byte_array()
+    }
+}
+
+// Sequences after the blocks the service synced, so that any of them can be read again from
+// the sequence before it
+#[derive(Debug, Clone, Default)]
+pub struct BatchSequenceHistory {
+    after: BTreeMap<u64, BatchSequence>,
+}
+
+impl BatchSequenceHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Sequence the block at height is read from, the one after the closest block below it
+    pub fn before(&self, height: u64) -> BatchSequence {
+        self.after
+            .range(..height)
+            .next_back()
+            .map(|(_, sequence)| sequence.clone())
+            .unwrap_or_default()
+    }
+
+    // Records the sequence after the block at height. The blocks above it are forgotten,
+    // they were replaced by a reorg, and so are the ones too deep to be read again
+    pub fn record(&mut self, height: u64, sequence: BatchSequence) {
+        self.after.split_off(&height);
+        self.after.insert(height, sequence);
+
+        let kept = self.after.split_off(&height.saturating_sub(HISTORY_DEPTH));
+        self.after = kept;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BatchSequence, BatchSequenceHistory, MAX_BATCH_NUMBER_GAP};
+    use crate::spec::blob::BlobKind;
+
+    #[test]
+    fn batch_sequence() {
+        let mut sequence = BatchSequence::new();
+
+        assert!(sequence.accept(b"alice", Some(1), BlobKind::Batch));
+        assert!(sequence.accept(b"alice", Some(2), BlobKind::Batch));
+        assert!(!sequence.accept(b"alice", Some(2), BlobKind::Batch));
+        assert!(!sequence.accept(b"alice", Some(1), BlobKind::Proof));
+        // senders are numbered on their own
+        assert!(sequence.accept(b"bob", Some(1), BlobKind::Batch));
+        assert_eq!(sequence.last(b"alice"), Some(2));
+        assert_eq!(sequence.last_of(|sender| sender != b"alice"), Some(1));
+        assert_eq!(sequence.last_of(|_| false), None);
+
+        // only checkpoints may go unnumbered
+        assert!(!sequence.accept(b"alice", None, BlobKind::Batch));
+        assert!(!sequence.accept(b"alice", None, BlobKind::Proof));
+        assert!(sequence.accept(b"alice", None, BlobKind::Checkpoint));
+
+        // a number cannot end the sequence of its sender
+        assert!(!sequence.accept(b"alice", Some(u64::MAX), BlobKind::Batch));
+        assert!(!sequence.accept(b"carol", Some(u64::MAX), BlobKind::Batch));
+        assert!(sequence.accept(b"alice", Some(2 + MAX_BATCH_NUMBER_GAP), BlobKind::Batch));
+        assert_eq!(sequence.last(b"alice"), Some(2 + MAX_BATCH_NUMBER_GAP));
+    }
+
+    #[test]
+    fn digest() {
+        let mut sequence = BatchSequence::new();
+        let empty = sequence.digest();
+        assert!(sequence.accept(b"alice", Some(1), BlobKind::Batch));
+        assert_ne!(sequence.digest(), empty);
+
+        let mut other = BatchSequence::new();
+        assert!(other.accept(b"alice", Some(2), BlobKind::Batch));
+        assert_ne!(sequence.digest(), other.digest());
+    }
+
+    #[test]
+    fn history() {
+        let mut history = BatchSequenceHistory::new();
+        assert_eq!(history.before(100), BatchSequence::new());
+
+        let mut sequence = history.before(100);
+        assert!(sequence.accept(b"alice", Some(1), BlobKind::Batch));
+        history.record(100, sequence.clone());
+        let mut next = history.before(101);
+        assert!(next.accept(b"alice", Some(5), BlobKind::Batch));
+        history.record(101, next.clone());
+
+        // reading a block again starts from the block before it, whatever was read since
+        assert_eq!(history.before(101), sequence);
+        assert_eq!(history.before(102), next);
+
+        // the block replacing 101 in a reorg forgets it
+        history.record(101, sequence.clone());
+        assert_eq!(history.before(102), sequence);
+
+        // deep blocks are forgotten
+        history.record(5_000, next.clone());
+        assert_eq!(history.after.len(), 1);
+    }
+}
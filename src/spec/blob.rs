@@ -1,28 +1,328 @@
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+use bitcoin::secp256k1::constants::SCHNORR_PUBLIC_KEY_SIZE;
+use borsh::{BorshDeserialize, BorshSerialize};
 use serde::{Deserialize, Serialize};
 use sov_rollup_interface::da::{BlobReaderTrait, CountedBufReader};
 use sov_rollup_interface::Buf;
 
 use super::address::AddressWrapper;
 
-// BlobBuf is a wrapper around Vec<u8> to implement Buf
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+// BlobBuf is a wrapper around the blob content to implement Buf
+// The content is shared with BlobWithSender, so it can be read without advancing the buffer
+#[derive(Clone, Debug, PartialEq)]
 pub struct BlobBuf {
-    pub data: Vec<u8>,
+    pub data: Arc<[u8]>,
 
     pub offset: usize,
 }
 
+// Optional metadata of the inscription, covered by the blob signature along with the body
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+pub struct InscriptionMetadata {
+    // rollup batch number
+    pub batch_number: Option<u64>,
+    // unix timestamp (seconds) of when the inscription was created
+    pub timestamp: Option<u64>,
+    // artifact carried by the blob, absent for plain rollup batches
+    #[serde(default)]
+    pub kind: Option<BlobKind>,
+    // RollupParams::fingerprint of the deployment that sent the blob
+    #[serde(default)]
+    pub params_fingerprint: Option<[u8; 8]>,
+    // position of the blob in a larger blob split across several reveal txs
+    #[serde(default)]
+    pub chunk: Option<ChunkManifest>,
+    // scheme of the blob signature, inferred from the public key length when absent
+    #[serde(default)]
+    pub signature_scheme: Option<SignatureScheme>,
+    // algorithm the body is compressed with, detected from the body when absent
+    #[serde(default)]
+    pub compression: Option<CompressionAlgorithm>,
+    // txid of the tx pre-committing to the blob hash, see spec::precommitment
+    #[serde(default)]
+    pub precommitment: Option<[u8; 32]>,
+}
+
+impl InscriptionMetadata {
+    // Blobs without a fingerprint predate it and are accepted
+    pub fn matches_params(&self, fingerprint: [u8; 8]) -> bool {
+        self.params_fingerprint
+            .map_or(true, |params_fingerprint| params_fingerprint == fingerprint)
+    }
+}
+
+// Manifest of a chunk of a blob too large for a single reveal tx
+// blob_hash is the sha256d of the whole uncompressed blob, chunks are compressed one by one
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+pub struct ChunkManifest {
+    pub index: u32,
+    pub total: u32,
+    pub blob_hash: [u8; 32],
+}
+
+impl ChunkManifest {
+    pub const ENCODED_LEN: usize = 40;
+
+    // index and total are 4 bytes little endian, followed by the blob hash
+    pub fn to_bytes(&self) -> [u8; Self::ENCODED_LEN] {
+        let mut bytes = [0u8; Self::ENCODED_LEN];
+        bytes[..4].copy_from_slice(&self.index.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.total.to_le_bytes());
+        bytes[8..].copy_from_slice(&self.blob_hash);
+        bytes
+    }
+
+    // None if the bytes are not an encoded manifest or the index is out of range
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != Self::ENCODED_LEN {
+            return None;
+        }
+
+        let manifest = Self {
+            index: u32::from_le_bytes(bytes[..4].try_into().ok()?),
+            total: u32::from_le_bytes(bytes[4..8].try_into().ok()?),
+            blob_hash: bytes[8..].try_into().ok()?,
+        };
+
+        (manifest.index < manifest.total).then_some(manifest)
+    }
+}
+
+// Scheme of the blob signature. ECDSA signatures come with a 33 byte compressed public key,
+// BIP340 Schnorr signatures with a 32 byte x-only public key and BIP-322 simple signatures
+// with the script pubkey of the signing address in place of the public key
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureScheme {
+    #[default]
+    Ecdsa,
+    Schnorr,
+    Bip322,
+}
+
+impl SignatureScheme {
+    pub fn to_u8(self) -> u8 {
+        match self {
+            SignatureScheme::Ecdsa => 0,
+            SignatureScheme::Schnorr => 1,
+            SignatureScheme::Bip322 => 2,
+        }
+    }
+
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(SignatureScheme::Ecdsa),
+            1 => Some(SignatureScheme::Schnorr),
+            2 => Some(SignatureScheme::Bip322),
+            _ => None,
+        }
+    }
+
+    // Envelopes without the scheme tag predate it, a 32 byte key is a MuSig2 aggregate key
+    pub fn from_public_key_len(len: usize) -> Self {
+        if len == SCHNORR_PUBLIC_KEY_SIZE {
+            SignatureScheme::Schnorr
+        } else {
+            SignatureScheme::Ecdsa
+        }
+    }
+}
+
+// Algorithm a blob body is compressed with. Brotli compresses best, zstd is much faster on
+// large batches, and none stores the body as is, for payloads already compressed or encrypted
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum CompressionAlgorithm {
+    #[default]
+    Brotli,
+    Zstd,
+    Gzip,
+    #[serde(alias = "store")]
+    None,
+}
+
+impl CompressionAlgorithm {
+    // Every algorithm this build decompresses, in the order of their codes
+    pub const ALL: [CompressionAlgorithm; 4] = [
+        CompressionAlgorithm::Brotli,
+        CompressionAlgorithm::Zstd,
+        CompressionAlgorithm::Gzip,
+        CompressionAlgorithm::None,
+    ];
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            CompressionAlgorithm::Brotli => 0,
+            CompressionAlgorithm::Zstd => 1,
+            CompressionAlgorithm::Gzip => 2,
+            CompressionAlgorithm::None => 3,
+        }
+    }
+
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(CompressionAlgorithm::Brotli),
+            1 => Some(CompressionAlgorithm::Zstd),
+            2 => Some(CompressionAlgorithm::Gzip),
+            3 => Some(CompressionAlgorithm::None),
+            _ => None,
+        }
+    }
+}
+
+// Body of a checkpoint blob, the latest state root of the rollup
+// The service posts one every few blocks as a heartbeat light clients can follow cheaply
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+pub struct Checkpoint {
+    pub state_root: [u8; 32],
+}
+
+impl Checkpoint {
+    pub const ENCODED_LEN: usize = 32;
+
+    pub fn to_bytes(&self) -> [u8; Self::ENCODED_LEN] {
+        self.state_root
+    }
+
+    // None if the bytes are not an encoded checkpoint
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        Some(Self {
+            state_root: bytes.try_into().ok()?,
+        })
+    }
+}
+
+// Kind of artifact a rollup posts, lets it route blobs without sniffing their content
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum BlobKind {
+    #[default]
+    Batch,
+    Proof,
+    Checkpoint,
+}
+
+impl BlobKind {
+    pub fn to_u8(self) -> u8 {
+        match self {
+            BlobKind::Batch => 0,
+            BlobKind::Proof => 1,
+            BlobKind::Checkpoint => 2,
+        }
+    }
+
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(BlobKind::Batch),
+            1 => Some(BlobKind::Proof),
+            2 => Some(BlobKind::Checkpoint),
+            _ => None,
+        }
+    }
+}
+
 impl BlobWithSender {
     pub fn new(blob: Vec<u8>, sender: Vec<u8>, hash: [u8; 32]) -> Self {
+        let content: Arc<[u8]> = blob.into();
+
         Self {
             blob: CountedBufReader::new(BlobBuf {
-                data: blob,
+                data: content.clone(),
                 offset: 0,
             }),
+            content,
             sender: AddressWrapper(sender),
             hash,
+            metadata: InscriptionMetadata::default(),
         }
     }
+
+    // Full content of the blob regardless of how much of it was read
+    // Meant for the verifier, it does not advance the blob
+    pub fn full_content(&self) -> &[u8] {
+        &self.content
+    }
+
+    pub fn with_metadata(mut self, metadata: InscriptionMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    // Blobs inscribed without a kind are rollup batches
+    pub fn kind(&self) -> BlobKind {
+        self.metadata.kind.unwrap_or_default()
+    }
 }
 
 impl Buf for BlobBuf {
@@ -41,12 +341,43 @@ impl Buf for BlobBuf {
 
 // BlobWithSender is a wrapper around BlobBuf to implement BlobReaderTrait
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(from = "SerializedBlob", into = "SerializedBlob")]
 pub struct BlobWithSender {
     pub hash: [u8; 32],
 
     pub sender: AddressWrapper,
 
     pub blob: CountedBufReader<BlobBuf>,
+
+    content: Arc<[u8]>,
+
+    pub metadata: InscriptionMetadata,
+}
+
+// Blob is serialized with its full content, regardless of how much of it was read
+#[derive(Serialize, Deserialize)]
+struct SerializedBlob {
+    hash: [u8; 32],
+    sender: AddressWrapper,
+    blob: Vec<u8>,
+    metadata: InscriptionMetadata,
+}
+
+impl From<SerializedBlob> for BlobWithSender {
+    fn from(blob: SerializedBlob) -> Self {
+        Self::new(blob.blob, blob.sender.0, blob.hash).with_metadata(blob.metadata)
+    }
+}
+
+impl From<BlobWithSender> for SerializedBlob {
+    fn from(blob: BlobWithSender) -> Self {
+        Self {
+            hash: blob.hash,
+            blob: blob.content.to_vec(),
+            sender: blob.sender,
+            metadata: blob.metadata,
+        }
+    }
 }
 
 impl BlobReaderTrait for BlobWithSender {
@@ -74,3 +405,48 @@ impl BlobReaderTrait for BlobWithSender {
         self.verified_data()
     }
 }
+
+// Blob is borsh encoded with its full content, regardless of how much of it was read
+impl BorshSerialize for BlobWithSender {
+    fn serialize<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        BorshSerialize::serialize(&self.hash, writer)?;
+        BorshSerialize::serialize(&self.sender, writer)?;
+        BorshSerialize::serialize(&self.content.to_vec(), writer)?;
+        BorshSerialize::serialize(&self.metadata, writer)
+    }
+}
+
+impl BorshDeserialize for BlobWithSender {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> std::io::Result<Self> {
+        let hash = <[u8; 32]>::deserialize_reader(reader)?;
+        let sender = AddressWrapper::deserialize_reader(reader)?;
+        let blob = Vec::<u8>::deserialize_reader(reader)?;
+        let metadata = InscriptionMetadata::deserialize_reader(reader)?;
+
+        Ok(Self::new(blob, sender.0, hash).with_metadata(metadata))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use borsh::{BorshDeserialize, BorshSerialize};
+    use sov_rollup_interface::da::BlobReaderTrait;
+
+    use super::BlobWithSender;
+
+    #[test]
+    fn full_content_does_not_advance() {
+        let mut blob = BlobWithSender::new(vec![1, 2, 3, 4], vec![5; 33], [6; 32]);
+
+        blob.blob.advance(2);
+
+        assert_eq!(blob.full_content(), &[1, 2, 3, 4]);
+        assert_eq!(blob.verified_data(), &[1, 2]);
+
+        // serialized with the full content
+        let deserialized = BlobWithSender::try_from_slice(&blob.try_to_vec().unwrap()).unwrap();
+
+        assert_eq!(deserialized.full_content(), &[1, 2, 3, 4]);
+        assert_eq!(deserialized.verified_data(), &[] as &[u8]);
+    }
+}
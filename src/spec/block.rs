@@ -2,6 +2,7 @@ use serde::{Deserialize, Serialize};
 use sov_rollup_interface::da::BlockHeaderTrait;
 use sov_rollup_interface::services::da::SlotData;
 
+use super::batch_sequence::BatchSequence;
 use super::header::HeaderWrapper;
 use super::transaction::Transaction;
 use crate::verifier::ChainValidityCondition;
@@ -25,10 +26,15 @@ impl SlotData for BitcoinBlock {
         &self.header
     }
 
+    // A block alone does not know the batch sequence, the digests are the ones of a deployment
+    // without monotonic batch numbers
     fn validity_condition(&self) -> Self::Cond {
+        let batch_sequence = BatchSequence::new().digest();
         ChainValidityCondition {
             prev_hash: self.header.prev_hash().to_byte_array(),
             block_hash: self.hash(),
+            prev_batch_sequence: batch_sequence,
+            batch_sequence,
         }
     }
 }
@@ -0,0 +1,156 @@
+// Merkle root over the body chunks of an envelope, one chunk per push of up to 520 bytes.
+// A consumer holding the root can check a single chunk with its proof, without the rest of
+// the reveal witness. Leaves and nodes are hashed with distinct prefixes and the last node
+// of an odd level is carried up unchanged, so no two bodies share a root
+
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+
+// Bytes of body per push, the largest push allowed in a tapscript
+pub const BODY_CHUNK_LEN: usize = 520;
+
+const LEAF_PREFIX: u8 = 0;
+const NODE_PREFIX: u8 = 1;
+
+fn leaf_hash(chunk: &[u8]) -> [u8; 32] {
+    let mut engine = sha256::Hash::engine();
+    engine.input(&[LEAF_PREFIX]);
+    engine.input(chunk);
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut engine = sha256::Hash::engine();
+    engine.input(&[NODE_PREFIX]);
+    engine.input(left);
+    engine.input(right);
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+// Chunks the body is pushed in, an empty body is a single empty chunk
+pub fn body_chunks(body: &[u8]) -> Vec<&[u8]> {
+    if body.is_empty() {
+        vec![body]
+    } else {
+        body.chunks(BODY_CHUNK_LEN).collect()
+    }
+}
+
+fn next_level(level: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => node_hash(left, right),
+            [last] => *last,
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+pub fn body_root(body: &[u8]) -> [u8; 32] {
+    let mut level: Vec<[u8; 32]> = body_chunks(body).into_iter().map(leaf_hash).collect();
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+
+    level[0]
+}
+
+// Sibling hashes from the leaf of chunk index up to the root, None if there is no such chunk
+pub fn body_chunk_proof(body: &[u8], index: usize) -> Option<Vec<[u8; 32]>> {
+    let mut level: Vec<[u8; 32]> = body_chunks(body).into_iter().map(leaf_hash).collect();
+    if index >= level.len() {
+        return None;
+    }
+
+    let mut proof = vec![];
+    let mut index = index;
+    while level.len() > 1 {
+        // the last node of an odd level has no sibling
+        if let Some(sibling) = level.get(index ^ 1) {
+            proof.push(*sibling);
+        }
+        level = next_level(&level);
+        index /= 2;
+    }
+
+    Some(proof)
+}
+
+// Whether chunk is chunk index of the chunk_count chunks of a body with the root
+pub fn verify_body_chunk(
+    root: &[u8; 32],
+    chunk: &[u8],
+    index: usize,
+    chunk_count: usize,
+    proof: &[[u8; 32]],
+) -> bool {
+    if index >= chunk_count || chunk.len() > BODY_CHUNK_LEN {
+        return false;
+    }
+
+    let mut hash = leaf_hash(chunk);
+    let mut siblings = proof.iter();
+    let (mut index, mut level_len) = (index, chunk_count);
+    while level_len > 1 {
+        if index ^ 1 < level_len {
+            let Some(sibling) = siblings.next() else {
+                return false;
+            };
+            hash = if index % 2 == 0 {
+                node_hash(&hash, sibling)
+            } else {
+                node_hash(sibling, &hash)
+            };
+        }
+        index /= 2;
+        level_len = (level_len + 1) / 2;
+    }
+
+    siblings.next().is_none() && hash == *root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{body_chunk_proof, body_chunks, body_root, verify_body_chunk, BODY_CHUNK_LEN};
+
+    #[test]
+    fn body_chunk_proofs() {
+        assert_eq!(body_chunks(&[]).len(), 1);
+        assert_ne!(body_root(&[]), body_root(&[0]));
+
+        for chunk_count in [1, 2, 3, 5, 8] {
+            let body: Vec<u8> = (0..BODY_CHUNK_LEN * chunk_count - 7)
+                .map(|i| (i % 251) as u8)
+                .collect();
+            let root = body_root(&body);
+            let chunks = body_chunks(&body);
+            assert_eq!(chunks.len(), chunk_count);
+
+            for (index, chunk) in chunks.iter().enumerate() {
+                let proof = body_chunk_proof(&body, index).unwrap();
+                assert!(verify_body_chunk(&root, chunk, index, chunk_count, &proof));
+
+                // a chunk only verifies at its own position
+                let tampered = [&chunk[1..], &[0]].concat();
+                assert!(!verify_body_chunk(
+                    &root,
+                    &tampered,
+                    index,
+                    chunk_count,
+                    &proof
+                ));
+                if chunk_count > 1 {
+                    let other = (index + 1) % chunk_count;
+                    assert!(!verify_body_chunk(&root, chunk, other, chunk_count, &proof));
+                }
+            }
+            assert_eq!(body_chunk_proof(&body, chunk_count), None);
+        }
+
+        // an odd level carries its last node up, it does not duplicate it
+        let body = vec![1; BODY_CHUNK_LEN * 3];
+        let two_chunks = vec![1; BODY_CHUNK_LEN * 2];
+        assert_ne!(body_root(&body), body_root(&two_chunks));
+        assert_eq!(body_chunk_proof(&body, 2).unwrap().len(), 1);
+    }
+}
@@ -1,8 +1,13 @@
+use std::io::{Error, ErrorKind, Read, Write};
+
 use bitcoin::block::Header;
+use bitcoin::consensus::{deserialize, serialize, Params};
 use bitcoin::hash_types::TxMerkleNode;
-use bitcoin::BlockHash;
+use bitcoin::{BlockHash, Network, Target};
+use borsh::{BorshDeserialize, BorshSerialize};
 use serde::{Deserialize, Serialize};
 use sov_rollup_interface::da::BlockHeaderTrait;
+use thiserror::Error;
 
 use super::block_hash::BlockHashWrapper;
 
@@ -14,6 +19,16 @@ pub struct HeaderWrapper {
     pub height: u64,
 }
 
+#[derive(Error, Debug)]
+pub enum HeaderError {
+    #[error("cannot decode header: {0}")]
+    Decode(#[from] bitcoin::consensus::encode::Error),
+    #[error("header hash does not meet its target: {0}")]
+    InvalidPow(#[from] bitcoin::Error),
+    #[error("header target is above the proof of work limit of the network")]
+    TargetAboveLimit,
+}
+
 impl BlockHeaderTrait for HeaderWrapper {
     type Hash = BlockHashWrapper;
 
@@ -43,6 +58,22 @@ impl HeaderWrapper {
         }
     }
 
+    // Builds the header from its 80 byte consensus encoding, as delivered by
+    // backends other than bitcoind (electrum, compact filters). Proof of work is checked
+    // against the target in the header, the target itself is not checked against the chain
+    pub fn from_raw(bytes: [u8; 80], height: u64, tx_count: u32) -> Result<Self, HeaderError> {
+        let header: Header = deserialize(&bytes)?;
+        header.validate_pow(header.target())?;
+
+        Ok(Self::new(header, tx_count, height))
+    }
+
+    pub fn to_raw(&self) -> [u8; 80] {
+        let mut bytes = [0u8; 80];
+        bytes.copy_from_slice(&serialize(&self.header));
+        bytes
+    }
+
     pub fn block_hash(&self) -> BlockHash {
         self.header.block_hash()
     }
@@ -50,4 +81,131 @@ impl HeaderWrapper {
     pub fn merkle_root(&self) -> TxMerkleNode {
         self.header.merkle_root
     }
+
+    // Whether this header directly builds on parent
+    pub fn links_to(&self, parent: &HeaderWrapper) -> bool {
+        self.header.prev_blockhash == parent.block_hash() && self.height == parent.height + 1
+    }
+
+    // Checks the hash meets the target of the header, and the target is within the proof of
+    // work limit of network
+    pub fn check_pow(&self, network: Network) -> Result<(), HeaderError> {
+        self.header.validate_pow(self.header.target())?;
+
+        // the limit is the largest target of the network, though held as a Work
+        let pow_limit = Target::from_be_bytes(Params::new(network).pow_limit.to_be_bytes());
+        if self.header.target() > pow_limit {
+            return Err(HeaderError::TargetAboveLimit);
+        }
+
+        Ok(())
+    }
+
+    // Whether the target is the one of parent, as it must be between two retargets on
+    // networks without minimum difficulty blocks
+    pub fn keeps_target_of(&self, parent: &HeaderWrapper, network: Network) -> bool {
+        let params = Params::new(network);
+        if params.allow_min_difficulty_blocks
+            || (!params.no_pow_retargeting
+                && self.height % params.difficulty_adjustment_interval() == 0)
+        {
+            return true;
+        }
+
+        self.header.bits == parent.header.bits
+    }
+}
+
+// Header is borsh encoded as its 80 byte consensus encoding
+impl BorshSerialize for HeaderWrapper {
+    fn serialize<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_all(&serialize(&self.header))?;
+        BorshSerialize::serialize(&self.tx_count, writer)?;
+        BorshSerialize::serialize(&self.height, writer)
+    }
+}
+
+impl BorshDeserialize for HeaderWrapper {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> std::io::Result<Self> {
+        let mut header = [0u8; 80];
+        reader.read_exact(&mut header)?;
+        let header = deserialize(&header).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+        Ok(Self {
+            header,
+            tx_count: u32::deserialize_reader(reader)?,
+            height: u64::deserialize_reader(reader)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HeaderError, HeaderWrapper};
+
+    // mainnet genesis block header
+    const GENESIS_HEADER: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c";
+    // mainnet block 1 header
+    const BLOCK_1_HEADER: &str = "010000006fe28c0ab6f1b372c1a6a246ae63f74f931e8365e15a089c68d6190000000000982051fd1e4ba744bbbe680e1fee14677ba1a3c3540bf7b1cdb606e857233e0e61bc6649ffff001d01e36299";
+
+    fn header(hex: &str, height: u64) -> HeaderWrapper {
+        let mut bytes = [0u8; 80];
+        bytes.copy_from_slice(&hex::decode(hex).unwrap());
+        HeaderWrapper::from_raw(bytes, height, 1).unwrap()
+    }
+
+    #[test]
+    fn header_from_raw() {
+        let mut bytes = [0u8; 80];
+        bytes.copy_from_slice(&hex::decode(GENESIS_HEADER).unwrap());
+
+        let header = HeaderWrapper::from_raw(bytes, 0, 1).unwrap();
+
+        assert_eq!(
+            header.block_hash().to_string(),
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+        );
+        assert_eq!(header.to_raw(), bytes);
+
+        // changing the nonce breaks the proof of work
+        bytes[79] ^= 1;
+        assert!(matches!(
+            HeaderWrapper::from_raw(bytes, 0, 1),
+            Err(HeaderError::InvalidPow(_))
+        ));
+    }
+
+    #[test]
+    fn header_links_to_parent() {
+        let genesis = header(GENESIS_HEADER, 0);
+        let block_1 = header(BLOCK_1_HEADER, 1);
+
+        assert!(block_1.links_to(&genesis));
+        assert!(!genesis.links_to(&block_1));
+        // right parent at the wrong height
+        assert!(!header(BLOCK_1_HEADER, 2).links_to(&genesis));
+    }
+
+    #[test]
+    fn header_pow() {
+        let genesis = header(GENESIS_HEADER, 0);
+        let block_1 = header(BLOCK_1_HEADER, 1);
+
+        assert!(genesis.check_pow(bitcoin::Network::Bitcoin).is_ok());
+        assert!(block_1.keeps_target_of(&genesis, bitcoin::Network::Bitcoin));
+
+        // a target above the limit of mainnet, met by any hash
+        let mut easy = genesis.header;
+        easy.bits = bitcoin::CompactTarget::from_consensus(0x207fffff);
+        while !easy.target().is_met_by(easy.block_hash()) {
+            easy.nonce += 1;
+        }
+        let easy = HeaderWrapper::new(easy, 1, 1);
+        assert!(matches!(
+            easy.check_pow(bitcoin::Network::Bitcoin),
+            Err(HeaderError::TargetAboveLimit)
+        ));
+        assert!(easy.check_pow(bitcoin::Network::Regtest).is_ok());
+        assert!(!easy.keeps_target_of(&genesis, bitcoin::Network::Bitcoin));
+    }
 }
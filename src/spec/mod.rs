@@ -1,20 +1,25 @@
+use bitcoin::hashes::{sha256, Hash, HashEngine};
 use bitcoin::Transaction;
 use sov_rollup_interface::da::DaSpec;
+use thiserror::Error;
 
 use self::address::AddressWrapper;
-use self::blob::BlobWithSender;
+use self::blob::{BlobWithSender, CompressionAlgorithm};
 use self::block_hash::BlockHashWrapper;
 use self::header::HeaderWrapper;
 use self::proof::InclusionMultiProof;
 use crate::verifier::ChainValidityCondition;
 
 pub mod address;
+pub mod batch_sequence;
 pub mod blob;
 pub mod block;
 mod block_hash;
+pub mod body_root;
 pub mod header;
 #[cfg(feature = "native")]
 pub mod header_stream;
+pub mod precommitment;
 pub mod proof;
 pub mod transaction;
 pub mod utxo;
@@ -22,9 +27,154 @@ pub mod utxo;
 #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct BitcoinSpec;
 
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct RollupParams {
     pub rollup_name: String,
     pub reveal_tx_id_prefix: Vec<u8>,
+    // leading zero bits the reveal txid needs on top of the prefix, for a finer difficulty
+    // than whole prefix bytes. Lower it on regtest, raise it to make spam costlier
+    pub reveal_tx_id_zero_bits: u8,
+    // cap on the relevant txs of a block, see relevant_tx_cap
+    pub max_relevant_txs: Option<u32>,
+    // blobs decompressing to more bytes are dropped like blobs that do not decompress, so
+    // that a decompression bomb cannot exhaust the memory of the verifier
+    // None caps blobs at DEFAULT_MAX_DECOMPRESSED_LEN
+    pub max_decompressed_len: Option<usize>,
+    // blobs must be numbered above the previous ones of their sender, others are dropped as
+    // replays. Only checkpoints go unnumbered, see batch_sequence
+    pub monotonic_batch_numbers: bool,
+}
+
+// Cap on the decompressed blobs of deployments that set none, far above what a block of
+// brotli can carry in practice, far below what a decompression bomb expands to
+pub const DEFAULT_MAX_DECOMPRESSED_LEN: usize = 64 << 20;
+
+// Version of the envelope layout, bumped on changes older parsers cannot read
+pub const ENVELOPE_VERSION: u8 = 1;
+
+// Rollup names are pushed as a single element of the envelope, well below the 520 byte limit
+pub const MAX_ROLLUP_NAME_LEN: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum RollupNameError {
+    #[error("rollup name is empty")]
+    Empty,
+    #[error("rollup name is {0} bytes, at most {MAX_ROLLUP_NAME_LEN} are allowed")]
+    TooLong(usize),
+    #[error(
+        "rollup name contains {0:?}, only ASCII letters, digits, '-', '_' and '.' are allowed"
+    )]
+    InvalidCharacter(char),
+}
+
+// Whether a reveal txid meets the proof of work of a deployment: it starts with prefix
+// and has at least zero_bits leading zero bits
+pub fn meets_reveal_pow(txid: &[u8], prefix: &[u8], zero_bits: u8) -> bool {
+    txid.starts_with(prefix) && leading_zero_bits(txid) >= u32::from(zero_bits)
+}
+
+pub(crate) fn relevant_tx_cap(max_relevant_txs: Option<u32>) -> usize {
+    max_relevant_txs.map_or(usize::MAX, |cap| cap as usize)
+}
+
+fn leading_zero_bits(bytes: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in bytes {
+        if *byte != 0 {
+            return bits + byte.leading_zeros();
+        }
+        bits += 8;
+    }
+    bits
+}
+
+// Checks that the rollup name can be inscribed and is unambiguous to read
+pub fn validate_rollup_name(rollup_name: &str) -> Result<(), RollupNameError> {
+    if rollup_name.is_empty() {
+        return Err(RollupNameError::Empty);
+    }
+
+    if rollup_name.len() > MAX_ROLLUP_NAME_LEN {
+        return Err(RollupNameError::TooLong(rollup_name.len()));
+    }
+
+    match rollup_name
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+    {
+        Some(c) => Err(RollupNameError::InvalidCharacter(c)),
+        None => Ok(()),
+    }
+}
+
+impl RollupParams {
+    pub fn check(&self) -> Result<(), RollupNameError> {
+        validate_rollup_name(&self.rollup_name)
+    }
+
+    pub fn meets_reveal_pow(&self, txid: &[u8]) -> bool {
+        meets_reveal_pow(txid, &self.reveal_tx_id_prefix, self.reveal_tx_id_zero_bits)
+    }
+
+    // Whether any txid is relevant, then reveal txs need no nonce grinding
+    pub fn requires_reveal_pow(&self) -> bool {
+        !self.reveal_tx_id_prefix.is_empty() || self.reveal_tx_id_zero_bits > 0
+    }
+
+    // Number of relevant txs of a block that are read
+    // Consensus rule shared by the service and the verifier: the txs meeting the reveal
+    // proof of work are taken in block order, the ones after the first max_relevant_txs
+    // are ignored as if they missed the proof of work. This bounds the completeness proof
+    // and the work of the verifier when someone grinds many txids of the deployment
+    pub fn relevant_tx_cap(&self) -> usize {
+        relevant_tx_cap(self.max_relevant_txs)
+    }
+
+    // Commits to everything the service and the verifier must agree on to read the same blobs:
+    // rollup name, reveal txid difficulty, envelope version and codecs
+    // Submissions carry it, so blobs of a mismatched deployment can be told apart
+    // The codecs are the ones this build decompresses, so a verifier that cannot read some
+    // algorithm the service posts with has another fingerprint
+    pub fn fingerprint(&self) -> [u8; 8] {
+        self.fingerprint_with_codecs(&CompressionAlgorithm::ALL)
+    }
+
+    fn fingerprint_with_codecs(&self, codecs: &[CompressionAlgorithm]) -> [u8; 8] {
+        let codecs: Vec<u8> = codecs.iter().map(|codec| codec.to_u8()).collect();
+
+        let mut engine = sha256::Hash::engine();
+        engine.input(b"bitcoin-da/chain-params");
+        for field in [
+            self.rollup_name.as_bytes(),
+            &self.reveal_tx_id_prefix,
+            &[ENVELOPE_VERSION],
+            &codecs,
+        ] {
+            // length prefixed so that fields cannot run into each other
+            engine.input(&(field.len() as u64).to_le_bytes());
+            engine.input(field);
+        }
+        // only committed to when set, so deployments that predate it keep their fingerprint
+        if self.reveal_tx_id_zero_bits > 0 {
+            engine.input(&[self.reveal_tx_id_zero_bits]);
+        }
+        if let Some(max_relevant_txs) = self.max_relevant_txs {
+            engine.input(b"max-relevant-txs");
+            engine.input(&max_relevant_txs.to_le_bytes());
+        }
+        if self.monotonic_batch_numbers {
+            engine.input(b"monotonic-batch-numbers");
+        }
+        if let Some(max_decompressed_len) = self.max_decompressed_len {
+            engine.input(b"max-decompressed-len");
+            engine.input(&(max_decompressed_len as u64).to_le_bytes());
+        }
+
+        let hash = sha256::Hash::from_engine(engine).to_byte_array();
+        let mut fingerprint = [0; 8];
+        fingerprint.copy_from_slice(&hash[..8]);
+        fingerprint
+    }
 }
 
 impl DaSpec for BitcoinSpec {
@@ -44,3 +194,131 @@ impl DaSpec for BitcoinSpec {
 
     type ValidityCondition = ChainValidityCondition;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        meets_reveal_pow, validate_rollup_name, RollupNameError, RollupParams, MAX_ROLLUP_NAME_LEN,
+    };
+    use crate::spec::blob::{CompressionAlgorithm, InscriptionMetadata};
+
+    #[test]
+    fn rollup_name() {
+        assert_eq!(validate_rollup_name("sov-btc"), Ok(()));
+        assert_eq!(validate_rollup_name("test_rollup.v2"), Ok(()));
+
+        assert_eq!(validate_rollup_name(""), Err(RollupNameError::Empty));
+        assert_eq!(
+            validate_rollup_name(&"a".repeat(MAX_ROLLUP_NAME_LEN + 1)),
+            Err(RollupNameError::TooLong(MAX_ROLLUP_NAME_LEN + 1))
+        );
+        assert_eq!(
+            validate_rollup_name(&"a".repeat(600)),
+            Err(RollupNameError::TooLong(600))
+        );
+        assert_eq!(
+            validate_rollup_name("sov btc"),
+            Err(RollupNameError::InvalidCharacter(' '))
+        );
+        assert_eq!(
+            validate_rollup_name("sov-bitcoin-ü"),
+            Err(RollupNameError::InvalidCharacter('ü'))
+        );
+    }
+
+    #[test]
+    fn fingerprint() {
+        let params = RollupParams {
+            rollup_name: "sov-btc".to_string(),
+            reveal_tx_id_prefix: vec![0, 0],
+            reveal_tx_id_zero_bits: 0,
+            max_relevant_txs: None,
+            max_decompressed_len: None,
+            monotonic_batch_numbers: false,
+        };
+
+        assert_eq!(params.fingerprint(), params.clone().fingerprint());
+        assert_ne!(
+            params.fingerprint(),
+            RollupParams {
+                reveal_tx_id_prefix: vec![0],
+                ..params.clone()
+            }
+            .fingerprint()
+        );
+        // the boundary between name and prefix is part of the fingerprint
+        assert_ne!(
+            RollupParams {
+                rollup_name: "sov-btc0".to_string(),
+                reveal_tx_id_prefix: vec![],
+                reveal_tx_id_zero_bits: 0,
+                max_relevant_txs: None,
+                max_decompressed_len: None,
+                monotonic_batch_numbers: false,
+            }
+            .fingerprint(),
+            RollupParams {
+                rollup_name: "sov-btc".to_string(),
+                reveal_tx_id_prefix: b"0".to_vec(),
+                reveal_tx_id_zero_bits: 0,
+                max_relevant_txs: None,
+                max_decompressed_len: None,
+                monotonic_batch_numbers: false,
+            }
+            .fingerprint()
+        );
+
+        assert_ne!(
+            params.fingerprint(),
+            RollupParams {
+                reveal_tx_id_zero_bits: 4,
+                ..params.clone()
+            }
+            .fingerprint()
+        );
+        assert_ne!(
+            params.fingerprint(),
+            RollupParams {
+                max_decompressed_len: Some(1 << 20),
+                ..params.clone()
+            }
+            .fingerprint()
+        );
+
+        // a build reading fewer codecs
+        assert_eq!(
+            params.fingerprint(),
+            params.fingerprint_with_codecs(&CompressionAlgorithm::ALL)
+        );
+        assert_ne!(
+            params.fingerprint(),
+            params.fingerprint_with_codecs(&[
+                CompressionAlgorithm::Brotli,
+                CompressionAlgorithm::Gzip,
+                CompressionAlgorithm::None,
+            ])
+        );
+
+        let metadata = InscriptionMetadata {
+            params_fingerprint: Some(params.fingerprint()),
+            ..Default::default()
+        };
+        assert!(metadata.matches_params(params.fingerprint()));
+        assert!(!metadata.matches_params([0; 8]));
+        assert!(InscriptionMetadata::default().matches_params([0; 8]));
+    }
+
+    #[test]
+    fn reveal_pow() {
+        let txid = [0, 0, 0x1f, 0xff];
+
+        assert!(meets_reveal_pow(&txid, &[], 0));
+        assert!(meets_reveal_pow(&txid, &[0, 0], 0));
+        assert!(!meets_reveal_pow(&txid, &[0, 0, 0], 0));
+        // 16 zero bits of the prefix bytes and 3 of the next byte
+        assert!(meets_reveal_pow(&txid, &[], 19));
+        assert!(meets_reveal_pow(&txid, &[0, 0], 19));
+        assert!(!meets_reveal_pow(&txid, &[], 20));
+        assert!(meets_reveal_pow(&[0; 32], &[], u8::MAX));
+    }
+}
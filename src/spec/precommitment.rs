@@ -0,0 +1,107 @@
+// Pre-commitment to a blob: a cheap tx with an OP_RETURN output committing to the hash of the
+// blob, posted before the inscription. The reveal tx carries the txid of the pre-commitment in
+// its envelope, so the rollup can order the blob at the block of the pre-commitment while the
+// body is inscribed later, e.g. once fees are lower
+// The blob hash is the sha256d of the compressed body, i.e. BlobWithSender::hash
+
+use bitcoin::blockdata::opcodes::all::OP_RETURN;
+use bitcoin::blockdata::script::{Builder, Instruction, PushBytesBuf};
+use bitcoin::hashes::Hash;
+use bitcoin::{ScriptBuf, Transaction};
+
+use super::blob::BlobWithSender;
+
+// Start of the OP_RETURN payload, followed by the blob hash
+pub const PRECOMMITMENT_MAGIC: &[u8] = b"bda/precommit";
+
+// Script of the OP_RETURN output committing to blob_hash
+pub fn precommitment_script(blob_hash: [u8; 32]) -> ScriptBuf {
+    let mut payload = PRECOMMITMENT_MAGIC.to_vec();
+    payload.extend_from_slice(&blob_hash);
+
+    Builder::new()
+        .push_opcode(OP_RETURN)
+        .push_slice(PushBytesBuf::try_from(payload).expect("Cannot push pre-commitment"))
+        .into_script()
+}
+
+// Blob hashes the outputs of tx pre-commit to, in output order
+pub fn parse_precommitments(tx: &Transaction) -> Vec<[u8; 32]> {
+    tx.output
+        .iter()
+        .filter(|output| output.script_pubkey.is_op_return())
+        .filter_map(|output| {
+            let mut instructions = output.script_pubkey.instructions().skip(1);
+            match (instructions.next(), instructions.next()) {
+                (Some(Ok(Instruction::PushBytes(payload))), None) => payload
+                    .as_bytes()
+                    .strip_prefix(PRECOMMITMENT_MAGIC)
+                    .and_then(|blob_hash| blob_hash.try_into().ok()),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+// Whether blob was pre-committed to by precommitment_tx, i.e. its envelope names the tx and
+// the tx commits to its hash. Blobs without a pre-commitment never match
+pub fn verify_precommitment(blob: &BlobWithSender, precommitment_tx: &Transaction) -> bool {
+    blob.metadata.precommitment == Some(precommitment_tx.txid().to_byte_array())
+        && parse_precommitments(precommitment_tx).contains(&blob.hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::absolute::LockTime;
+    use bitcoin::hashes::Hash;
+    use bitcoin::{ScriptBuf, Transaction, TxOut};
+
+    use super::{parse_precommitments, precommitment_script, verify_precommitment};
+    use crate::spec::blob::{BlobWithSender, InscriptionMetadata};
+
+    fn tx(scripts: Vec<ScriptBuf>) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: scripts
+                .into_iter()
+                .map(|script_pubkey| TxOut {
+                    value: 0,
+                    script_pubkey,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn precommitment() {
+        let script = precommitment_script([1; 32]);
+        assert!(script.is_op_return());
+        // well below the 83 bytes of a standard OP_RETURN output
+        assert_eq!(script.len(), 47);
+
+        let precommitment_tx = tx(vec![
+            ScriptBuf::new_op_return(&[2; 32]),
+            script,
+            precommitment_script([3; 32]),
+        ]);
+        assert_eq!(
+            parse_precommitments(&precommitment_tx),
+            vec![[1; 32], [3; 32]]
+        );
+
+        let blob = BlobWithSender::new(vec![0], vec![5; 33], [1; 32]);
+        assert!(!verify_precommitment(&blob, &precommitment_tx));
+
+        let blob = blob.with_metadata(InscriptionMetadata {
+            precommitment: Some(precommitment_tx.txid().to_byte_array()),
+            ..Default::default()
+        });
+        assert!(verify_precommitment(&blob, &precommitment_tx));
+        assert!(!verify_precommitment(
+            &blob,
+            &tx(vec![precommitment_script([1; 32])])
+        ));
+    }
+}
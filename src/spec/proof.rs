@@ -1,7 +1,162 @@
+use std::io::{Error, ErrorKind, Read, Write};
+
+use bitcoin::consensus::{deserialize, serialize};
+use borsh::{BorshDeserialize, BorshSerialize};
 use serde::{Deserialize, Serialize};
 
+use super::batch_sequence::BatchSequence;
+use super::blob::BlobWithSender;
+use super::header::HeaderWrapper;
+use super::transaction::Transaction;
+
+// Version of the borsh encoding of the proof types: HeaderWrapper, InclusionMultiProof,
+// BlobWithSender and ChainValidityCondition. Prover and verifier must agree on it, the
+// golden tests below freeze the layout so that it cannot change silently
+// To change the layout on purpose, bump the version and regenerate the golden files of the
+// new version with BITCOIN_DA_UPDATE_GOLDEN=1 cargo test golden, the ones of older versions
+// are kept as a record
+pub const PROOF_FORMAT_VERSION: u8 = 4;
+
 // Set of proofs for inclusion of a transaction in a block
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
 pub struct InclusionMultiProof {
     pub txs: Vec<[u8; 32]>,
 }
+
+// Everything verify_relevant_tx_list needs for a single DA block
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProofInput {
+    pub header: HeaderWrapper,
+    pub blobs: Vec<BlobWithSender>,
+    pub inclusion_proof: InclusionMultiProof,
+    pub completeness_proof: Vec<Transaction>,
+    // batch sequence before the block, if batch numbers must increase
+    pub batch_sequence: Option<BatchSequence>,
+}
+
+// Completeness proof is borsh encoded as the consensus encoding of the transactions
+impl BorshSerialize for ProofInput {
+    fn serialize<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        BorshSerialize::serialize(&self.header, writer)?;
+        BorshSerialize::serialize(&self.blobs, writer)?;
+        BorshSerialize::serialize(&self.inclusion_proof, writer)?;
+        BorshSerialize::serialize(&serialize(&self.completeness_proof), writer)?;
+        BorshSerialize::serialize(&self.batch_sequence, writer)
+    }
+}
+
+impl BorshDeserialize for ProofInput {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> std::io::Result<Self> {
+        let header = HeaderWrapper::deserialize_reader(reader)?;
+        let blobs = Vec::<BlobWithSender>::deserialize_reader(reader)?;
+        let inclusion_proof = InclusionMultiProof::deserialize_reader(reader)?;
+        let completeness_proof = deserialize(&Vec::<u8>::deserialize_reader(reader)?)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        let batch_sequence = Option::<BatchSequence>::deserialize_reader(reader)?;
+
+        Ok(Self {
+            header,
+            blobs,
+            inclusion_proof,
+            completeness_proof,
+            batch_sequence,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::fmt::Debug;
+
+    use borsh::{BorshDeserialize, BorshSerialize};
+
+    use super::{InclusionMultiProof, PROOF_FORMAT_VERSION};
+    use crate::spec::batch_sequence::BatchSequence;
+    use crate::spec::blob::{
+        BlobKind, BlobWithSender, ChunkManifest, CompressionAlgorithm, InscriptionMetadata,
+        SignatureScheme,
+    };
+    use crate::spec::header::HeaderWrapper;
+    use crate::verifier::ChainValidityCondition;
+
+    // mainnet genesis block header
+    const GENESIS_HEADER: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c";
+
+    // Checks the encoding of value against its golden file, and that the golden file decodes
+    // to value, or writes the golden file when BITCOIN_DA_UPDATE_GOLDEN is set
+    fn check_golden<T: BorshSerialize + BorshDeserialize + PartialEq + Debug>(
+        name: &str,
+        value: &T,
+    ) {
+        let path = format!("test_data/golden/v{PROOF_FORMAT_VERSION}/{name}.hex");
+        let encoded = value.try_to_vec().unwrap();
+
+        if std::env::var("BITCOIN_DA_UPDATE_GOLDEN").is_ok() {
+            std::fs::create_dir_all(format!("test_data/golden/v{PROOF_FORMAT_VERSION}")).unwrap();
+            std::fs::write(&path, hex::encode(&encoded) + "\n").unwrap();
+            return;
+        }
+
+        let golden = hex::decode(std::fs::read_to_string(&path).unwrap().trim()).unwrap();
+        assert_eq!(
+            hex::encode(&encoded),
+            hex::encode(&golden),
+            "borsh encoding of {name} changed, see PROOF_FORMAT_VERSION"
+        );
+        assert_eq!(&T::try_from_slice(&golden).unwrap(), value);
+    }
+
+    #[test]
+    fn golden_proof_encoding() {
+        let mut header = [0u8; 80];
+        header.copy_from_slice(&hex::decode(GENESIS_HEADER).unwrap());
+        check_golden(
+            "header_wrapper",
+            &HeaderWrapper::from_raw(header, 0, 1).unwrap(),
+        );
+
+        check_golden(
+            "inclusion_multi_proof",
+            &InclusionMultiProof {
+                txs: vec![[1; 32], [2; 32]],
+            },
+        );
+
+        // every metadata field is set, so that a new one changes the layout
+        let blob = BlobWithSender::new(vec![1, 2, 3, 4], vec![5; 33], [6; 32]).with_metadata(
+            InscriptionMetadata {
+                batch_number: Some(7),
+                timestamp: Some(1_700_000_000),
+                kind: Some(BlobKind::Checkpoint),
+                params_fingerprint: Some([8; 8]),
+                chunk: Some(ChunkManifest {
+                    index: 1,
+                    total: 2,
+                    blob_hash: [9; 32],
+                }),
+                signature_scheme: Some(SignatureScheme::Schnorr),
+                compression: Some(CompressionAlgorithm::Zstd),
+                precommitment: Some([10; 32]),
+            },
+        );
+        check_golden("blob_with_sender", &blob);
+        check_golden(
+            "blob_with_sender_no_metadata",
+            &BlobWithSender::new(vec![], vec![5; 33], [6; 32]),
+        );
+
+        let mut batch_sequence = BatchSequence::new();
+        assert!(batch_sequence.accept(&[5; 33], Some(7), BlobKind::Batch));
+        check_golden("batch_sequence", &batch_sequence);
+
+        check_golden(
+            "chain_validity_condition",
+            &ChainValidityCondition {
+                prev_hash: [10; 32],
+                block_hash: [11; 32],
+                prev_batch_sequence: [12; 32],
+                batch_sequence: [13; 32],
+            },
+        );
+    }
+}
@@ -1,2 +1,181 @@
+use std::io::{Error, ErrorKind, Read, Write};
+
+use bitcoin::block::Block;
+use bitcoin::consensus::{deserialize, serialize};
+use bitcoin::hash_types::{WitnessCommitment, WitnessMerkleNode};
+use bitcoin::hashes::Hash;
+use bitcoin::{merkle_tree, Witness, Wtxid};
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+
 // pub use bitcoin::Transaction;
 pub type Transaction = bitcoin::Transaction;
+
+/// Compact form of a completeness proof transaction for long term archival
+/// Witnesses of all inputs except the first one, which carries the envelope, are stripped, so
+/// the blobs can still be read and the txid is unchanged. If any was stripped, the original
+/// wtxid is kept since it can no longer be computed, the block commits to it through its
+/// witness commitment, see WitnessCommitmentProof
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompactTransaction {
+    pub tx: Transaction,
+    // None if no witness was stripped
+    pub stripped_wtxid: Option<Wtxid>,
+}
+
+impl CompactTransaction {
+    pub fn new(tx: &Transaction) -> Self {
+        let mut compact_tx = tx.clone();
+
+        let mut stripped = false;
+        for input in compact_tx.input.iter_mut().skip(1) {
+            stripped |= !input.witness.is_empty();
+            input.witness = Witness::new();
+        }
+
+        Self {
+            tx: compact_tx,
+            stripped_wtxid: stripped.then(|| tx.wtxid()),
+        }
+    }
+
+    // Whether the witness of any input after the envelope one was stripped
+    pub fn is_stripped(&self) -> bool {
+        self.stripped_wtxid.is_some()
+    }
+
+    // wtxid of the original tx
+    pub fn wtxid(&self) -> Wtxid {
+        self.stripped_wtxid.unwrap_or_else(|| self.tx.wtxid())
+    }
+
+    // Puts the witnesses of the inputs after the envelope one back, None unless they are the
+    // stripped ones
+    pub fn restore(&self, witnesses: Vec<Witness>) -> Option<Transaction> {
+        if witnesses.len() + 1 != self.tx.input.len() {
+            return None;
+        }
+
+        let mut tx = self.tx.clone();
+        for (input, witness) in tx.input.iter_mut().skip(1).zip(witnesses) {
+            input.witness = witness;
+        }
+
+        (tx.wtxid() == self.wtxid()).then_some(tx)
+    }
+}
+
+/// Witness commitment of a block, which commits to the wtxids of the compact transactions
+/// with stripped witnesses. The coinbase tx is bound to the block by its txid in the inclusion proof
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WitnessCommitmentProof {
+    pub coinbase_tx: Transaction,
+    // wtxids of the txs of the block in order, the one of the coinbase tx is zero
+    pub wtxids: Vec<[u8; 32]>,
+}
+
+impl WitnessCommitmentProof {
+    pub fn new(block_txs: &[Transaction]) -> Self {
+        Self {
+            coinbase_tx: block_txs.first().cloned().expect("block has a coinbase tx"),
+            wtxids: block_txs
+                .iter()
+                .enumerate()
+                .map(|(i, tx)| {
+                    if i == 0 {
+                        [0; 32]
+                    } else {
+                        tx.wtxid().to_byte_array()
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    // Whether the coinbase tx commits to the wtxids, in its last output carrying a witness
+    // commitment as consensus requires
+    pub fn is_committed(&self) -> bool {
+        const MAGIC: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+
+        let Some(commitment) = self
+            .coinbase_tx
+            .output
+            .iter()
+            .rev()
+            .map(|output| output.script_pubkey.as_bytes())
+            .find(|script| script.len() >= 38 && script[..6] == MAGIC)
+        else {
+            return false;
+        };
+        let Some(reserved_value) = self
+            .coinbase_tx
+            .input
+            .first()
+            .filter(|input| input.witness.len() == 1)
+            .and_then(|input| input.witness.nth(0))
+            .filter(|reserved_value| reserved_value.len() == 32)
+        else {
+            return false;
+        };
+        let Some(witness_root) = merkle_tree::calculate_root(
+            self.wtxids
+                .iter()
+                .map(|wtxid| Wtxid::from_byte_array(*wtxid).to_raw_hash()),
+        ) else {
+            return false;
+        };
+
+        Block::compute_witness_commitment(
+            &WitnessMerkleNode::from_raw_hash(witness_root),
+            reserved_value,
+        ) == WitnessCommitment::from_slice(&commitment[6..38]).expect("commitment is 32 bytes")
+    }
+}
+
+pub fn compact_completeness_proof(completeness_proof: &[Transaction]) -> Vec<CompactTransaction> {
+    completeness_proof
+        .iter()
+        .map(CompactTransaction::new)
+        .collect()
+}
+
+impl BorshSerialize for CompactTransaction {
+    fn serialize<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        BorshSerialize::serialize(&serialize(&self.tx), writer)?;
+        BorshSerialize::serialize(
+            &self.stripped_wtxid.map(|wtxid| wtxid.to_byte_array()),
+            writer,
+        )
+    }
+}
+
+impl BorshDeserialize for CompactTransaction {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> std::io::Result<Self> {
+        let tx = deserialize(&Vec::<u8>::deserialize_reader(reader)?)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        let stripped_wtxid =
+            Option::<[u8; 32]>::deserialize_reader(reader)?.map(Wtxid::from_byte_array);
+
+        Ok(Self { tx, stripped_wtxid })
+    }
+}
+
+impl BorshSerialize for WitnessCommitmentProof {
+    fn serialize<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        BorshSerialize::serialize(&serialize(&self.coinbase_tx), writer)?;
+        BorshSerialize::serialize(&self.wtxids, writer)
+    }
+}
+
+impl BorshDeserialize for WitnessCommitmentProof {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> std::io::Result<Self> {
+        let coinbase_tx = deserialize(&Vec::<u8>::deserialize_reader(reader)?)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        let wtxids = Vec::<[u8; 32]>::deserialize_reader(reader)?;
+
+        Ok(Self {
+            coinbase_tx,
+            wtxids,
+        })
+    }
+}
@@ -1,6 +1,119 @@
-use bitcoin::Txid;
+use bitcoin::script::PushBytesBuf;
+use bitcoin::secp256k1::{Secp256k1, SecretKey};
+use bitcoin::{script, Address, Network, PublicKey, Script, ScriptBuf, Txid, Witness};
 use serde::{Deserialize, Serialize};
 
+// DER encoded ecdsa signature with sighash flag, upper bound used by Core for estimation
+const ECDSA_SIGNATURE_SIZE: usize = 72;
+// schnorr signature with default sighash
+const SCHNORR_SIGNATURE_SIZE: usize = 64;
+
+// Script types of the wallet outputs that can fund a commit tx
+// Each is spent by a single (compressed) key, so the size of its signed input is known
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputScriptType {
+    // key path spend
+    P2tr,
+    P2wpkh,
+    P2shP2wpkh,
+    P2pkh,
+}
+
+impl InputScriptType {
+    // P2SH outputs are assumed to wrap P2WPKH, the only P2SH type wallets create for single keys
+    pub fn from_script_pubkey(script_pubkey: &Script) -> Option<Self> {
+        if script_pubkey.is_v1_p2tr() {
+            Some(InputScriptType::P2tr)
+        } else if script_pubkey.is_v0_p2wpkh() {
+            Some(InputScriptType::P2wpkh)
+        } else if script_pubkey.is_p2sh() {
+            Some(InputScriptType::P2shP2wpkh)
+        } else if script_pubkey.is_p2pkh() {
+            Some(InputScriptType::P2pkh)
+        } else {
+            None
+        }
+    }
+
+    // Key behind the placeholder addresses and signatures, it is not secret
+    fn placeholder_public_key() -> PublicKey {
+        let secret_key = SecretKey::from_slice(&[1; 32]).expect("Valid secret key");
+        PublicKey::new(secret_key.public_key(&Secp256k1::new()))
+    }
+
+    // Placeholder address of this type
+    pub fn address(&self, network: Network) -> Address {
+        self.address_of(&Self::placeholder_public_key(), network)
+    }
+
+    // Script pubkey of this type locked to public_key
+    pub fn script_pubkey(&self, public_key: &PublicKey) -> ScriptBuf {
+        // script pubkeys do not depend on the network
+        self.address_of(public_key, Network::Bitcoin)
+            .script_pubkey()
+    }
+
+    fn address_of(&self, public_key: &PublicKey, network: Network) -> Address {
+        let public_key = *public_key;
+
+        match self {
+            InputScriptType::P2tr => Address::p2tr(
+                &Secp256k1::new(),
+                public_key.inner.x_only_public_key().0,
+                None,
+                network,
+            ),
+            InputScriptType::P2wpkh => {
+                Address::p2wpkh(&public_key, network).expect("Compressed public key")
+            }
+            InputScriptType::P2shP2wpkh => {
+                Address::p2shwpkh(&public_key, network).expect("Compressed public key")
+            }
+            InputScriptType::P2pkh => Address::p2pkh(&public_key, network),
+        }
+    }
+
+    // Script sig of a signed input with a placeholder signature of the largest size
+    pub fn script_sig(&self) -> ScriptBuf {
+        let public_key = Self::placeholder_public_key();
+
+        match self {
+            InputScriptType::P2tr | InputScriptType::P2wpkh => ScriptBuf::new(),
+            InputScriptType::P2shP2wpkh => {
+                let redeem_script = ScriptBuf::new_v0_p2wpkh(
+                    &public_key.wpubkey_hash().expect("Compressed public key"),
+                );
+                script::Builder::new()
+                    .push_slice(
+                        PushBytesBuf::try_from(redeem_script.into_bytes())
+                            .expect("Cannot push redeem script"),
+                    )
+                    .into_script()
+            }
+            InputScriptType::P2pkh => script::Builder::new()
+                .push_slice([0u8; ECDSA_SIGNATURE_SIZE])
+                .push_key(&public_key)
+                .into_script(),
+        }
+    }
+
+    // Witness of a signed input with a placeholder signature of the largest size
+    pub fn witness(&self) -> Witness {
+        let mut witness = Witness::new();
+
+        match self {
+            InputScriptType::P2tr => witness.push([0u8; SCHNORR_SIGNATURE_SIZE]),
+            InputScriptType::P2wpkh | InputScriptType::P2shP2wpkh => {
+                witness.push([0u8; ECDSA_SIGNATURE_SIZE]);
+                witness.push(Self::placeholder_public_key().to_bytes());
+            }
+            InputScriptType::P2pkh => {}
+        }
+
+        witness
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize)]
 pub struct UTXO {
     pub tx_id: Txid,
@@ -11,6 +124,16 @@ pub struct UTXO {
     pub confirmations: u64,
     pub spendable: bool,
     pub solvable: bool,
+    // unconfirmed txs the output depends on, its own tx included, None once confirmed
+    pub ancestor_count: Option<u32>,
+}
+
+impl UTXO {
+    // None if the script pubkey is not a single key type
+    pub fn script_type(&self) -> Option<InputScriptType> {
+        let script_pubkey = ScriptBuf::from_hex(&self.script_pubkey).ok()?;
+        InputScriptType::from_script_pubkey(&script_pubkey)
+    }
 }
 
 // Temporary struct to deserialize UTXO from JSON
@@ -25,6 +148,8 @@ struct RawUTXO {
     confirmations: u64,
     spendable: bool,
     solvable: bool,
+    #[serde(default)]
+    ancestorcount: Option<u32>,
 }
 
 // Deserialize UTXO from JSON
@@ -43,6 +168,7 @@ impl<'de> serde::Deserialize<'de> for UTXO {
             confirmations: raw_utxo.confirmations,
             spendable: raw_utxo.spendable,
             solvable: raw_utxo.solvable,
+            ancestor_count: raw_utxo.ancestorcount,
         })
     }
 }
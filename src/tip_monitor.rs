@@ -0,0 +1,359 @@
+use std::time::{Duration, Instant};
+
+use bitcoin::Network;
+use thiserror::Error;
+
+use crate::spec::header::HeaderWrapper;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum TipAlarm {
+    #[error(
+        "node tip at height {node_height} diverged from validated header at height {local_height} \
+        by {blocks} blocks for {duration:?}"
+    )]
+    Divergence {
+        local_height: u64,
+        node_height: u64,
+        blocks: u64,
+        duration: Duration,
+    },
+    #[error("node tip stuck at height {node_height} for {duration:?}")]
+    Stalled {
+        node_height: u64,
+        duration: Duration,
+    },
+    #[error("node returned an invalid header at height {height}: {reason}")]
+    InvalidHeader { height: u64, reason: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TipStatus {
+    InSync,
+    // node disagrees with the validated headers, but not for long enough to raise the alarm
+    Diverged { blocks: u64, since: Instant },
+}
+
+// ChainTipMonitor compares the tip reported by the node with the last header the service
+// validated. A divergence lasting more than max_blocks or max_duration, a node tip that does
+// not advance for more than max_stall, or a header that fails validation raises an alarm
+// that stays raised until it is cleared manually
+#[derive(Debug, Clone)]
+pub struct ChainTipMonitor {
+    max_blocks: u64,
+    max_duration: Duration,
+    max_stall: Duration,
+    network: Network,
+    // last validated header, its hash is computed here rather than taken from the node
+    local_tip: Option<HeaderWrapper>,
+    // highest node tip seen, and when it was first seen
+    node_tip: Option<(u64, Instant)>,
+    diverged_since: Option<Instant>,
+    alarm: Option<TipAlarm>,
+}
+
+impl ChainTipMonitor {
+    pub fn new(
+        max_blocks: u64,
+        max_duration: Duration,
+        max_stall: Duration,
+        network: Network,
+    ) -> Self {
+        Self {
+            max_blocks,
+            max_duration,
+            max_stall,
+            network,
+            local_tip: None,
+            node_tip: None,
+            diverged_since: None,
+            alarm: None,
+        }
+    }
+
+    // (height, hash) of the last validated header
+    pub fn local_tip(&self) -> Option<(u64, String)> {
+        self.local_tip
+            .as_ref()
+            .map(|header| (header.height, header.block_hash().to_string()))
+    }
+
+    // Extends the local tip with headers, ordered from the child of the local tip to the new
+    // tip, or a single header when nothing was validated yet. Each header must meet its proof
+    // of work and link to the one before it, so the node cannot move the local tip to a chain
+    // that does not build on it
+    pub fn record_validated_tip(&mut self, headers: &[HeaderWrapper]) -> Result<(), TipAlarm> {
+        if let Some(alarm) = &self.alarm {
+            return Err(alarm.clone());
+        }
+
+        let mut parent = self.local_tip.as_ref();
+        for header in headers {
+            if let Err(reason) = self.check_header(header, parent) {
+                let alarm = TipAlarm::InvalidHeader {
+                    height: header.height,
+                    reason,
+                };
+                self.alarm = Some(alarm.clone());
+
+                return Err(alarm);
+            }
+            parent = Some(header);
+        }
+
+        if let Some(tip) = headers.last() {
+            self.local_tip = Some(tip.clone());
+        }
+
+        Ok(())
+    }
+
+    fn check_header(
+        &self,
+        header: &HeaderWrapper,
+        parent: Option<&HeaderWrapper>,
+    ) -> Result<(), String> {
+        header.check_pow(self.network).map_err(|e| e.to_string())?;
+
+        let Some(parent) = parent else {
+            return Ok(());
+        };
+        if !header.links_to(parent) {
+            return Err(format!(
+                "does not link to the header at height {}",
+                parent.height
+            ));
+        }
+        if !header.keeps_target_of(parent, self.network) {
+            return Err("target changed outside of a retarget".to_string());
+        }
+
+        Ok(())
+    }
+
+    // node_hash_at_local_tip is the node's block hash at the height of the local tip,
+    // None if the node does not have a block at that height
+    pub fn observe_node_tip(
+        &mut self,
+        node_height: u64,
+        node_hash_at_local_tip: Option<&str>,
+        now: Instant,
+    ) -> Result<TipStatus, TipAlarm> {
+        if let Some(alarm) = &self.alarm {
+            return Err(alarm.clone());
+        }
+
+        let advanced_at = match self.node_tip {
+            Some((height, at)) if node_height <= height => at,
+            _ => {
+                self.node_tip = Some((node_height, now));
+                now
+            }
+        };
+        let stalled_for = now.duration_since(advanced_at);
+        if stalled_for > self.max_stall {
+            let alarm = TipAlarm::Stalled {
+                node_height,
+                duration: stalled_for,
+            };
+            self.alarm = Some(alarm.clone());
+
+            return Err(alarm);
+        }
+
+        let Some((local_height, local_hash)) = self.local_tip() else {
+            return Ok(TipStatus::InSync);
+        };
+
+        let blocks = if node_height < local_height {
+            // node is behind the headers we already validated
+            local_height - node_height
+        } else if node_hash_at_local_tip != Some(local_hash.as_str()) {
+            // node is on another fork, count the blocks it has built on it
+            node_height - local_height + 1
+        } else {
+            self.diverged_since = None;
+            return Ok(TipStatus::InSync);
+        };
+
+        let since = *self.diverged_since.get_or_insert(now);
+        let duration = now.duration_since(since);
+
+        if blocks > self.max_blocks || duration > self.max_duration {
+            let alarm = TipAlarm::Divergence {
+                local_height,
+                node_height,
+                blocks,
+                duration,
+            };
+            self.alarm = Some(alarm.clone());
+
+            return Err(alarm);
+        }
+
+        Ok(TipStatus::Diverged { blocks, since })
+    }
+
+    pub fn alarm(&self) -> Option<&TipAlarm> {
+        self.alarm.as_ref()
+    }
+
+    pub fn clear_alarm(&mut self) {
+        self.alarm = None;
+        self.node_tip = None;
+        self.diverged_since = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use bitcoin::consensus::deserialize;
+    use bitcoin::Network;
+
+    use super::{ChainTipMonitor, TipAlarm, TipStatus};
+    use crate::spec::header::HeaderWrapper;
+
+    // mainnet headers of blocks 0 to 2
+    const HEADERS: [&str; 3] = [
+        "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c",
+        "010000006fe28c0ab6f1b372c1a6a246ae63f74f931e8365e15a089c68d6190000000000982051fd1e4ba744bbbe680e1fee14677ba1a3c3540bf7b1cdb606e857233e0e61bc6649ffff001d01e36299",
+        "010000004860eb18bf1b1620e37e9490fc8a427514416fd75159ab86688e9a8300000000d5fdcc541e25de1c7a5addedf24858b8bb665c9f36ef744ee42c316022c90f9bb0bc6649ffff001d08d2bd61",
+    ];
+
+    fn header(height: usize) -> HeaderWrapper {
+        HeaderWrapper::new(
+            deserialize(&hex::decode(HEADERS[height]).unwrap()).unwrap(),
+            1,
+            height as u64,
+        )
+    }
+
+    fn hash(height: usize) -> String {
+        header(height).block_hash().to_string()
+    }
+
+    fn new_monitor(max_blocks: u64) -> ChainTipMonitor {
+        ChainTipMonitor::new(
+            max_blocks,
+            Duration::from_secs(600),
+            Duration::from_secs(3_600),
+            Network::Bitcoin,
+        )
+    }
+
+    #[test]
+    fn tip_divergence_alarm() {
+        let mut monitor = new_monitor(1);
+        let start = Instant::now();
+
+        // nothing validated yet
+        assert_eq!(
+            monitor.observe_node_tip(5, None, start),
+            Ok(TipStatus::InSync)
+        );
+
+        monitor.record_validated_tip(&[header(1)]).unwrap();
+        assert_eq!(monitor.local_tip(), Some((1, hash(1))));
+
+        assert_eq!(
+            monitor.observe_node_tip(5, Some(&hash(1)), start),
+            Ok(TipStatus::InSync)
+        );
+
+        // node switched to another fork, tolerated for a while
+        assert_eq!(
+            monitor.observe_node_tip(1, Some(&hash(2)), start),
+            Ok(TipStatus::Diverged {
+                blocks: 1,
+                since: start
+            })
+        );
+
+        // back in sync resets the divergence
+        assert_eq!(
+            monitor.observe_node_tip(5, Some(&hash(1)), start),
+            Ok(TipStatus::InSync)
+        );
+
+        // node is behind for too many blocks
+        monitor.record_validated_tip(&[header(2)]).unwrap();
+        assert_eq!(
+            monitor.observe_node_tip(0, None, start),
+            Err(TipAlarm::Divergence {
+                local_height: 2,
+                node_height: 0,
+                blocks: 2,
+                duration: Duration::ZERO
+            })
+        );
+
+        // alarm is persistent
+        assert!(monitor.observe_node_tip(5, Some(&hash(2)), start).is_err());
+        assert!(monitor.alarm().is_some());
+
+        monitor.clear_alarm();
+        assert_eq!(
+            monitor.observe_node_tip(5, Some(&hash(2)), start),
+            Ok(TipStatus::InSync)
+        );
+
+        // divergence lasting too long
+        assert!(monitor.observe_node_tip(1, None, start).is_ok());
+        assert!(monitor
+            .observe_node_tip(1, None, start + Duration::from_secs(601))
+            .is_err());
+    }
+
+    #[test]
+    fn tip_stall_alarm() {
+        let mut monitor = new_monitor(3);
+        let start = Instant::now();
+
+        assert!(monitor.observe_node_tip(5, None, start).is_ok());
+        // the tip advanced, the stall is measured from there
+        let advanced = start + Duration::from_secs(3_000);
+        assert!(monitor.observe_node_tip(6, None, advanced).is_ok());
+        assert!(monitor
+            .observe_node_tip(6, None, start + Duration::from_secs(3_601))
+            .is_ok());
+
+        assert_eq!(
+            monitor.observe_node_tip(6, None, advanced + Duration::from_secs(3_601)),
+            Err(TipAlarm::Stalled {
+                node_height: 6,
+                duration: Duration::from_secs(3_601)
+            })
+        );
+    }
+
+    #[test]
+    fn validated_headers() {
+        let mut monitor = new_monitor(3);
+        monitor.record_validated_tip(&[header(0)]).unwrap();
+        monitor
+            .record_validated_tip(&[header(1), header(2)])
+            .unwrap();
+        assert_eq!(monitor.local_tip(), Some((2, hash(2))));
+
+        // headers that skip a block do not link to the local tip
+        let mut skipping = new_monitor(3);
+        skipping.record_validated_tip(&[header(0)]).unwrap();
+        assert!(matches!(
+            skipping.record_validated_tip(&[header(2)]),
+            Err(TipAlarm::InvalidHeader { height: 2, .. })
+        ));
+        assert_eq!(skipping.local_tip(), Some((0, hash(0))));
+
+        // a header whose hash does not meet its target
+        let mut forged =
+            deserialize::<bitcoin::block::Header>(&hex::decode(HEADERS[1]).unwrap()).unwrap();
+        forged.nonce += 1;
+        let mut monitor = new_monitor(3);
+        monitor.record_validated_tip(&[header(0)]).unwrap();
+        assert!(matches!(
+            monitor.record_validated_tip(&[HeaderWrapper::new(forged, 1, 1)]),
+            Err(TipAlarm::InvalidHeader { height: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,271 @@
+// Weight diff of an estimated tx against the tx that was finally signed and broadcast, to
+// diagnose fee estimation discrepancies. The service traces it for every commit tx, e.g.
+// RUST_LOG=bitcoin_da=trace
+
+use core::fmt;
+
+use bitcoin::consensus::encode::{serialize, VarInt};
+use bitcoin::Transaction;
+
+// Serialized part of a transaction and its weight, non witness bytes weigh 4 units each
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Component {
+    name: String,
+    bytes: Vec<u8>,
+    weight: usize,
+}
+
+impl Component {
+    fn base(name: String, bytes: Vec<u8>) -> Self {
+        let weight = bytes.len() * 4;
+        Self {
+            name,
+            bytes,
+            weight,
+        }
+    }
+
+    fn witness(name: String, bytes: Vec<u8>) -> Self {
+        let weight = bytes.len();
+        Self {
+            name,
+            bytes,
+            weight,
+        }
+    }
+}
+
+// Components of tx in serialization order, their weights sum up to the weight of the tx
+fn components(tx: &Transaction) -> Vec<Component> {
+    let segwit = tx.input.iter().any(|input| !input.witness.is_empty());
+    let mut components = vec![Component::base("version".into(), serialize(&tx.version))];
+
+    if segwit {
+        components.push(Component::witness("segwit marker".into(), vec![0, 1]));
+    }
+
+    components.push(Component::base(
+        "input count".into(),
+        serialize(&VarInt(tx.input.len() as u64)),
+    ));
+    for (index, input) in tx.input.iter().enumerate() {
+        let script_sig = input.script_sig.as_bytes();
+        components.push(Component::base(
+            format!("input {} outpoint", index),
+            serialize(&input.previous_output),
+        ));
+        components.push(Component::base(
+            format!("input {} script sig length", index),
+            serialize(&VarInt(script_sig.len() as u64)),
+        ));
+        components.push(Component::base(
+            format!("input {} script sig", index),
+            script_sig.to_vec(),
+        ));
+        components.push(Component::base(
+            format!("input {} sequence", index),
+            serialize(&input.sequence),
+        ));
+    }
+
+    components.push(Component::base(
+        "output count".into(),
+        serialize(&VarInt(tx.output.len() as u64)),
+    ));
+    for (index, output) in tx.output.iter().enumerate() {
+        let script_pubkey = output.script_pubkey.as_bytes();
+        components.push(Component::base(
+            format!("output {} value", index),
+            serialize(&output.value),
+        ));
+        components.push(Component::base(
+            format!("output {} script pubkey length", index),
+            serialize(&VarInt(script_pubkey.len() as u64)),
+        ));
+        components.push(Component::base(
+            format!("output {} script pubkey", index),
+            script_pubkey.to_vec(),
+        ));
+    }
+
+    // witnesses come after the outputs, one per input even if empty
+    if segwit {
+        for (index, input) in tx.input.iter().enumerate() {
+            components.push(Component::witness(
+                format!("input {} witness item count", index),
+                serialize(&VarInt(input.witness.len() as u64)),
+            ));
+            for (item_index, item) in input.witness.iter().enumerate() {
+                components.push(Component::witness(
+                    format!("input {} witness item {} length", index, item_index),
+                    serialize(&VarInt(item.len() as u64)),
+                ));
+                components.push(Component::witness(
+                    format!("input {} witness item {}", index, item_index),
+                    item.to_vec(),
+                ));
+            }
+        }
+    }
+
+    components.push(Component::base(
+        "lock time".into(),
+        serialize(&tx.lock_time),
+    ));
+
+    components
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentDiff {
+    pub component: String,
+    // weight units, 0 if the tx does not have the component
+    pub estimated: usize,
+    pub actual: usize,
+    // the bytes differ, even if the weights do not
+    pub changed: bool,
+}
+
+impl ComponentDiff {
+    pub fn delta(&self) -> i64 {
+        self.actual as i64 - self.estimated as i64
+    }
+}
+
+/// Component by component weight diff of two versions of a transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxDiff {
+    pub components: Vec<ComponentDiff>,
+    pub estimated_weight: usize,
+    pub actual_weight: usize,
+}
+
+impl TxDiff {
+    pub fn new(estimated: &Transaction, actual: &Transaction) -> Self {
+        let estimated_components = components(estimated);
+        let actual_components = components(actual);
+
+        // components of the actual tx in order, then those only the estimate has
+        let mut diffs: Vec<ComponentDiff> = actual_components
+            .iter()
+            .map(|actual| {
+                let estimated = estimated_components.iter().find(|c| c.name == actual.name);
+                ComponentDiff {
+                    component: actual.name.clone(),
+                    estimated: estimated.map_or(0, |c| c.weight),
+                    actual: actual.weight,
+                    changed: estimated.map_or(true, |c| c.bytes != actual.bytes),
+                }
+            })
+            .collect();
+        diffs.extend(
+            estimated_components
+                .iter()
+                .filter(|estimated| !actual_components.iter().any(|c| c.name == estimated.name))
+                .map(|estimated| ComponentDiff {
+                    component: estimated.name.clone(),
+                    estimated: estimated.weight,
+                    actual: 0,
+                    changed: true,
+                }),
+        );
+
+        Self {
+            components: diffs,
+            estimated_weight: estimated.weight().to_wu() as usize,
+            actual_weight: actual.weight().to_wu() as usize,
+        }
+    }
+
+    // Components whose weight differs, the ones a fee estimate gets wrong
+    pub fn weight_differences(&self) -> impl Iterator<Item = &ComponentDiff> {
+        self.components.iter().filter(|diff| diff.delta() != 0)
+    }
+}
+
+// One line per component, differing weights are marked with a *
+impl fmt::Display for TxDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<40} {:>10} {:>10} {:>8}",
+            "component (wu)", "estimated", "actual", "delta"
+        )?;
+        for diff in &self.components {
+            writeln!(
+                f,
+                "{}{:<39} {:>10} {:>10} {:>+8}{}",
+                if diff.delta() != 0 { "*" } else { " " },
+                diff.component,
+                diff.estimated,
+                diff.actual,
+                diff.delta(),
+                if diff.changed { " changed" } else { "" }
+            )?;
+        }
+        write!(
+            f,
+            " {:<39} {:>10} {:>10} {:>+8}",
+            "total",
+            self.estimated_weight,
+            self.actual_weight,
+            self.actual_weight as i64 - self.estimated_weight as i64
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::absolute::LockTime;
+    use bitcoin::hashes::Hash;
+    use bitcoin::{OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness};
+
+    use super::{components, TxDiff};
+
+    #[test]
+    fn tx_diff() {
+        let input = |witness: &[&[u8]]| TxIn {
+            previous_output: OutPoint::new(Txid::all_zeros(), 1),
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Witness::from_slice(witness),
+        };
+        let tx = |inputs: Vec<TxIn>| Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: inputs,
+            output: vec![TxOut {
+                value: 10_000,
+                script_pubkey: ScriptBuf::from_bytes([0x51, 0x20].repeat(17)),
+            }],
+        };
+
+        // a 72 byte placeholder signature, 71 bytes once signed
+        let estimated = tx(vec![input(&[&[0; 72], &[2; 33]]), input(&[])]);
+        let actual = tx(vec![input(&[&[1; 71], &[2; 33]]), input(&[])]);
+
+        for tx in [&estimated, &actual] {
+            let weight: usize = components(tx).iter().map(|c| c.weight).sum();
+            assert_eq!(weight as u64, tx.weight().to_wu());
+        }
+
+        let diff = TxDiff::new(&estimated, &actual);
+        assert_eq!(diff.actual_weight + 1, diff.estimated_weight);
+
+        let differences: Vec<_> = diff.weight_differences().collect();
+        assert_eq!(differences.len(), 1);
+        assert_eq!(differences[0].component, "input 0 witness item 0");
+        assert_eq!(differences[0].delta(), -1);
+        assert!(diff
+            .components
+            .iter()
+            .any(|c| c.component == "input 1 witness item count" && c.actual == 1));
+
+        // a legacy estimate is missing the marker and the witnesses
+        let diff = TxDiff::new(&tx(vec![input(&[]), input(&[])]), &actual);
+        assert!(diff
+            .weight_differences()
+            .any(|c| c.component == "segwit marker" && c.estimated == 0));
+        assert!(diff.to_string().contains("*segwit marker"));
+    }
+}
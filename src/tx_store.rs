@@ -0,0 +1,334 @@
+// Persistence of the signed reveal txs of the service. A reveal tx is stored before its
+// commit tx is broadcast, so that it can still be broadcast after a crash, and removed once
+// the node accepts it
+// The store also hands out the indexes of the derived commit keys, so that no index is used
+// twice across restarts, and keeps the index of the commit key of each reveal tx
+
+use core::fmt::Debug;
+use core::str::FromStr;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use bitcoin::consensus::encode;
+use bitcoin::{Transaction, Txid};
+
+pub trait TxStore: Debug + Send + Sync {
+    // Stores the reveal tx spending the commit tx commit_txid, replacing any previous one,
+    // with the index of the commit key it is signed with if the key was derived
+    fn put_reveal(
+        &self,
+        commit_txid: &Txid,
+        reveal_tx: &Transaction,
+        commit_key_index: Option<u32>,
+    ) -> Result<(), anyhow::Error>;
+
+    fn get_reveal(&self, commit_txid: &Txid) -> Result<Option<Transaction>, anyhow::Error>;
+
+    // Index of the commit key of the stored reveal tx, None if the key was not derived
+    fn commit_key_index(&self, commit_txid: &Txid) -> Result<Option<u32>, anyhow::Error>;
+
+    // Stored reveal txs by commit txid
+    fn reveals(&self) -> Result<Vec<(Txid, Transaction)>, anyhow::Error>;
+
+    fn remove_reveal(&self, commit_txid: &Txid) -> Result<(), anyhow::Error>;
+
+    // Hands out the next commit key index, start at the earliest. The next one is persisted
+    // before the index is returned
+    fn reserve_commit_key_index(&self, start: u32) -> Result<u32, anyhow::Error>;
+}
+
+// Highest index of a hardened derivation
+const MAX_COMMIT_KEY_INDEX: u32 = (1 << 31) - 1;
+
+fn next_commit_key_index(index: u32) -> Result<u32, anyhow::Error> {
+    if index > MAX_COMMIT_KEY_INDEX {
+        return Err(anyhow::anyhow!("Commit key indexes are exhausted"));
+    }
+
+    Ok(index + 1)
+}
+
+// FsTxStore keeps each reveal tx in dir as reveal_<commit txid>.tx, consensus encoded, the
+// index of its commit key as reveal_<commit txid>.index and the next commit key index as
+// commit_key_index, the dir is created with the first one. Files are written to a temporary
+// file first and renamed, so a crash never leaves a torn one behind
+#[derive(Debug)]
+pub struct FsTxStore {
+    dir: PathBuf,
+    // serializes the read and write of the next commit key index
+    commit_key_index: Mutex<()>,
+}
+
+impl FsTxStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            commit_key_index: Mutex::new(()),
+        }
+    }
+
+    pub fn dir(&self) -> &PathBuf {
+        &self.dir
+    }
+
+    fn path(&self, commit_txid: &Txid) -> PathBuf {
+        self.dir.join(format!("reveal_{}.tx", commit_txid))
+    }
+
+    fn index_path(&self, commit_txid: &Txid) -> PathBuf {
+        self.dir.join(format!("reveal_{}.index", commit_txid))
+    }
+
+    fn write(&self, path: PathBuf, bytes: &[u8]) -> Result<(), anyhow::Error> {
+        fs::create_dir_all(&self.dir)?;
+        let mut tmp_path = path.clone().into_os_string();
+        tmp_path.push(".tmp");
+
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &path)?;
+
+        Ok(())
+    }
+
+    fn read_index(path: PathBuf) -> Result<Option<u32>, anyhow::Error> {
+        match fs::read_to_string(path) {
+            Ok(index) => Ok(Some(index.trim().parse()?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+fn remove_file(path: PathBuf) -> Result<(), anyhow::Error> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+impl TxStore for FsTxStore {
+    fn put_reveal(
+        &self,
+        commit_txid: &Txid,
+        reveal_tx: &Transaction,
+        commit_key_index: Option<u32>,
+    ) -> Result<(), anyhow::Error> {
+        // the index goes first, a stored reveal tx always has the index of its key
+        match commit_key_index {
+            Some(index) => {
+                self.write(self.index_path(commit_txid), index.to_string().as_bytes())?
+            }
+            None => remove_file(self.index_path(commit_txid))?,
+        }
+
+        self.write(self.path(commit_txid), &encode::serialize(reveal_tx))
+    }
+
+    fn get_reveal(&self, commit_txid: &Txid) -> Result<Option<Transaction>, anyhow::Error> {
+        let path = self.path(commit_txid);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        Ok(Some(encode::deserialize(&fs::read(path)?)?))
+    }
+
+    fn commit_key_index(&self, commit_txid: &Txid) -> Result<Option<u32>, anyhow::Error> {
+        Self::read_index(self.index_path(commit_txid))
+    }
+
+    fn reveals(&self) -> Result<Vec<(Txid, Transaction)>, anyhow::Error> {
+        let mut reveals = Vec::new();
+        if !self.dir.exists() {
+            return Ok(reveals);
+        }
+
+        for entry in fs::read_dir(&self.dir)? {
+            let name = entry?.file_name();
+            let Some(commit_txid) = name
+                .to_str()
+                .and_then(|name| name.strip_prefix("reveal_"))
+                .and_then(|name| name.strip_suffix(".tx"))
+                .and_then(|txid| Txid::from_str(txid).ok())
+            else {
+                continue;
+            };
+
+            if let Some(reveal_tx) = self.get_reveal(&commit_txid)? {
+                reveals.push((commit_txid, reveal_tx));
+            }
+        }
+        reveals.sort_by_key(|(commit_txid, _)| *commit_txid);
+
+        Ok(reveals)
+    }
+
+    fn remove_reveal(&self, commit_txid: &Txid) -> Result<(), anyhow::Error> {
+        remove_file(self.path(commit_txid))?;
+        remove_file(self.index_path(commit_txid))
+    }
+
+    fn reserve_commit_key_index(&self, start: u32) -> Result<u32, anyhow::Error> {
+        let _guard = self
+            .commit_key_index
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Tx store lock is poisoned"))?;
+
+        let path = self.dir.join("commit_key_index");
+        let index = Self::read_index(path.clone())?.unwrap_or(0).max(start);
+        self.write(path, next_commit_key_index(index)?.to_string().as_bytes())?;
+
+        Ok(index)
+    }
+}
+
+// Reveal txs by commit txid, with the index of their commit key
+type Reveals = BTreeMap<Txid, (Transaction, Option<u32>)>;
+
+// InMemoryTxStore keeps the reveal txs for the lifetime of the service, e.g. in tests
+#[derive(Debug, Default)]
+pub struct InMemoryTxStore {
+    reveals: Mutex<Reveals>,
+    next_commit_key_index: Mutex<u32>,
+}
+
+impl InMemoryTxStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn reveals_map(&self) -> Result<std::sync::MutexGuard<'_, Reveals>, anyhow::Error> {
+        self.reveals
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Tx store lock is poisoned"))
+    }
+}
+
+impl TxStore for InMemoryTxStore {
+    fn put_reveal(
+        &self,
+        commit_txid: &Txid,
+        reveal_tx: &Transaction,
+        commit_key_index: Option<u32>,
+    ) -> Result<(), anyhow::Error> {
+        self.reveals_map()?
+            .insert(*commit_txid, (reveal_tx.clone(), commit_key_index));
+        Ok(())
+    }
+
+    fn get_reveal(&self, commit_txid: &Txid) -> Result<Option<Transaction>, anyhow::Error> {
+        Ok(self
+            .reveals_map()?
+            .get(commit_txid)
+            .map(|(reveal_tx, _)| reveal_tx.clone()))
+    }
+
+    fn commit_key_index(&self, commit_txid: &Txid) -> Result<Option<u32>, anyhow::Error> {
+        Ok(self
+            .reveals_map()?
+            .get(commit_txid)
+            .and_then(|(_, index)| *index))
+    }
+
+    fn reveals(&self) -> Result<Vec<(Txid, Transaction)>, anyhow::Error> {
+        Ok(self
+            .reveals_map()?
+            .iter()
+            .map(|(commit_txid, (reveal_tx, _))| (*commit_txid, reveal_tx.clone()))
+            .collect())
+    }
+
+    fn remove_reveal(&self, commit_txid: &Txid) -> Result<(), anyhow::Error> {
+        self.reveals_map()?.remove(commit_txid);
+        Ok(())
+    }
+
+    fn reserve_commit_key_index(&self, start: u32) -> Result<u32, anyhow::Error> {
+        let mut next = self
+            .next_commit_key_index
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Tx store lock is poisoned"))?;
+
+        let index = (*next).max(start);
+        *next = next_commit_key_index(index)?;
+
+        Ok(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+    use bitcoin::{absolute, Transaction, TxOut, Txid};
+
+    use super::{FsTxStore, InMemoryTxStore, TxStore};
+
+    fn reveal_tx(value: u64) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![],
+            output: vec![TxOut {
+                value,
+                script_pubkey: Default::default(),
+            }],
+        }
+    }
+
+    fn check_store(store: &dyn TxStore) {
+        let first = Txid::from_byte_array([1; 32]);
+        let second = Txid::from_byte_array([2; 32]);
+
+        assert_eq!(store.get_reveal(&first).unwrap(), None);
+
+        store.put_reveal(&second, &reveal_tx(2), None).unwrap();
+        store.put_reveal(&first, &reveal_tx(0), Some(7)).unwrap();
+        store.put_reveal(&first, &reveal_tx(1), Some(8)).unwrap();
+        assert_eq!(store.get_reveal(&first).unwrap(), Some(reveal_tx(1)));
+        assert_eq!(store.commit_key_index(&first).unwrap(), Some(8));
+        assert_eq!(store.commit_key_index(&second).unwrap(), None);
+        assert_eq!(
+            store.reveals().unwrap(),
+            vec![(first, reveal_tx(1)), (second, reveal_tx(2))]
+        );
+
+        store.remove_reveal(&first).unwrap();
+        // removing twice is fine
+        store.remove_reveal(&first).unwrap();
+        assert_eq!(store.reveals().unwrap(), vec![(second, reveal_tx(2))]);
+        assert_eq!(store.commit_key_index(&first).unwrap(), None);
+
+        // indexes are handed out once, from the start index at the earliest
+        assert_eq!(store.reserve_commit_key_index(0).unwrap(), 0);
+        assert_eq!(store.reserve_commit_key_index(0).unwrap(), 1);
+        assert_eq!(store.reserve_commit_key_index(5).unwrap(), 5);
+        assert_eq!(store.reserve_commit_key_index(0).unwrap(), 6);
+        assert!(store.reserve_commit_key_index(1 << 31).is_err());
+    }
+
+    #[test]
+    fn tx_stores() {
+        check_store(&InMemoryTxStore::new());
+
+        let dir = std::env::temp_dir().join(format!("tx_store_{}", std::process::id()));
+        let store = FsTxStore::new(dir.join("reveals"));
+        assert_eq!(store.reveals().unwrap(), vec![]);
+        check_store(&store);
+
+        // other files of the dir are left alone
+        std::fs::write(store.dir().join("handoff_1.json"), "{}").unwrap();
+        std::fs::write(store.dir().join("reveal_1.tx.tmp"), [0]).unwrap();
+        assert_eq!(store.reveals().unwrap().len(), 1);
+
+        // the next commit key index outlives the store
+        let store = FsTxStore::new(dir.join("reveals"));
+        assert_eq!(store.reserve_commit_key_index(0).unwrap(), 7);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
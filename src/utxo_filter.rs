@@ -0,0 +1,143 @@
+use core::fmt::Debug;
+use std::collections::HashSet;
+
+use bitcoin::OutPoint;
+
+use crate::spec::utxo::UTXO;
+
+/// Decides which wallet UTXOs must not fund commit transactions, e.g. ones that carry
+/// ordinal inscriptions or asset commitments. Implementations can be backed by an external
+/// indexer (ord, tapd) that is queried ahead of time
+pub trait UtxoFilter: Debug + Send + Sync {
+    fn is_excluded(&self, utxo: &UTXO) -> bool;
+}
+
+// Excludes an explicit list of outpoints
+#[derive(Debug, Clone, Default)]
+pub struct OutPointDenylist {
+    outpoints: HashSet<OutPoint>,
+}
+
+impl OutPointDenylist {
+    pub fn new(outpoints: impl IntoIterator<Item = OutPoint>) -> Self {
+        Self {
+            outpoints: outpoints.into_iter().collect(),
+        }
+    }
+}
+
+impl UtxoFilter for OutPointDenylist {
+    fn is_excluded(&self, utxo: &UTXO) -> bool {
+        self.outpoints.contains(&OutPoint {
+            txid: utxo.tx_id,
+            vout: utxo.vout,
+        })
+    }
+}
+
+// Excludes UTXOs up to max_amount sats, inscriptions and assets usually sit on
+// postage sized outputs (546 or 10000 sats) rather than on funding outputs
+#[derive(Debug, Clone, Copy)]
+pub struct PostageAmountFilter {
+    pub max_amount: u64,
+}
+
+impl UtxoFilter for PostageAmountFilter {
+    fn is_excluded(&self, utxo: &UTXO) -> bool {
+        utxo.amount <= self.max_amount
+    }
+}
+
+// Excludes utxos with fewer confirmations, unconfirmed change above all. If its tx is
+// replaced or evicted, so are the commit and reveal txs built on it
+#[derive(Debug, Clone, Copy)]
+pub struct MinConfirmationsFilter {
+    pub min_confirmations: u64,
+}
+
+impl UtxoFilter for MinConfirmationsFilter {
+    fn is_excluded(&self, utxo: &UTXO) -> bool {
+        utxo.confirmations < self.min_confirmations
+    }
+}
+
+// Excludes unconfirmed utxos with more ancestors, the commit and reveal txs would hit the
+// mempool limit on chains of unconfirmed txs (25 by default)
+#[derive(Debug, Clone, Copy)]
+pub struct AncestorCountFilter {
+    pub max_ancestor_count: u32,
+}
+
+impl UtxoFilter for AncestorCountFilter {
+    fn is_excluded(&self, utxo: &UTXO) -> bool {
+        utxo.ancestor_count
+            .map_or(false, |count| count > self.max_ancestor_count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+
+    use bitcoin::{OutPoint, Txid};
+
+    use super::{
+        AncestorCountFilter, MinConfirmationsFilter, OutPointDenylist, PostageAmountFilter,
+        UtxoFilter,
+    };
+    use crate::spec::utxo::UTXO;
+
+    #[test]
+    fn utxo_filters() {
+        let utxo = UTXO {
+            tx_id: Txid::from_str(
+                "4cfbec13cf1510545f285cceceb6229bd7b6a918a8f6eba1dbee64d26226a3b7",
+            )
+            .unwrap(),
+            vout: 1,
+            address: "bc1pp8qru0ve43rw9xffmdd8pvveths3cx6a5t6mcr0xfn9cpxx2k24qf70xq9".to_string(),
+            script_pubkey: "512009c03e3d99ac46e29929db5a70b1995de11c1b5da2f5bc0de64ccb8098cab2aa"
+                .to_string(),
+            amount: 10_000,
+            confirmations: 100,
+            spendable: true,
+            solvable: true,
+            ancestor_count: None,
+        };
+
+        let denylist = OutPointDenylist::new([OutPoint {
+            txid: utxo.tx_id,
+            vout: 1,
+        }]);
+        assert!(denylist.is_excluded(&utxo));
+        assert!(!denylist.is_excluded(&UTXO {
+            vout: 0,
+            ..utxo.clone()
+        }));
+
+        assert!(PostageAmountFilter { max_amount: 10_000 }.is_excluded(&utxo));
+        assert!(!PostageAmountFilter { max_amount: 9_999 }.is_excluded(&utxo));
+
+        let unconfirmed_change = UTXO {
+            confirmations: 0,
+            ancestor_count: Some(3),
+            ..utxo.clone()
+        };
+        let min_confirmations = MinConfirmationsFilter {
+            min_confirmations: 1,
+        };
+        assert!(!min_confirmations.is_excluded(&utxo));
+        assert!(min_confirmations.is_excluded(&unconfirmed_change));
+
+        // confirmed utxos have no unconfirmed ancestors
+        let ancestor_count = AncestorCountFilter {
+            max_ancestor_count: 2,
+        };
+        assert!(!ancestor_count.is_excluded(&utxo));
+        assert!(ancestor_count.is_excluded(&unconfirmed_change));
+        assert!(!AncestorCountFilter {
+            max_ancestor_count: 3
+        }
+        .is_excluded(&unconfirmed_change));
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use bitcoin::{OutPoint, Transaction};
+
+use crate::spec::utxo::UTXO;
+
+/// Utxos spent by commit txs that are being built, signed or broadcast. Shared by the
+/// concurrent submissions of a service so that two of them never fund their commit txs with
+/// the same coins, see BitcoinService::with_utxo_reservations
+#[derive(Debug, Clone, Default)]
+pub struct UtxoReservations {
+    reserved: Arc<Mutex<HashSet<OutPoint>>>,
+}
+
+impl UtxoReservations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Locks the set while a commit tx is built, no other submission can reserve meanwhile
+    pub fn lock(&self) -> Result<ReservationLock<'_>, anyhow::Error> {
+        Ok(ReservationLock {
+            reservations: self,
+            reserved: self
+                .reserved
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Utxo reservations lock is poisoned"))?,
+        })
+    }
+
+    pub fn is_reserved(&self, outpoint: &OutPoint) -> bool {
+        self.reserved
+            .lock()
+            .map_or(false, |reserved| reserved.contains(outpoint))
+    }
+}
+
+pub struct ReservationLock<'a> {
+    reservations: &'a UtxoReservations,
+    reserved: MutexGuard<'a, HashSet<OutPoint>>,
+}
+
+impl ReservationLock<'_> {
+    // The utxos no other submission holds
+    pub fn available(&self, mut utxos: Vec<UTXO>) -> Vec<UTXO> {
+        utxos.retain(|utxo| {
+            !self
+                .reserved
+                .contains(&OutPoint::new(utxo.tx_id, utxo.vout))
+        });
+        utxos
+    }
+
+    // Reserves the inputs of the commit tx and releases the lock
+    pub fn reserve(mut self, commit_tx: &Transaction) -> UtxoReservation {
+        let outpoints: Vec<OutPoint> = commit_tx
+            .input
+            .iter()
+            .map(|input| input.previous_output)
+            .filter(|outpoint| self.reserved.insert(*outpoint))
+            .collect();
+
+        UtxoReservation {
+            reservations: self.reservations.clone(),
+            outpoints,
+        }
+    }
+}
+
+/// Utxos held by a submission, released when it is dropped. By then the commit tx is either
+/// in the mempool, and the wallet no longer lists its inputs, or it failed
+#[derive(Debug)]
+pub struct UtxoReservation {
+    reservations: UtxoReservations,
+    outpoints: Vec<OutPoint>,
+}
+
+impl UtxoReservation {
+    pub fn outpoints(&self) -> &[OutPoint] {
+        &self.outpoints
+    }
+}
+
+impl Drop for UtxoReservation {
+    fn drop(&mut self) {
+        // a poisoned set stays poisoned, every later submission fails on it anyway
+        if let Ok(mut reserved) = self.reservations.reserved.lock() {
+            for outpoint in &self.outpoints {
+                reserved.remove(outpoint);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+
+    use bitcoin::absolute::LockTime;
+    use bitcoin::{OutPoint, ScriptBuf, Sequence, Transaction, TxIn, Txid, Witness};
+
+    use super::UtxoReservations;
+    use crate::spec::utxo::UTXO;
+
+    fn utxo(index: u8) -> UTXO {
+        UTXO {
+            tx_id: Txid::from_str(&format!("{:064x}", index)).unwrap(),
+            vout: 0,
+            address: "bc1pp8qru0ve43rw9xffmdd8pvveths3cx6a5t6mcr0xfn9cpxx2k24qf70xq9".to_string(),
+            script_pubkey: "512009c03e3d99ac46e29929db5a70b1995de11c1b5da2f5bc0de64ccb8098cab2aa"
+                .to_string(),
+            amount: 100_000,
+            confirmations: 1,
+            spendable: true,
+            solvable: true,
+            ancestor_count: None,
+        }
+    }
+
+    fn spending(utxos: &[UTXO]) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: utxos
+                .iter()
+                .map(|utxo| TxIn {
+                    previous_output: OutPoint::new(utxo.tx_id, utxo.vout),
+                    script_sig: ScriptBuf::new(),
+                    sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                    witness: Witness::new(),
+                })
+                .collect(),
+            output: vec![],
+        }
+    }
+
+    #[test]
+    fn utxo_reservations() {
+        let utxos = vec![utxo(1), utxo(2), utxo(3)];
+        let reservations = UtxoReservations::new();
+
+        let lock = reservations.lock().unwrap();
+        assert_eq!(lock.available(utxos.clone()), utxos);
+        let first = lock.reserve(&spending(&utxos[..2]));
+        assert_eq!(first.outpoints().len(), 2);
+
+        // a concurrent submission only sees the coins left
+        let lock = reservations.lock().unwrap();
+        assert_eq!(lock.available(utxos.clone()), vec![utxo(3)]);
+        let second = lock.reserve(&spending(&utxos[2..]));
+        assert!(reservations
+            .lock()
+            .unwrap()
+            .available(utxos.clone())
+            .is_empty());
+
+        drop(first);
+        assert!(!reservations.is_reserved(&OutPoint::new(utxos[0].tx_id, 0)));
+        assert!(reservations.is_reserved(&OutPoint::new(utxos[2].tx_id, 0)));
+        assert_eq!(
+            reservations.lock().unwrap().available(utxos.clone()),
+            utxos[..2]
+        );
+
+        drop(second);
+        assert_eq!(reservations.lock().unwrap().available(utxos.clone()), utxos);
+    }
+}
@@ -9,13 +9,26 @@ use sov_rollup_interface::digest::Digest;
 use sov_rollup_interface::zk::ValidityCondition;
 use thiserror::Error;
 
-use crate::helpers::builders::decompress_blob;
-use crate::helpers::parsers::parse_transaction;
-use crate::spec::BitcoinSpec;
+use crate::helpers::builders::DecompressError;
+use crate::helpers::parsers::{
+    parse_inscriptions, reassemble_chunks, ChunkError, ParsedInscription,
+};
+use crate::spec::batch_sequence::BatchSequence;
+use crate::spec::blob::{BlobWithSender, Checkpoint, InscriptionMetadata};
+use crate::spec::header::HeaderWrapper;
+use crate::spec::proof::InclusionMultiProof;
+use crate::spec::transaction::{CompactTransaction, Transaction, WitnessCommitmentProof};
+use crate::spec::{meets_reveal_pow, relevant_tx_cap, BitcoinSpec};
 
 pub struct BitcoinVerifier {
     rollup_name: String,
     reveal_tx_id_prefix: Vec<u8>,
+    reveal_tx_id_zero_bits: u8,
+    max_relevant_txs: Option<u32>,
+    max_decompressed_len: Option<usize>,
+    params_fingerprint: [u8; 8],
+    // batch numbers accepted before the block to verify, if they must increase
+    batch_sequence: Option<BatchSequence>,
 }
 
 // TODO: custom errors based on our implementation
@@ -24,6 +37,128 @@ pub enum ValidationError {
     InvalidTx,
     InvalidProof,
     InvalidBlock,
+    BlobHashMismatch,
+    InvalidBlob(DecompressError),
+    InvalidChunks(ChunkError),
+    InvalidCheckpoint,
+}
+
+/// Blob carried by a single transaction, checked by verify_blob_tx
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifiedBlob {
+    pub txid: Txid,
+    pub blob_hash: [u8; 32],
+    pub sender: Vec<u8>,
+    // decompressed blob
+    pub blob: Vec<u8>,
+    pub metadata: InscriptionMetadata,
+}
+
+// Checks that tx carries a properly signed blob with the expected hash for the rollup,
+// in any envelope of a batched reveal tx
+// It does not check the txid prefix or that the tx is included in a block
+pub fn verify_blob_tx(
+    tx: &Transaction,
+    expected_blob_hash: [u8; 32],
+    rollup_name: &str,
+) -> Result<VerifiedBlob, ValidationError> {
+    let signed: Vec<([u8; 32], ParsedInscription)> = parse_inscriptions(tx, rollup_name)
+        .map_err(|_| ValidationError::InvalidTx)?
+        .into_iter()
+        .filter_map(|parsed_tx| Some((parsed_tx.get_sig_verified_hash()?, parsed_tx)))
+        .collect();
+
+    if signed.is_empty() {
+        return Err(ValidationError::InvalidTx);
+    }
+
+    let (blob_hash, parsed_tx) = signed
+        .into_iter()
+        .find(|(blob_hash, _)| *blob_hash == expected_blob_hash)
+        .ok_or(ValidationError::BlobHashMismatch)?;
+
+    Ok(VerifiedBlob {
+        txid: tx.txid(),
+        blob_hash,
+        blob: parsed_tx
+            .decompressed_body_with_limit(None)
+            .map_err(ValidationError::InvalidBlob)?,
+        sender: parsed_tx.public_key,
+        metadata: parsed_tx.metadata,
+    })
+}
+
+/// Checkpoint carried by a single transaction, checked by verify_checkpoint_tx
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerifiedCheckpoint {
+    pub txid: Txid,
+    pub checkpoint: Checkpoint,
+}
+
+// Checks that tx carries a checkpoint of the rollup signed by sender, the first one if a
+// batched reveal tx carries several. Like verify_blob_tx, inclusion is not checked
+pub fn verify_checkpoint_tx(
+    tx: &Transaction,
+    sender: &[u8],
+    rollup_name: &str,
+) -> Result<VerifiedCheckpoint, ValidationError> {
+    let inscription = parse_inscriptions(tx, rollup_name)
+        .map_err(|_| ValidationError::InvalidTx)?
+        .into_iter()
+        .find(|inscription| {
+            inscription.public_key == sender && inscription.get_sig_verified_hash().is_some()
+        })
+        .ok_or(ValidationError::InvalidTx)?;
+
+    Ok(VerifiedCheckpoint {
+        txid: tx.txid(),
+        checkpoint: inscription
+            .checkpoint()
+            .ok_or(ValidationError::InvalidCheckpoint)?,
+    })
+}
+
+// Checks that txs carry every chunk of a blob split by the builder, properly signed for the
+// rollup, and reconstructs the blob with the expected hash. Chunks of other blobs are ignored
+// The txid of the verified blob is the one of the reveal tx carrying the last chunk
+pub fn verify_chunked_blob_txs(
+    txs: &[Transaction],
+    expected_blob_hash: [u8; 32],
+    rollup_name: &str,
+) -> Result<VerifiedBlob, ValidationError> {
+    let mut chunks = vec![];
+    let mut last_txid = None;
+    for tx in txs {
+        let inscriptions =
+            parse_inscriptions(tx, rollup_name).map_err(|_| ValidationError::InvalidTx)?;
+        for inscription in inscriptions {
+            let Some(manifest) = inscription.metadata.chunk else {
+                continue;
+            };
+            if manifest.blob_hash != expected_blob_hash {
+                continue;
+            }
+
+            if manifest.index + 1 == manifest.total {
+                last_txid = Some(tx.txid());
+            }
+            chunks.push(inscription);
+        }
+    }
+
+    if chunks.is_empty() {
+        return Err(ValidationError::BlobHashMismatch);
+    }
+
+    let reassembled = reassemble_chunks(&chunks, None).map_err(ValidationError::InvalidChunks)?;
+
+    Ok(VerifiedBlob {
+        txid: last_txid.ok_or(ValidationError::InvalidChunks(ChunkError::MissingChunks))?,
+        blob_hash: reassembled.blob_hash,
+        sender: reassembled.public_key,
+        blob: reassembled.blob,
+        metadata: reassembled.metadata,
+    })
 }
 
 #[derive(
@@ -39,14 +174,20 @@ pub enum ValidationError {
     BorshSerialize,
 )]
 /// A validity condition expressing that a chain of DA layer blocks is contiguous and canonical
+/// and that the batch sequence of each block continues the one of the block before
 pub struct ChainValidityCondition {
     pub prev_hash: [u8; 32],
     pub block_hash: [u8; 32],
+    // digests of the batch sequence before and after the block, see BatchSequence
+    pub prev_batch_sequence: [u8; 32],
+    pub batch_sequence: [u8; 32],
 }
 #[derive(Error, Debug)]
 pub enum ValidityConditionError {
     #[error("conditions for validity can only be combined if the blocks are consecutive")]
     BlocksNotConsecutive,
+    #[error("the batch sequence of a block does not continue the one of the block before")]
+    BatchSequenceNotContinued,
 }
 
 impl ValidityCondition for ChainValidityCondition {
@@ -55,6 +196,9 @@ impl ValidityCondition for ChainValidityCondition {
         if self.block_hash != rhs.prev_hash {
             return Err(ValidityConditionError::BlocksNotConsecutive);
         }
+        if self.batch_sequence != rhs.prev_batch_sequence {
+            return Err(ValidityConditionError::BatchSequenceNotContinued);
+        }
         Ok(rhs)
     }
 }
@@ -66,12 +210,19 @@ impl DaVerifier for BitcoinVerifier {
 
     fn new(params: <Self::Spec as DaSpec>::ChainParams) -> Self {
         Self {
+            params_fingerprint: params.fingerprint(),
             rollup_name: params.rollup_name,
             reveal_tx_id_prefix: params.reveal_tx_id_prefix,
+            reveal_tx_id_zero_bits: params.reveal_tx_id_zero_bits,
+            max_relevant_txs: params.max_relevant_txs,
+            max_decompressed_len: params.max_decompressed_len,
+            batch_sequence: params.monotonic_batch_numbers.then(BatchSequence::new),
         }
     }
 
     // Verify that the given list of blob transactions is complete and correct.
+    // Batch numbers are checked from the sequence the verifier was built with, the validity
+    // condition commits to it and to the sequence after the block
     fn verify_relevant_tx_list(
         &self,
         block_header: &<Self::Spec as DaSpec>::BlockHeader,
@@ -79,10 +230,42 @@ impl DaVerifier for BitcoinVerifier {
         inclusion_proof: <Self::Spec as DaSpec>::InclusionMultiProof,
         completeness_proof: <Self::Spec as DaSpec>::CompletenessProof,
     ) -> Result<<Self::Spec as DaSpec>::ValidityCondition, Self::Error> {
-        let validity_condition = ChainValidityCondition {
-            prev_hash: block_header.prev_hash().to_byte_array(),
-            block_hash: block_header.prev_hash().to_byte_array(),
-        };
+        let mut batch_sequence = self.batch_sequence.clone();
+        self.verify_relevant_tx_list_from(
+            block_header,
+            blobs,
+            inclusion_proof,
+            completeness_proof,
+            batch_sequence.as_mut(),
+        )
+    }
+}
+
+impl BitcoinVerifier {
+    // Verifies blocks after the ones that left the given batch sequence, e.g. the sequence
+    // after the previous block passed to the zk guest. It is ignored unless batch numbers
+    // must increase
+    pub fn with_batch_sequence(mut self, batch_sequence: BatchSequence) -> Self {
+        if self.batch_sequence.is_some() {
+            self.batch_sequence = Some(batch_sequence);
+        }
+        self
+    }
+
+    // Like verify_relevant_tx_list, but starts from the given batch sequence and advances it
+    // past the block, so that consecutive blocks can be verified in a row
+    // The sequence is only meaningful if the verification succeeds
+    pub fn verify_relevant_tx_list_from(
+        &self,
+        block_header: &HeaderWrapper,
+        blobs: &[BlobWithSender],
+        inclusion_proof: InclusionMultiProof,
+        completeness_proof: Vec<Transaction>,
+        mut batch_sequence: Option<&mut BatchSequence>,
+    ) -> Result<ChainValidityCondition, ValidationError> {
+        let prev_batch_sequence = batch_sequence
+            .as_deref()
+            .map_or_else(|| BatchSequence::new().digest(), BatchSequence::digest);
 
         // completeness proof
 
@@ -91,17 +274,29 @@ impl DaVerifier for BitcoinVerifier {
 
         let mut prev_index_in_inclusion = 0;
 
-        let prefix = self.reveal_tx_id_prefix.as_slice();
+        let is_relevant = |tx_hash: &[u8]| {
+            meets_reveal_pow(
+                tx_hash,
+                &self.reveal_tx_id_prefix,
+                self.reveal_tx_id_zero_bits,
+            )
+        };
+        // relevant txs past the cap are ignored, see RollupParams::relevant_tx_cap
+        let cap = relevant_tx_cap(self.max_relevant_txs);
+        assert!(
+            completeness_proof.len() <= cap,
+            "completeness proof exceeds the relevant tx cap"
+        );
+
         // Check starting bytes tx that parsed correctly is in blobs
         let mut completeness_tx_hashes = completeness_proof
             .iter()
-            .enumerate()
-            .map(|(index_completeness, tx)| {
+            .map(|tx| {
                 let tx_hash = tx.txid().to_raw_hash().to_byte_array();
 
-                // make sure it starts with the correct prefix
+                // make sure it meets the reveal txid proof of work
                 assert!(
-                    tx_hash.starts_with(prefix),
+                    is_relevant(&tx_hash),
                     "non-relevant tx found in completeness proof"
                 );
 
@@ -123,9 +318,33 @@ impl DaVerifier for BitcoinVerifier {
                     "tx in completeness proof is not found in DA block or order was not preserved"
                 );
 
-                // it must be parsed correctly
-                if let Ok(parsed_tx) = parse_transaction(tx, &self.rollup_name) {
-                    if let Some(blob_hash) = parsed_tx.get_sig_verified_hash() {
+                // it must be parsed correctly and be sent with the same chain params
+                // blobs of another deployment are dropped rather than failing the block, since
+                // anyone can inscribe a blob carrying the rollup name
+                // a batched reveal tx carries one blob per envelope, in order
+                let parsed_txs = parse_inscriptions(tx, &self.rollup_name).unwrap_or_default();
+                for parsed_tx in parsed_txs
+                    .iter()
+                    .filter(|parsed_tx| parsed_tx.metadata.matches_params(self.params_fingerprint))
+                {
+                    // the service drops blobs that do not decompress, as anyone can sign one
+                    let verified = parsed_tx.get_sig_verified_hash().and_then(|blob_hash| {
+                        let decompressed_blob = parsed_tx
+                            .decompressed_body_with_limit(self.max_decompressed_len)
+                            .ok()?;
+                        Some((blob_hash, decompressed_blob))
+                    });
+                    // replayed batches are dropped by the service too
+                    let verified = verified.filter(|_| {
+                        batch_sequence.as_mut().map_or(true, |batch_sequence| {
+                            batch_sequence.accept(
+                                &parsed_tx.public_key,
+                                parsed_tx.metadata.batch_number,
+                                parsed_tx.metadata.kind.unwrap_or_default(),
+                            )
+                        })
+                    });
+                    if let Some((blob_hash, decompressed_blob)) = verified {
                         let blob = blobs_iter.next();
 
                         assert!(blob.is_some(), "valid blob was not found in blobs");
@@ -139,16 +358,17 @@ impl DaVerifier for BitcoinVerifier {
                             "incorrect sender in blob"
                         );
 
-                        // decompress the blob
-                        let decompressed_blob = decompress_blob(&parsed_tx.body);
-
-                        // read the supplied blob from txs
-                        let mut blob_content = blobs[index_completeness].blob.clone();
-                        blob_content.advance(blob_content.total_len());
-                        let blob_content = blob_content.accumulator();
+                        assert_eq!(
+                            parsed_tx.metadata, blob.metadata,
+                            "blob metadata was modified"
+                        );
 
                         // assert tx content is not modified
-                        assert_eq!(blob_content, decompressed_blob, "blob content was modified");
+                        assert_eq!(
+                            blob.full_content(),
+                            decompressed_blob,
+                            "blob content was modified"
+                        );
                     }
                 }
 
@@ -162,16 +382,19 @@ impl DaVerifier for BitcoinVerifier {
             "completeness proof is incorrect"
         );
 
-        // no prefix bytes left behind completeness proof
-        inclusion_proof.txs.iter().for_each(|tx_hash| {
-            if tx_hash.starts_with(prefix) {
+        // no prefix bytes left behind completeness proof, up to the cap
+        inclusion_proof
+            .txs
+            .iter()
+            .filter(|tx_hash| is_relevant(tx_hash.as_slice()))
+            .take(cap)
+            .for_each(|tx_hash| {
                 // assert all prefixed transactions are included in completeness proof
                 assert!(
                     completeness_tx_hashes.remove(tx_hash),
                     "relevant transaction in DA block was not included in completeness proof"
                 );
-            }
-        });
+            });
 
         // assert no other (irrelevant) tx is in completeness proof
         assert!(
@@ -194,11 +417,77 @@ impl DaVerifier for BitcoinVerifier {
             // Check that the tx root in the block header matches the tx root in the inclusion proof.
             assert_eq!(root_from_inclusion, tx_root, "inclusion proof is incorrect");
 
-            Ok(validity_condition)
+            Ok(ChainValidityCondition {
+                prev_hash: block_header.prev_hash().to_byte_array(),
+                block_hash: block_header.prev_hash().to_byte_array(),
+                prev_batch_sequence,
+                batch_sequence: batch_sequence
+                    .as_deref()
+                    .map_or_else(|| BatchSequence::new().digest(), BatchSequence::digest),
+            })
         } else {
             panic!("merkle root couldn't be computed")
         }
     }
+
+    // Verifies a completeness proof stored in the compact form, as verify_relevant_tx_list
+    // does: the blobs are read from the envelopes, which are kept. The wtxid of each tx with
+    // stripped witnesses must be one the block commits to, witness_proof shows it and is only
+    // needed if any witness was stripped
+    pub fn verify_compact_relevant_tx_list(
+        &self,
+        block_header: &HeaderWrapper,
+        blobs: &[BlobWithSender],
+        inclusion_proof: InclusionMultiProof,
+        completeness_proof: Vec<CompactTransaction>,
+        witness_proof: Option<WitnessCommitmentProof>,
+    ) -> Result<ChainValidityCondition, ValidationError> {
+        if completeness_proof
+            .iter()
+            .any(CompactTransaction::is_stripped)
+        {
+            let witness_proof = witness_proof.ok_or(ValidationError::InvalidProof)?;
+            if inclusion_proof.txs.first()
+                != Some(
+                    &witness_proof
+                        .coinbase_tx
+                        .txid()
+                        .to_raw_hash()
+                        .to_byte_array(),
+                )
+                || witness_proof.wtxids.len() != inclusion_proof.txs.len()
+                || !witness_proof.is_committed()
+            {
+                return Err(ValidationError::InvalidProof);
+            }
+
+            for compact_tx in completeness_proof.iter().filter(|tx| tx.is_stripped()) {
+                let txid = compact_tx.tx.txid().to_raw_hash().to_byte_array();
+                let committed = inclusion_proof
+                    .txs
+                    .iter()
+                    .position(|tx_hash| *tx_hash == txid)
+                    .map_or(false, |i| {
+                        witness_proof.wtxids[i] == compact_tx.wtxid().to_byte_array()
+                    });
+                if !committed {
+                    return Err(ValidationError::InvalidProof);
+                }
+            }
+        }
+
+        let mut batch_sequence = self.batch_sequence.clone();
+        self.verify_relevant_tx_list_from(
+            block_header,
+            blobs,
+            inclusion_proof,
+            completeness_proof
+                .into_iter()
+                .map(|compact_tx| compact_tx.tx)
+                .collect(),
+            batch_sequence.as_mut(),
+        )
+    }
 }
 
 #[cfg(test)]
@@ -208,21 +497,37 @@ mod tests {
     // So verifier takes in [0, 0]
 
     use core::str::FromStr;
+    use std::panic;
 
     use bitcoin::block::{Header, Version};
     use bitcoin::hash_types::TxMerkleNode;
     use bitcoin::hashes::{sha256d, Hash};
     use bitcoin::string::FromHexStr;
     use bitcoin::{BlockHash, CompactTarget};
+    use borsh::{BorshDeserialize, BorshSerialize};
     use sov_rollup_interface::da::{DaSpec, DaVerifier};
 
-    use super::BitcoinVerifier;
-    use crate::helpers::builders::decompress_blob;
-    use crate::helpers::parsers::{parse_hex_transaction, parse_transaction};
-    use crate::spec::blob::BlobWithSender;
+    use super::{
+        verify_blob_tx, verify_checkpoint_tx, verify_chunked_blob_txs, BitcoinVerifier,
+        ValidationError,
+    };
+    use crate::bip322;
+    use crate::helpers::builders::{
+        blob_signing_message, compress_blob, compress_blob_with, decompress_blob,
+        sign_blob_schnorr_with_private_key, sign_blob_with_private_key, split_blob,
+        DecompressError, InscriptionBuilder, SignedBlob,
+    };
+    use crate::helpers::parsers::{parse_hex_transaction, parse_transaction, ChunkError};
+    use crate::spec::batch_sequence::BatchSequence;
+    use crate::spec::blob::{
+        BlobKind, BlobWithSender, Checkpoint, CompressionAlgorithm, InscriptionMetadata,
+        SignatureScheme,
+    };
     use crate::spec::header::HeaderWrapper;
-    use crate::spec::proof::InclusionMultiProof;
-    use crate::spec::transaction::Transaction;
+    use crate::spec::proof::{InclusionMultiProof, ProofInput};
+    use crate::spec::transaction::{
+        compact_completeness_proof, CompactTransaction, Transaction, WitnessCommitmentProof,
+    };
     use crate::spec::RollupParams;
 
     fn get_mock_txs() -> Vec<Transaction> {
@@ -242,13 +547,14 @@ mod tests {
         let blob = parsed_inscription.body;
 
         // Decompress the blob
-        let decompressed_blob = decompress_blob(&blob);
+        let decompressed_blob = decompress_blob(&blob).unwrap();
 
         BlobWithSender::new(
             decompressed_blob,
             parsed_inscription.public_key,
             sha256d::Hash::hash(&blob).to_byte_array(),
         )
+        .with_metadata(parsed_inscription.metadata)
     }
 
     #[allow(clippy::type_complexity)]
@@ -304,14 +610,238 @@ mod tests {
         (header, inclusion_proof, completeness_proof, txs)
     }
 
+    #[test]
+    fn single_blob_tx() {
+        let (_, _, completeness_proof, txs) = get_mock_data();
+
+        let verified = verify_blob_tx(&completeness_proof[0], txs[0].hash, "sov-btc").unwrap();
+
+        assert_eq!(verified.txid, completeness_proof[0].txid());
+        assert_eq!(verified.sender, txs[0].sender.0);
+        assert_eq!(
+            verified.blob,
+            decompress_blob(
+                &parse_transaction(&completeness_proof[0], "sov-btc")
+                    .unwrap()
+                    .body
+            )
+            .unwrap()
+        );
+
+        assert_eq!(
+            verify_blob_tx(&completeness_proof[0], txs[1].hash, "sov-btc"),
+            Err(ValidationError::BlobHashMismatch)
+        );
+        assert_eq!(
+            verify_blob_tx(&completeness_proof[0], txs[0].hash, "other-rollup"),
+            Err(ValidationError::InvalidTx)
+        );
+    }
+
     #[test]
     fn correct() {
         let verifier = BitcoinVerifier::new(RollupParams {
             rollup_name: "sov-btc".to_string(),
             reveal_tx_id_prefix: vec![0, 0],
+            reveal_tx_id_zero_bits: 0,
+            max_relevant_txs: None,
+            max_decompressed_len: None,
+            monotonic_batch_numbers: false,
+        });
+
+        let (block_header, inclusion_proof, completeness_proof, txs) = get_mock_data();
+
+        assert!(verifier
+            .verify_relevant_tx_list(
+                &block_header,
+                txs.as_slice(),
+                inclusion_proof,
+                completeness_proof
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn borsh_proof_input() {
+        let verifier = BitcoinVerifier::new(RollupParams {
+            rollup_name: "sov-btc".to_string(),
+            reveal_tx_id_prefix: vec![0, 0],
+            reveal_tx_id_zero_bits: 0,
+            max_relevant_txs: None,
+            max_decompressed_len: None,
+            monotonic_batch_numbers: false,
+        });
+
+        let (header, inclusion_proof, completeness_proof, blobs) = get_mock_data();
+
+        let proof_input = ProofInput {
+            header,
+            blobs,
+            inclusion_proof,
+            completeness_proof,
+            batch_sequence: None,
+        };
+
+        let serialized = proof_input.try_to_vec().unwrap();
+        let deserialized = ProofInput::try_from_slice(&serialized).unwrap();
+
+        assert_eq!(proof_input, deserialized);
+
+        assert!(verifier
+            .verify_relevant_tx_list(
+                &deserialized.header,
+                deserialized.blobs.as_slice(),
+                deserialized.inclusion_proof,
+                deserialized.completeness_proof
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn compact_completeness_proof_verifies() {
+        let verifier = BitcoinVerifier::new(RollupParams {
+            rollup_name: "sov-btc".to_string(),
+            reveal_tx_id_prefix: vec![0, 0],
+            reveal_tx_id_zero_bits: 0,
+            max_relevant_txs: None,
+            max_decompressed_len: None,
+            monotonic_batch_numbers: false,
         });
 
         let (block_header, inclusion_proof, completeness_proof, txs) = get_mock_data();
+        let witness_proof = WitnessCommitmentProof::new(&get_mock_txs());
+        assert!(witness_proof.is_committed());
+
+        let compact_proof = compact_completeness_proof(&completeness_proof);
+
+        // the envelopes are kept, the txids are unchanged
+        for (compact_tx, tx) in compact_proof.iter().zip(&completeness_proof) {
+            assert_eq!(compact_tx.tx.input[0].witness, tx.input[0].witness);
+            assert!(compact_tx
+                .tx
+                .input
+                .iter()
+                .skip(1)
+                .all(|input| input.witness.is_empty()));
+            assert_eq!(compact_tx.tx.txid(), tx.txid());
+            assert_eq!(compact_tx.wtxid(), tx.wtxid());
+        }
+
+        let serialized = (compact_proof.clone(), witness_proof.clone())
+            .try_to_vec()
+            .unwrap();
+        let deserialized =
+            <(Vec<CompactTransaction>, WitnessCommitmentProof)>::try_from_slice(&serialized)
+                .unwrap();
+        assert_eq!((compact_proof.clone(), witness_proof.clone()), deserialized);
+
+        // the blobs are read from the kept envelopes
+        assert!(verifier
+            .verify_compact_relevant_tx_list(
+                &block_header,
+                &txs,
+                inclusion_proof.clone(),
+                compact_proof.clone(),
+                Some(witness_proof.clone()),
+            )
+            .is_ok());
+        let missing = panic::catch_unwind(|| {
+            verifier.verify_compact_relevant_tx_list(
+                &block_header,
+                &txs[1..],
+                inclusion_proof.clone(),
+                compact_proof.clone(),
+                Some(witness_proof.clone()),
+            )
+        });
+        assert!(missing.is_err());
+
+        // as if a witness of the first tx was stripped, its wtxid must be committed
+        let mut stripped = compact_proof.clone();
+        stripped[0].stripped_wtxid = Some(completeness_proof[0].wtxid());
+        assert!(stripped[0].is_stripped());
+        assert!(verifier
+            .verify_compact_relevant_tx_list(
+                &block_header,
+                &txs,
+                inclusion_proof.clone(),
+                stripped.clone(),
+                Some(witness_proof.clone()),
+            )
+            .is_ok());
+        assert_eq!(
+            verifier.verify_compact_relevant_tx_list(
+                &block_header,
+                &txs,
+                inclusion_proof.clone(),
+                stripped.clone(),
+                None,
+            ),
+            Err(ValidationError::InvalidProof)
+        );
+
+        // a wtxid the block does not commit to
+        let mut tampered = stripped.clone();
+        tampered[0].stripped_wtxid = Some(completeness_proof[1].wtxid());
+        assert_eq!(
+            verifier.verify_compact_relevant_tx_list(
+                &block_header,
+                &txs,
+                inclusion_proof.clone(),
+                tampered,
+                Some(witness_proof.clone()),
+            ),
+            Err(ValidationError::InvalidProof)
+        );
+
+        // wtxids the coinbase does not commit to
+        let mut tampered_witness_proof = witness_proof;
+        tampered_witness_proof.wtxids[6] = tampered_witness_proof.wtxids[8];
+        assert_eq!(
+            verifier.verify_compact_relevant_tx_list(
+                &block_header,
+                &txs,
+                inclusion_proof,
+                stripped,
+                Some(tampered_witness_proof),
+            ),
+            Err(ValidationError::InvalidProof)
+        );
+
+        // the witnesses after the envelope are stripped and restored
+        let mut tx = completeness_proof[0].clone();
+        let mut input = tx.input[0].clone();
+        input.previous_output.vout += 1;
+        input.witness = vec![vec![1; 64]].into();
+        tx.input.push(input);
+        let compact_tx = CompactTransaction::new(&tx);
+        assert!(compact_tx.is_stripped());
+        assert!(compact_tx.tx.input[1].witness.is_empty());
+        assert!(compact_tx.try_to_vec().unwrap().len() < bitcoin::consensus::serialize(&tx).len());
+        assert_eq!(
+            compact_tx.restore(vec![tx.input[1].witness.clone()]),
+            Some(tx.clone())
+        );
+        // a forged witness does not restore
+        assert_eq!(compact_tx.restore(vec![vec![vec![2; 64]].into()]), None);
+    }
+
+    #[test]
+    fn relevant_tx_cap() {
+        let verifier = BitcoinVerifier::new(RollupParams {
+            rollup_name: "sov-btc".to_string(),
+            reveal_tx_id_prefix: vec![0, 0],
+            reveal_tx_id_zero_bits: 0,
+            max_relevant_txs: Some(2),
+            max_decompressed_len: None,
+            monotonic_batch_numbers: false,
+        });
+
+        let (block_header, inclusion_proof, mut completeness_proof, mut txs) = get_mock_data();
+
+        // only the first two relevant txs in block order are read
+        completeness_proof.truncate(2);
+        txs.truncate(2);
 
         assert!(verifier
             .verify_relevant_tx_list(
@@ -323,12 +853,110 @@ mod tests {
             .is_ok());
     }
 
+    #[test]
+    fn monotonic_batch_numbers() {
+        let params = RollupParams {
+            rollup_name: "sov-btc".to_string(),
+            reveal_tx_id_prefix: vec![0, 0],
+            reveal_tx_id_zero_bits: 0,
+            max_relevant_txs: None,
+            max_decompressed_len: None,
+            monotonic_batch_numbers: true,
+        };
+        let verifier = BitcoinVerifier::new(params.clone());
+
+        // the blobs of the block are unnumbered batches, which are dropped, and the verifier
+        // keeps no state, so the block verifies the same way again
+        let (block_header, inclusion_proof, completeness_proof, txs) = get_mock_data();
+        assert!(!txs.is_empty());
+        assert!(txs.iter().all(|blob| blob.metadata.batch_number.is_none()));
+        for _ in 0..2 {
+            let validity_condition = verifier
+                .verify_relevant_tx_list(
+                    &block_header,
+                    &[],
+                    inclusion_proof.clone(),
+                    completeness_proof.clone(),
+                )
+                .unwrap();
+            assert_eq!(
+                validity_condition.prev_batch_sequence,
+                BatchSequence::new().digest()
+            );
+            assert_eq!(
+                validity_condition.batch_sequence,
+                validity_condition.prev_batch_sequence
+            );
+        }
+
+        // the validity condition commits to the sequence the verifier starts from
+        let mut batch_sequence = BatchSequence::new();
+        assert!(batch_sequence.accept(&[1; 33], Some(1), BlobKind::Batch));
+        let validity_condition = BitcoinVerifier::new(params)
+            .with_batch_sequence(batch_sequence.clone())
+            .verify_relevant_tx_list(&block_header, &[], inclusion_proof, completeness_proof)
+            .unwrap();
+        assert_eq!(
+            validity_condition.prev_batch_sequence,
+            batch_sequence.digest()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "completeness proof is incorrect")]
+    fn monotonic_batch_numbers_drop_unnumbered_batches() {
+        let verifier = BitcoinVerifier::new(RollupParams {
+            rollup_name: "sov-btc".to_string(),
+            reveal_tx_id_prefix: vec![0, 0],
+            reveal_tx_id_zero_bits: 0,
+            max_relevant_txs: None,
+            max_decompressed_len: None,
+            monotonic_batch_numbers: true,
+        });
+
+        let (block_header, inclusion_proof, completeness_proof, txs) = get_mock_data();
+        let _ = verifier.verify_relevant_tx_list(
+            &block_header,
+            txs.as_slice(),
+            inclusion_proof,
+            completeness_proof,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "completeness proof exceeds the relevant tx cap")]
+    fn completeness_proof_over_cap() {
+        let verifier = BitcoinVerifier::new(RollupParams {
+            rollup_name: "sov-btc".to_string(),
+            reveal_tx_id_prefix: vec![0, 0],
+            reveal_tx_id_zero_bits: 0,
+            max_relevant_txs: Some(2),
+            max_decompressed_len: None,
+            monotonic_batch_numbers: false,
+        });
+
+        let (block_header, inclusion_proof, completeness_proof, txs) = get_mock_data();
+
+        verifier
+            .verify_relevant_tx_list(
+                &block_header,
+                txs.as_slice(),
+                inclusion_proof,
+                completeness_proof,
+            )
+            .unwrap();
+    }
+
     #[test]
     #[should_panic(expected = "inclusion proof is incorrect")]
     fn extra_tx_in_inclusion() {
         let verifier = BitcoinVerifier::new(RollupParams {
             rollup_name: "sov-btc".to_string(),
             reveal_tx_id_prefix: vec![0, 0],
+            reveal_tx_id_zero_bits: 0,
+            max_relevant_txs: None,
+            max_decompressed_len: None,
+            monotonic_batch_numbers: false,
         });
 
         let (block_header, mut inclusion_proof, completeness_proof, txs) = get_mock_data();
@@ -353,6 +981,10 @@ mod tests {
         let verifier = BitcoinVerifier::new(RollupParams {
             rollup_name: "sov-btc".to_string(),
             reveal_tx_id_prefix: vec![0, 0],
+            reveal_tx_id_zero_bits: 0,
+            max_relevant_txs: None,
+            max_decompressed_len: None,
+            monotonic_batch_numbers: false,
         });
 
         let (block_header, mut inclusion_proof, completeness_proof, txs) = get_mock_data();
@@ -375,6 +1007,10 @@ mod tests {
         let verifier = BitcoinVerifier::new(RollupParams {
             rollup_name: "sov-btc".to_string(),
             reveal_tx_id_prefix: vec![0, 0],
+            reveal_tx_id_zero_bits: 0,
+            max_relevant_txs: None,
+            max_decompressed_len: None,
+            monotonic_batch_numbers: false,
         });
 
         let (block_header, mut inclusion_proof, completeness_proof, txs) = get_mock_data();
@@ -397,6 +1033,10 @@ mod tests {
         let verifier = BitcoinVerifier::new(RollupParams {
             rollup_name: "sov-btc".to_string(),
             reveal_tx_id_prefix: vec![0, 0],
+            reveal_tx_id_zero_bits: 0,
+            max_relevant_txs: None,
+            max_decompressed_len: None,
+            monotonic_batch_numbers: false,
         });
 
         let (block_header, mut inclusion_proof, completeness_proof, txs) = get_mock_data();
@@ -419,6 +1059,10 @@ mod tests {
         let verifier = BitcoinVerifier::new(RollupParams {
             rollup_name: "sov-btc".to_string(),
             reveal_tx_id_prefix: vec![0, 0],
+            reveal_tx_id_zero_bits: 0,
+            max_relevant_txs: None,
+            max_decompressed_len: None,
+            monotonic_batch_numbers: false,
         });
 
         let (block_header, inclusion_proof, mut completeness_proof, txs) = get_mock_data();
@@ -441,6 +1085,10 @@ mod tests {
         let verifier = BitcoinVerifier::new(RollupParams {
             rollup_name: "sov-btc".to_string(),
             reveal_tx_id_prefix: vec![0, 0],
+            reveal_tx_id_zero_bits: 0,
+            max_relevant_txs: None,
+            max_decompressed_len: None,
+            monotonic_batch_numbers: false,
         });
 
         let (block_header, inclusion_proof, mut completeness_proof, txs) = get_mock_data();
@@ -463,6 +1111,10 @@ mod tests {
         let verifier = BitcoinVerifier::new(RollupParams {
             rollup_name: "sov-btc".to_string(),
             reveal_tx_id_prefix: vec![0, 0],
+            reveal_tx_id_zero_bits: 0,
+            max_relevant_txs: None,
+            max_decompressed_len: None,
+            monotonic_batch_numbers: false,
         });
 
         let (block_header, inclusion_proof, mut completeness_proof, txs) = get_mock_data();
@@ -487,6 +1139,10 @@ mod tests {
         let verifier = BitcoinVerifier::new(RollupParams {
             rollup_name: "sov-btc".to_string(),
             reveal_tx_id_prefix: vec![0, 0],
+            reveal_tx_id_zero_bits: 0,
+            max_relevant_txs: None,
+            max_decompressed_len: None,
+            monotonic_batch_numbers: false,
         });
 
         let (block_header, inclusion_proof, mut completeness_proof, mut txs) = get_mock_data();
@@ -510,6 +1166,10 @@ mod tests {
         let verifier = BitcoinVerifier::new(RollupParams {
             rollup_name: "sov-btc".to_string(),
             reveal_tx_id_prefix: vec![0, 0],
+            reveal_tx_id_zero_bits: 0,
+            max_relevant_txs: None,
+            max_decompressed_len: None,
+            monotonic_batch_numbers: false,
         });
 
         let (block_header, inclusion_proof, completeness_proof, mut txs) = get_mock_data();
@@ -532,6 +1192,10 @@ mod tests {
         let verifier = BitcoinVerifier::new(RollupParams {
             rollup_name: "sov-btc".to_string(),
             reveal_tx_id_prefix: vec![0, 0],
+            reveal_tx_id_zero_bits: 0,
+            max_relevant_txs: None,
+            max_decompressed_len: None,
+            monotonic_batch_numbers: false,
         });
 
         let (block_header, inclusion_proof, mut completeness_proof, mut txs) = get_mock_data();
@@ -555,6 +1219,10 @@ mod tests {
         let verifier = BitcoinVerifier::new(RollupParams {
             rollup_name: "sov-btc".to_string(),
             reveal_tx_id_prefix: vec![0, 0],
+            reveal_tx_id_zero_bits: 0,
+            max_relevant_txs: None,
+            max_decompressed_len: None,
+            monotonic_batch_numbers: false,
         });
 
         let (block_header, inclusion_proof, completeness_proof, mut txs) = get_mock_data();
@@ -579,6 +1247,10 @@ mod tests {
         let verifier = BitcoinVerifier::new(RollupParams {
             rollup_name: "sov-btc".to_string(),
             reveal_tx_id_prefix: vec![0, 0],
+            reveal_tx_id_zero_bits: 0,
+            max_relevant_txs: None,
+            max_decompressed_len: None,
+            monotonic_batch_numbers: false,
         });
 
         let (block_header, inclusion_proof, completeness_proof, mut txs) = get_mock_data();
@@ -601,12 +1273,42 @@ mod tests {
             .unwrap();
     }
 
+    #[test]
+    #[should_panic(expected = "blob metadata was modified")]
+    fn tamper_metadata() {
+        let verifier = BitcoinVerifier::new(RollupParams {
+            rollup_name: "sov-btc".to_string(),
+            reveal_tx_id_prefix: vec![0, 0],
+            reveal_tx_id_zero_bits: 0,
+            max_relevant_txs: None,
+            max_decompressed_len: None,
+            monotonic_batch_numbers: false,
+        });
+
+        let (block_header, inclusion_proof, completeness_proof, mut txs) = get_mock_data();
+
+        txs[1].metadata.batch_number = Some(1);
+
+        verifier
+            .verify_relevant_tx_list(
+                &block_header,
+                txs.as_slice(),
+                inclusion_proof,
+                completeness_proof,
+            )
+            .unwrap();
+    }
+
     #[test]
     #[should_panic(expected = "valid blob was not found in blobs")]
     fn missing_rel_tx() {
         let verifier = BitcoinVerifier::new(RollupParams {
             rollup_name: "sov-btc".to_string(),
             reveal_tx_id_prefix: vec![0, 0],
+            reveal_tx_id_zero_bits: 0,
+            max_relevant_txs: None,
+            max_decompressed_len: None,
+            monotonic_batch_numbers: false,
         });
 
         let (block_header, inclusion_proof, completeness_proof, mut txs) = get_mock_data();
@@ -622,4 +1324,617 @@ mod tests {
             )
             .unwrap();
     }
+
+    // Reveal tx of a body signed by the sequencer key, whatever the body is
+    fn signed_reveal_tx(body: Vec<u8>) -> Transaction {
+        let secret_key = bitcoin::secp256k1::SecretKey::from_slice(&[1; 32]).unwrap();
+        let (signature, public_key) = sign_blob_with_private_key(&body, &secret_key);
+
+        let address = bitcoin::Address::from_str(
+            "bc1pp8qru0ve43rw9xffmdd8pvveths3cx6a5t6mcr0xfn9cpxx2k24qf70xq9",
+        )
+        .unwrap()
+        .assume_checked();
+        let (_, reveal_tx) = InscriptionBuilder::new("sov-btc")
+            .with_body(body)
+            .with_signature(signature, public_key)
+            .with_utxos(vec![crate::spec::utxo::UTXO {
+                tx_id: bitcoin::Txid::all_zeros(),
+                vout: 0,
+                address: address.to_string(),
+                script_pubkey: address.script_pubkey().to_hex_string(),
+                amount: 1_000_000,
+                confirmations: 1,
+                spendable: true,
+                solvable: true,
+                ancestor_count: None,
+            }])
+            .with_recipient(address)
+            .with_fee_rates(1.0, 1.0)
+            .build()
+            .unwrap();
+
+        reveal_tx
+    }
+
+    // Verifies a block holding only tx, from which no blob is expected
+    fn verify_without_blobs(verifier: &BitcoinVerifier, tx: Transaction) -> bool {
+        let header = HeaderWrapper::new(
+            Header {
+                version: Version::from_consensus(536870912),
+                prev_blockhash: BlockHash::all_zeros(),
+                merkle_root: TxMerkleNode::from_raw_hash(tx.txid().to_raw_hash()),
+                time: 1694177029,
+                bits: CompactTarget::from_hex_str_no_prefix("207fffff").unwrap(),
+                nonce: 0,
+            },
+            1,
+            2,
+        );
+
+        verifier
+            .verify_relevant_tx_list(
+                &header,
+                &[],
+                InclusionMultiProof {
+                    txs: vec![tx.txid().to_raw_hash().to_byte_array()],
+                },
+                vec![tx],
+            )
+            .is_ok()
+    }
+
+    #[test]
+    fn corrupted_blob_is_dropped() {
+        // a properly signed body that is not a brotli stream
+        let reveal_tx = signed_reveal_tx(vec![0xff; 100]);
+        let blob_hash = parse_transaction(&reveal_tx, "sov-btc")
+            .unwrap()
+            .get_sig_verified_hash()
+            .unwrap();
+
+        assert_eq!(
+            verify_blob_tx(&reveal_tx, blob_hash, "sov-btc"),
+            Err(ValidationError::InvalidBlob(DecompressError::Corrupted))
+        );
+
+        // the blob is not expected in the relevant tx list of its block
+        let verifier = BitcoinVerifier::new(RollupParams {
+            rollup_name: "sov-btc".to_string(),
+            reveal_tx_id_prefix: vec![],
+            reveal_tx_id_zero_bits: 0,
+            max_relevant_txs: None,
+            max_decompressed_len: None,
+            monotonic_batch_numbers: false,
+        });
+
+        assert!(verify_without_blobs(&verifier, reveal_tx));
+    }
+
+    #[test]
+    fn decompression_bomb_is_dropped() {
+        // a megabyte of zeros compresses to a few bytes
+        let reveal_tx = signed_reveal_tx(compress_blob(&vec![0; 1_000_000]));
+        let verifier = BitcoinVerifier::new(RollupParams {
+            rollup_name: "sov-btc".to_string(),
+            reveal_tx_id_prefix: vec![],
+            reveal_tx_id_zero_bits: 0,
+            max_relevant_txs: None,
+            max_decompressed_len: Some(100_000),
+            monotonic_batch_numbers: false,
+        });
+
+        assert!(verify_without_blobs(&verifier, reveal_tx));
+    }
+
+    #[test]
+    fn batched_blobs() {
+        let secret_key = bitcoin::secp256k1::SecretKey::from_slice(&[1; 32]).unwrap();
+        let signed_blob = |blob: &[u8]| {
+            let body = compress_blob(blob);
+            let (signature, public_key) = sign_blob_with_private_key(&body, &secret_key);
+            SignedBlob {
+                body,
+                signature,
+                public_key,
+                metadata: Default::default(),
+            }
+        };
+        let first = signed_blob(b"first");
+        let second = signed_blob(b"second");
+
+        let address = bitcoin::Address::from_str(
+            "bc1pp8qru0ve43rw9xffmdd8pvveths3cx6a5t6mcr0xfn9cpxx2k24qf70xq9",
+        )
+        .unwrap()
+        .assume_checked();
+        let (_, reveal_tx) = InscriptionBuilder::new("sov-btc")
+            .with_body(first.body.clone())
+            .with_signature(first.signature.clone(), first.public_key.clone())
+            .with_batched_blob(second.clone())
+            .with_utxos(vec![crate::spec::utxo::UTXO {
+                tx_id: bitcoin::Txid::all_zeros(),
+                vout: 0,
+                address: address.to_string(),
+                script_pubkey: address.script_pubkey().to_hex_string(),
+                amount: 1_000_000,
+                confirmations: 1,
+                spendable: true,
+                solvable: true,
+                ancestor_count: None,
+            }])
+            .with_recipient(address)
+            .with_fee_rates(1.0, 1.0)
+            .build()
+            .unwrap();
+
+        let blobs: Vec<BlobWithSender> = [first, second]
+            .iter()
+            .map(|blob| {
+                BlobWithSender::new(
+                    decompress_blob(&blob.body).unwrap(),
+                    blob.public_key.clone(),
+                    sha256d::Hash::hash(&blob.body).to_byte_array(),
+                )
+            })
+            .collect();
+
+        // any blob of the tx can be verified on its own
+        let verified = verify_blob_tx(&reveal_tx, blobs[1].hash, "sov-btc").unwrap();
+        assert_eq!(verified.blob, b"second");
+
+        let header = HeaderWrapper::new(
+            Header {
+                version: Version::from_consensus(536870912),
+                prev_blockhash: BlockHash::all_zeros(),
+                merkle_root: TxMerkleNode::from_raw_hash(reveal_tx.txid().to_raw_hash()),
+                time: 1694177029,
+                bits: CompactTarget::from_hex_str_no_prefix("207fffff").unwrap(),
+                nonce: 0,
+            },
+            1,
+            2,
+        );
+        let verifier = BitcoinVerifier::new(RollupParams {
+            rollup_name: "sov-btc".to_string(),
+            reveal_tx_id_prefix: vec![],
+            reveal_tx_id_zero_bits: 0,
+            max_relevant_txs: None,
+            max_decompressed_len: None,
+            monotonic_batch_numbers: false,
+        });
+        let inclusion_proof = InclusionMultiProof {
+            txs: vec![reveal_tx.txid().to_raw_hash().to_byte_array()],
+        };
+
+        assert!(verifier
+            .verify_relevant_tx_list(
+                &header,
+                &blobs,
+                inclusion_proof.clone(),
+                vec![reveal_tx.clone()],
+            )
+            .is_ok());
+
+        // leaving out a blob of the batch is caught
+        let missing = panic::catch_unwind(|| {
+            verifier.verify_relevant_tx_list(&header, &blobs[..1], inclusion_proof, vec![reveal_tx])
+        });
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn chunked_blob_txs() {
+        let secret_key = bitcoin::secp256k1::SecretKey::from_slice(&[1; 32]).unwrap();
+        let address = bitcoin::Address::from_str(
+            "bc1pp8qru0ve43rw9xffmdd8pvveths3cx6a5t6mcr0xfn9cpxx2k24qf70xq9",
+        )
+        .unwrap()
+        .assume_checked();
+
+        let blob = b"a blob split across three reveal txs".to_vec();
+        let blob_hash = sha256d::Hash::hash(&blob).to_byte_array();
+        let reveal_txs: Vec<Transaction> = split_blob(&blob, 16)
+            .into_iter()
+            .map(|(chunk, manifest)| {
+                let body = compress_blob(&chunk);
+                let metadata = InscriptionMetadata {
+                    chunk: Some(manifest),
+                    ..Default::default()
+                };
+                let (signature, public_key) = sign_blob_with_private_key(
+                    &blob_signing_message(&body, &metadata, []),
+                    &secret_key,
+                );
+                InscriptionBuilder::new("sov-btc")
+                    .with_body(body)
+                    .with_signature(signature, public_key)
+                    .with_metadata(metadata)
+                    .with_utxos(vec![crate::spec::utxo::UTXO {
+                        tx_id: bitcoin::Txid::all_zeros(),
+                        vout: 0,
+                        address: address.to_string(),
+                        script_pubkey: address.script_pubkey().to_hex_string(),
+                        amount: 1_000_000,
+                        confirmations: 1,
+                        spendable: true,
+                        solvable: true,
+                        ancestor_count: None,
+                    }])
+                    .with_recipient(address.clone())
+                    .with_fee_rates(1.0, 1.0)
+                    .build()
+                    .unwrap()
+                    .1
+            })
+            .collect();
+        assert_eq!(reveal_txs.len(), 3);
+
+        let verified = verify_chunked_blob_txs(&reveal_txs, blob_hash, "sov-btc").unwrap();
+        assert_eq!(verified.blob, blob);
+        assert_eq!(verified.txid, reveal_txs[2].txid());
+
+        assert_eq!(
+            verify_chunked_blob_txs(&reveal_txs[..2], blob_hash, "sov-btc"),
+            Err(ValidationError::InvalidChunks(ChunkError::MissingChunks))
+        );
+        assert_eq!(
+            verify_chunked_blob_txs(&reveal_txs, [0; 32], "sov-btc"),
+            Err(ValidationError::BlobHashMismatch)
+        );
+    }
+
+    #[test]
+    fn verify_checkpoint() {
+        let secret_key = bitcoin::secp256k1::SecretKey::from_slice(&[5; 32]).unwrap();
+        let address = bitcoin::Address::from_str(
+            "bc1pp8qru0ve43rw9xffmdd8pvveths3cx6a5t6mcr0xfn9cpxx2k24qf70xq9",
+        )
+        .unwrap()
+        .assume_checked();
+        let reveal_tx = |body: &[u8], kind| {
+            let body = compress_blob(body);
+            let metadata = InscriptionMetadata {
+                kind,
+                ..Default::default()
+            };
+            let (signature, public_key) = sign_blob_with_private_key(
+                &blob_signing_message(&body, &metadata, []),
+                &secret_key,
+            );
+            let (_, reveal_tx) = InscriptionBuilder::new("sov-btc")
+                .with_body(body)
+                .with_signature(signature, public_key.clone())
+                .with_metadata(metadata)
+                .with_utxos(vec![crate::spec::utxo::UTXO {
+                    tx_id: bitcoin::Txid::all_zeros(),
+                    vout: 0,
+                    address: address.to_string(),
+                    script_pubkey: address.script_pubkey().to_hex_string(),
+                    amount: 1_000_000,
+                    confirmations: 1,
+                    spendable: true,
+                    solvable: true,
+                    ancestor_count: None,
+                }])
+                .with_recipient(address.clone())
+                .with_fee_rates(1.0, 1.0)
+                .build()
+                .unwrap();
+            (reveal_tx, public_key)
+        };
+
+        let checkpoint = Checkpoint {
+            state_root: [7; 32],
+        };
+        let (tx, sender) = reveal_tx(&checkpoint.to_bytes(), Some(BlobKind::Checkpoint));
+        let verified = verify_checkpoint_tx(&tx, &sender, "sov-btc").unwrap();
+        assert_eq!(verified.checkpoint, checkpoint);
+        assert_eq!(verified.txid, tx.txid());
+
+        // another sender
+        assert_eq!(
+            verify_checkpoint_tx(&tx, &[2; 33], "sov-btc"),
+            Err(ValidationError::InvalidTx)
+        );
+
+        // a batch, or a checkpoint that is not a state root
+        let (tx, sender) = reveal_tx(&checkpoint.to_bytes(), None);
+        assert_eq!(
+            verify_checkpoint_tx(&tx, &sender, "sov-btc"),
+            Err(ValidationError::InvalidCheckpoint)
+        );
+        let (tx, sender) = reveal_tx(&[7; 33], Some(BlobKind::Checkpoint));
+        assert_eq!(
+            verify_checkpoint_tx(&tx, &sender, "sov-btc"),
+            Err(ValidationError::InvalidCheckpoint)
+        );
+    }
+
+    #[test]
+    fn verify_schnorr_blob() {
+        let secret_key = bitcoin::secp256k1::SecretKey::from_slice(&[6; 32]).unwrap();
+        let address = bitcoin::Address::from_str(
+            "bc1pp8qru0ve43rw9xffmdd8pvveths3cx6a5t6mcr0xfn9cpxx2k24qf70xq9",
+        )
+        .unwrap()
+        .assume_checked();
+        let body = compress_blob(b"schnorr");
+        let blob_hash = sha256d::Hash::hash(&body).to_byte_array();
+        let message = blob_signing_message(
+            &body,
+            &InscriptionMetadata {
+                signature_scheme: Some(SignatureScheme::Schnorr),
+                ..Default::default()
+            },
+            [],
+        );
+        let (signature, public_key) = sign_blob_schnorr_with_private_key(&message, &secret_key);
+        assert_eq!((signature.len(), public_key.len()), (64, 32));
+
+        let reveal_tx = |signature: Vec<u8>, public_key: Vec<u8>, scheme| {
+            InscriptionBuilder::new("sov-btc")
+                .with_body(body.clone())
+                .with_signature(signature, public_key)
+                .with_metadata(InscriptionMetadata {
+                    signature_scheme: Some(scheme),
+                    ..Default::default()
+                })
+                .with_utxos(vec![crate::spec::utxo::UTXO {
+                    tx_id: bitcoin::Txid::all_zeros(),
+                    vout: 0,
+                    address: address.to_string(),
+                    script_pubkey: address.script_pubkey().to_hex_string(),
+                    amount: 1_000_000,
+                    confirmations: 1,
+                    spendable: true,
+                    solvable: true,
+                    ancestor_count: None,
+                }])
+                .with_recipient(address.clone())
+                .with_fee_rates(1.0, 1.0)
+                .build()
+                .unwrap()
+                .1
+        };
+
+        let tx = reveal_tx(
+            signature.clone(),
+            public_key.clone(),
+            SignatureScheme::Schnorr,
+        );
+        let verified = verify_blob_tx(&tx, blob_hash, "sov-btc").unwrap();
+        assert_eq!(verified.sender, public_key);
+        assert_eq!(
+            verified.metadata.signature_scheme,
+            Some(SignatureScheme::Schnorr)
+        );
+
+        // the tag decides the scheme, not the public key length
+        let tx = reveal_tx(signature, public_key, SignatureScheme::Ecdsa);
+        assert_eq!(
+            verify_blob_tx(&tx, blob_hash, "sov-btc"),
+            Err(ValidationError::InvalidTx)
+        );
+        let (signature, public_key) = sign_blob_with_private_key(&message, &secret_key);
+        let tx = reveal_tx(signature, public_key, SignatureScheme::Schnorr);
+        assert_eq!(
+            verify_blob_tx(&tx, blob_hash, "sov-btc"),
+            Err(ValidationError::InvalidTx)
+        );
+    }
+
+    #[test]
+    fn verify_bip322_blob() {
+        let secret_key = bitcoin::secp256k1::SecretKey::from_slice(&[8; 32]).unwrap();
+        let address = bitcoin::Address::from_str(
+            "bc1pp8qru0ve43rw9xffmdd8pvveths3cx6a5t6mcr0xfn9cpxx2k24qf70xq9",
+        )
+        .unwrap()
+        .assume_checked();
+        let body = compress_blob(b"bip322");
+        let blob_hash = sha256d::Hash::hash(&body).to_byte_array();
+        let message = blob_signing_message(
+            &body,
+            &InscriptionMetadata {
+                signature_scheme: Some(SignatureScheme::Bip322),
+                ..Default::default()
+            },
+            [],
+        );
+        let (signature, script_pubkey) =
+            bip322::sign_simple_p2tr(&sha256d::Hash::hash(&message).to_byte_array(), &secret_key);
+
+        let reveal_tx = |signature: Vec<u8>, scheme| {
+            InscriptionBuilder::new("sov-btc")
+                .with_body(body.clone())
+                .with_signature(signature, script_pubkey.clone())
+                .with_metadata(InscriptionMetadata {
+                    signature_scheme: scheme,
+                    ..Default::default()
+                })
+                .with_utxos(vec![crate::spec::utxo::UTXO {
+                    tx_id: bitcoin::Txid::all_zeros(),
+                    vout: 0,
+                    address: address.to_string(),
+                    script_pubkey: address.script_pubkey().to_hex_string(),
+                    amount: 1_000_000,
+                    confirmations: 1,
+                    spendable: true,
+                    solvable: true,
+                    ancestor_count: None,
+                }])
+                .with_recipient(address.clone())
+                .with_fee_rates(1.0, 1.0)
+                .build()
+                .unwrap()
+                .1
+        };
+
+        let tx = reveal_tx(signature.clone(), Some(SignatureScheme::Bip322));
+        let verified = verify_blob_tx(&tx, blob_hash, "sov-btc").unwrap();
+        let sender_address = bitcoin::Address::from_script(
+            bitcoin::Script::from_bytes(&verified.sender),
+            bitcoin::Network::Bitcoin,
+        )
+        .unwrap();
+        assert!(bip322::is_allowed_sender(
+            &verified.sender,
+            &[sender_address]
+        ));
+        assert!(!bip322::is_allowed_sender(
+            &verified.sender,
+            std::slice::from_ref(&address)
+        ));
+
+        // without the tag, a script pubkey is not a public key
+        let tx = reveal_tx(signature, None);
+        assert_eq!(
+            verify_blob_tx(&tx, blob_hash, "sov-btc"),
+            Err(ValidationError::InvalidTx)
+        );
+        // a signature of another blob
+        let (signature, _) = bip322::sign_simple_p2tr(&[0; 32], &secret_key);
+        let tx = reveal_tx(signature, Some(SignatureScheme::Bip322));
+        assert_eq!(
+            verify_blob_tx(&tx, blob_hash, "sov-btc"),
+            Err(ValidationError::InvalidTx)
+        );
+    }
+
+    #[test]
+    fn verify_zstd_blob() {
+        let secret_key = bitcoin::secp256k1::SecretKey::from_slice(&[9; 32]).unwrap();
+        let address = bitcoin::Address::from_str(
+            "bc1pp8qru0ve43rw9xffmdd8pvveths3cx6a5t6mcr0xfn9cpxx2k24qf70xq9",
+        )
+        .unwrap()
+        .assume_checked();
+        let blob = b"zstd".repeat(100);
+        let body = compress_blob_with(&blob, CompressionAlgorithm::Zstd);
+        let blob_hash = sha256d::Hash::hash(&body).to_byte_array();
+
+        // signs the body with the signed algorithm and tags it with the other one
+        let reveal_tx = |signed_compression, compression| {
+            let metadata = InscriptionMetadata {
+                compression: signed_compression,
+                ..Default::default()
+            };
+            let (signature, public_key) = sign_blob_with_private_key(
+                &blob_signing_message(&body, &metadata, []),
+                &secret_key,
+            );
+            InscriptionBuilder::new("sov-btc")
+                .with_body(body.clone())
+                .with_signature(signature, public_key)
+                .with_metadata(InscriptionMetadata {
+                    compression,
+                    ..metadata
+                })
+                .with_utxos(vec![crate::spec::utxo::UTXO {
+                    tx_id: bitcoin::Txid::all_zeros(),
+                    vout: 0,
+                    address: address.to_string(),
+                    script_pubkey: address.script_pubkey().to_hex_string(),
+                    amount: 1_000_000,
+                    confirmations: 1,
+                    spendable: true,
+                    solvable: true,
+                    ancestor_count: None,
+                }])
+                .with_recipient(address.clone())
+                .with_fee_rates(1.0, 1.0)
+                .build()
+                .unwrap()
+                .1
+        };
+
+        let tx = reveal_tx(
+            Some(CompressionAlgorithm::Zstd),
+            Some(CompressionAlgorithm::Zstd),
+        );
+        let verified = verify_blob_tx(&tx, blob_hash, "sov-btc").unwrap();
+        assert_eq!(verified.blob, blob);
+        assert_eq!(
+            verified.metadata.compression,
+            Some(CompressionAlgorithm::Zstd)
+        );
+
+        // without the tag the algorithm is detected
+        let tx = reveal_tx(None, None);
+        assert_eq!(
+            verify_blob_tx(&tx, blob_hash, "sov-btc").unwrap().blob,
+            blob
+        );
+        // the tag is not second-guessed
+        let tx = reveal_tx(
+            Some(CompressionAlgorithm::Brotli),
+            Some(CompressionAlgorithm::Brotli),
+        );
+        assert!(matches!(
+            verify_blob_tx(&tx, blob_hash, "sov-btc"),
+            Err(ValidationError::InvalidBlob(_))
+        ));
+        // the tag is signed, the body cannot be re-tagged to read as another blob under the
+        // same hash
+        for compression in [None, Some(CompressionAlgorithm::Brotli)] {
+            let tx = reveal_tx(Some(CompressionAlgorithm::Zstd), compression);
+            assert_eq!(
+                verify_blob_tx(&tx, blob_hash, "sov-btc"),
+                Err(ValidationError::InvalidTx)
+            );
+        }
+    }
+
+    #[test]
+    fn verify_stored_blob() {
+        let secret_key = bitcoin::secp256k1::SecretKey::from_slice(&[10; 32]).unwrap();
+        let address = bitcoin::Address::from_str(
+            "bc1pp8qru0ve43rw9xffmdd8pvveths3cx6a5t6mcr0xfn9cpxx2k24qf70xq9",
+        )
+        .unwrap()
+        .assume_checked();
+        let blob = b"stored as is".to_vec();
+        let blob_hash = sha256d::Hash::hash(&blob).to_byte_array();
+        let metadata = InscriptionMetadata {
+            compression: Some(CompressionAlgorithm::None),
+            ..Default::default()
+        };
+
+        let reveal_tx = |signature: Vec<u8>, public_key: Vec<u8>| {
+            InscriptionBuilder::new("sov-btc")
+                .with_body(blob.clone())
+                .with_signature(signature, public_key)
+                .with_metadata(metadata)
+                .with_utxos(vec![crate::spec::utxo::UTXO {
+                    tx_id: bitcoin::Txid::all_zeros(),
+                    vout: 0,
+                    address: address.to_string(),
+                    script_pubkey: address.script_pubkey().to_hex_string(),
+                    amount: 1_000_000,
+                    confirmations: 1,
+                    spendable: true,
+                    solvable: true,
+                    ancestor_count: None,
+                }])
+                .with_recipient(address.clone())
+                .with_fee_rates(1.0, 1.0)
+                .build()
+                .unwrap()
+                .1
+        };
+
+        let (signature, public_key) =
+            sign_blob_with_private_key(&blob_signing_message(&blob, &metadata, []), &secret_key);
+        let verified = verify_blob_tx(&reveal_tx(signature, public_key), blob_hash, "sov-btc");
+        assert_eq!(verified.unwrap().blob, blob);
+
+        // a signature of the bare bytes, e.g. of any other message of the key, is not one of
+        // a stored blob
+        let (signature, public_key) = sign_blob_with_private_key(&blob, &secret_key);
+        assert_eq!(
+            verify_blob_tx(&reveal_tx(signature, public_key), blob_hash, "sov-btc"),
+            Err(ValidationError::InvalidTx)
+        );
+    }
 }
@@ -0,0 +1,245 @@
+use std::time::Duration;
+
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+use bitcoin::Txid;
+use hex::ToHex;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+// Headers that carry the authentication data of a webhook payload
+pub const SIGNATURE_HEADER: &str = "X-BitcoinDA-Signature";
+pub const PUBLIC_KEY_HEADER: &str = "X-BitcoinDA-Public-Key";
+
+// Tag of the hash webhook payloads are signed over
+const WEBHOOK_SIGNING_TAG: &[u8] = b"bitcoin-da/webhook";
+
+// Attempts to deliver a notification, the delay doubles after every failed one
+const NOTIFY_ATTEMPTS: u32 = 5;
+const NOTIFY_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlobStatus {
+    // reveal tx is mined in a block
+    Included,
+    // block containing the reveal tx is FINALITY_DEPTH deep
+    Finalized,
+}
+
+/// JSON body POSTed to the webhook url when a blob changes status
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlobStatusNotification {
+    pub txid: Txid,
+    pub status: BlobStatus,
+    pub block_hash: String,
+    pub height: u64,
+}
+
+/// BIP340 tagged hash of a webhook payload, the message of its signature.
+/// A single sha256 over the 64 byte tag prefix, it is never the sha256d a blob signature is
+/// over, so a notification signed with the da key is not a valid blob signature
+pub fn notification_hash(body: &[u8]) -> [u8; 32] {
+    let tag_hash = sha256::Hash::hash(WEBHOOK_SIGNING_TAG);
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag_hash.as_ref());
+    engine.input(tag_hash.as_ref());
+    engine.input(body);
+
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+// WebhookNotifier posts blob status notifications to an external url
+// Payloads are signed with the webhook key, the sequencer da key unless one is configured,
+// so receivers can authenticate them by checking the ecdsa signature over
+// notification_hash(body) against the public key header
+// Failed deliveries, non-2xx responses included, are retried with a growing delay
+#[derive(Debug, Clone)]
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    signing_key: SecretKey,
+    attempts: u32,
+    retry_delay: Duration,
+}
+
+impl WebhookNotifier {
+    pub fn new(signing_key: SecretKey) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            signing_key,
+            attempts: NOTIFY_ATTEMPTS,
+            retry_delay: NOTIFY_RETRY_DELAY,
+        }
+    }
+
+    // Attempts to deliver a notification and delay before the first retry
+    pub fn with_retries(mut self, attempts: u32, retry_delay: Duration) -> Self {
+        self.attempts = attempts.max(1);
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    // Serializes and signs the notification, returns (body, signature, public key)
+    #[allow(clippy::type_complexity)]
+    pub fn sign_notification(
+        &self,
+        notification: &BlobStatusNotification,
+    ) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>), anyhow::Error> {
+        let body = serde_json::to_vec(notification)?;
+
+        let secp = Secp256k1::signing_only();
+        let message = Message::from_slice(&notification_hash(&body))?;
+        let signature = secp.sign_ecdsa(&message, &self.signing_key);
+        let public_key = PublicKey::from_secret_key(&secp, &self.signing_key);
+
+        Ok((
+            body,
+            signature.serialize_compact().to_vec(),
+            public_key.serialize().to_vec(),
+        ))
+    }
+
+    pub async fn notify(
+        &self,
+        url: &str,
+        notification: &BlobStatusNotification,
+    ) -> Result<(), anyhow::Error> {
+        let (body, signature, public_key) = self.sign_notification(notification)?;
+
+        let mut retry_delay = self.retry_delay;
+        let mut attempt = 1;
+        loop {
+            match self.post(url, &body, &signature, &public_key).await {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt < self.attempts => {
+                    warn!(
+                        "Webhook notification attempt {} of {} failed: {}",
+                        attempt, self.attempts, e
+                    );
+                    tokio::time::sleep(retry_delay).await;
+                    retry_delay *= 2;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn post(
+        &self,
+        url: &str,
+        body: &[u8],
+        signature: &[u8],
+        public_key: &[u8],
+    ) -> Result<(), anyhow::Error> {
+        let response = self
+            .client
+            .post(url)
+            .header("Content-Type", "application/json")
+            .header(SIGNATURE_HEADER, signature.encode_hex::<String>())
+            .header(PUBLIC_KEY_HEADER, public_key.encode_hex::<String>())
+            .body(body.to_vec())
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "Webhook {} responded with status {}",
+                url,
+                response.status()
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+    use std::time::Duration;
+
+    use bitcoin::hashes::{sha256d, Hash};
+    use bitcoin::secp256k1::{ecdsa, Message, PublicKey, Secp256k1, SecretKey};
+    use bitcoin::Txid;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::{notification_hash, BlobStatus, BlobStatusNotification, WebhookNotifier};
+
+    fn notification() -> BlobStatusNotification {
+        BlobStatusNotification {
+            txid: Txid::from_str(
+                "4cfbec13cf1510545f285cceceb6229bd7b6a918a8f6eba1dbee64d26226a3b7",
+            )
+            .unwrap(),
+            status: BlobStatus::Finalized,
+            block_hash: "6b15a2e4b17b0aabbd418634ae9410b46feaabf693eea4c8621ffe71435d24b0"
+                .to_string(),
+            height: 13,
+        }
+    }
+
+    #[test]
+    fn signed_notification() {
+        let key =
+            SecretKey::from_str("E9873D79C6D87DC0FB6A5778633389F4453213303DA61F20BD67FC233AA33262")
+                .unwrap();
+        let notifier = WebhookNotifier::new(key);
+
+        let (body, signature, public_key) = notifier.sign_notification(&notification()).unwrap();
+
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["status"], "finalized");
+        assert_eq!(json["height"], 13);
+
+        // receivers verify the signature over the tagged hash of the body
+        let message = Message::from_slice(&notification_hash(&body)).unwrap();
+        let signature = ecdsa::Signature::from_compact(&signature).unwrap();
+        let public_key = PublicKey::from_slice(&public_key).unwrap();
+
+        let secp = Secp256k1::new();
+        assert!(secp.verify_ecdsa(&message, &signature, &public_key).is_ok());
+
+        // it is not a signature of the payload as a blob
+        let blob_message =
+            Message::from_slice(&sha256d::Hash::hash(&body).to_byte_array()).unwrap();
+        assert!(secp
+            .verify_ecdsa(&blob_message, &signature, &public_key)
+            .is_err());
+    }
+
+    // Serves one connection per given status code, in order
+    async fn serve(statuses: Vec<u16>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            for status in statuses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = [0u8; 4096];
+                let _ = stream.read(&mut request).await.unwrap();
+                let response = format!(
+                    "HTTP/1.1 {status} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        url
+    }
+
+    #[tokio::test]
+    async fn retried_notification() {
+        let notifier = WebhookNotifier::new(SecretKey::from_slice(&[1; 32]).unwrap())
+            .with_retries(3, Duration::from_millis(1));
+
+        // non-2xx responses are retried
+        let url = serve(vec![500, 503, 200]).await;
+        notifier.notify(&url, &notification()).await.unwrap();
+
+        // until the attempts run out
+        let url = serve(vec![500, 500, 500]).await;
+        assert!(notifier.notify(&url, &notification()).await.is_err());
+    }
+}